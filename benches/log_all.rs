@@ -0,0 +1,73 @@
+//! Compares history walks for `log --all` with and without the commit-graph
+//! cache, over 1000 commits spread across 10 branches.
+//!
+//! Run with `cargo bench --bench log_all`.
+
+use aigit::core::revlist::{rev_list, RevRange};
+use aigit::core::{Commit, CommitGraph, Object, ObjectType, Repository, Tree};
+use std::time::{Duration, Instant};
+
+const BRANCHES: usize = 10;
+const COMMITS_PER_BRANCH: usize = 100;
+const RUNS: u32 = 5;
+
+fn build_history(repo: &Repository) -> Vec<String> {
+    let tree = Object::create(repo, ObjectType::Tree, &serde_json::to_vec(&Tree::new()).unwrap()).unwrap();
+    let root = Commit::new(tree.clone(), None, "Bench".into(), "bench@example.com".into(), "root".into());
+    let root = Object::create(repo, ObjectType::Commit, serde_json::to_string(&root).unwrap().as_bytes()).unwrap();
+
+    let mut tips = Vec::new();
+    for branch in 0..BRANCHES {
+        let mut parent = root.clone();
+        for n in 0..COMMITS_PER_BRANCH {
+            let commit = Commit::new(
+                tree.clone(),
+                Some(parent),
+                "Bench".into(),
+                "bench@example.com".into(),
+                format!("branch {} commit {}", branch, n),
+            );
+            parent = Object::create(repo, ObjectType::Commit, serde_json::to_string(&commit).unwrap().as_bytes()).unwrap();
+        }
+        std::fs::write(repo.heads_dir().join(format!("branch-{}", branch)), &parent).unwrap();
+        tips.push(parent);
+    }
+    tips
+}
+
+fn time<F: FnMut() -> usize>(label: &str, mut walk: F) -> Duration {
+    let mut total = Duration::ZERO;
+    let mut count = 0;
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        count = walk();
+        total += start.elapsed();
+    }
+    let average = total / RUNS;
+    println!("{:<28} {:>10.2?} per walk ({} commits)", label, average, count);
+    average
+}
+
+fn main() {
+    let dir = std::env::temp_dir().join(format!("aigit-bench-log-all-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let repo = Repository::init(&dir, false).unwrap();
+    let tips = build_history(&repo);
+
+    let uncached = time("uncached (object reads)", || {
+        rev_list(&repo, &RevRange { include: tips.clone(), exclude: Vec::new() }).unwrap().len()
+    });
+
+    // Populate the cache once, as the first `log` run would.
+    let mut graph = CommitGraph::load(&repo);
+    graph.extend(&repo, &tips).unwrap();
+    graph.save(&repo).unwrap();
+
+    let cached = time("cached (commit-graph)", || {
+        CommitGraph::load(&repo).walk(&repo, &tips).unwrap().len()
+    });
+
+    println!("speedup: {:.1}x", uncached.as_secs_f64() / cached.as_secs_f64());
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -1,8 +1,20 @@
 use crate::core::{Repository, Index};
+use crate::utils::complexity::ast_complexity;
+use crate::utils::ignore::GitIgnore;
 use walkdir::WalkDir;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
 
-#[derive(Debug)]
+/// Where the last analysis is kept, relative to the repository directory.
+const ANALYSIS_CACHE: &str = "cache/analysis.json";
+/// Bumped whenever scoring changes, so older cached results are recomputed.
+const ANALYSIS_CACHE_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct CodeAnalysis {
     pub total_files: usize,
     pub total_lines: usize,
@@ -15,11 +27,51 @@ pub struct CodeAnalysis {
 }
 
 pub async fn analyze_codebase(repo: &Repository) -> String {
-    let analysis = perform_comprehensive_analysis(repo).await;
+    analyze_codebase_with(repo, false).await
+}
+
+/// `analyze_codebase`, reusing `.aigit/cache/analysis.json` while no file
+/// has changed size or modification time. `refresh` recomputes regardless.
+pub async fn analyze_codebase_with(repo: &Repository, refresh: bool) -> String {
+    let analysis = perform_comprehensive_analysis(repo, refresh).await;
     format_analysis_report(&analysis)
 }
 
-async fn perform_comprehensive_analysis(repo: &Repository) -> CodeAnalysis {
+#[derive(Serialize, Deserialize)]
+struct AnalysisCache {
+    version: u32,
+    fingerprint: String,
+    analysis: CodeAnalysis,
+}
+
+/// What one file contributes to the report.
+struct FileAnalysis {
+    path: String,
+    extension: Option<String>,
+    /// `None` when the file could not be read as text.
+    lines: Option<usize>,
+    complexity: f32,
+    security_issues: usize,
+    maintainability_debt: f32,
+}
+
+async fn perform_comprehensive_analysis(repo: &Repository, refresh: bool) -> CodeAnalysis {
+    let (root, git_dir) = (repo.path.clone(), repo.git_dir.clone());
+    let ignore = GitIgnore::new(&repo.path);
+    let (paths, fingerprint) = tokio::task::spawn_blocking(move || {
+        let paths = collect_files(&root, &git_dir, &ignore);
+        let fingerprint = fingerprint_files(&git_dir, &paths);
+        (paths, fingerprint)
+    })
+    .await
+    .unwrap_or_default();
+
+    if !refresh {
+        if let Some(analysis) = load_cached_analysis(repo, &fingerprint) {
+            return analysis;
+        }
+    }
+
     let mut analysis = CodeAnalysis {
         total_files: 0,
         total_lines: 0,
@@ -31,41 +83,29 @@ async fn perform_comprehensive_analysis(repo: &Repository) -> CodeAnalysis {
         maintainability_score: 100.0,
     };
 
+    // Reading and scoring files is CPU and IO bound, so it runs on rayon's
+    // pool off the async runtime rather than blocking the caller's spinner.
+    let files: Vec<FileAnalysis> = tokio::task::spawn_blocking(move || paths.par_iter().map(|path| analyze_file(path)).collect())
+        .await
+        .unwrap_or_default();
+
     let mut file_sizes = Vec::new();
     let mut total_complexity = 0.0;
     let mut security_issues = 0;
 
-    for entry in WalkDir::new(&repo.path)
-        .into_iter()
-        .filter_map(|e| e.ok())
-        .filter(|e| e.file_type().is_file())
-        .filter(|e| !e.path().starts_with(&repo.git_dir))
-        .filter(|e| !should_ignore_file(e.path()))
-    {
+    for file in files {
         analysis.total_files += 1;
 
-        if let Some(ext) = entry.path().extension() {
-            if let Some(ext_str) = ext.to_str() {
-                *analysis.file_types.entry(ext_str.to_string()).or_insert(0) += 1;
-            }
+        if let Some(ext) = file.extension {
+            *analysis.file_types.entry(ext).or_insert(0) += 1;
         }
 
-        if let Ok(content) = std::fs::read_to_string(entry.path()) {
-            let line_count = content.lines().count();
+        if let Some(line_count) = file.lines {
             analysis.total_lines += line_count;
-            
-            if let Some(path_str) = entry.path().to_str() {
-                file_sizes.push((path_str.to_string(), line_count));
-            }
-
-            let file_complexity = calculate_file_complexity(&content);
-            total_complexity += file_complexity;
-
-            let file_security_issues = scan_security_patterns(&content);
-            security_issues += file_security_issues;
-
-            let maintainability_impact = calculate_maintainability(&content);
-            analysis.maintainability_score -= maintainability_impact;
+            file_sizes.push((file.path, line_count));
+            total_complexity += file.complexity;
+            security_issues += file.security_issues;
+            analysis.maintainability_score -= file.maintainability_debt;
         }
     }
 
@@ -80,9 +120,97 @@ async fn perform_comprehensive_analysis(repo: &Repository) -> CodeAnalysis {
 
     analysis.recent_changes = get_recent_changes(repo).await;
 
+    store_cached_analysis(repo, &fingerprint, &analysis);
     analysis
 }
 
+/// The files to analyze: everything under `root` except `git_dir`, anything
+/// `.gitignore` excludes and the built-in defaults, in a stable order so the
+/// parallel results fold the same way every run.
+fn collect_files(root: &Path, git_dir: &Path, ignore: &GitIgnore) -> Vec<PathBuf> {
+    WalkDir::new(root)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|e| {
+            // Patterns are relative to the root, and pruning an ignored
+            // directory here skips everything under it.
+            let relative = e.path().strip_prefix(root).unwrap_or(e.path());
+            e.depth() == 0 || !ignore.is_ignored_entry(relative, e.file_type().is_dir())
+        })
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter(|e| !e.path().starts_with(git_dir))
+        .filter(|e| !should_ignore_file(e.path()))
+        .map(|e| e.into_path())
+        .collect()
+}
+
+/// Hashes the path, size and modification time of every file, plus the
+/// index that recent changes are read from. Any edit, addition or removal
+/// changes the result.
+fn fingerprint_files(git_dir: &Path, paths: &[PathBuf]) -> String {
+    let index = git_dir.join("index");
+    let mut hasher = Sha256::new();
+    for path in paths.iter().chain(std::iter::once(&index)) {
+        let (size, modified) = std::fs::metadata(path)
+            .map(|meta| {
+                let modified = meta.modified().ok()
+                    .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                    .map(|since| since.as_nanos())
+                    .unwrap_or(0);
+                (meta.len(), modified)
+            })
+            .unwrap_or((0, 0));
+        hasher.update(format!("{}\0{}\0{}\n", path.to_string_lossy(), size, modified).as_bytes());
+    }
+    hex::encode(hasher.finalize())
+}
+
+fn load_cached_analysis(repo: &Repository, fingerprint: &str) -> Option<CodeAnalysis> {
+    let content = std::fs::read_to_string(repo.git_dir.join(ANALYSIS_CACHE)).ok()?;
+    let cache: AnalysisCache = serde_json::from_str(&content).ok()?;
+    (cache.version == ANALYSIS_CACHE_VERSION && cache.fingerprint == fingerprint).then_some(cache.analysis)
+}
+
+/// Best effort: a cache that cannot be written only costs the next run a
+/// full walk.
+fn store_cached_analysis(repo: &Repository, fingerprint: &str, analysis: &CodeAnalysis) {
+    let path = repo.git_dir.join(ANALYSIS_CACHE);
+    let cache = serde_json::json!({
+        "version": ANALYSIS_CACHE_VERSION,
+        "fingerprint": fingerprint,
+        "analysis": analysis,
+    });
+    if let Some(parent) = path.parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    let _ = std::fs::write(&path, cache.to_string());
+}
+
+fn analyze_file(path: &Path) -> FileAnalysis {
+    let extension = path.extension().and_then(|ext| ext.to_str()).map(str::to_string);
+    let mut file = FileAnalysis {
+        path: path.to_string_lossy().to_string(),
+        extension,
+        lines: None,
+        complexity: 0.0,
+        security_issues: 0,
+        maintainability_debt: 0.0,
+    };
+
+    if let Ok(content) = std::fs::read_to_string(path) {
+        file.lines = Some(content.lines().count());
+        // Parsed languages are scored from their syntax tree; the rest fall
+        // back to keyword matching.
+        file.complexity = file.extension.as_deref()
+            .and_then(|ext| ast_complexity(ext, &content))
+            .unwrap_or_else(|| calculate_file_complexity(&content));
+        file.security_issues = scan_security_patterns(&content);
+        file.maintainability_debt = calculate_maintainability(&content);
+    }
+    file
+}
+
 fn should_ignore_file(path: &std::path::Path) -> bool {
     let ignore_patterns = [
         "target", "node_modules", ".git", "build", "dist", "__pycache__",
@@ -1,311 +1,69 @@
+use crate::ai::{api_key, max_output_tokens, model, temperature, AiError, AiProvider};
+use crate::ai::response::{drain_sse_data, extract_stream_text, extract_text, max_response_bytes, TRUNCATION_NOTE};
+use crate::ai::retry::RetryPolicy;
+use crate::ai::tokens::{check_prompt_size, max_context_tokens};
+use crate::core::Config;
+use async_trait::async_trait;
+use colored::*;
+use indicatif::ProgressBar;
 use reqwest::Client;
 use serde_json::json;
-use std::env;
 use std::time::Duration;
 
+/// Model used when `ai.model` is unset.
+pub const DEFAULT_MODEL: &str = "gemini-1.5-flash";
+
 pub struct GeminiClient {
     client: Client,
     api_key: String,
+    model: String,
+    temperature: f64,
+    max_output_tokens: u32,
+    max_context_tokens: usize,
+    max_response_bytes: usize,
+    retry: RetryPolicy,
 }
 
 impl GeminiClient {
-    pub fn new() -> Self {
-        dotenv::dotenv().ok();
-        
+    /// Builds a client from `GEMINI_API_KEY` in the environment or `.env`,
+    /// taking the model and limits from `config`. Fails without panicking
+    /// when the key is missing, so commands that do not need AI keep working.
+    pub fn new(config: &Config) -> Result<Self, AiError> {
         let client = Client::builder()
             .timeout(Duration::from_secs(30))
-            .build()
-            .expect("Failed to create HTTP client");
-
-        let api_key = env::var("GEMINI_API_KEY")
-            .or_else(|_| {
-                if std::path::Path::new(".env").exists() {
-                    let env_content = std::fs::read_to_string(".env")
-                        .expect("Failed to read .env file");
-                    
-                    for line in env_content.lines() {
-                        if line.starts_with("GEMINI_API_KEY=") {
-                            return Ok(line.strip_prefix("GEMINI_API_KEY=").unwrap_or("").to_string());
-                        }
-                    }
-                }
-                Err(env::VarError::NotPresent)
-            })
-            .expect("GEMINI_API_KEY must be set in environment or .env file");
+            .build()?;
 
-        Self {
+        Ok(Self {
             client,
-            api_key,
-        }
-    }
-
-    pub async fn generate_commit_message(&self, diff: &str) -> Result<String, Box<dyn std::error::Error>> {
-        let prompt = format!(
-            "Generate a concise git commit message for these changes. \
-            Use conventional commit format (feat:, fix:, docs:, style:, refactor:, test:, chore:). \
-            Keep it under 60 characters and focus on the main change:\n\n{}",
-            diff.chars().take(2500).collect::<String>()
-        );
-
-        let response = self.generate_text(&prompt).await?;
-        Ok(response.lines().next().unwrap_or("chore: update files").trim().to_string())
-    }
-
-    pub async fn review_code(&self, diff: &str) -> Result<String, Box<dyn std::error::Error>> {
-        let prompt = format!(
-            "Provide a thorough code review for these changes. Focus on:\n\
-            - Potential bugs and logical errors\n\
-            - Code quality and best practices\n\
-            - Security vulnerabilities\n\
-            - Performance implications\n\
-            - Maintainability concerns\n\
-            Be constructive and specific with suggestions.\n\n\
-            Changes:\n{}",
-            diff.chars().take(4000).collect::<String>()
-        );
-
-        self.generate_text(&prompt).await
-    }
-
-    pub async fn comprehensive_review(&self, diff: &str, detailed: bool) -> Result<String, Box<dyn std::error::Error>> {
-        let analysis_depth = if detailed { 
-            "comprehensive and detailed" 
-        } else { 
-            "focused and concise" 
-        };
-
-        let prompt = format!(
-            "Provide a {} code review for these changes:\n\n\
-            **Code Quality Analysis:**\n\
-            - Adherence to best practices and coding standards\n\
-            - Code structure and organization\n\
-            - Readability and maintainability\n\n\
-            **Bug Detection:**\n\
-            - Potential runtime errors\n\
-            - Logic flaws and edge cases\n\
-            - Type safety issues\n\n\
-            **Security Assessment:**\n\
-            - Vulnerability patterns\n\
-            - Input validation\n\
-            - Data exposure risks\n\n\
-            **Performance Review:**\n\
-            - Algorithmic efficiency\n\
-            - Resource usage\n\
-            - Scalability concerns\n\n\
-            **Architecture & Design:**\n\
-            - Design patterns usage\n\
-            - Separation of concerns\n\
-            - Testability\n\n\
-            Changes to review:\n{}",
-            analysis_depth,
-            diff.chars().take(5000).collect::<String>()
-        );
-
-        self.generate_text(&prompt).await
-    }
-
-    pub async fn suggest_improvements(&self, diff: &str) -> Result<String, Box<dyn std::error::Error>> {
-        let prompt = format!(
-            "Based on these code changes, provide specific improvement suggestions:\n\n\
-            **Immediate Improvements:**\n\
-            - Code optimizations\n\
-            - Bug fixes\n\
-            - Style improvements\n\n\
-            **Enhancement Opportunities:**\n\
-            - Performance optimizations\n\
-            - Feature additions\n\
-            - Error handling improvements\n\n\
-            **Long-term Considerations:**\n\
-            - Refactoring opportunities\n\
-            - Architecture improvements\n\
-            - Technical debt reduction\n\n\
-            Code changes:\n{}",
-            diff.chars().take(4000).collect::<String>()
-        );
-
-        self.generate_text(&prompt).await
+            api_key: api_key("GEMINI_API_KEY")?,
+            model: gemini_model(config),
+            temperature: temperature(config),
+            max_output_tokens: max_output_tokens(config),
+            max_context_tokens: max_context_tokens(config),
+            max_response_bytes: max_response_bytes(config),
+            retry: RetryPolicy::from_config(config),
+        })
     }
 
-    pub async fn explain_diff(&self, diff: &str) -> Result<String, Box<dyn std::error::Error>> {
-        let prompt = format!(
-            "Explain what these code changes accomplish in clear, non-technical terms. \
-            Focus on:\n\
-            - What functionality is being added/modified/removed\n\
-            - Why these changes might be necessary\n\
-            - The impact on the overall system\n\
-            - Any notable patterns or approaches used\n\n\
-            Changes:\n{}",
-            diff.chars().take(3000).collect::<String>()
-        );
-
-        self.generate_text(&prompt).await
-    }
-
-    pub async fn suggest_next_commit(&self, context: &str) -> Result<String, Box<dyn std::error::Error>> {
-        let prompt = format!(
-            "Based on this project analysis, suggest what should be worked on next. \
-            Consider:\n\
-            - High-priority bugs or security issues\n\
-            - Important missing features\n\
-            - Code quality improvements\n\
-            - Technical debt reduction\n\
-            - Performance optimizations\n\
-            Provide actionable recommendations with reasoning.\n\n\
-            Project context:\n{}",
-            context
-        );
-
-        self.generate_text(&prompt).await
-    }
-
-    pub async fn suggest_branch_name(&self, context: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
-        let prompt = format!(
-            "Suggest 5 good branch names for upcoming development work based on this project. \
-            Use conventional naming:\n\
-            - feature/ for new features\n\
-            - bugfix/ or fix/ for bug fixes\n\
-            - hotfix/ for critical fixes\n\
-            - refactor/ for code improvements\n\
-            - chore/ for maintenance tasks\n\
-            - docs/ for documentation\n\
-            - test/ for testing improvements\n\
-            Make them descriptive but concise.\n\n\
-            Project context:\n{}",
-            context
-        );
-
-        let response = self.generate_text(&prompt).await?;
-        let suggestions: Vec<String> = response
-            .lines()
-            .filter_map(|line| {
-                let trimmed = line.trim();
-                if trimmed.starts_with(char::is_numeric) || 
-                   trimmed.starts_with("- ") || 
-                   trimmed.starts_with("* ") ||
-                   trimmed.starts_with("• ") {
-                    Some(extract_branch_name(trimmed))
-                } else if !trimmed.is_empty() && 
-                         (trimmed.contains('/') || !trimmed.contains(' ')) &&
-                         trimmed.len() < 50 {
-                    Some(trimmed.to_string())
-                } else {
-                    None
-                }
-            })
-            .take(5)
-            .collect();
-
-        if suggestions.is_empty() {
-            Ok(vec![
-                "feature/new-functionality".to_string(),
-                "bugfix/critical-issue".to_string(),
-                "refactor/code-cleanup".to_string(),
-                "chore/dependency-update".to_string(),
-                "docs/api-documentation".to_string(),
-            ])
-        } else {
-            Ok(suggestions)
-        }
-    }
-
-    pub async fn suggest_refactoring(&self, context: &str) -> Result<String, Box<dyn std::error::Error>> {
-        let prompt = format!(
-            "Analyze this codebase and suggest refactoring opportunities:\n\n\
-            **Code Analysis:**\n\
-            - Identify code smells and anti-patterns\n\
-            - Find duplicated code\n\
-            - Locate overly complex functions\n\n\
-            **Refactoring Suggestions:**\n\
-            - Extract methods/functions\n\
-            - Simplify conditional logic\n\
-            - Improve naming conventions\n\
-            - Reduce coupling\n\n\
-            **Impact Assessment:**\n\
-            - Priority level (high/medium/low)\n\
-            - Effort estimation\n\
-            - Benefits and risks\n\n\
-            Codebase context:\n{}",
-            context
-        );
-
-        self.generate_text(&prompt).await
-    }
-
-    pub async fn suggest_tests(&self, context: &str) -> Result<String, Box<dyn std::error::Error>> {
-        let prompt = format!(
-            "Analyze this codebase for testing opportunities:\n\n\
-            **Test Coverage Analysis:**\n\
-            - Identify untested code paths\n\
-            - Find critical functions without tests\n\
-            - Locate edge cases that need testing\n\n\
-            **Test Recommendations:**\n\
-            - Unit tests for core functionality\n\
-            - Integration tests for component interaction\n\
-            - Error handling and edge case tests\n\
-            - Performance and load tests\n\n\
-            **Priority Suggestions:**\n\
-            - High-risk areas that need immediate testing\n\
-            - Complex logic that benefits from test coverage\n\
-            - Public APIs that require comprehensive testing\n\n\
-            Codebase analysis:\n{}",
-            context
-        );
-
-        self.generate_text(&prompt).await
+    /// The model requests go to, without any `models/` prefix.
+    pub fn model(&self) -> &str {
+        &self.model
     }
 
-    pub async fn analyze_merge(&self, context: &str) -> Result<String, Box<dyn std::error::Error>> {
-        let prompt = format!(
-            "Analyze this merge operation and provide insights:\n\n\
-            **Merge Strategy Analysis:**\n\
-            - Compatibility assessment\n\
-            - Potential conflict areas\n\
-            - Risk evaluation\n\n\
-            **Conflict Prevention:**\n\
-            - Identify likely merge conflicts\n\
-            - Suggest resolution strategies\n\
-            - Recommend pre-merge actions\n\n\
-            **Recommendations:**\n\
-            - Best merge approach\n\
-            - Testing requirements\n\
-            - Post-merge verification steps\n\n\
-            Merge context:\n{}",
-            context
-        );
-
-        self.generate_text(&prompt).await
-    }
-
-    pub async fn resolve_conflict(&self, conflict_content: &str) -> Result<String, Box<dyn std::error::Error>> {
-        let prompt = format!(
-            "Help resolve this merge conflict by analyzing both sides and suggesting the best resolution:\n\n\
-            **Conflict Analysis:**\n\
-            - Understand what each side is trying to achieve\n\
-            - Identify the root cause of the conflict\n\
-            - Assess the importance of each change\n\n\
-            **Resolution Strategy:**\n\
-            - Suggest which version to keep or how to merge both\n\
-            - Explain the reasoning behind the recommendation\n\
-            - Highlight any additional considerations\n\n\
-            Conflict content:\n{}",
-            conflict_content
-        );
-
-        self.generate_text(&prompt).await
-    }
-
-    pub async fn generate_text(&self, prompt: &str) -> Result<String, Box<dyn std::error::Error>> {
-        let payload = json!({
+    /// The `generateContent` request body, shared by the streaming and
+    /// non-streaming endpoints.
+    fn request_body(&self, prompt: &str) -> serde_json::Value {
+        json!({
             "contents": [{
                 "parts": [{
                     "text": prompt
                 }]
             }],
             "generationConfig": {
-                "temperature": 0.7,
+                "temperature": self.temperature,
                 "topK": 40,
                 "topP": 0.95,
-                "maxOutputTokens": 4096,
+                "maxOutputTokens": self.max_output_tokens,
                 "stopSequences": []
             },
             "safetySettings": [
@@ -318,18 +76,42 @@ impl GeminiClient {
                     "threshold": "BLOCK_MEDIUM_AND_ABOVE"
                 }
             ]
-        });
+        })
+    }
+}
 
-        let response = self
+/// `ai.model` with the `models/` prefix the Gemini docs use stripped, since
+/// the endpoint URL already contains it.
+fn gemini_model(config: &Config) -> String {
+    let model = model(config, DEFAULT_MODEL);
+    match model.strip_prefix("models/") {
+        Some(name) if !name.is_empty() => name.to_string(),
+        _ => model,
+    }
+}
+
+#[async_trait(?Send)]
+impl AiProvider for GeminiClient {
+    fn set_progress(&mut self, progress: ProgressBar) {
+        self.retry.progress = Some(progress);
+    }
+
+    async fn generate_text(&self, prompt: &str) -> Result<String, Box<dyn std::error::Error>> {
+        if let Some(warning) = check_prompt_size(prompt, self.max_context_tokens) {
+            eprintln!("{} {}", "Warning:".yellow(), warning);
+        }
+
+        let payload = self.request_body(prompt);
+        let url = format!(
+            "https://generativelanguage.googleapis.com/v1beta/models/{}:generateContent?key={}",
+            self.model, self.api_key
+        );
+        let response = self.retry.send(|| self
             .client
-            .post(&format!(
-                "https://generativelanguage.googleapis.com/v1beta/models/gemini-1.5-flash:generateContent?key={}",
-                self.api_key
-            ))
+            .post(&url)
             .header("Content-Type", "application/json")
             .json(&payload)
-            .send()
-            .await?;
+        ).await?;
 
         if !response.status().is_success() {
             let status = response.status();
@@ -338,30 +120,80 @@ impl GeminiClient {
         }
 
         let json: serde_json::Value = response.json().await?;
-        
-        if let Some(error) = json.get("error") {
-            return Err(format!("Gemini API error: {}", error).into());
-        }
-        
-        json["candidates"][0]["content"]["parts"][0]["text"]
-            .as_str()
-            .map(|s| s.trim().to_string())
-            .ok_or_else(|| "No response from Gemini API".into())
+        Ok(extract_text(&json, self.max_response_bytes)?)
     }
-}
 
-fn extract_branch_name(line: &str) -> String {
-    let cleaned = line
-        .trim_start_matches(char::is_numeric)
-        .trim_start_matches(". ")
-        .trim_start_matches("- ")
-        .trim_start_matches("* ")
-        .trim_start_matches("• ")
-        .trim();
-    
-    if let Some(space_pos) = cleaned.find(' ') {
-        cleaned[..space_pos].to_string()
-    } else {
-        cleaned.to_string()
+    /// Reads `streamGenerateContent` as server-sent events, passing on the
+    /// text of each event as it arrives. The reply is capped at
+    /// `ai.maxResponseBytes` like `generate_text`; once it is reached the
+    /// rest of the stream is dropped.
+    async fn generate_text_streaming(&self, prompt: &str, on_chunk: &mut dyn for<'c> FnMut(&'c str)) -> Result<String, Box<dyn std::error::Error>> {
+        if let Some(warning) = check_prompt_size(prompt, self.max_context_tokens) {
+            eprintln!("{} {}", "Warning:".yellow(), warning);
+        }
+
+        let payload = self.request_body(prompt);
+        let url = format!(
+            "https://generativelanguage.googleapis.com/v1beta/models/{}:streamGenerateContent?alt=sse&key={}",
+            self.model, self.api_key
+        );
+        let mut response = self.retry.send(|| self
+            .client
+            .post(&url)
+            .header("Content-Type", "application/json")
+            .json(&payload)
+        ).await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await?;
+            return Err(format!("Gemini API error: {} - {}", status, error_text).into());
+        }
+
+        let mut buffer = Vec::new();
+        let mut text = String::new();
+        let mut finished = false;
+        while !finished {
+            match response.chunk().await? {
+                Some(bytes) => buffer.extend_from_slice(&bytes),
+                // A last event without the closing blank line still counts.
+                None => {
+                    buffer.extend_from_slice(b"\n\n");
+                    finished = true;
+                },
+            }
+
+            for data in drain_sse_data(&mut buffer) {
+                let json: serde_json::Value = serde_json::from_str(&data)
+                    .map_err(|e| format!("Malformed Gemini stream event: {}", e))?;
+                let mut piece = extract_stream_text(&json)?;
+                // Leading blank lines would print before anything useful.
+                if text.is_empty() {
+                    piece = piece.trim_start().to_string();
+                }
+                if piece.is_empty() {
+                    continue;
+                }
+
+                let remaining = self.max_response_bytes - text.len();
+                if piece.len() > remaining {
+                    let mut end = remaining;
+                    while !piece.is_char_boundary(end) {
+                        end -= 1;
+                    }
+                    text.push_str(&piece[..end]);
+                    on_chunk(&piece[..end]);
+                    on_chunk(&format!("\n\n{}", TRUNCATION_NOTE));
+                    return Ok(format!("{}\n\n{}", text.trim_end(), TRUNCATION_NOTE));
+                }
+                text.push_str(&piece);
+                on_chunk(&piece);
+            }
+        }
+
+        if text.trim().is_empty() {
+            return Err("Gemini response contained no text".into());
+        }
+        Ok(text.trim_end().to_string())
     }
 }
@@ -1,2 +1,454 @@
 pub mod gemini;
+pub mod openai;
+pub mod ollama;
 pub mod analyzer;
+pub mod tokens;
+pub mod response;
+pub mod retry;
+
+use crate::core::Config;
+use crate::core::config::parse_bool;
+use async_trait::async_trait;
+use indicatif::ProgressBar;
+use std::sync::atomic::{AtomicBool, Ordering};
+use thiserror::Error;
+
+pub use gemini::GeminiClient;
+pub use openai::OpenAiClient;
+pub use ollama::OllamaClient;
+
+/// Providers accepted by `ai.provider`. The first one is the default.
+pub const PROVIDERS: &[&str] = &["gemini", "openai", "ollama"];
+
+/// Default for `ai.temperature` when it is unset or not a number.
+pub const DEFAULT_TEMPERATURE: f64 = 0.7;
+
+/// Default for `ai.maxTokens`, the cap on generated tokens per reply.
+pub const DEFAULT_MAX_OUTPUT_TOKENS: u32 = 4096;
+
+/// Set by `--no-ai`; overrides `ai.enabled` for the rest of the process.
+static AI_DISABLED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Error, Debug)]
+pub enum AiError {
+    #[error("AI features are disabled (--no-ai or ai.enabled = false)")]
+    Disabled,
+    #[error("{0} is not set. Set {0} (in the environment or .env) or run without --ai-* options")]
+    MissingApiKey(&'static str),
+    #[error("Failed to read .env file: {0}")]
+    EnvFile(#[from] std::io::Error),
+    #[error("Failed to create HTTP client: {0}")]
+    HttpClient(#[from] reqwest::Error),
+    #[error("Unknown AI provider '{0}' (ai.provider must be one of: gemini, openai, ollama)")]
+    UnknownProvider(String),
+}
+
+/// A language model backend. Implementors only send a prompt and return the
+/// reply; the prompts behind every AI feature are shared default methods, so
+/// all providers are asked the same questions.
+#[async_trait(?Send)]
+pub trait AiProvider {
+    /// Reports retries of rate-limited or failed requests on `progress`.
+    fn set_progress(&mut self, progress: ProgressBar);
+
+    async fn generate_text(&self, prompt: &str) -> Result<String, Box<dyn std::error::Error>>;
+
+    /// `generate_text`, handing each piece of the reply to `on_chunk` as it
+    /// arrives and returning the whole reply at the end. Providers without a
+    /// streaming endpoint deliver the reply as one chunk once it is complete.
+    async fn generate_text_streaming(&self, prompt: &str, on_chunk: &mut dyn for<'c> FnMut(&'c str)) -> Result<String, Box<dyn std::error::Error>> {
+        let text = self.generate_text(prompt).await?;
+        on_chunk(&text);
+        Ok(text)
+    }
+
+    async fn generate_commit_message(&self, diff: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let prompt = format!(
+            "Generate a concise git commit message for these changes. \
+            Use conventional commit format (feat:, fix:, docs:, style:, refactor:, test:, chore:). \
+            Keep it under 60 characters and focus on the main change:\n\n{}",
+            diff.chars().take(2500).collect::<String>()
+        );
+
+        let response = self.generate_text(&prompt).await?;
+        Ok(response.lines().next().unwrap_or("chore: update files").trim().to_string())
+    }
+
+    async fn review_code(&self, diff: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let prompt = format!(
+            "Provide a thorough code review for these changes. Focus on:\n\
+            - Potential bugs and logical errors\n\
+            - Code quality and best practices\n\
+            - Security vulnerabilities\n\
+            - Performance implications\n\
+            - Maintainability concerns\n\
+            Be constructive and specific with suggestions.\n\n\
+            Changes:\n{}",
+            diff.chars().take(4000).collect::<String>()
+        );
+
+        self.generate_text(&prompt).await
+    }
+
+    async fn comprehensive_review(&self, diff: &str, detailed: bool) -> Result<String, Box<dyn std::error::Error>> {
+        self.generate_text(&comprehensive_review_prompt(diff, detailed)).await
+    }
+
+    async fn comprehensive_review_streaming(&self, diff: &str, detailed: bool, on_chunk: &mut dyn for<'c> FnMut(&'c str)) -> Result<String, Box<dyn std::error::Error>> {
+        self.generate_text_streaming(&comprehensive_review_prompt(diff, detailed), on_chunk).await
+    }
+
+    async fn suggest_improvements(&self, diff: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let prompt = format!(
+            "Based on these code changes, provide specific improvement suggestions:\n\n\
+            **Immediate Improvements:**\n\
+            - Code optimizations\n\
+            - Bug fixes\n\
+            - Style improvements\n\n\
+            **Enhancement Opportunities:**\n\
+            - Performance optimizations\n\
+            - Feature additions\n\
+            - Error handling improvements\n\n\
+            **Long-term Considerations:**\n\
+            - Refactoring opportunities\n\
+            - Architecture improvements\n\
+            - Technical debt reduction\n\n\
+            Code changes:\n{}",
+            diff.chars().take(4000).collect::<String>()
+        );
+
+        self.generate_text(&prompt).await
+    }
+
+    async fn explain_diff(&self, diff: &str) -> Result<String, Box<dyn std::error::Error>> {
+        self.generate_text(&explain_diff_prompt(diff)).await
+    }
+
+    async fn explain_diff_streaming(&self, diff: &str, on_chunk: &mut dyn for<'c> FnMut(&'c str)) -> Result<String, Box<dyn std::error::Error>> {
+        self.generate_text_streaming(&explain_diff_prompt(diff), on_chunk).await
+    }
+
+    async fn suggest_next_commit(&self, context: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let prompt = format!(
+            "Based on this project analysis, suggest what should be worked on next. \
+            Consider:\n\
+            - High-priority bugs or security issues\n\
+            - Important missing features\n\
+            - Code quality improvements\n\
+            - Technical debt reduction\n\
+            - Performance optimizations\n\
+            Provide actionable recommendations with reasoning.\n\n\
+            Project context:\n{}",
+            context
+        );
+
+        self.generate_text(&prompt).await
+    }
+
+    async fn suggest_branch_name(&self, context: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let prompt = format!(
+            "Suggest 5 good branch names for upcoming development work based on this project. \
+            Use conventional naming:\n\
+            - feature/ for new features\n\
+            - bugfix/ or fix/ for bug fixes\n\
+            - hotfix/ for critical fixes\n\
+            - refactor/ for code improvements\n\
+            - chore/ for maintenance tasks\n\
+            - docs/ for documentation\n\
+            - test/ for testing improvements\n\
+            Make them descriptive but concise.\n\n\
+            Project context:\n{}",
+            context
+        );
+
+        let response = self.generate_text(&prompt).await?;
+        let suggestions: Vec<String> = response
+            .lines()
+            .filter_map(|line| {
+                let trimmed = line.trim();
+                if trimmed.starts_with(char::is_numeric) || 
+                   trimmed.starts_with("- ") || 
+                   trimmed.starts_with("* ") ||
+                   trimmed.starts_with("• ") {
+                    Some(extract_branch_name(trimmed))
+                } else if !trimmed.is_empty() && 
+                         (trimmed.contains('/') || !trimmed.contains(' ')) &&
+                         trimmed.len() < 50 {
+                    Some(trimmed.to_string())
+                } else {
+                    None
+                }
+            })
+            .take(5)
+            .collect();
+
+        if suggestions.is_empty() {
+            Ok(vec![
+                "feature/new-functionality".to_string(),
+                "bugfix/critical-issue".to_string(),
+                "refactor/code-cleanup".to_string(),
+                "chore/dependency-update".to_string(),
+                "docs/api-documentation".to_string(),
+            ])
+        } else {
+            Ok(suggestions)
+        }
+    }
+
+    async fn suggest_refactoring(&self, context: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let prompt = format!(
+            "Analyze this codebase and suggest refactoring opportunities:\n\n\
+            **Code Analysis:**\n\
+            - Identify code smells and anti-patterns\n\
+            - Find duplicated code\n\
+            - Locate overly complex functions\n\n\
+            **Refactoring Suggestions:**\n\
+            - Extract methods/functions\n\
+            - Simplify conditional logic\n\
+            - Improve naming conventions\n\
+            - Reduce coupling\n\n\
+            **Impact Assessment:**\n\
+            - Priority level (high/medium/low)\n\
+            - Effort estimation\n\
+            - Benefits and risks\n\n\
+            Codebase context:\n{}",
+            context
+        );
+
+        self.generate_text(&prompt).await
+    }
+
+    async fn propose_refactor(&self, path: &str, content: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let prompt = format!(
+            "Refactor the following file to improve readability and maintainability \
+            without changing its behavior. Respond with the complete refactored file \
+            in a single fenced code block and nothing else.\n\n\
+            File: {}\n\n\
+            ```\n{}\n```",
+            path,
+            content
+        );
+
+        self.generate_text(&prompt).await
+    }
+
+    async fn suggest_tests(&self, context: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let prompt = format!(
+            "Analyze this codebase for testing opportunities:\n\n\
+            **Test Coverage Analysis:**\n\
+            - Identify untested code paths\n\
+            - Find critical functions without tests\n\
+            - Locate edge cases that need testing\n\n\
+            **Test Recommendations:**\n\
+            - Unit tests for core functionality\n\
+            - Integration tests for component interaction\n\
+            - Error handling and edge case tests\n\
+            - Performance and load tests\n\n\
+            **Priority Suggestions:**\n\
+            - High-risk areas that need immediate testing\n\
+            - Complex logic that benefits from test coverage\n\
+            - Public APIs that require comprehensive testing\n\n\
+            Codebase analysis:\n{}",
+            context
+        );
+
+        self.generate_text(&prompt).await
+    }
+
+    async fn analyze_merge(&self, context: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let prompt = format!(
+            "Analyze this merge operation and provide insights:\n\n\
+            **Merge Strategy Analysis:**\n\
+            - Compatibility assessment\n\
+            - Potential conflict areas\n\
+            - Risk evaluation\n\n\
+            **Conflict Prevention:**\n\
+            - Identify likely merge conflicts\n\
+            - Suggest resolution strategies\n\
+            - Recommend pre-merge actions\n\n\
+            **Recommendations:**\n\
+            - Best merge approach\n\
+            - Testing requirements\n\
+            - Post-merge verification steps\n\n\
+            Merge context:\n{}",
+            context
+        );
+
+        self.generate_text(&prompt).await
+    }
+
+    async fn resolve_conflict(&self, conflict_content: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let prompt = format!(
+            "Resolve this single merge conflict hunk. Understand what each side is trying to \
+            achieve and combine them, or keep one side if the other is obsolete.\n\n\
+            Reply with the resolved lines only, inside one fenced code block, without conflict \
+            markers. After the block add one line of the form \
+            'Confidence: <high|medium|low> - <short reason>'.\n\n\
+            Conflict hunk:\n{}",
+            conflict_content
+        );
+
+        self.generate_text(&prompt).await
+    }
+}
+
+fn comprehensive_review_prompt(diff: &str, detailed: bool) -> String {
+    let analysis_depth = if detailed { 
+        "comprehensive and detailed" 
+    } else { 
+        "focused and concise" 
+    };
+
+    format!(
+        "Provide a {} code review for these changes:\n\n\
+        **Code Quality Analysis:**\n\
+        - Adherence to best practices and coding standards\n\
+        - Code structure and organization\n\
+        - Readability and maintainability\n\n\
+        **Bug Detection:**\n\
+        - Potential runtime errors\n\
+        - Logic flaws and edge cases\n\
+        - Type safety issues\n\n\
+        **Security Assessment:**\n\
+        - Vulnerability patterns\n\
+        - Input validation\n\
+        - Data exposure risks\n\n\
+        **Performance Review:**\n\
+        - Algorithmic efficiency\n\
+        - Resource usage\n\
+        - Scalability concerns\n\n\
+        **Architecture & Design:**\n\
+        - Design patterns usage\n\
+        - Separation of concerns\n\
+        - Testability\n\n\
+        Changes to review:\n{}",
+        analysis_depth,
+        diff.chars().take(5000).collect::<String>()
+    )
+}
+
+fn explain_diff_prompt(diff: &str) -> String {
+    format!(
+        "Explain what these code changes accomplish in clear, non-technical terms. \
+        Focus on:\n\
+        - What functionality is being added/modified/removed\n\
+        - Why these changes might be necessary\n\
+        - The impact on the overall system\n\
+        - Any notable patterns or approaches used\n\n\
+        Changes:\n{}",
+        diff.chars().take(3000).collect::<String>()
+    )
+}
+
+/// Turns off every AI feature for the rest of the process, whatever
+/// `ai.enabled` says.
+pub fn disable_ai() {
+    AI_DISABLED.store(true, Ordering::Relaxed);
+}
+
+/// Whether AI features may run: `--no-ai` was not given and `ai.enabled` is
+/// not false. Commands check this to take their manual path without a word
+/// about missing keys.
+pub fn ai_enabled(config: &Config) -> bool {
+    !AI_DISABLED.load(Ordering::Relaxed)
+        && config.get("ai.enabled").and_then(|value| parse_bool(value)) != Some(false)
+}
+
+/// Stops commands that have nothing to do without AI, with the same message
+/// as `AiError::Disabled`.
+pub fn require_ai(config: &Config) -> Result<(), Box<dyn std::error::Error>> {
+    if ai_enabled(config) {
+        Ok(())
+    } else {
+        Err(AiError::Disabled.to_string().into())
+    }
+}
+
+/// Builds the provider named by `ai.provider`, defaulting to Gemini. Fails
+/// while AI is disabled, so no code path can reach the network.
+pub fn provider_from_config(config: &Config) -> Result<Box<dyn AiProvider>, AiError> {
+    if !ai_enabled(config) {
+        return Err(AiError::Disabled);
+    }
+    match config.get("ai.provider").map(String::as_str).unwrap_or("gemini") {
+        "gemini" => Ok(Box::new(GeminiClient::new(config)?)),
+        "openai" => Ok(Box::new(OpenAiClient::new(config)?)),
+        "ollama" => Ok(Box::new(OllamaClient::new(config)?)),
+        other => Err(AiError::UnknownProvider(other.to_string())),
+    }
+}
+
+/// `provider_from_config` with retries reported on the caller's spinner.
+pub fn provider_with_progress(config: &Config, progress: &ProgressBar) -> Result<Box<dyn AiProvider>, AiError> {
+    let mut provider = provider_from_config(config)?;
+    provider.set_progress(progress.clone());
+    Ok(provider)
+}
+
+/// The model named by `ai.model`, or `default` when it is unset.
+pub fn model(config: &Config, default: &str) -> String {
+    config.get("ai.model")
+        .filter(|model| !model.trim().is_empty())
+        .cloned()
+        .unwrap_or_else(|| default.to_string())
+}
+
+/// The sampling temperature from `ai.temperature`.
+pub fn temperature(config: &Config) -> f64 {
+    config.get("ai.temperature")
+        .and_then(|value| value.parse::<f64>().ok())
+        .unwrap_or(DEFAULT_TEMPERATURE)
+}
+
+/// The reply length cap from `ai.maxTokens`, or the default if it is unset or
+/// not a positive number.
+pub fn max_output_tokens(config: &Config) -> u32 {
+    config.get("ai.maxTokens")
+        .and_then(|value| value.parse::<u32>().ok())
+        .filter(|&limit| limit > 0)
+        .unwrap_or(DEFAULT_MAX_OUTPUT_TOKENS)
+}
+
+/// Whether `model` can be placed in a request URL or body as is.
+pub fn is_valid_model_name(model: &str) -> bool {
+    !model.is_empty() && model.chars().all(|c| c.is_ascii_alphanumeric() || "-_.:/".contains(c))
+}
+
+/// Reads the API key `var` from the environment, falling back to a
+/// `VAR=value` line in `.env`.
+pub(crate) fn api_key(var: &'static str) -> Result<String, AiError> {
+    dotenv::dotenv().ok();
+
+    let key = match std::env::var(var) {
+        Ok(key) => key,
+        Err(_) if std::path::Path::new(".env").exists() => {
+            let prefix = format!("{}=", var);
+            std::fs::read_to_string(".env")?
+                .lines()
+                .find_map(|line| line.strip_prefix(prefix.as_str()))
+                .unwrap_or("")
+                .to_string()
+        },
+        Err(_) => String::new(),
+    };
+    if key.trim().is_empty() {
+        return Err(AiError::MissingApiKey(var));
+    }
+    Ok(key)
+}
+
+fn extract_branch_name(line: &str) -> String {
+    let cleaned = line
+        .trim_start_matches(char::is_numeric)
+        .trim_start_matches(". ")
+        .trim_start_matches("- ")
+        .trim_start_matches("* ")
+        .trim_start_matches("• ")
+        .trim();
+    
+    if let Some(space_pos) = cleaned.find(' ') {
+        cleaned[..space_pos].to_string()
+    } else {
+        cleaned.to_string()
+    }
+}
@@ -0,0 +1,104 @@
+use crate::ai::{max_output_tokens, model, temperature, AiError, AiProvider};
+use crate::ai::response::{extract_ollama_text, max_response_bytes};
+use crate::ai::retry::RetryPolicy;
+use crate::ai::tokens::{check_prompt_size, max_context_tokens};
+use crate::core::Config;
+use async_trait::async_trait;
+use colored::*;
+use indicatif::ProgressBar;
+use reqwest::Client;
+use serde_json::json;
+use std::time::Duration;
+
+/// Model used when `ai.model` is unset.
+pub const DEFAULT_MODEL: &str = "llama3";
+
+/// Where a local Ollama server listens unless `ai.endpoint` says otherwise.
+pub const DEFAULT_ENDPOINT: &str = "http://localhost:11434";
+
+/// A client for a local or self-hosted Ollama server. Nothing leaves the
+/// machine unless `ai.endpoint` points elsewhere, and no API key is needed.
+pub struct OllamaClient {
+    client: Client,
+    endpoint: String,
+    model: String,
+    temperature: f64,
+    max_output_tokens: u32,
+    max_context_tokens: usize,
+    max_response_bytes: usize,
+    retry: RetryPolicy,
+}
+
+impl OllamaClient {
+    pub fn new(config: &Config) -> Result<Self, AiError> {
+        // Local models can take minutes on a long prompt, so allow more
+        // time than the hosted providers get.
+        let client = Client::builder()
+            .timeout(Duration::from_secs(300))
+            .build()?;
+
+        let endpoint = config.get("ai.endpoint")
+            .filter(|endpoint| !endpoint.trim().is_empty())
+            .map(|endpoint| endpoint.trim().trim_end_matches('/').to_string())
+            .unwrap_or_else(|| DEFAULT_ENDPOINT.to_string());
+
+        Ok(Self {
+            client,
+            endpoint,
+            model: model(config, DEFAULT_MODEL),
+            temperature: temperature(config),
+            max_output_tokens: max_output_tokens(config),
+            max_context_tokens: max_context_tokens(config),
+            max_response_bytes: max_response_bytes(config),
+            retry: RetryPolicy::from_config(config),
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl AiProvider for OllamaClient {
+    fn set_progress(&mut self, progress: ProgressBar) {
+        self.retry.progress = Some(progress);
+    }
+
+    async fn generate_text(&self, prompt: &str) -> Result<String, Box<dyn std::error::Error>> {
+        if let Some(warning) = check_prompt_size(prompt, self.max_context_tokens) {
+            eprintln!("{} {}", "Warning:".yellow(), warning);
+        }
+
+        // With streaming off Ollama accumulates the whole reply into one
+        // JSON object, which keeps parsing the same as the other providers.
+        let payload = json!({
+            "model": self.model,
+            "prompt": prompt,
+            "stream": false,
+            "options": {
+                "temperature": self.temperature,
+                "num_predict": self.max_output_tokens
+            }
+        });
+
+        let url = format!("{}/api/generate", self.endpoint);
+        let response = self.retry.send(|| self
+            .client
+            .post(&url)
+            .json(&payload)
+        ).await
+            .map_err(|e| {
+                if e.is_connect() || e.is_timeout() {
+                    format!("Ollama not reachable at {}: {}", self.endpoint, e)
+                } else {
+                    format!("Ollama request failed: {}", e)
+                }
+            })?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await?;
+            return Err(format!("Ollama error: {} - {}", status, error_text).into());
+        }
+
+        let json: serde_json::Value = response.json().await?;
+        Ok(extract_ollama_text(&json, self.max_response_bytes)?)
+    }
+}
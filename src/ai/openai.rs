@@ -0,0 +1,87 @@
+use crate::ai::{api_key, max_output_tokens, model, temperature, AiError, AiProvider};
+use crate::ai::response::{extract_chat_text, max_response_bytes};
+use crate::ai::retry::RetryPolicy;
+use crate::ai::tokens::{check_prompt_size, max_context_tokens};
+use crate::core::Config;
+use async_trait::async_trait;
+use colored::*;
+use indicatif::ProgressBar;
+use reqwest::Client;
+use serde_json::json;
+use std::time::Duration;
+
+/// Model used when `ai.model` is unset.
+pub const DEFAULT_MODEL: &str = "gpt-4o-mini";
+
+const CHAT_COMPLETIONS_URL: &str = "https://api.openai.com/v1/chat/completions";
+
+pub struct OpenAiClient {
+    client: Client,
+    api_key: String,
+    model: String,
+    temperature: f64,
+    max_output_tokens: u32,
+    max_context_tokens: usize,
+    max_response_bytes: usize,
+    retry: RetryPolicy,
+}
+
+impl OpenAiClient {
+    /// Builds a client from `OPENAI_API_KEY` in the environment or `.env`,
+    /// taking the model and limits from `config`.
+    pub fn new(config: &Config) -> Result<Self, AiError> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()?;
+
+        Ok(Self {
+            client,
+            api_key: api_key("OPENAI_API_KEY")?,
+            model: model(config, DEFAULT_MODEL),
+            temperature: temperature(config),
+            max_output_tokens: max_output_tokens(config),
+            max_context_tokens: max_context_tokens(config),
+            max_response_bytes: max_response_bytes(config),
+            retry: RetryPolicy::from_config(config),
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl AiProvider for OpenAiClient {
+    fn set_progress(&mut self, progress: ProgressBar) {
+        self.retry.progress = Some(progress);
+    }
+
+    async fn generate_text(&self, prompt: &str) -> Result<String, Box<dyn std::error::Error>> {
+        if let Some(warning) = check_prompt_size(prompt, self.max_context_tokens) {
+            eprintln!("{} {}", "Warning:".yellow(), warning);
+        }
+
+        let payload = json!({
+            "model": self.model,
+            "messages": [{
+                "role": "user",
+                "content": prompt
+            }],
+            "temperature": self.temperature,
+            "max_tokens": self.max_output_tokens
+        });
+
+        let response = self.retry.send(|| self
+            .client
+            .post(CHAT_COMPLETIONS_URL)
+            .bearer_auth(&self.api_key)
+            .json(&payload)
+        ).await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await?;
+            return Err(format!("OpenAI API error: {} - {}", status, error_text).into());
+        }
+
+        let json: serde_json::Value = response.json().await?;
+        Ok(extract_chat_text(&json, self.max_response_bytes)?)
+    }
+}
@@ -0,0 +1,183 @@
+use crate::core::Config;
+use serde_json::Value;
+
+/// Largest response text kept when `ai.maxResponseBytes` is unset.
+pub const DEFAULT_MAX_RESPONSE_BYTES: usize = 256 * 1024;
+
+pub const TRUNCATION_NOTE: &str = "(response truncated)";
+
+/// The response cap from `ai.maxResponseBytes`, or the default if it is
+/// unset or not a positive number.
+pub fn max_response_bytes(config: &Config) -> usize {
+    config.get("ai.maxResponseBytes")
+        .and_then(|value| value.parse::<usize>().ok())
+        .filter(|&limit| limit > 0)
+        .unwrap_or(DEFAULT_MAX_RESPONSE_BYTES)
+}
+
+/// Pulls the generated text out of a `generateContent` response, joining
+/// every text part of the first candidate and capping it at `max_bytes`.
+/// Responses without usable text produce an error saying what was wrong
+/// instead of a generic "no response".
+pub fn extract_text(json: &Value, max_bytes: usize) -> Result<String, String> {
+    if let Some(error) = json.get("error") {
+        return Err(format!("Gemini API error: {}", error));
+    }
+
+    let candidate = match json.get("candidates") {
+        Some(Value::Array(candidates)) => candidates.first()
+            .ok_or_else(|| blocked_reason(json).unwrap_or_else(|| "Gemini returned no candidates".to_string()))?,
+        Some(other) => return Err(format!("Malformed Gemini response: 'candidates' is {}", type_name(other))),
+        None => return Err(blocked_reason(json).unwrap_or_else(|| "No response from Gemini API".to_string())),
+    };
+
+    let parts = match candidate.pointer("/content/parts") {
+        Some(Value::Array(parts)) => parts,
+        Some(other) => return Err(format!("Malformed Gemini response: 'parts' is {}", type_name(other))),
+        None => {
+            return Err(match candidate.get("finishReason").and_then(Value::as_str) {
+                Some(reason) => format!("Gemini returned no text (finish reason: {})", reason),
+                None => "Malformed Gemini response: candidate has no content".to_string(),
+            });
+        }
+    };
+
+    let text: String = parts.iter()
+        .filter_map(|part| part.get("text").and_then(Value::as_str))
+        .collect();
+    if text.trim().is_empty() {
+        return Err("Gemini response contained no text".to_string());
+    }
+
+    Ok(truncate_response(text.trim(), max_bytes))
+}
+
+/// The text carried by one event of a `streamGenerateContent` stream. Events
+/// without text, such as a final one holding only usage data, give an empty
+/// string; the caller decides whether the stream as a whole said anything.
+pub fn extract_stream_text(json: &Value) -> Result<String, String> {
+    if let Some(error) = json.get("error") {
+        return Err(format!("Gemini API error: {}", error));
+    }
+
+    let candidate = match json.get("candidates") {
+        Some(Value::Array(candidates)) => match candidates.first() {
+            Some(candidate) => candidate,
+            None => return blocked_reason(json).map_or(Ok(String::new()), Err),
+        },
+        Some(other) => return Err(format!("Malformed Gemini response: 'candidates' is {}", type_name(other))),
+        None => return blocked_reason(json).map_or(Ok(String::new()), Err),
+    };
+
+    Ok(match candidate.pointer("/content/parts") {
+        Some(Value::Array(parts)) => parts.iter()
+            .filter_map(|part| part.get("text").and_then(Value::as_str))
+            .collect(),
+        _ => String::new(),
+    })
+}
+
+/// Removes every complete server-sent event from `buffer` and returns the
+/// `data:` payload of each, leaving a trailing partial event for the next
+/// read. `buffer` holds raw bytes so a character split across network
+/// chunks is only decoded once both halves have arrived.
+pub fn drain_sse_data(buffer: &mut Vec<u8>) -> Vec<String> {
+    buffer.retain(|&byte| byte != b'\r');
+
+    let mut payloads = Vec::new();
+    while let Some(end) = buffer.windows(2).position(|pair| pair == b"\n\n") {
+        let event: Vec<u8> = buffer.drain(..end + 2).collect();
+        let event = String::from_utf8_lossy(&event);
+        let data: Vec<&str> = event.lines()
+            .filter_map(|line| line.strip_prefix("data:"))
+            .map(|data| data.strip_prefix(' ').unwrap_or(data))
+            .collect();
+        if !data.is_empty() {
+            payloads.push(data.join("\n"));
+        }
+    }
+    payloads
+}
+
+/// Pulls the reply out of a chat completions response, capped at
+/// `max_bytes` like `extract_text`.
+pub fn extract_chat_text(json: &Value, max_bytes: usize) -> Result<String, String> {
+    if let Some(error) = json.get("error") {
+        let message = error.get("message").and_then(Value::as_str)
+            .map(str::to_string)
+            .unwrap_or_else(|| error.to_string());
+        return Err(format!("OpenAI API error: {}", message));
+    }
+
+    let choice = match json.get("choices") {
+        Some(Value::Array(choices)) => choices.first().ok_or("OpenAI returned no choices")?,
+        Some(other) => return Err(format!("Malformed OpenAI response: 'choices' is {}", type_name(other))),
+        None => return Err("No response from OpenAI API".to_string()),
+    };
+
+    let text = match choice.pointer("/message/content") {
+        Some(Value::String(text)) => text,
+        Some(Value::Null) | None => {
+            return Err(match choice.get("finish_reason").and_then(Value::as_str) {
+                Some(reason) => format!("OpenAI returned no text (finish reason: {})", reason),
+                None => "Malformed OpenAI response: choice has no message content".to_string(),
+            });
+        }
+        Some(other) => return Err(format!("Malformed OpenAI response: 'content' is {}", type_name(other))),
+    };
+    if text.trim().is_empty() {
+        return Err("OpenAI response contained no text".to_string());
+    }
+
+    Ok(truncate_response(text.trim(), max_bytes))
+}
+
+/// Pulls the reply out of a non-streaming Ollama `/api/generate` response,
+/// capped at `max_bytes` like `extract_text`.
+pub fn extract_ollama_text(json: &Value, max_bytes: usize) -> Result<String, String> {
+    if let Some(error) = json.get("error") {
+        return Err(format!("Ollama error: {}", error.as_str().map(str::to_string).unwrap_or_else(|| error.to_string())));
+    }
+
+    let text = match json.get("response") {
+        Some(Value::String(text)) => text,
+        Some(other) => return Err(format!("Malformed Ollama response: 'response' is {}", type_name(other))),
+        None => return Err("No response from Ollama".to_string()),
+    };
+    if text.trim().is_empty() {
+        return Err("Ollama response contained no text".to_string());
+    }
+
+    Ok(truncate_response(text.trim(), max_bytes))
+}
+
+/// Cuts `text` to at most `max_bytes` on a character boundary and appends a
+/// note saying so. Text within the limit is returned unchanged.
+pub fn truncate_response(text: &str, max_bytes: usize) -> String {
+    if text.len() <= max_bytes {
+        return text.to_string();
+    }
+
+    let mut end = max_bytes;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}\n\n{}", text[..end].trim_end(), TRUNCATION_NOTE)
+}
+
+fn blocked_reason(json: &Value) -> Option<String> {
+    json.pointer("/promptFeedback/blockReason")
+        .and_then(Value::as_str)
+        .map(|reason| format!("Gemini blocked the prompt ({})", reason))
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
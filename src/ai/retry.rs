@@ -0,0 +1,65 @@
+use crate::core::Config;
+use indicatif::ProgressBar;
+use reqwest::{RequestBuilder, Response, StatusCode};
+use std::time::Duration;
+
+/// Retries after the first attempt when `ai.maxRetries` is unset.
+pub const DEFAULT_MAX_RETRIES: u32 = 3;
+
+/// Wait before the first retry; each later retry waits twice as long.
+pub const BASE_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// How often a request is retried and where to report it.
+#[derive(Clone, Default)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    /// Spinner whose message announces each retry.
+    pub progress: Option<ProgressBar>,
+}
+
+impl RetryPolicy {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            max_retries: config.get("ai.maxRetries")
+                .and_then(|value| value.parse::<u32>().ok())
+                .unwrap_or(DEFAULT_MAX_RETRIES),
+            progress: None,
+        }
+    }
+
+    /// Sends the request built by `build`, retrying rate limits, server
+    /// errors and timeouts with exponential backoff. Any other response,
+    /// successful or not, is returned for the caller to inspect; so is the
+    /// last one once retries run out.
+    pub async fn send(&self, build: impl Fn() -> RequestBuilder) -> Result<Response, reqwest::Error> {
+        let mut attempt = 0;
+        loop {
+            let reason = match build().send().await {
+                Ok(response) if attempt < self.max_retries && is_retryable_status(response.status()) => {
+                    response.status().to_string()
+                },
+                Err(e) if attempt < self.max_retries && e.is_timeout() => "request timed out".to_string(),
+                result => return result,
+            };
+
+            let delay = backoff_delay(attempt);
+            attempt += 1;
+            if let Some(progress) = &self.progress {
+                progress.set_message(format!("{}, retrying in {}ms ({}/{})...",
+                                             reason, delay.as_millis(), attempt, self.max_retries));
+            }
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+/// Responses worth retrying: rate limiting and transient server errors.
+/// Client errors such as 400, 401 and 403 will not succeed on a retry.
+pub fn is_retryable_status(status: StatusCode) -> bool {
+    matches!(status.as_u16(), 429 | 500 | 502 | 503)
+}
+
+/// 500ms, 1s, 2s, ... for retries 0, 1, 2, ...
+pub fn backoff_delay(retry: u32) -> Duration {
+    BASE_RETRY_DELAY * 2u32.saturating_pow(retry.min(16))
+}
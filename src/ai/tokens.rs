@@ -0,0 +1,37 @@
+use crate::core::Config;
+
+/// Rough characters-per-token ratio for English text and code.
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Input limit of gemini-1.5-flash, used when `ai.maxContextTokens` is unset.
+pub const DEFAULT_MAX_CONTEXT_TOKENS: usize = 1_048_576;
+
+/// Estimates how many tokens `text` will use. This is a heuristic, not the
+/// model's tokenizer, so treat it as an order of magnitude.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(CHARS_PER_TOKEN)
+}
+
+/// The context limit from `ai.maxContextTokens`, or the default if it is
+/// unset or not a positive number.
+pub fn max_context_tokens(config: &Config) -> usize {
+    config.get("ai.maxContextTokens")
+        .and_then(|value| value.parse::<usize>().ok())
+        .filter(|&limit| limit > 0)
+        .unwrap_or(DEFAULT_MAX_CONTEXT_TOKENS)
+}
+
+/// Returns a warning when the estimated size of `prompt` exceeds `max_tokens`.
+pub fn check_prompt_size(prompt: &str, max_tokens: usize) -> Option<String> {
+    let estimated = estimate_tokens(prompt);
+    if estimated <= max_tokens {
+        return None;
+    }
+
+    Some(format!(
+        "Prompt is ~{} tokens, which exceeds the {} token context limit (ai.maxContextTokens). \
+        The request may be truncated or rejected; consider splitting the change into smaller chunks \
+        or narrowing the scope to specific files.",
+        estimated, max_tokens
+    ))
+}
@@ -1,6 +1,7 @@
-use crate::core::{Repository, Index, Object, ObjectType};
+use crate::core::{Repository, Index, Object, ObjectType, Quarantine};
+use crate::core::index::index_path;
 use crate::utils::ignore::GitIgnore;
-use walkdir::WalkDir;
+use crate::utils::worktree::{kind_of, nested_repo_root, read_entry_content, walk_worktree, EntryKind, SYMLINK_MODE};
 use std::path::Path;
 use colored::*;
 use indicatif::{ProgressBar, ProgressStyle};
@@ -8,9 +9,15 @@ use ring::digest;
 use std::io::Write;
 
 pub async fn run(files: Vec<String>, all: bool) -> Result<(), Box<dyn std::error::Error>> {
-    let repo = Repository::new(".aigit").ok_or("Not in a repository")?;
+    let repo = Repository::discover().ok_or("Not in a repository")?;
     let mut index = Index::load(&repo)?;
     let ignore = GitIgnore::new(&repo.path);
+    let config = repo.config();
+    let mut quarantine = if config.get_bool("security.quarantineSecrets").unwrap_or(false) {
+        Some(Quarantine::load(&repo)?)
+    } else {
+        None
+    };
     
     let pb = ProgressBar::new_spinner();
     pb.set_style(ProgressStyle::default_spinner().template("{spinner} {msg}").unwrap());
@@ -20,59 +27,89 @@ pub async fn run(files: Vec<String>, all: bool) -> Result<(), Box<dyn std::error
     let mut added_count = 0;
     let mut skipped_count = 0;
     let mut error_count = 0;
+    let mut quarantined_count = 0;
+
+    // A directory argument adds everything under it, the way `.` (or --all)
+    // adds the whole tree and makes other arguments redundant.
+    let (dirs, files): (Vec<String>, Vec<String>) = if all || files.contains(&".".to_string()) {
+        (vec![".".to_string()], Vec::new())
+    } else {
+        files.into_iter().partition(|file| kind_of(file) == Some(EntryKind::Directory))
+    };
+
+    for dir in &dirs {
+        let walk = walk_worktree(dir);
+        for nested in &walk.nested_repos {
+            pb.println(format!("{} {} (nested repo)", "Skipping".yellow(), nested));
+            skipped_count += 1;
+        }
 
-    if all || files.contains(&".".to_string()) {
-        for entry in WalkDir::new(".")
-            .into_iter()
-            .filter_map(|e| e.ok())
-            .filter(|e| e.file_type().is_file())
-            .filter(|e| !e.path().starts_with(".aigit"))
-        {
-            let path = entry.path();
+        for path in &walk.files {
+            let path = path.as_path();
             
             if ignore.is_ignored(path) {
                 skipped_count += 1;
                 continue;
             }
             
+            let path_str = match path.to_str() {
+                Some(path_str) => path_str,
+                None => {
+                    println!("{} {} (path is not valid UTF-8)", "Skipping".yellow(), path.to_string_lossy());
+                    skipped_count += 1;
+                    continue;
+                }
+            };
+            
             if is_secure_file(path)? {
-                match add_file_to_index(&mut index, &repo, path.to_str().unwrap()).await {
-                    Ok(true) => added_count += 1,
-                    Ok(false) => skipped_count += 1,
+                match add_file_to_index(&mut index, &repo, path_str, quarantine.as_mut()).await {
+                    Ok(AddOutcome::Added) => added_count += 1,
+                    Ok(AddOutcome::Skipped) => skipped_count += 1,
+                    Ok(AddOutcome::Quarantined) => quarantined_count += 1,
                     Err(_) => error_count += 1,
                 }
             } else {
                 skipped_count += 1;
             }
         }
-    } else {
-        for file in files {
-            if !Path::new(&file).exists() {
-                pb.finish_and_clear();
-                println!("{} {}", "File not found:".red(), file);
-                return Err("File not found".into());
-            }
-            
-            if ignore.is_ignored(&file) {
-                println!("{} {} (ignored)", "Skipping".yellow(), file);
-                skipped_count += 1;
-                continue;
-            }
-            
-            if is_secure_file(Path::new(&file))? {
-                match add_file_to_index(&mut index, &repo, &file).await {
-                    Ok(true) => added_count += 1,
-                    Ok(false) => skipped_count += 1,
-                    Err(_) => error_count += 1,
-                }
-            } else {
-                println!("{} {} (security check failed)", "Skipping".yellow(), file);
-                skipped_count += 1;
+    }
+
+    for file in files {
+        if kind_of(&file).is_none() {
+            pb.finish_and_clear();
+            println!("{} {}", "File not found:".red(), file);
+            return Err("File not found".into());
+        }
+        
+        if ignore.is_ignored(&file) {
+            println!("{} {} (ignored)", "Skipping".yellow(), file);
+            skipped_count += 1;
+            continue;
+        }
+        
+        if let Some(nested) = nested_repo_root(&file) {
+            println!("{} {} (inside nested repo {})", "Skipping".yellow(), file, nested);
+            skipped_count += 1;
+            continue;
+        }
+        
+        if is_secure_file(Path::new(&file))? {
+            match add_file_to_index(&mut index, &repo, &file, quarantine.as_mut()).await {
+                Ok(AddOutcome::Added) => added_count += 1,
+                Ok(AddOutcome::Skipped) => skipped_count += 1,
+                Ok(AddOutcome::Quarantined) => quarantined_count += 1,
+                Err(_) => error_count += 1,
             }
+        } else {
+            println!("{} {} (security check failed)", "Skipping".yellow(), file);
+            skipped_count += 1;
         }
     }
 
     index.save(&repo)?;
+    if let Some(quarantine) = &quarantine {
+        quarantine.save(&repo)?;
+    }
     pb.finish_and_clear();
     
     if added_count > 0 {
@@ -84,8 +121,12 @@ pub async fn run(files: Vec<String>, all: bool) -> Result<(), Box<dyn std::error
     if error_count > 0 {
         println!("{} {} files", "Errors".red(), error_count);
     }
+    if quarantined_count > 0 {
+        println!("{} {} files containing secrets", "Quarantined".red(), quarantined_count);
+        println!("{}", "Review with 'aigit status', clear with 'aigit quarantine clear'".bright_black());
+    }
     
-    if added_count == 0 && skipped_count == 0 && error_count == 0 {
+    if added_count == 0 && skipped_count == 0 && error_count == 0 && quarantined_count == 0 {
         println!("{}", "No files to add".yellow());
     }
     
@@ -93,15 +134,33 @@ pub async fn run(files: Vec<String>, all: bool) -> Result<(), Box<dyn std::error
     Ok(())
 }
 
-async fn add_file_to_index(index: &mut Index, repo: &Repository, file_path: &str) -> Result<bool, Box<dyn std::error::Error>> {
-    let content = std::fs::read(file_path)?;
+enum AddOutcome {
+    Added,
+    Skipped,
+    Quarantined,
+}
+
+async fn add_file_to_index(
+    index: &mut Index, 
+    repo: &Repository, 
+    file_path: &str, 
+    quarantine: Option<&mut Quarantine>
+) -> Result<AddOutcome, Box<dyn std::error::Error>> {
+    let file_path = &index_path(file_path);
+    let content = read_entry_content(file_path)?;
     
     if content.len() > 104_857_600 {
         println!("{} {} (file too large)", "Skipping".yellow(), file_path);
-        return Ok(false);
+        return Ok(AddOutcome::Skipped);
     }
     
-    scan_file_content(&content, file_path)?;
+    if let Some(pattern) = scan_file_content(&content, file_path)? {
+        if let Some(quarantine) = quarantine {
+            println!("{} {} (matched {})", "Quarantined".red(), file_path, pattern.bright_black());
+            quarantine.record(file_path, pattern);
+            return Ok(AddOutcome::Quarantined);
+        }
+    }
     
     let blob_hash = Object::create(repo, ObjectType::Blob, &content)?;
     let mode = get_file_mode(file_path);
@@ -110,7 +169,7 @@ async fn add_file_to_index(index: &mut Index, repo: &Repository, file_path: &str
     
     index.add_entry_secure(file_path.to_string(), blob_hash, mode, size, checksum);
     
-    Ok(true)
+    Ok(AddOutcome::Added)
 }
 
 fn is_secure_file(path: &Path) -> Result<bool, Box<dyn std::error::Error>> {
@@ -143,9 +202,9 @@ fn is_secure_file(path: &Path) -> Result<bool, Box<dyn std::error::Error>> {
     Ok(true)
 }
 
-fn scan_file_content(content: &[u8], file_path: &str) -> Result<(), Box<dyn std::error::Error>> {
+fn scan_file_content(content: &[u8], file_path: &str) -> Result<Option<&'static str>, Box<dyn std::error::Error>> {
     if content.is_empty() {
-        return Ok(());
+        return Ok(None);
     }
     
     let text_content = String::from_utf8_lossy(content);
@@ -163,15 +222,19 @@ fn scan_file_content(content: &[u8], file_path: &str) -> Result<(), Box<dyn std:
         if let Ok(re) = regex::Regex::new(pattern) {
             if re.is_match(&text_content) {
                 println!("{} {} contains potentially sensitive data", "Warning".yellow(), file_path);
-                break;
+                return Ok(Some(*pattern));
             }
         }
     }
     
-    Ok(())
+    Ok(None)
 }
 
-fn get_file_mode(file_path: &str) -> String {
+pub fn get_file_mode(file_path: &str) -> String {
+    if kind_of(file_path) == Some(EntryKind::Symlink) {
+        return SYMLINK_MODE.to_string();
+    }
+    
     #[cfg(unix)]
     {
         use std::os::unix::fs::PermissionsExt;
@@ -0,0 +1,241 @@
+use crate::core::{Repository, Index, Object, ObjectType, Commit, Author, Tree, Branch};
+use crate::core::reflog;
+use crate::commands::add::get_file_mode;
+use crate::commands::checkout::update_worktree;
+use crate::utils::patch::{apply_patches, parse_mail, parse_patch, check_patch_path, patch_target, MailPatch};
+use crate::utils::worktree::{read_entry_content, remove_file_and_empty_parents};
+use chrono::Utc;
+use colored::*;
+use ring::digest;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::PathBuf;
+
+/// An `am` run that stopped on a patch, kept in `.aigit/am-state.json` so
+/// `--continue`, `--skip` and `--abort` can pick it up.
+#[derive(Serialize, Deserialize, Default)]
+struct AmState {
+    /// Each patch mail, in the order they are applied.
+    patches: Vec<String>,
+    /// Index into `patches` of the patch being applied.
+    next: usize,
+    /// HEAD when the run started, restored by `--abort`.
+    orig_head: Option<String>,
+}
+
+impl AmState {
+    fn path(repo: &Repository) -> PathBuf {
+        repo.git_dir.join("am-state.json")
+    }
+
+    fn load(repo: &Repository) -> Result<Option<Self>, Box<dyn std::error::Error>> {
+        let path = Self::path(repo);
+        if !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_slice(&std::fs::read(path)?)?))
+    }
+
+    fn save(&self, repo: &Repository) -> Result<(), Box<dyn std::error::Error>> {
+        std::fs::write(Self::path(repo), serde_json::to_vec_pretty(self)?)?;
+        Ok(())
+    }
+
+    fn remove(repo: &Repository) -> Result<(), Box<dyn std::error::Error>> {
+        let path = Self::path(repo);
+        if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+}
+
+/// Applies each patch mail in `files` and commits it with the original
+/// author, date and message. On a patch that does not apply the run stops;
+/// `resume` finishes it once the working tree has been fixed up, `skip`
+/// drops it, and `abort` returns to where the run started.
+pub async fn run(files: Vec<String>, resume: bool, skip: bool, abort: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let repo = Repository::discover().ok_or("Not in a repository")?;
+    let existing = AmState::load(&repo)?;
+
+    if resume || skip || abort {
+        let mut state = existing.ok_or("No am session in progress")?;
+
+        if abort {
+            if let Some(orig_head) = &state.orig_head {
+                update_worktree(&repo, orig_head, true)?;
+                update_head(&repo, orig_head)?;
+            }
+            AmState::remove(&repo)?;
+            println!("{} am session aborted", "✓".green());
+            return Ok(());
+        }
+
+        if resume {
+            let mail = parse_mail(&state.patches[state.next])?;
+            let paths: Vec<String> = parse_patch(&mail.diff)?
+                .iter()
+                .flat_map(|patch| [patch.old_path.clone(), patch.new_path.clone()])
+                .flatten()
+                .collect();
+            let hash = commit_mail(&repo, &mail, &paths)?;
+            print_applied(&hash, &mail);
+        } else {
+            println!("{} {}", "Skipped:".yellow(), parse_mail(&state.patches[state.next])?.subject);
+        }
+        state.next += 1;
+        return apply_remaining(&repo, state).await;
+    }
+
+    if existing.is_some() {
+        return Err("An am session is already in progress; use --continue, --skip or --abort".into());
+    }
+    if files.is_empty() {
+        return Err("Please specify the patch files to apply".into());
+    }
+
+    let mut patches = Vec::new();
+    for file in &files {
+        let text = std::fs::read_to_string(file)
+            .map_err(|e| format!("Cannot read patch '{}': {}", file, e))?;
+        patches.extend(split_mbox(&text));
+    }
+
+    let state = AmState {
+        patches,
+        next: 0,
+        orig_head: Branch::get_current_commit(&repo),
+    };
+    apply_remaining(&repo, state).await
+}
+
+async fn apply_remaining(repo: &Repository, mut state: AmState) -> Result<(), Box<dyn std::error::Error>> {
+    let total = state.patches.len();
+
+    while state.next < total {
+        let mail = parse_mail(&state.patches[state.next])?;
+        let file_patches = parse_patch(&mail.diff)?;
+
+        let results = match apply_patches(&file_patches, |path| std::fs::read_to_string(patch_target(&repo.path, path).ok()?).ok()) {
+            Ok(results) => results,
+            Err(errors) => {
+                state.save(repo)?;
+                println!("{} {}", "Patch failed:".red().bold(), mail.subject);
+                for error in &errors {
+                    println!("  {}", error);
+                }
+                println!("{}", "Fix up the working tree and run 'aigit am --continue',".yellow());
+                println!("{}", "or use 'aigit am --skip' to drop this patch or 'aigit am --abort' to give up.".yellow());
+                return Err(format!("Patch {}/{} does not apply", state.next + 1, total).into());
+            }
+        };
+
+        for (path, content) in &results {
+            let target = patch_target(&repo.path, path)?;
+            match content {
+                Some(content) => {
+                    if let Some(parent) = target.parent() {
+                        std::fs::create_dir_all(parent)?;
+                    }
+                    std::fs::write(&target, content)?;
+                },
+                None => remove_file_and_empty_parents(&target)?,
+            }
+        }
+
+        let paths: Vec<String> = results.keys().cloned().collect();
+        let hash = commit_mail(repo, &mail, &paths)?;
+        print_applied(&hash, &mail);
+        state.next += 1;
+    }
+
+    AmState::remove(repo)?;
+    audit_am(total).await?;
+    Ok(())
+}
+
+/// Splits an mbox holding several patch mails at each `From <hash>` line.
+fn split_mbox(text: &str) -> Vec<String> {
+    let mut mails: Vec<String> = Vec::new();
+    for line in text.split_inclusive('\n') {
+        if (line.starts_with("From ") && !line.starts_with("From: ")) || mails.is_empty() {
+            mails.push(String::new());
+        }
+        if let Some(mail) = mails.last_mut() {
+            mail.push_str(line);
+        }
+    }
+    mails.retain(|mail| !mail.trim().is_empty());
+    mails
+}
+
+/// Stages `paths` as they are in the working tree and commits them with the
+/// mail's author, date and message.
+fn commit_mail(repo: &Repository, mail: &MailPatch, paths: &[String]) -> Result<String, Box<dyn std::error::Error>> {
+    let mut index = Index::load(repo)?;
+    for path in paths {
+        let relative = path.strip_prefix("./").unwrap_or(path);
+        check_patch_path(relative)?;
+        index.remove_entry(relative);
+
+        if let Ok(content) = read_entry_content(relative) {
+            let hash = Object::create(repo, ObjectType::Blob, &content)?;
+            let checksum = hex::encode(digest::digest(&digest::SHA256, &content).as_ref());
+            index.add_entry_secure(relative.to_string(), hash, get_file_mode(relative), content.len() as u64, checksum);
+        }
+    }
+    index.save(repo)?;
+
+    let tree = Tree::create_from_index(repo, &index)?;
+    let parent = Branch::get_current_commit(repo);
+    let config = repo.config();
+
+    let mut commit = Commit::new(tree, parent.clone(), mail.author_name.clone(), mail.author_email.clone(), mail.message());
+    commit.author.timestamp = mail.date;
+    commit.committer = Author {
+        name: config.get_user_name(),
+        email: config.get_user_email(),
+        timestamp: Utc::now(),
+    };
+
+    let hash = Object::create(repo, ObjectType::Commit, &serde_json::to_vec(&commit)?)?;
+    update_head(repo, &hash)?;
+    reflog::append(repo, parent.as_deref(), &hash, &format!("am: {}", mail.subject))?;
+    Ok(hash)
+}
+
+fn update_head(repo: &Repository, commit_hash: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let head_content = std::fs::read_to_string(repo.git_dir.join("HEAD"))?;
+    match head_content.trim().strip_prefix("ref: ") {
+        Some(ref_path) => std::fs::write(repo.git_dir.join(ref_path), commit_hash)?,
+        None => std::fs::write(repo.git_dir.join("HEAD"), commit_hash)?,
+    }
+    Ok(())
+}
+
+fn print_applied(hash: &str, mail: &MailPatch) {
+    println!("{} {} {} {}",
+            "Applied:".green(),
+            hash[..8].bright_yellow(),
+            mail.subject,
+            format!("({})", mail.author_name).bright_black());
+}
+
+async fn audit_am(patches: usize) -> Result<(), Box<dyn std::error::Error>> {
+    let audit_file = std::path::PathBuf::from(".aigit/logs/audit.log");
+    if !audit_file.exists() {
+        return Ok(());
+    }
+
+    let timestamp = chrono::Utc::now().to_rfc3339();
+    let user = whoami::username();
+    let details = format!("patches:{}", patches);
+
+    let entry = format!("{},am,{},{},commit\n", timestamp, user, details);
+    std::fs::OpenOptions::new()
+        .append(true)
+        .open(audit_file)?
+        .write_all(entry.as_bytes())?;
+
+    Ok(())
+}
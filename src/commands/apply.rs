@@ -0,0 +1,82 @@
+use crate::core::Repository;
+use crate::utils::patch::{apply_patches, parse_patch, patch_target, FilePatch};
+use colored::*;
+use std::io::{Read, Write};
+
+/// Applies a unified diff from `patch_file` (`-` reads stdin) to the working
+/// tree. With `reverse` the inverse of each change is applied; with `check`
+/// nothing is written and the command only reports whether the patch applies.
+/// Either every file applies or nothing is changed.
+pub async fn run(patch_file: String, reverse: bool, check: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let repo = Repository::discover().ok_or("Not in a repository")?;
+
+    let text = if patch_file == "-" {
+        let mut text = String::new();
+        std::io::stdin().read_to_string(&mut text)?;
+        text
+    } else {
+        std::fs::read_to_string(&patch_file)
+            .map_err(|e| format!("Cannot read patch '{}': {}", patch_file, e))?
+    };
+
+    let mut patches = parse_patch(&text)?;
+    if reverse {
+        patches = patches.iter().map(FilePatch::reversed).collect();
+    }
+
+    let results = match apply_patches(&patches, |path| std::fs::read_to_string(patch_target(&repo.path, path).ok()?).ok()) {
+        Ok(results) => results,
+        Err(errors) => {
+            for error in &errors {
+                println!("{} {}", "error:".red(), error);
+            }
+            return Err(format!("Patch does not apply ({} error(s))", errors.len()).into());
+        }
+    };
+
+    if check {
+        println!("{} {} file(s) would be patched cleanly", "✓".green(), results.len());
+        return Ok(());
+    }
+
+    for (path, content) in &results {
+        let target = patch_target(&repo.path, path)?;
+        match content {
+            Some(content) => {
+                if let Some(parent) = target.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::write(&target, content)?;
+                println!("{} {}", "Patched".green(), path);
+            },
+            None => {
+                if target.exists() {
+                    std::fs::remove_file(&target)?;
+                }
+                println!("{} {}", "Deleted".red(), path);
+            },
+        }
+    }
+
+    audit_apply(&patch_file, results.len(), reverse).await?;
+    Ok(())
+}
+
+async fn audit_apply(patch_file: &str, files: usize, reverse: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let audit_file = std::path::PathBuf::from(".aigit/logs/audit.log");
+    if !audit_file.exists() {
+        return Ok(());
+    }
+
+    let timestamp = chrono::Utc::now().to_rfc3339();
+    let user = whoami::username();
+    let details = format!("patch:{},files:{},reverse:{}", patch_file, files, reverse);
+
+    let entry = format!("{},apply,{},{},files\n", timestamp, user, details);
+    std::fs::OpenOptions::new()
+        .append(true)
+        .open(audit_file)?
+        .write_all(entry.as_bytes())?;
+
+    Ok(())
+}
@@ -1,5 +1,5 @@
 use crate::core::{Repository, Branch, Config};
-use crate::ai::gemini::GeminiClient;
+use crate::ai::{provider_with_progress, require_ai};
 use crate::utils::analyzer::analyze_codebase;
 use std::fs;
 use colored::*;
@@ -8,87 +8,157 @@ use std::io::Write;
 
 pub async fn run(
     name: Option<String>, 
+    start_point: Option<String>,
     delete: Option<String>, 
     ai_suggest: bool
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let repo = Repository::new(".aigit").ok_or("Not in a repository")?;
-    let config = Config::load_repo(&repo).unwrap_or_else(|_| Config::load_global().unwrap_or_default());
+    let repo = Repository::discover().ok_or("Not in a repository")?;
+    let config = repo.config();
 
     if let Some(branch_name) = delete {
-        delete_branch(&repo, &branch_name, &config).await?;
+        delete_branch(&repo, &branch_name, config).await?;
         return Ok(());
     }
 
     if ai_suggest {
+        require_ai(config)?;
         suggest_branch_names(&repo).await?;
         return Ok(());
     }
 
     if let Some(branch_name) = name {
         validate_branch_name(&branch_name)?;
-        create_branch(&repo, &branch_name, &config).await?;
+        create_branch(&repo, &branch_name, start_point.as_deref(), config).await?;
     } else {
-        list_branches(&repo, &config).await?;
+        list_branches(&repo, config).await?;
     }
     
     Ok(())
 }
 
+pub async fn copy(source: Option<String>, target: String, force: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let repo = Repository::discover().ok_or("Not in a repository")?;
+    let config = repo.config();
+
+    let source = match source {
+        Some(source) => source,
+        None => Branch::get_current_branch(&repo).ok_or("No current branch to copy")?,
+    };
+    validate_branch_name(&target)?;
+
+    let source_path = repo.heads_dir().join(&source);
+    if !source_path.exists() {
+        return Err(format!("Branch '{}' does not exist", source).into());
+    }
+
+    let target_path = repo.heads_dir().join(&target);
+    if target_path.exists() && !force {
+        return Err(format!("Branch '{}' already exists (use -C to overwrite)", target).into());
+    }
+
+    let tip = fs::read_to_string(&source_path)?.trim().to_string();
+    fs::write(&target_path, &tip)?;
+
+    let copied = copy_branch_config(&repo, &source, &target)?;
+
+    println!("{} {} {} {}",
+            "Copied branch:".green(),
+            source.bright_cyan(),
+            "to".bright_black(),
+            target.bright_cyan());
+    if copied > 0 {
+        println!("{} {} config keys", "Copied".bright_black(), copied.to_string().bright_yellow());
+    }
+
+    let tip = if tip.is_empty() { None } else { Some(tip) };
+    audit_branch_operation("copy", &format!("{}->{}", source, target), &tip, config).await?;
+    Ok(())
+}
+
+fn copy_branch_config(repo: &Repository, source: &str, target: &str) -> Result<usize, Box<dyn std::error::Error>> {
+    let mut repo_config = Config::load_repo(repo)?;
+    let source_prefix = format!("branch.{}.", source);
+    let target_prefix = format!("branch.{}.", target);
+
+    let stale: Vec<String> = repo_config.iter()
+        .filter(|(key, _)| key.starts_with(&target_prefix))
+        .map(|(key, _)| key.clone())
+        .collect();
+    for key in stale {
+        repo_config.remove(&key);
+    }
+
+    let copied: Vec<(String, String)> = repo_config.iter()
+        .filter_map(|(key, value)| {
+            key.strip_prefix(&source_prefix)
+                .map(|suffix| (format!("{}{}", target_prefix, suffix), value.clone()))
+        })
+        .collect();
+
+    if copied.is_empty() {
+        return Ok(0);
+    }
+
+    for (key, value) in &copied {
+        repo_config.set(key, value);
+    }
+    repo_config.save_repo(repo)?;
+
+    Ok(copied.len())
+}
+
 fn validate_branch_name(name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    validate_ref_name(name, "Branch")
+}
+
+/// Name rules shared by branches and tags; `kind` starts each error message.
+pub fn validate_ref_name(name: &str, kind: &str) -> Result<(), Box<dyn std::error::Error>> {
     if name.is_empty() {
-        return Err("Branch name cannot be empty".into());
+        return Err(format!("{} name cannot be empty", kind).into());
     }
     
     if name.len() > 100 {
-        return Err("Branch name too long (max 100 characters)".into());
+        return Err(format!("{} name too long (max 100 characters)", kind).into());
     }
     
     let invalid_chars = ['~', '^', ':', '?', '*', '[', '\\', ' ', '\t', '\n'];
     if name.chars().any(|c| invalid_chars.contains(&c)) {
-        return Err("Branch name contains invalid characters".into());
+        return Err(format!("{} name contains invalid characters", kind).into());
     }
     
     if name.starts_with('-') || name.ends_with('.') || name.contains("..") {
-        return Err("Invalid branch name format".into());
+        return Err(format!("Invalid {} name format", kind.to_lowercase()).into());
     }
     
     let reserved_names = ["HEAD", "ORIG_HEAD", "FETCH_HEAD", "MERGE_HEAD"];
     if reserved_names.contains(&name) {
-        return Err("Branch name is reserved".into());
+        return Err(format!("{} name is reserved", kind).into());
     }
     
     Ok(())
 }
 
-async fn create_branch(repo: &Repository, name: &str, config: &Config) -> Result<(), Box<dyn std::error::Error>> {
-    let branch_path = repo.heads_dir().join(name);
-    
-    if branch_path.exists() {
-        return Err(format!("Branch '{}' already exists", name).into());
+async fn create_branch(
+    repo: &Repository,
+    name: &str,
+    start_point: Option<&str>,
+    config: &Config
+) -> Result<(), Box<dyn std::error::Error>> {
+    if start_point.is_none() && Branch::get_current_commit(repo).is_none() {
+        return Err(format!(
+            "Cannot create branch '{}': HEAD has no commits yet. Use 'aigit checkout -b {}' to start it unborn",
+            name, name
+        ).into());
     }
 
-    let head_commit = Branch::get_current_commit(repo);
-    // let branch_id = generate_branch_id(name, &head_commit);
-    
-    match &head_commit {
-        Some(commit_hash) => {
-            fs::write(&branch_path, commit_hash)?;
-            println!("{} {} {} {}", 
-                    "Created branch:".green(), 
-                    name.bright_cyan(),
-                    "at".bright_black(),
-                    commit_hash[..8].bright_yellow());
-        },
-        None => {
-            fs::write(&branch_path, "")?;
-            println!("{} {} {}", 
-                    "Created empty branch:".green(), 
-                    name.bright_cyan(), 
-                    "(no commits yet)".bright_black());
-        }
-    }
+    let commit_hash = Branch::create(repo, name, start_point)?;
+    println!("{} {} {} {}", 
+            "Created branch:".green(), 
+            name.bright_cyan(),
+            "at".bright_black(),
+            commit_hash[..8].bright_yellow());
     
-    audit_branch_operation("create", name, &head_commit, config).await?;
+    audit_branch_operation("create", name, &Some(commit_hash), config).await?;
     Ok(())
 }
 
@@ -128,7 +198,15 @@ async fn delete_branch(repo: &Repository, name: &str, config: &Config) -> Result
 
 async fn list_branches(repo: &Repository, config: &Config) -> Result<(), Box<dyn std::error::Error>> {
     let current_branch = Branch::get_current_branch(repo);
-    let branches = Branch::list(repo)?;
+    let mut branches = Branch::list(repo)?;
+
+    // An unborn current branch has no ref file yet, but it is still the branch you are on.
+    if let Some(current) = &current_branch {
+        if !branches.iter().any(|branch| &branch.name == current) {
+            branches.push(Branch::new(current.clone(), None));
+            branches.sort_by(|a, b| a.name.cmp(&b.name));
+        }
+    }
     
     if branches.is_empty() {
         println!("{}", "No branches found".yellow());
@@ -162,7 +240,7 @@ async fn list_branches(repo: &Repository, config: &Config) -> Result<(), Box<dyn
         }
     }
     
-    if config.get("security.auditLog").map(|v| v == "true").unwrap_or(false) {
+    if config.get_bool("security.auditLog").unwrap_or(false) {
         println!("\n{} Branch operations are being audited", "🔍".cyan());
     }
     
@@ -176,9 +254,15 @@ async fn suggest_branch_names(repo: &Repository) -> Result<(), Box<dyn std::erro
     pb.enable_steady_tick(std::time::Duration::from_millis(100));
 
     let context = analyze_codebase(repo).await;
-    let gemini = GeminiClient::new();
+    let provider = match provider_with_progress(repo.config(), &pb) {
+        Ok(provider) => provider,
+        Err(e) => {
+            pb.finish_and_clear();
+            return Err(e.to_string().into());
+        }
+    };
 
-    match gemini.suggest_branch_name(&context).await {
+    match provider.suggest_branch_name(&context).await {
         Ok(suggestions) => {
             pb.finish_and_clear();
             println!("{}", "AI suggested branch names:".cyan().bold());
@@ -234,7 +318,7 @@ async fn audit_branch_operation(
     commit_hash: &Option<String>, 
     config: &Config
 ) -> Result<(), Box<dyn std::error::Error>> {
-    if !config.get("security.auditLog").map(|v| v == "true").unwrap_or(false) {
+    if !config.get_bool("security.auditLog").unwrap_or(false) {
         return Ok(());
     }
     
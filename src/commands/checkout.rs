@@ -1,17 +1,36 @@
-use crate::core::{Repository, Branch};
+use crate::core::{Repository, Branch, Index, Object, ObjectType, Commit, Tree, TreeEntry, resolve_revision};
+use crate::core::object::hash_object;
+use crate::utils::worktree::{read_entry_content, remove_file_and_empty_parents, write_entry};
 use colored::*;
+use ring::digest;
+use std::collections::{BTreeMap, BTreeSet};
 use std::path::Path;
 
-pub async fn run(target: String, create: bool) -> Result<(), Box<dyn std::error::Error>> {
+pub async fn run(target: Option<String>, create: bool, force: bool, paths: Vec<String>) -> Result<(), Box<dyn std::error::Error>> {
+    // `checkout [<revision>] -- <paths>` restores files instead of switching.
+    if !paths.is_empty() {
+        if create {
+            return Err("Cannot create a branch while checking out paths".into());
+        }
+        return crate::commands::restore::run_from(target, paths).await;
+    }
+
+    let target = target.ok_or("Please specify a branch or commit to check out")?;
     let repo_path = Path::new(".aigit");
     if !repo_path.exists() {
         return Err("Not in an AIGIT repository".into());
     }
 
-    let repo = Repository::new(".aigit")
+    let repo = Repository::discover()
         .ok_or("Failed to open repository")?;
     
     if create {
+        if Branch::get_current_commit(&repo).is_none() {
+            // Nothing to branch from yet: move HEAD and let the first commit create the ref.
+            Branch::start_unborn(&repo, &target)?;
+            println!("{} Switched to a new branch '{}' (no commits yet)", "✓".green(), target);
+            return Ok(());
+        }
         // Create and checkout new branch
         println!("{} Creating and switching to branch '{}'", "✓".green(), target);
         Branch::create(&repo, &target, None)?;
@@ -19,20 +38,123 @@ pub async fn run(target: String, create: bool) -> Result<(), Box<dyn std::error:
         println!("{} Switched to new branch '{}'", "✓".green(), target);
     } else {
         // Checkout existing branch or commit
-        let branch_path = repo.heads_dir().join(&target);
-        if branch_path.exists() {
-            Branch::checkout(&repo, &target)?;
-            println!("{} Switched to branch '{}'", "✓".green(), target);
+        if repo.heads_dir().join(&target).exists() {
+            switch_to_branch(&repo, &target, force)?;
         } else {
-            // Try to checkout as commit hash
-            if target.len() >= 4 && target.chars().all(|c| c.is_ascii_hexdigit()) {
-                Branch::checkout(&repo, &target)?;
-                println!("{} Switched to commit '{}'", "✓".green(), target);
-            } else {
-                return Err(format!("Branch '{}' does not exist. Use --create to create it.", target).into());
-            }
+            // Any other revision (a hash, tag or HEAD@{n}) detaches HEAD.
+            let commit = match resolve_revision(&repo, &target) {
+                Ok(commit) => commit,
+                Err(_) if !target.chars().all(|c| c.is_ascii_hexdigit()) => {
+                    return Err(format!("Branch '{}' does not exist. Use --create to create it.", target).into());
+                },
+                Err(e) => return Err(e),
+            };
+            update_worktree(&repo, &commit, force)?;
+            Branch::checkout(&repo, &commit)?;
+            println!("{} Switched to commit '{}'", "✓".green(), target);
         }
     }
     
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Updates the working tree to the tip of the existing branch `name` and
+/// points HEAD at it. Shared by `checkout` and `switch`.
+pub fn switch_to_branch(repo: &Repository, name: &str, force: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let commit = std::fs::read_to_string(repo.heads_dir().join(name))?.trim().to_string();
+    update_worktree(repo, &commit, force)?;
+    Branch::checkout(repo, name)?;
+    println!("{} Switched to branch '{}'", "✓".green(), name);
+    Ok(())
+}
+
+/// Makes the working tree and index match `target_commit`. Only paths whose
+/// content differs between HEAD and the target are touched, so local changes
+/// to other files carry over. If a touched path has unstaged or staged
+/// changes, or an untracked file would be overwritten, nothing is written and
+/// the paths are listed in the error, unless `force` is set. With `force`,
+/// every tracked path with local changes is also restored to the target;
+/// files that are only staged are unstaged and left in the working tree.
+pub fn update_worktree(repo: &Repository, target_commit: &str, force: bool) -> Result<usize, Box<dyn std::error::Error>> {
+    let current = match Branch::get_current_commit(repo) {
+        Some(hash) => commit_files(repo, &hash)?,
+        None => BTreeMap::new(),
+    };
+    let target = commit_files(repo, target_commit)?;
+
+    let mut changed: BTreeSet<String> = current.keys()
+        .chain(target.keys())
+        .filter(|path| current.get(*path).map(|e| &e.hash) != target.get(*path).map(|e| &e.hash))
+        .cloned()
+        .collect();
+
+    let mut index = Index::load(repo)?;
+
+    if force {
+        let modified: Vec<String> = current.keys()
+            .chain(index.entries.keys())
+            .filter(|path| !changed.contains(*path))
+            .filter(|path| has_local_changes(&index, path, current.get(*path), target.get(*path)))
+            .cloned()
+            .collect();
+        changed.extend(modified);
+    }
+
+    if !force {
+        let conflicts: Vec<&String> = changed.iter()
+            .filter(|path| has_local_changes(&index, path, current.get(*path), target.get(*path)))
+            .collect();
+        if !conflicts.is_empty() {
+            let list: Vec<String> = conflicts.iter().map(|path| format!("  {}", path)).collect();
+            return Err(format!(
+                "Your local changes to the following files would be overwritten by checkout:\n{}\nCommit them, or use --force to discard them",
+                list.join("\n")).into());
+        }
+    }
+
+    for path in &changed {
+        index.remove_entry(path);
+        match target.get(path) {
+            Some(entry) => {
+                let content = Object::read(repo, &entry.hash)?;
+                if Path::new(path.as_str()).is_dir() {
+                    std::fs::remove_dir_all(path.as_str())?;
+                }
+                write_entry(path.as_str(), &content, &entry.mode)?;
+                let checksum = hex::encode(digest::digest(&digest::SHA256, &content).as_ref());
+                index.add_entry_secure(path.to_string(), entry.hash.clone(), entry.mode.clone(), content.len() as u64, checksum);
+            },
+            None if current.contains_key(path) => remove_file_and_empty_parents(path)?,
+            None => {},
+        }
+    }
+
+    index.save(repo)?;
+    Ok(changed.len())
+}
+
+fn commit_files(repo: &Repository, hash: &str) -> Result<BTreeMap<String, TreeEntry>, Box<dyn std::error::Error>> {
+    let commit: Commit = serde_json::from_slice(&Object::read(repo, hash)?)?;
+    Ok(Tree::from_hash(repo, &commit.tree)?
+        .flatten_entries(repo, "")?
+        .into_iter()
+        .map(|(path, entry)| (path.strip_prefix("./").map(str::to_string).unwrap_or(path), entry))
+        .collect())
+}
+
+fn has_local_changes(index: &Index, path: &str, current: Option<&TreeEntry>, target: Option<&TreeEntry>) -> bool {
+    let worktree_hash = read_entry_content(path)
+        .ok()
+        .map(|content| hash_object(&ObjectType::Blob, &content));
+
+    match index.entries.get(path) {
+        // Staged content that is not what HEAD has, or unstaged edits on top.
+        Some(staged) => current.map(|e| &e.hash) != Some(staged) || worktree_hash.as_ref() != Some(staged),
+        None => match current {
+            // Removed from the index since HEAD.
+            Some(_) => true,
+            // Untracked: only a problem if the target would replace different content.
+            None => worktree_hash.is_some_and(|hash| target.map(|e| &e.hash) != Some(&hash)),
+        },
+    }
+}
@@ -1,21 +1,103 @@
 use crate::core::{Repository, Index, Object, ObjectType, Commit, Tree, Config};
-use crate::ai::gemini::GeminiClient;
-use crate::utils::diff::get_staged_diff;
+use crate::core::reflog;
+use crate::core::signing;
+use crate::commands::merge;
+use crate::ai::{ai_enabled, provider_with_progress};
+use crate::utils::hooks::run_hook;
+use crate::utils::editor::resolve_editor;
+use crate::utils::trailers::{append_trailers, parse_trailer};
+use crate::utils::worktree::{kind_of, read_entry_content};
+use crate::utils::diff::{get_staged_diff, list_hunks, apply_selected_hunks, print_diff, commit_file_stats, format_short_stat, head_files};
 use chrono::Utc;
 use colored::*;
 use indicatif::{ProgressBar, ProgressStyle};
 use ring::digest;
-use std::io::Write;
+use std::collections::{BTreeMap, BTreeSet};
+use regex::Regex;
+use std::io::{BufRead, IsTerminal, Write};
+use std::sync::LazyLock;
+
+/// How a commit message is tidied before it is stored, whichever way it was
+/// supplied (`-m`, `-F`, the editor or the AI suggestion).
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq)]
+pub enum CleanupMode {
+    /// Drop `#` comment lines, trailing whitespace and leading/trailing blank lines.
+    #[default]
+    Strip,
+    /// Only drop trailing whitespace.
+    Whitespace,
+    /// Keep the message exactly as given.
+    Verbatim,
+}
+
+pub fn cleanup_message(message: &str, mode: CleanupMode) -> String {
+    if mode == CleanupMode::Verbatim {
+        return message.to_string();
+    }
+
+    let lines: Vec<&str> = message.lines()
+        .filter(|line| mode != CleanupMode::Strip || !line.starts_with('#'))
+        .map(|line| line.trim_end())
+        .collect();
+
+    let start = match mode {
+        CleanupMode::Strip => lines.iter().position(|line| !line.is_empty()).unwrap_or(lines.len()),
+        _ => 0,
+    };
+    let end = lines.iter().rposition(|line| !line.is_empty()).map_or(start, |last| last + 1);
+    lines[start..end.max(start)].join("\n")
+}
+
+#[derive(Default, Clone)]
+pub struct CommitOptions {
+    pub message: Option<String>,
+    pub amend: bool,
+    pub ai_review: bool,
+    pub signoff: bool,
+    pub patch: bool,
+    pub trailers: Vec<String>,
+    pub cleanup: CleanupMode,
+    /// `-S[<keyid>]`: sign with this key, or with `user.signingkey` when empty.
+    pub gpg_sign: Option<String>,
+    /// Offer to re-stage files that changed on disk after they were staged,
+    /// instead of aborting.
+    pub include_changes: bool,
+    /// Print files changed, insertions and deletions once committed. Also on
+    /// when `commit.showStat` is set.
+    pub short_stat: bool,
+    /// Skip the `pre-commit` and `commit-msg` hooks.
+    pub no_verify: bool,
+}
 
 pub async fn run(
     message: Option<String>, 
     amend: bool, 
     ai_review: bool, 
-    signoff: bool
+    signoff: bool,
+    patch: bool,
+    trailers: Vec<String>
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let repo = Repository::new(".aigit").ok_or("Not in a repository")?;
+    run_with(CommitOptions { message, amend, ai_review, signoff, patch, trailers, ..Default::default() }).await
+}
+
+pub async fn run_with(options: CommitOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let CommitOptions { message, amend, ai_review, signoff, patch, trailers, cleanup, gpg_sign, include_changes, short_stat, no_verify } = options;
+    let repo = Repository::discover().ok_or("Not in a repository")?;
+    for trailer in &trailers {
+        parse_trailer(trailer)?;
+    }
     let mut index = Index::load(&repo)?;
-    let config = Config::load_repo(&repo).unwrap_or_else(|_| Config::load_global().unwrap_or_default());
+    let config = repo.config();
+    let signing_key = resolve_signing_key(gpg_sign.as_deref(), config)?;
+
+    if patch {
+        let stdin = std::io::stdin();
+        let staged = stage_selected_hunks(&repo, &mut index, &mut stdin.lock())?;
+        if staged.is_empty() {
+            println!("{}", "No hunks selected".yellow());
+        }
+        index.save(&repo)?;
+    }
 
     if index.entries.is_empty() && !amend {
         println!("{}", "Nothing to commit".yellow());
@@ -31,19 +113,53 @@ pub async fn run(
         return Err("Unresolved conflicts".into());
     }
 
+    if include_changes {
+        let stdin = std::io::stdin();
+        if !restage_drifted_files(&repo, &mut index, &mut stdin.lock())?.is_empty() {
+            index.save(&repo)?;
+        }
+    }
+
+    let threshold = config.get("commit.largeCommitWarning")
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_LARGE_COMMIT_WARNING);
+    if let Some(warning) = large_commit_warning(&repo, &index, threshold) {
+        // Scripts and hooks cannot answer, so they only get the warning.
+        let stdin = std::io::stdin();
+        if stdin.is_terminal() {
+            if !confirm_large_commit(&warning, &mut stdin.lock())? {
+                println!("{}", "Commit aborted".yellow());
+                return Ok(());
+            }
+        } else {
+            print_large_commit_warning(&warning);
+        }
+    }
+
     security_pre_commit_checks(&index).await?;
+    if !no_verify {
+        run_pre_commit_hook(&repo)?;
+    }
 
     let pb = ProgressBar::new_spinner();
     pb.set_style(ProgressStyle::default_spinner().template("{spinner} {msg}").unwrap());
 
-    if ai_review {
+    let use_ai = ai_enabled(config);
+    if ai_review && !use_ai {
+        println!("{}", "Skipping AI review: AI features are disabled".bright_black());
+    }
+
+    if ai_review && use_ai {
         pb.set_message("AI reviewing changes...");
         pb.enable_steady_tick(std::time::Duration::from_millis(100));
         
         let diff_content = get_staged_diff(&repo, &index).await;
-        let gemini = GeminiClient::new();
+        let review = match provider_with_progress(repo.config(), &pb) {
+            Ok(provider) => provider.review_code(&diff_content).await,
+            Err(e) => Err(e.into()),
+        };
         
-        match gemini.review_code(&diff_content).await {
+        match review {
             Ok(review) => {
                 pb.finish_and_clear();
                 println!("\n{}", "AI Code Review:".cyan().bold());
@@ -64,19 +180,24 @@ pub async fn run(
         }
     }
 
-    let commit_message = match message {
-        Some(msg) => {
-            validate_commit_message(&msg)?;
-            msg
-        },
-        None => {
+    // A revert stopped by --no-commit or a conflict left its message behind;
+    // it only seeds the editor, since the revert may have been abandoned.
+    let prepared_message = std::fs::read_to_string(repo.git_dir.join(merge::MERGE_MSG)).ok();
+    let raw_message = match (message, prepared_message) {
+        (Some(msg), _) => msg,
+        (None, Some(prepared)) => edit_commit_message(&revert_message_template(&prepared, cleanup), config)?,
+        (None, None) if !use_ai => prompt_manual_message()?,
+        (None, None) => {
             pb.set_message("Generating AI commit message...");
             pb.enable_steady_tick(std::time::Duration::from_millis(100));
             
             let diff_content = get_staged_diff(&repo, &index).await;
-            let gemini = GeminiClient::new();
+            let suggestion = match provider_with_progress(repo.config(), &pb) {
+                Ok(provider) => provider.generate_commit_message(&diff_content).await,
+                Err(e) => Err(e.into()),
+            };
             
-            match gemini.generate_commit_message(&diff_content).await {
+            match suggestion {
                 Ok(ai_msg) => {
                     pb.finish_and_clear();
                     println!("{} {}", "AI suggested:".cyan(), ai_msg.bright_white());
@@ -86,46 +207,40 @@ pub async fn run(
                     std::io::stdin().read_line(&mut input)?;
                     
                     match input.trim().to_lowercase().as_str() {
-                        "n" | "no" => {
-                            println!("{}", "Enter commit message:".yellow());
-                            let mut manual_msg = String::new();
-                            std::io::stdin().read_line(&mut manual_msg)?;
-                            let msg = manual_msg.trim().to_string();
-                            validate_commit_message(&msg)?;
-                            msg
-                        },
+                        "n" | "no" => prompt_manual_message()?,
                         "e" | "edit" => {
-                            edit_commit_message(&ai_msg, &config)?
+                            edit_commit_message(&ai_msg, config)?
                         },
-                        _ => {
-                            validate_commit_message(&ai_msg)?;
-                            ai_msg
-                        }
+                        _ => ai_msg,
                     }
                 },
-                Err(_) => {
+                Err(e) => {
                     pb.finish_and_clear();
-                    println!("{}", "Enter commit message:".yellow());
-                    let mut manual_msg = String::new();
-                    std::io::stdin().read_line(&mut manual_msg)?;
-                    let msg = manual_msg.trim().to_string();
-                    validate_commit_message(&msg)?;
-                    msg
+                    println!("{} {}", "AI commit message unavailable:".yellow(), e);
+                    prompt_manual_message()?
                 }
             }
         }
     };
+    let commit_message = cleanup_message(&raw_message, cleanup);
+    validate_commit_message(&commit_message, config)?;
 
+    let with_trailers = append_trailers(&commit_message, &trailers)?;
     let final_message = if signoff {
-        add_signoff(commit_message, &config)
+        add_signoff(with_trailers, config)?
+    } else {
+        with_trailers
+    };
+    let final_message = if no_verify {
+        final_message
     } else {
-        commit_message
+        run_commit_msg_hook(&repo, final_message)?
     };
 
     pb.set_message("Creating commit...");
     pb.enable_steady_tick(std::time::Duration::from_millis(100));
 
-    let tree_hash = Tree::create_from_index(&repo, &index)?;
+    let (tree_hash, tree_objects) = Tree::prepare_from_index(&index)?;
     let parent = if amend {
         get_previous_commit_parent(&repo)
     } else {
@@ -136,7 +251,7 @@ pub async fn run(
     let author_email = config.get_user_email();
     // let timestamp = Utc::now();
 
-    let commit = Commit::new_secure(
+    let mut commit = Commit::new_secure(
         tree_hash.clone(),
         parent,
         author_name.clone(),
@@ -144,23 +259,156 @@ pub async fn run(
         final_message.clone(),
         generate_commit_signature(&final_message, &tree_hash)?,
     );
+    // Committing the resolution of a conflicted merge concludes it.
+    let merge_head = if amend { None } else { merge::merge_head(&repo) };
+    if let Some(merge_head) = &merge_head {
+        commit.parents.push(merge_head.clone());
+    }
+    if let Some(key_id) = &signing_key {
+        signing::sign_commit(&repo, &mut commit, key_id)?;
+    }
 
-    let commit_content = serde_json::to_string(&commit)?;
-    let commit_hash = Object::create(&repo, ObjectType::Commit, commit_content.as_bytes())?;
+    let mut objects: Vec<(ObjectType, Vec<u8>)> = tree_objects
+        .into_iter()
+        .map(|content| (ObjectType::Tree, content))
+        .collect();
+    objects.push((ObjectType::Commit, serde_json::to_vec(&commit)?));
+    
+    // Every object lands before HEAD moves, so a crash leaves at most unreferenced objects.
+    let commit_hash = Object::create_batch(&repo, &objects)?
+        .pop()
+        .ok_or("Failed to write commit object")?;
     
+    let previous_head = get_last_commit(&repo);
     update_head(&repo, &commit_hash);
+    let action = match (&previous_head, amend) {
+        (_, true) => "commit (amend)",
+        (None, _) => "commit (initial)",
+        _ if merge_head.is_some() => "commit (merge)",
+        _ => "commit",
+    };
+    reflog::append(&repo, previous_head.as_deref(), &commit_hash, &format!("{}: {}", action, commit.short_message()))?;
+    if merge_head.is_some() {
+        std::fs::remove_file(repo.git_dir.join(merge::MERGE_HEAD))?;
+    }
+    std::fs::remove_file(repo.git_dir.join(merge::MERGE_MSG)).ok();
     index.clear(&repo)?;
     
     pb.finish_and_clear();
     println!("{} {}", "Committed:".green().bold(), commit_hash[..8].bright_yellow());
     println!("{} {}", "Message:".cyan(), final_message.lines().next().unwrap_or("").bright_white());
+    if short_stat || config.get_bool("commit.showStat").unwrap_or(false) {
+        match commit_file_stats(&repo, &commit).await {
+            Ok(stats) => println!("{}", format_short_stat(&stats)),
+            Err(e) => println!("{} could not compute stat: {}", "Warning:".yellow(), e),
+        }
+    }
     
     audit_commit(&commit_hash, &final_message, &author_name).await?;
+    run_post_commit_hook(&repo);
+    // Likewise a failed auto-gc leaves the new commit in place.
+    if let Err(e) = crate::commands::gc::run_auto(&repo, config).await {
+        println!("{} auto-gc failed: {}", "Warning:".yellow(), e);
+    }
     
     Ok(())
 }
 
-fn validate_commit_message(message: &str) -> Result<(), Box<dyn std::error::Error>> {
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageFinding {
+    pub line: usize,
+    pub kind: &'static str,
+    pub snippet: String,
+    pub is_secret: bool,
+}
+
+/// Credential-looking assignments such as `api_key = ...` or `token: ...`.
+/// The value is captured so prose like `token: refresh on login` can be told
+/// apart from an actual secret.
+static CREDENTIAL_ASSIGNMENT: LazyLock<Regex> = LazyLock::new(|| Regex::new(
+    r#"(?i)\b(password|passwd|secret|client[_-]?secret|api[_-]?key|access[_-]?key|private[_-]?key|auth[_-]?token|access[_-]?token|token)\s*[:=]\s*["']?([^\s"']{8,})"#
+).expect("valid credential pattern"));
+static HIGH_ENTROPY: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"[a-zA-Z0-9+/]{40,}={0,2}").expect("valid entropy pattern"));
+static PROFANITY: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)\b(fuck|shit|damn|crap)\b").expect("valid profanity pattern"));
+
+/// Scans a commit message line by line for pasted credentials and, when
+/// `profanity_check` is on, for profanity. Secret snippets are redacted.
+pub fn scan_commit_message(message: &str, profanity_check: bool) -> Vec<MessageFinding> {
+    let mut findings = Vec::new();
+    for (n, line) in message.lines().enumerate() {
+        let credential = CREDENTIAL_ASSIGNMENT.captures_iter(line)
+            .find(|captures| looks_like_secret(&captures[2]))
+            .map(|captures| ("credential assignment", captures.get(0).map_or("", |m| m.as_str())));
+        // Commit hashes are long but not secret.
+        let entropy = || HIGH_ENTROPY.find_iter(line)
+            .find(|m| !m.as_str().chars().all(|c| c.is_ascii_hexdigit()))
+            .map(|m| ("high-entropy string", m.as_str()));
+        if let Some((kind, found)) = credential.or_else(entropy) {
+            findings.push(MessageFinding {
+                line: n + 1,
+                kind,
+                snippet: redact(found),
+                is_secret: true,
+            });
+        }
+
+        if let Some(found) = PROFANITY.find(line).filter(|_| profanity_check) {
+            findings.push(MessageFinding {
+                line: n + 1,
+                kind: "profanity",
+                snippet: found.as_str().to_string(),
+                is_secret: false,
+            });
+        }
+    }
+
+    findings
+}
+
+/// Generated secrets mix letters and digits; words and identifiers rarely do.
+fn looks_like_secret(value: &str) -> bool {
+    value.chars().any(|c| c.is_ascii_digit()) && value.chars().any(|c| c.is_ascii_alphabetic())
+}
+
+fn redact(matched: &str) -> String {
+    let (label, value) = match matched.find([':', '=']) {
+        Some(pos) => matched.split_at(pos + 1),
+        None => ("", matched),
+    };
+    let trimmed = value.trim_start();
+    let spacing = &value[..value.len() - trimmed.len()];
+    let visible: String = trimmed.chars().take(4).collect();
+    format!("{}{}{}****", label, spacing, visible)
+}
+
+/// Prints each finding and, if any look like secrets, asks whether to abort.
+/// Returns whether the commit should go ahead.
+pub fn review_message_findings(findings: &[MessageFinding], input: &mut dyn BufRead) -> Result<bool, Box<dyn std::error::Error>> {
+    print_message_findings(findings);
+
+    if !findings.iter().any(|finding| finding.is_secret) {
+        return Ok(true);
+    }
+
+    print!("{} ", "The commit message may contain a secret. Abort commit? [Y/n]".yellow());
+    std::io::stdout().flush()?;
+
+    let mut reply = String::new();
+    input.read_line(&mut reply)?;
+    Ok(matches!(reply.trim().to_lowercase().as_str(), "n" | "no"))
+}
+
+fn print_message_findings(findings: &[MessageFinding]) {
+    for finding in findings {
+        println!("{} line {}: {} ({})",
+                "Warning:".yellow(),
+                finding.line,
+                finding.kind,
+                finding.snippet.bright_white());
+    }
+}
+
+fn validate_commit_message(message: &str, config: &Config) -> Result<(), Box<dyn std::error::Error>> {
     if message.trim().is_empty() {
         return Err("Commit message cannot be empty".into());
     }
@@ -174,66 +422,92 @@ fn validate_commit_message(message: &str) -> Result<(), Box<dyn std::error::Erro
         println!("{}", "Warning: First line is longer than 80 characters".yellow());
     }
     
-    let suspicious_patterns = [
-        r"(?i)(password|secret|key|token)\s*[:=]\s*\S+",
-        r"(?i)fuck|shit|damn|crap",
-        r"[a-zA-Z0-9+/]{40,}={0,2}",
-    ];
-    
-    for pattern in &suspicious_patterns {
-        if let Ok(re) = regex::Regex::new(pattern) {
-            if re.is_match(message) {
-                println!("{}", "Warning: Commit message may contain sensitive or inappropriate content".yellow());
-                break;
-            }
+    let profanity_check = config.get_bool("commit.profanityCheck").unwrap_or(false);
+    let findings = scan_commit_message(message, profanity_check);
+    if !findings.is_empty() {
+        // Scripts and CI cannot answer, so they only get the warning.
+        let stdin = std::io::stdin();
+        if !stdin.is_terminal() {
+            print_message_findings(&findings);
+        } else if !review_message_findings(&findings, &mut stdin.lock())? {
+            return Err("Commit aborted: message may contain a secret".into());
         }
     }
     
     Ok(())
 }
 
-fn edit_commit_message(initial_message: &str, config: &Config) -> Result<String, Box<dyn std::error::Error>> {
-    let editor = config.get("core.editor")
-        .cloned()
-        .or_else(|| std::env::var("EDITOR").ok())
-        .unwrap_or_else(|| "nano".to_string());
+/// Reads a commit message typed on one line of stdin.
+/// The editor template for a commit made while a stopped revert's
+/// `MERGE_MSG` is around. With the default cleanup the reminder lines are
+/// comments and never reach the commit.
+fn revert_message_template(prepared: &str, cleanup: CleanupMode) -> String {
+    if cleanup != CleanupMode::Strip {
+        return prepared.to_string();
+    }
+    format!(
+        "{}\n\n# It looks like you may be committing a revert.\n# If this is not correct, replace this message, or run\n#\taigit revert --abort\n# to drop the revert.\n",
+        prepared.trim_end()
+    )
+}
+
+fn prompt_manual_message() -> std::io::Result<String> {
+    println!("{}", "Enter commit message:".yellow());
+    let mut message = String::new();
+    std::io::stdin().read_line(&mut message)?;
+    Ok(message)
+}
+
+pub fn edit_commit_message(initial_message: &str, config: &Config) -> Result<String, Box<dyn std::error::Error>> {
+    let env_editor = std::env::var("EDITOR").ok();
+    let (editor, warning) = resolve_editor(config.get("core.editor").map(String::as_str), env_editor.as_deref());
+    if let Some(warning) = warning {
+        println!("{} {}", "Warning:".yellow(), warning);
+    }
     
     let temp_file = format!(".aigit/COMMIT_EDITMSG_{}", uuid::Uuid::new_v4());
     std::fs::write(&temp_file, initial_message)?;
     
-    let status = std::process::Command::new(editor)
+    let mut words = editor.split_whitespace();
+    let status = std::process::Command::new(words.next().unwrap_or_default())
+        .args(words)
         .arg(&temp_file)
-        .status()?;
+        .status()
+        .map_err(|e| {
+            let _ = std::fs::remove_file(&temp_file);
+            format!("Failed to start editor '{}': {}", editor, e)
+        })?;
     
     if !status.success() {
         std::fs::remove_file(&temp_file)?;
         return Err("Editor exited with error".into());
     }
     
+    // Comment lines are removed later by the selected --cleanup mode.
     let edited_message = std::fs::read_to_string(&temp_file)?;
     std::fs::remove_file(&temp_file)?;
-    
-    let cleaned_message = edited_message
-        .lines()
-        .filter(|line| !line.starts_with('#'))
-        .collect::<Vec<_>>()
-        .join("\n")
-        .trim()
-        .to_string();
-    
-    validate_commit_message(&cleaned_message)?;
-    Ok(cleaned_message)
+    Ok(edited_message)
 }
 
-fn add_signoff(message: String, config: &Config) -> String {
+fn add_signoff(message: String, config: &Config) -> Result<String, Box<dyn std::error::Error>> {
     let signoff = format!("Signed-off-by: {} <{}>", 
                          config.get_user_name(), 
                          config.get_user_email());
     
-    if message.contains(&signoff) {
-        message
-    } else {
-        format!("{}\n\n{}", message, signoff)
+    append_trailers(&message, &[signoff])
+}
+
+/// The key to sign with: the one given to `-S`, else `user.signingkey` when
+/// `-S` has no key id or `commit.gpgsign` is on. `None` leaves the commit unsigned.
+fn resolve_signing_key(gpg_sign: Option<&str>, config: &Config) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let configured = || config.get("user.signingkey").cloned()
+        .ok_or_else(|| "No signing key: pass -S<keyid> or set user.signingkey".into());
+
+    match gpg_sign {
+        Some("") => configured().map(Some),
+        Some(key_id) => Ok(Some(key_id.to_string())),
+        None if config.get_bool("commit.gpgsign").unwrap_or(false) => configured().map(Some),
+        None => Ok(None),
     }
 }
 
@@ -243,6 +517,90 @@ fn generate_commit_signature(message: &str, tree_hash: &str) -> Result<String, B
     Ok(signature)
 }
 
+/// Staged-file count above which `commit` asks before going ahead, unless
+/// `commit.largeCommitWarning` says otherwise.
+pub const DEFAULT_LARGE_COMMIT_WARNING: usize = 1000;
+
+/// Directory names that almost always hold build output or installed
+/// dependencies rather than source.
+const GENERATED_DIRS: &[&str] = &[
+    "node_modules", "target", "dist", "build", "__pycache__", ".venv", "venv", ".tox", ".gradle", ".next",
+];
+
+/// Why a commit looks like an accidental `add .` of the whole repository.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LargeCommitWarning {
+    /// Files added or changed relative to HEAD.
+    pub staged: usize,
+    pub threshold: usize,
+    /// Generated directories with staged files in them, e.g. "node_modules/".
+    pub generated_dirs: Vec<String>,
+}
+
+/// Looks at the files the commit would add or change relative to HEAD and
+/// returns a warning if there are more than `threshold` of them (0 turns the
+/// count check off) or any sit in a typically generated directory.
+pub fn large_commit_warning(repo: &Repository, index: &Index, threshold: usize) -> Option<LargeCommitWarning> {
+    let head = head_files(repo);
+    let changed: Vec<&str> = index.entries.iter()
+        .filter(|(path, hash)| head.get(*path) != Some(*hash))
+        .map(|(path, _)| path.as_str())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+
+    let generated_dirs: Vec<String> = changed.iter()
+        .filter_map(|path| generated_dir(path))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+
+    let too_many = threshold > 0 && changed.len() > threshold;
+    if !too_many && generated_dirs.is_empty() {
+        return None;
+    }
+    Some(LargeCommitWarning { staged: changed.len(), threshold, generated_dirs })
+}
+
+/// The leading part of `path` up to and including its first generated
+/// directory, e.g. "web/node_modules/" for "web/node_modules/a/index.js".
+fn generated_dir(path: &str) -> Option<String> {
+    let components: Vec<&str> = path.split('/').collect();
+    let (_, dirs) = components.split_last()?;
+    let position = dirs.iter().position(|dir| GENERATED_DIRS.contains(dir))?;
+    Some(format!("{}/", components[..=position].join("/")))
+}
+
+/// Prints `warning` and asks whether to commit anyway. Anything but "y" or
+/// "yes", including end of input, declines.
+pub fn confirm_large_commit(warning: &LargeCommitWarning, input: &mut dyn BufRead) -> Result<bool, Box<dyn std::error::Error>> {
+    print_large_commit_warning(warning);
+    print!("{} ", "Commit anyway? [y/N]".yellow());
+    std::io::stdout().flush()?;
+    let mut reply = String::new();
+    if input.read_line(&mut reply)? == 0 {
+        println!();
+        return Ok(false);
+    }
+    Ok(matches!(reply.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+fn print_large_commit_warning(warning: &LargeCommitWarning) {
+    if warning.threshold > 0 && warning.staged > warning.threshold {
+        println!("{}", format!(
+            "warning: {} files are staged (commit.largeCommitWarning is {})",
+            warning.staged, warning.threshold
+        ).yellow());
+    }
+    if !warning.generated_dirs.is_empty() {
+        println!("{}", "warning: staged files in directories that usually hold generated output:".yellow());
+        for dir in &warning.generated_dirs {
+            println!("  {}", dir.yellow());
+        }
+        println!("  {}", "Add them to .gitignore and unstage them if this was not intended.".bright_black());
+    }
+}
+
 async fn security_pre_commit_checks(index: &Index) -> Result<(), Box<dyn std::error::Error>> {
     let staged_files = index.entries.len();
     let total_size: u64 = index.metadata.values().map(|m| m.size).sum();
@@ -255,18 +613,210 @@ async fn security_pre_commit_checks(index: &Index) -> Result<(), Box<dyn std::er
         return Err("Commit size too large (max 1GB)".into());
     }
     
+    if let Some(file_path) = drifted_files(index)?.first() {
+        return Err(format!(
+            "File {} was modified after staging (add it again or commit with --include-changes)",
+            file_path
+        ).into());
+    }
+    
+    Ok(())
+}
+
+/// Staged files whose working-tree content no longer matches what was
+/// staged, sorted. Partially staged files are expected to differ and are
+/// left out.
+pub fn drifted_files(index: &Index) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let mut drifted = Vec::new();
     for (file_path, entry) in &index.metadata {
-        if std::path::Path::new(file_path).exists() {
-            let current_content = std::fs::read(file_path)?;
-            let current_checksum = hex::encode(digest::digest(&digest::SHA256, &current_content).as_ref());
-            
-            if entry.checksum != current_checksum {
-                return Err(format!("File {} was modified after staging", file_path).into());
+        if index.is_partial(file_path) || kind_of(file_path).is_none() {
+            continue;
+        }
+        let current_content = read_entry_content(file_path)?;
+        if hex::encode(digest::digest(&digest::SHA256, &current_content).as_ref()) != entry.checksum {
+            drifted.push(file_path.clone());
+        }
+    }
+    drifted.sort();
+    Ok(drifted)
+}
+
+/// Asks, for each file that changed after it was staged, whether to stage
+/// its current content instead. Returns the files that were re-staged;
+/// anything declined is left for `security_pre_commit_checks` to refuse.
+pub fn restage_drifted_files(
+    repo: &Repository,
+    index: &mut Index,
+    input: &mut dyn BufRead
+) -> Result<BTreeSet<String>, Box<dyn std::error::Error>> {
+    let mut restaged = BTreeSet::new();
+
+    for path in drifted_files(index)? {
+        print!("{} ", format!("{} changed after it was staged. Stage the current content? [y/N]", path).yellow());
+        std::io::stdout().flush()?;
+
+        let mut reply = String::new();
+        if input.read_line(&mut reply)? == 0 {
+            break;
+        }
+        if !matches!(reply.trim().to_lowercase().as_str(), "y" | "yes") {
+            continue;
+        }
+
+        let content = read_entry_content(&path)?;
+        let blob_hash = Object::create(repo, ObjectType::Blob, &content)?;
+        let mode = index.metadata.get(&path)
+            .map(|m| m.mode.clone())
+            .unwrap_or_else(|| "100644".to_string());
+        let checksum = hex::encode(digest::digest(&digest::SHA256, &content).as_ref());
+
+        index.add_entry_secure(path.clone(), blob_hash, mode, content.len() as u64, checksum);
+        println!("{} {}", "Re-staged".green(), path);
+        restaged.insert(path);
+    }
+
+    Ok(restaged)
+}
+
+pub fn stage_selected_hunks(
+    repo: &Repository,
+    index: &mut Index,
+    input: &mut dyn BufRead
+) -> Result<BTreeSet<String>, Box<dyn std::error::Error>> {
+    let head_files = get_last_commit(repo)
+        .and_then(|hash| Object::read(repo, &hash).ok())
+        .and_then(|content| serde_json::from_slice::<Commit>(&content).ok())
+        .map(|commit| Tree::from_hash(repo, &commit.tree).and_then(|tree| tree.flatten(repo, "")))
+        .transpose()?
+        .unwrap_or_default();
+
+    let mut candidates: BTreeMap<String, String> = head_files;
+    candidates.extend(index.entries.iter().map(|(path, hash)| (path.clone(), hash.clone())));
+
+    let mut staged = BTreeSet::new();
+
+    'files: for (path, base_hash) in candidates {
+        let base = match Object::read(repo, &base_hash).ok().and_then(|c| String::from_utf8(c).ok()) {
+            Some(base) => base,
+            None => continue,
+        };
+        let current = match std::fs::read(&path).ok().and_then(|c| String::from_utf8(c).ok()) {
+            Some(current) => current,
+            None => continue,
+        };
+        if base == current {
+            continue;
+        }
+
+        let hunks = list_hunks(&base, &current);
+        let mut selected = vec![false; hunks.len()];
+        let mut quit = false;
+        let mut answer_rest: Option<bool> = None;
+
+        println!("{} {}", "diff".bold(), path.bright_white());
+        for (i, hunk) in hunks.iter().enumerate() {
+            if let Some(answer) = answer_rest {
+                selected[i] = answer;
+                continue;
+            }
+
+            print_diff(hunk);
+            print!("{} ", format!("Stage this hunk [{}/{}] [y,n,a,d,q]?", i + 1, hunks.len()).cyan());
+            std::io::stdout().flush()?;
+
+            let mut reply = String::new();
+            if input.read_line(&mut reply)? == 0 {
+                quit = true;
+                break;
             }
+
+            match reply.trim().to_lowercase().as_str() {
+                "y" | "yes" => selected[i] = true,
+                "a" => {
+                    selected[i] = true;
+                    answer_rest = Some(true);
+                },
+                "d" => answer_rest = Some(false),
+                "q" => {
+                    quit = true;
+                    break;
+                },
+                _ => {},
+            }
+        }
+
+        if selected.iter().any(|s| *s) {
+            let content = apply_selected_hunks(&base, &current, &selected);
+            let blob_hash = Object::create(repo, ObjectType::Blob, content.as_bytes())?;
+            let mode = index.metadata.get(&path)
+                .map(|m| m.mode.clone())
+                .unwrap_or_else(|| "100644".to_string());
+            let checksum = hex::encode(digest::digest(&digest::SHA256, content.as_bytes()).as_ref());
+
+            index.add_entry_secure(path.clone(), blob_hash, mode, content.len() as u64, checksum);
+            if content != current {
+                index.mark_partial(&path);
+            }
+            staged.insert(path);
+        }
+
+        if quit {
+            break 'files;
         }
     }
-    
-    Ok(())
+
+    Ok(staged)
+}
+
+/// The file holding the message while the `commit-msg` hook runs, inside the
+/// repository directory.
+const COMMIT_EDITMSG: &str = "COMMIT_EDITMSG";
+
+/// Runs the `pre-commit` hook before the message is asked for or anything is
+/// written. A non-zero exit aborts the commit.
+fn run_pre_commit_hook(repo: &Repository) -> Result<(), Box<dyn std::error::Error>> {
+    match run_hook(repo, "pre-commit", &[])? {
+        Some(status) if !status.success() => {
+            Err(format!("pre-commit hook exited with {}; commit aborted (use --no-verify to skip hooks)", status).into())
+        },
+        _ => Ok(()),
+    }
+}
+
+/// Runs the `commit-msg` hook on a file holding the final message and
+/// returns the file's content afterwards, so the hook may rewrite it. A
+/// non-zero exit, or a message the hook emptied, aborts the commit.
+fn run_commit_msg_hook(repo: &Repository, message: String) -> Result<String, Box<dyn std::error::Error>> {
+    let path = repo.git_dir.join(COMMIT_EDITMSG);
+    std::fs::write(&path, format!("{}\n", message))?;
+    let status = run_hook(repo, "commit-msg", &[&path.to_string_lossy()]);
+    let edited = std::fs::read_to_string(&path);
+    std::fs::remove_file(&path).ok();
+
+    match status? {
+        None => Ok(message),
+        Some(status) if !status.success() => {
+            Err(format!("commit-msg hook exited with {}; commit aborted (use --no-verify to skip hooks)", status).into())
+        },
+        Some(_) => {
+            let edited = edited?.trim_end().to_string();
+            if edited.trim().is_empty() {
+                return Err("commit-msg hook left an empty message; commit aborted".into());
+            }
+            Ok(edited)
+        },
+    }
+}
+
+fn run_post_commit_hook(repo: &Repository) {
+    // The commit already exists, so a failing hook is reported but never fatal.
+    match run_hook(repo, "post-commit", &[]) {
+        Ok(Some(status)) if !status.success() => {
+            println!("{} post-commit hook exited with {}", "Warning:".yellow(), status);
+        },
+        Err(e) => println!("{} {}", "Warning:".yellow(), e),
+        _ => {},
+    }
 }
 
 fn get_last_commit(repo: &Repository) -> Option<String> {
@@ -1,5 +1,11 @@
 use clap::Subcommand;
 use crate::core::{Config};
+use crate::ai::{is_valid_model_name, PROVIDERS};
+use crate::core::config::parse_bool;
+use crate::core::gc::parse_reflog_expire;
+use crate::core::repository::MAX_OBJECT_SHARDING;
+use crate::core::revision::{FULL_HASH_LEN, MIN_ABBREV};
+use crate::utils::editor::{editor_program, find_program};
 use colored::*;
 use std::path::PathBuf;
 use std::io::Write;
@@ -12,8 +18,13 @@ pub enum ConfigAction {
     },
     Get {
         key: String,
+        #[arg(long = "type", value_enum)]
+        value_type: Option<ConfigType>,
+    },
+    List {
+        #[arg(long)]
+        name_only: bool,
     },
-    List,
     User {
         name: Option<String>,
         #[arg(long)]
@@ -21,18 +32,39 @@ pub enum ConfigAction {
     },
 }
 
+/// How `config get --type` interprets and prints a value.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum ConfigType {
+    /// `true` or `false`, from any accepted boolean spelling.
+    Bool,
+    /// A decimal integer, optionally scaled by a `k`, `m` or `g` suffix.
+    Int,
+    /// An absolute path, with a leading `~` expanded to the home directory.
+    Path,
+}
+
 pub async fn run(action: &ConfigAction) -> Result<(), Box<dyn std::error::Error>> {
     match action {
         ConfigAction::Set { key, value } => {
             validate_config_key(key)?;
             validate_config_value(key, value)?;
             set_config(key, value).await?;
+            if key == "core.editor" && find_program(value).is_none() {
+                println!("{} editor '{}' was not found on PATH; commits will fall back to another editor",
+                        "Warning:".yellow(), editor_program(value));
+            }
         },
-        ConfigAction::Get { key } => {
-            get_config(key).await?;
+        ConfigAction::Get { key, value_type } => {
+            get_config(key, *value_type).await?;
         },
-        ConfigAction::List => {
-            list_config().await?;
+        ConfigAction::List { name_only } => {
+            if *name_only {
+                for key in config_key_names() {
+                    println!("{}", key);
+                }
+            } else {
+                list_config().await?;
+            }
         },
         ConfigAction::User { name, email } => {
             set_user_config(name.as_deref(), email.as_deref()).await?;
@@ -44,12 +76,17 @@ pub async fn run(action: &ConfigAction) -> Result<(), Box<dyn std::error::Error>
 fn validate_config_key(key: &str) -> Result<(), Box<dyn std::error::Error>> {
     let allowed_keys = [
         "user.name", "user.email", "user.signingkey",
-        "core.editor", "core.autocrlf", "core.safecrlf",
-        "ai.enabled", "ai.model", "ai.temperature",
-        "security.requireSignature", "security.auditLog",
-        "commit.gpgsign", "commit.template"
+        "core.editor", "core.autocrlf", "core.safecrlf", "core.objectSharding", "core.abbrev", "core.commitGraph",
+        "ai.enabled", "ai.provider", "ai.endpoint", "ai.model", "ai.temperature", "ai.maxTokens", "ai.maxContextTokens", "ai.maxResponseBytes", "ai.maxRetries",
+        "security.requireSignature", "security.auditLog", "security.quarantineSecrets", "security.keyring", "security.encryptObjects",
+        "commit.gpgsign", "commit.template", "commit.profanityCheck", "commit.showStat", "commit.largeCommitWarning",
+        "gc.auto", "gc.reflogExpire"
     ];
 
+    if is_branch_key(key) || is_remote_key(key) {
+        return Ok(());
+    }
+
     if !allowed_keys.contains(&key) {
         return Err(format!("Invalid configuration key: {}", key).into());
     }
@@ -57,37 +94,112 @@ fn validate_config_key(key: &str) -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-fn validate_config_value(key: &str, value: &str) -> Result<(), Box<dyn std::error::Error>> {
+/// Checks a value for `key` the way `config set` does; keys without rules
+/// accept anything.
+pub fn validate_config_value(key: &str, value: &str) -> Result<(), Box<dyn std::error::Error>> {
     match key {
-        "user.email" => {
-            if !value.contains('@') || !value.contains('.') {
-                return Err("Invalid email format".into());
-            }
+        "user.email" if !value.contains('@') || !value.contains('.') => {
+            return Err("Invalid email format".into());
+        },
+        "ai.provider" if !PROVIDERS.contains(&value) => {
+            return Err(format!("ai.provider must be one of: {}", PROVIDERS.join(", ")).into());
+        },
+        "ai.endpoint" if !(value.starts_with("http://") || value.starts_with("https://")) => {
+            return Err("ai.endpoint must be an http:// or https:// URL".into());
         },
         "ai.temperature" => {
             if let Ok(temp) = value.parse::<f32>() {
-                if temp < 0.0 || temp > 2.0 {
+                if !(0.0..=2.0).contains(&temp) {
                     return Err("Temperature must be between 0.0 and 2.0".into());
                 }
             } else {
                 return Err("Temperature must be a number".into());
             }
         },
-        key if key.ends_with(".enabled") || key.ends_with("gpgsign") || key.ends_with("auditLog") => {
-            match value.to_lowercase().as_str() {
-                "true" | "false" | "yes" | "no" | "1" | "0" => {},
-                _ => return Err("Boolean values must be true/false, yes/no, or 1/0".into()),
+        "core.objectSharding" => {
+            match value.parse::<usize>() {
+                Ok(depth) if (1..=MAX_OBJECT_SHARDING).contains(&depth) => {},
+                _ => return Err(format!("core.objectSharding must be between 1 and {}", MAX_OBJECT_SHARDING).into()),
             }
         },
+        "core.abbrev" if value != "auto" && !matches!(value.parse::<usize>(), Ok(n) if (MIN_ABBREV..=FULL_HASH_LEN).contains(&n)) => {
+            return Err(format!("core.abbrev must be 'auto' or between {} and {}", MIN_ABBREV, FULL_HASH_LEN).into());
+        },
+        "ai.model" if !is_valid_model_name(value) => {
+            return Err("ai.model may only contain letters, digits and '-', '_', '.', ':' or '/'".into());
+        },
+        "ai.maxTokens" if !matches!(value.parse::<u32>(), Ok(n) if n > 0) => {
+            return Err("ai.maxTokens must be a positive integer".into());
+        },
+        "ai.maxContextTokens" if !matches!(value.parse::<usize>(), Ok(n) if n > 0) => {
+            return Err("ai.maxContextTokens must be a positive integer".into());
+        },
+        "ai.maxResponseBytes" if !matches!(value.parse::<usize>(), Ok(n) if n > 0) => {
+            return Err("ai.maxResponseBytes must be a positive integer".into());
+        },
+        "ai.maxRetries" if value.parse::<u32>().is_err() => {
+            return Err("ai.maxRetries must be a non-negative integer".into());
+        },
+        "commit.largeCommitWarning" if value.parse::<usize>().is_err() => {
+            return Err("commit.largeCommitWarning must be a non-negative integer".into());
+        },
+        "gc.auto" if value.parse::<usize>().is_err() => {
+            return Err("gc.auto must be a non-negative integer".into());
+        },
+        "gc.reflogExpire" if parse_reflog_expire(value).is_none() => {
+            return Err("gc.reflogExpire must be 'never', 'now' or a number of days".into());
+        },
+        key if is_boolean_key(key) && parse_bool(value).is_none() => {
+            return Err("Boolean values must be true/false, yes/no, on/off, or 1/0".into());
+        },
         _ => {}
     }
     
     Ok(())
 }
 
+fn is_branch_key(key: &str) -> bool {
+    match key.strip_prefix("branch.").and_then(|rest| rest.rsplit_once('.')) {
+        Some((branch, setting)) => !branch.is_empty() && ["description", "remote", "merge"].contains(&setting),
+        None => false,
+    }
+}
+
+fn is_remote_key(key: &str) -> bool {
+    match key.strip_prefix("remote.").and_then(|rest| rest.rsplit_once('.')) {
+        Some((remote, setting)) => !remote.is_empty() && setting == "url",
+        None => false,
+    }
+}
+
+fn is_boolean_key(key: &str) -> bool {
+    key.ends_with(".enabled")
+        || key.ends_with("gpgsign")
+        || key.ends_with("auditLog")
+        || key.ends_with("requireSignature")
+        || key.ends_with("quarantineSecrets")
+        || key.ends_with("profanityCheck")
+        || key.ends_with("commitGraph")
+        || key.ends_with("encryptObjects")
+        || key.ends_with("showStat")
+}
+
+fn normalize_config_value(key: &str, value: &str) -> String {
+    match parse_bool(value) {
+        Some(flag) if is_boolean_key(key) => flag.to_string(),
+        _ => value.to_string(),
+    }
+}
+
 async fn set_config(key: &str, value: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let value = &normalize_config_value(key, value);
     let repo_config_path = PathBuf::from(".aigit/config.json");
-    let mut config = if repo_config_path.exists() {
+    let in_repo = repo_config_path.parent().map(|p| p.exists()).unwrap_or(false);
+    
+    // Only the file being written is loaded, so global values are never
+    // copied into the repository config (where they would shadow later
+    // global changes).
+    let mut config = if in_repo {
         Config::load_from_file(&repo_config_path)?
     } else {
         Config::load_global().unwrap_or_default()
@@ -95,7 +207,7 @@ async fn set_config(key: &str, value: &str) -> Result<(), Box<dyn std::error::Er
     
     config.set(key, value);
     
-    if repo_config_path.parent().map(|p| p.exists()).unwrap_or(false) {
+    if in_repo {
         config.save_to_file(&repo_config_path)?;
         println!("{} {} = {}", "Set".green(), key.cyan(), value);
     } else {
@@ -107,18 +219,23 @@ async fn set_config(key: &str, value: &str) -> Result<(), Box<dyn std::error::Er
     Ok(())
 }
 
-async fn get_config(key: &str) -> Result<(), Box<dyn std::error::Error>> {
+async fn get_config(key: &str, value_type: Option<ConfigType>) -> Result<(), Box<dyn std::error::Error>> {
     let repo_config = Config::load_from_file(&PathBuf::from(".aigit/config.json")).ok();
     let global_config = Config::load_global().unwrap_or_default();
+    let defaults = Config::builtin_defaults();
     
     let value = repo_config
         .as_ref()
         .and_then(|c| c.get(key))
-        .or_else(|| global_config.get(key));
+        .or_else(|| global_config.get(key))
+        .or_else(|| defaults.get(key));
     
     match value {
         Some(val) => {
-            println!("{}", val);
+            match value_type {
+                Some(value_type) => println!("{}", typed_value(key, val, value_type)?),
+                None => println!("{}", val),
+            }
             audit_config_change("get", key, None).await?;
         },
         None => println!("{} {}", "No value found for".red(), key.cyan()),
@@ -127,6 +244,43 @@ async fn get_config(key: &str) -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+/// `value` of `key` in the canonical form of `value_type`, or an error if it
+/// cannot be read as that type.
+pub fn typed_value(key: &str, value: &str, value_type: ConfigType) -> Result<String, Box<dyn std::error::Error>> {
+    match value_type {
+        ConfigType::Bool => parse_bool(value)
+            .map(|flag| flag.to_string())
+            .ok_or_else(|| format!("bad boolean config value '{}' for '{}'", value, key).into()),
+        ConfigType::Int => parse_int(value)
+            .map(|n| n.to_string())
+            .ok_or_else(|| format!("bad numeric config value '{}' for '{}'", value, key).into()),
+        ConfigType::Path => {
+            let home = || dirs::home_dir().ok_or("Cannot find home directory");
+            let path = match value.trim() {
+                "" => return Err(format!("bad path config value '' for '{}'", key).into()),
+                "~" => home()?,
+                path => match path.strip_prefix("~/") {
+                    Some(rest) => home()?.join(rest),
+                    None => PathBuf::from(path),
+                },
+            };
+            Ok(std::path::absolute(path)?.display().to_string())
+        },
+    }
+}
+
+/// Parses an integer with an optional `k`, `m` or `g` suffix (powers of 1024).
+fn parse_int(value: &str) -> Option<i64> {
+    let value = value.trim();
+    let (digits, scale) = match value.chars().last()?.to_ascii_lowercase() {
+        'k' => (&value[..value.len() - 1], 1 << 10),
+        'm' => (&value[..value.len() - 1], 1 << 20),
+        'g' => (&value[..value.len() - 1], 1 << 30),
+        _ => (value, 1),
+    };
+    digits.parse::<i64>().ok()?.checked_mul(scale)
+}
+
 async fn list_config() -> Result<(), Box<dyn std::error::Error>> {
     let repo_config = Config::load_from_file(&PathBuf::from(".aigit/config.json")).ok();
     let global_config = Config::load_global().unwrap_or_default();
@@ -136,7 +290,7 @@ async fn list_config() -> Result<(), Box<dyn std::error::Error>> {
         if config.is_empty() {
             println!("  {}", "No repository configuration found".yellow());
         } else {
-            for (key, value) in config.iter() {
+            for (key, value) in sorted_entries(config) {
                 println!("  {} = {}", key.cyan(), value);
             }
         }
@@ -148,7 +302,7 @@ async fn list_config() -> Result<(), Box<dyn std::error::Error>> {
     if global_config.is_empty() {
         println!("  {}", "No global configuration found".yellow());
     } else {
-        for (key, value) in global_config.iter() {
+        for (key, value) in sorted_entries(&global_config) {
             println!("  {} = {}", key.cyan(), value);
         }
     }
@@ -157,6 +311,25 @@ async fn list_config() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+/// Every key set in the repository or global configuration, sorted and
+/// listed once, for `config list --name-only`.
+pub fn config_key_names() -> Vec<String> {
+    let repo_config = Config::load_from_file(&PathBuf::from(".aigit/config.json")).unwrap_or_default();
+    let global_config = Config::load_global().unwrap_or_default();
+
+    let keys: std::collections::BTreeSet<String> = repo_config.iter()
+        .chain(global_config.iter())
+        .map(|(key, _)| key.clone())
+        .collect();
+    keys.into_iter().collect()
+}
+
+fn sorted_entries(config: &Config) -> Vec<(&String, &String)> {
+    let mut entries: Vec<_> = config.iter().collect();
+    entries.sort();
+    entries
+}
+
 async fn set_user_config(name: Option<&str>, email: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
     if name.is_none() && email.is_none() {
         return Err("Please provide name and/or email".into());
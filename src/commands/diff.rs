@@ -1,16 +1,49 @@
-use crate::core::{Repository, Index};
-use crate::ai::gemini::GeminiClient;
-use crate::utils::diff::{generate_diff, calculate_diff_stats};
+use crate::core::{Repository, Index, resolve_revision};
+use crate::ai::{ai_enabled, provider_with_progress};
+use crate::utils::diff::{generate_diff_for_paths, calculate_diff_stats, commit_pair_changes, diff_file_stats, format_changes_patch, format_stat_block, print_diff, PathScope};
 use colored::*;
 use indicatif::{ProgressBar, ProgressStyle};
+use std::io::{IsTerminal, Write};
 
-pub async fn run(cached: bool, ai_explain: bool) -> Result<(), Box<dyn std::error::Error>> {
-    let repo = Repository::new(".aigit").ok_or("Not in a repository")?;
+#[derive(Default, Clone)]
+pub struct DiffOptions {
+    pub cached: bool,
+    pub ai_explain: bool,
+    pub binary: bool,
+    /// `--stat`: per-file insertion and deletion counts instead of the diff.
+    pub stat: bool,
+    /// `--color[=always|never|auto]`; `None` behaves like `auto`.
+    pub color: Option<String>,
+    /// `<rev-a> <rev-b>` or `<rev-a>..<rev-b>`: compare two commits instead
+    /// of the index and working tree.
+    pub revisions: Vec<String>,
+    /// `--aigit-header`: the older `diff --aigit a/x b/x (staged)` headers
+    /// instead of standard `diff --git` ones.
+    pub aigit_header: bool,
+    /// Paths or globs after `--`; only matching files are diffed.
+    pub paths: Vec<String>,
+}
+
+pub async fn run(cached: bool, ai_explain: bool, binary: bool) -> Result<(), Box<dyn std::error::Error>> {
+    run_with(DiffOptions { cached, ai_explain, binary, ..Default::default() }).await
+}
+
+pub async fn run_with(options: DiffOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let DiffOptions { cached, ai_explain, binary, stat, color, revisions, aigit_header, paths } = options;
+    let repo = Repository::discover().ok_or("Not in a repository")?;
+    colored::control::set_override(use_color(color.as_deref())?);
+    let commits = revision_pair(&repo, &revisions)?;
+    let paths = PathScope::new(&paths)?;
+    if commits.is_some() && cached {
+        return Err("--cached compares the index with HEAD and cannot be used with two revisions".into());
+    }
     
-    let diff_content = if cached {
-        get_staged_diff(&repo).await?
+    let diff_content = if let Some((old, new)) = &commits {
+        format_changes_patch(&repo, &commit_pair_changes(&repo, old, new)?, &paths)?
+    } else if cached {
+        get_staged_diff(&repo, binary, &paths).await?
     } else {
-        get_working_diff(&repo).await?
+        get_working_diff(&repo, binary, &paths).await?
     };
 
     if diff_content.is_empty() {
@@ -18,33 +51,97 @@ pub async fn run(cached: bool, ai_explain: bool) -> Result<(), Box<dyn std::erro
         return Ok(());
     }
 
+    if stat {
+        print!("{}", format_stat_block(&diff_file_stats(&diff_content).await));
+        return Ok(());
+    }
+
     let (additions, deletions, modifications) = calculate_diff_stats(&diff_content).await;
     
-    println!("{}", diff_content);
+    if aigit_header {
+        let annotation = match &commits {
+            Some(_) => revisions.join(".."),
+            None if cached => "staged".to_string(),
+            None => "working".to_string(),
+        };
+        print_diff(&with_aigit_headers(&diff_content, &annotation));
+    } else {
+        print_diff(&diff_content);
+    }
     
-    print_diff_summary(additions, deletions, modifications, cached);
+    let label = match (&commits, cached) {
+        (Some(_), _) => revisions.join(".."),
+        (None, true) => "staged".to_string(),
+        (None, false) => "working tree".to_string(),
+    };
+    print_diff_summary(additions, deletions, modifications, &label);
 
-    if ai_explain {
-        explain_changes_with_ai(&diff_content).await?;
+    if ai_explain && !ai_enabled(repo.config()) {
+        println!("\n{}", "Skipping AI explanation: AI features are disabled".bright_black());
+    } else if ai_explain {
+        // Base85 patch data means nothing to the model; explain the summary form.
+        let explain_content = if binary && commits.is_none() {
+            let index = Index::load(&repo)?;
+            generate_diff_for_paths(&repo, &index, cached, false, &paths).await?
+        } else {
+            diff_content
+        };
+        explain_changes_with_ai(&repo, &explain_content).await?;
     }
         
     
     Ok(())
 }
 
-async fn get_staged_diff(repo: &Repository) -> Result<String, Box<dyn std::error::Error>> {
+/// Rewrites each `diff --git a/x b/x` line as `diff --aigit a/x b/x
+/// (<annotation>)`, the header format used before diffs followed git's.
+fn with_aigit_headers(diff: &str, annotation: &str) -> String {
+    diff.lines()
+        .map(|line| match line.strip_prefix("diff --git ") {
+            Some(paths) => format!("diff --aigit {} ({})\n", paths, annotation),
+            None => format!("{}\n", line),
+        })
+        .collect()
+}
+
+/// Resolves `<rev-a> <rev-b>` or `<rev-a>..<rev-b>` to two commit hashes;
+/// `None` when no revisions were given.
+fn revision_pair(repo: &Repository, revisions: &[String]) -> Result<Option<(String, String)>, Box<dyn std::error::Error>> {
+    let (old, new) = match revisions {
+        [] => return Ok(None),
+        [range] => match range.split_once("..") {
+            Some((old, new)) if !old.is_empty() && !new.is_empty() => (old, new),
+            _ => return Err(format!("Expected two revisions or <a>..<b>, got '{}'", range).into()),
+        },
+        [old, new] => (old.as_str(), new.as_str()),
+        _ => return Err("aigit diff compares at most two revisions".into()),
+    };
+    Ok(Some((resolve_revision(repo, old)?, resolve_revision(repo, new)?)))
+}
+
+/// Resolves `--color`: `always` and `never` are taken as given; `auto` (the
+/// default) colors only a terminal, and only when `NO_COLOR` is unset.
+pub fn use_color(mode: Option<&str>) -> Result<bool, Box<dyn std::error::Error>> {
+    match mode {
+        Some("always") => Ok(true),
+        Some("never") => Ok(false),
+        None | Some("auto") => Ok(std::io::stdout().is_terminal()
+            && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())),
+        Some(other) => Err(format!("Invalid --color value '{}': expected always, never or auto", other).into()),
+    }
+}
+
+async fn get_staged_diff(repo: &Repository, binary: bool, paths: &PathScope) -> Result<String, Box<dyn std::error::Error>> {
     let index = Index::load(repo)?;
-    generate_diff(repo, &index, true).await
+    generate_diff_for_paths(repo, &index, true, binary, paths).await
 }
 
-async fn get_working_diff(repo: &Repository) -> Result<String, Box<dyn std::error::Error>> {
+async fn get_working_diff(repo: &Repository, binary: bool, paths: &PathScope) -> Result<String, Box<dyn std::error::Error>> {
     let index = Index::load(repo)?;
-    generate_diff(repo, &index, false).await
+    generate_diff_for_paths(repo, &index, false, binary, paths).await
 }
 
-fn print_diff_summary(additions: usize, deletions: usize, modifications: usize, staged: bool) {
-    let diff_type = if staged { "staged" } else { "working tree" };
-    
+fn print_diff_summary(additions: usize, deletions: usize, modifications: usize, diff_type: &str) {
     println!("\n{}", format!("=== {} changes ===", diff_type).cyan().bold());
     
     if additions > 0 {
@@ -65,21 +162,33 @@ fn print_diff_summary(additions: usize, deletions: usize, modifications: usize,
     }
 }
 
-async fn explain_changes_with_ai(diff_content: &str) -> Result<(), Box<dyn std::error::Error>> {
+async fn explain_changes_with_ai(repo: &Repository, diff_content: &str) -> Result<(), Box<dyn std::error::Error>> {
     let pb = ProgressBar::new_spinner();
     pb.set_style(ProgressStyle::default_spinner().template("{spinner} {msg}").unwrap());
     pb.set_message("AI analyzing changes...");
     pb.enable_steady_tick(std::time::Duration::from_millis(100));
 
-    let gemini = GeminiClient::new();
-    match gemini.explain_diff(diff_content).await {
-        Ok(explanation) => {
+    let mut started = false;
+    let mut print_chunk = |chunk: &str| {
+        if !started {
             pb.finish_and_clear();
             println!("\n{}", "=== AI Explanation ===".cyan().bold());
-            println!("{}", explanation);
-        },
+            started = true;
+        }
+        print!("{}", chunk);
+        let _ = std::io::stdout().flush();
+    };
+    let explanation = match provider_with_progress(repo.config(), &pb) {
+        Ok(provider) => provider.explain_diff_streaming(diff_content, &mut print_chunk).await,
+        Err(e) => Err(e.into()),
+    };
+    match explanation {
+        Ok(_) => println!(),
         Err(e) => {
             pb.finish_and_clear();
+            if started {
+                println!();
+            }
             println!("{} {}", "Failed to explain changes:".red(), e);
         }
     }
@@ -0,0 +1,278 @@
+use crate::core::{Repository, Config, Index};
+use crate::ai::{ai_enabled, provider_from_config};
+use crate::commands::config::validate_config_value;
+use crate::utils::editor::{find_program, resolve_editor};
+use colored::*;
+use std::io::Write;
+
+/// Prompt sent by `doctor --test-ai`; any reply counts as success.
+const AI_TEST_PROMPT: &str = "Reply with the single word OK.";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+/// The outcome of one check, with a hint on how to fix anything that is not
+/// a pass.
+#[derive(Debug, Clone)]
+pub struct CheckResult {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+    pub hint: Option<String>,
+}
+
+impl CheckResult {
+    fn pass(name: &'static str, detail: impl Into<String>) -> Self {
+        Self { name, status: CheckStatus::Pass, detail: detail.into(), hint: None }
+    }
+
+    fn warn(name: &'static str, detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self { name, status: CheckStatus::Warn, detail: detail.into(), hint: Some(hint.into()) }
+    }
+
+    fn fail(name: &'static str, detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self { name, status: CheckStatus::Fail, detail: detail.into(), hint: Some(hint.into()) }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DoctorReport {
+    pub checks: Vec<CheckResult>,
+}
+
+impl DoctorReport {
+    pub fn count(&self, status: CheckStatus) -> usize {
+        self.checks.iter().filter(|check| check.status == status).count()
+    }
+
+    pub fn has_failures(&self) -> bool {
+        self.count(CheckStatus::Fail) > 0
+    }
+
+    /// The result of the check called `name`, if it ran.
+    pub fn check(&self, name: &str) -> Option<&CheckResult> {
+        self.checks.iter().find(|check| check.name == name)
+    }
+}
+
+/// Runs every check and prints a pass/warn/fail report. With `test_ai` the
+/// configured provider is also sent a short request. Fails when any check
+/// failed, so scripts can gate on it.
+pub async fn run(test_ai: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let repo = Repository::discover();
+    let mut report = diagnose(repo.as_ref());
+    if test_ai {
+        let config = match &repo {
+            Some(repo) => repo.config().clone(),
+            None => Config::load_global().unwrap_or_default(),
+        };
+        report.checks.push(check_ai_call(&config).await);
+    }
+
+    print_report(&report);
+    audit_doctor(&report).await?;
+
+    match report.count(CheckStatus::Fail) {
+        0 => Ok(()),
+        failed => Err(format!("doctor found {} problem{}", failed, if failed == 1 { "" } else { "s" }).into()),
+    }
+}
+
+/// The checks that need no network: identity, editor, AI key, config and,
+/// inside a repository, its structure and index.
+pub fn diagnose(repo: Option<&Repository>) -> DoctorReport {
+    let config = match repo {
+        Some(repo) => repo.config().clone(),
+        None => Config::load_global().unwrap_or_default(),
+    };
+
+    let mut checks = vec![
+        check_identity(&config),
+        check_editor(&config),
+        check_ai_key(&config),
+        check_config_files(repo),
+    ];
+    match repo {
+        Some(repo) => {
+            checks.push(check_repository(repo));
+            checks.push(check_index(repo));
+        },
+        None => checks.push(CheckResult::warn("repository", "not inside an aigit repository",
+                                             "Run doctor from a working tree, or 'aigit init' to create one")),
+    }
+
+    DoctorReport { checks }
+}
+
+/// The author identity comes from `user.name`/`user.email` or `AIGIT_AUTHOR`;
+/// without them commits silently fall back to a placeholder.
+fn check_identity(config: &Config) -> CheckResult {
+    let env_author = std::env::var("AIGIT_AUTHOR").ok().filter(|value| !value.trim().is_empty());
+    let mut missing = Vec::new();
+    if config.get("user.name").is_none() && env_author.is_none() {
+        missing.push("user.name");
+    }
+    if config.get("user.email").is_none() && !env_author.as_deref().is_some_and(|author| author.contains('<')) {
+        missing.push("user.email");
+    }
+
+    if missing.is_empty() {
+        return CheckResult::pass("identity", config.get_author_string());
+    }
+    CheckResult::fail(
+        "identity",
+        format!("{} not set; commits would be authored as {}", missing.join(" and "), config.get_author_string()),
+        "Run 'aigit config user \"Your Name\" --email you@example.com' or set AIGIT_AUTHOR",
+    )
+}
+
+fn check_editor(config: &Config) -> CheckResult {
+    let env_editor = std::env::var("EDITOR").ok();
+    let (editor, warning) = resolve_editor(config.get("core.editor").map(String::as_str), env_editor.as_deref());
+
+    if find_program(&editor).is_none() {
+        return CheckResult::fail("editor", format!("no editor found (tried '{}')", editor),
+                                 "Install an editor or run 'aigit config set core.editor <command>'");
+    }
+    match warning {
+        Some(warning) => CheckResult::warn("editor", warning, "Run 'aigit config set core.editor <command>' with an installed editor"),
+        None => CheckResult::pass("editor", editor),
+    }
+}
+
+/// Builds the configured provider, which is where a missing API key or an
+/// unknown provider shows up.
+fn check_ai_key(config: &Config) -> CheckResult {
+    if !ai_enabled(config) {
+        return CheckResult::pass("ai", "AI features are disabled (--no-ai or ai.enabled = false)");
+    }
+
+    let provider = config.get("ai.provider").map(String::as_str).unwrap_or("gemini");
+    match provider_from_config(config) {
+        Ok(_) => CheckResult::pass("ai", format!("provider '{}' is configured", provider)),
+        Err(e) => CheckResult::warn("ai", e.to_string(),
+                                    "AI commands will fail until this is fixed; the rest of aigit works without it"),
+    }
+}
+
+/// Config files that do not parse are otherwise ignored without a word, and
+/// values written by hand skip `config set` validation.
+fn check_config_files(repo: Option<&Repository>) -> CheckResult {
+    let mut files = Vec::new();
+    if let Ok(global) = Config::global_path() {
+        files.push(global);
+    }
+    if let Some(repo) = repo {
+        files.push(repo.git_dir.join("config.json"));
+    }
+
+    files.retain(|file| file.exists());
+
+    let mut problems = Vec::new();
+    for file in &files {
+        let config = match std::fs::read_to_string(file).map_err(|e| e.to_string())
+            .and_then(|content| serde_json::from_str::<Config>(&content).map_err(|e| e.to_string())) {
+            Ok(config) => config,
+            Err(e) => {
+                problems.push(format!("{} cannot be read: {}", file.display(), e));
+                continue;
+            },
+        };
+        let mut entries: Vec<_> = config.iter().collect();
+        entries.sort();
+        for (key, value) in entries {
+            if let Err(e) = validate_config_value(key, value) {
+                problems.push(format!("{} = {}: {}", key, value, e));
+            }
+        }
+    }
+
+    if problems.is_empty() {
+        return CheckResult::pass("config", format!("{} file{} valid", files.len(), if files.len() == 1 { "" } else { "s" }));
+    }
+    CheckResult::fail("config", problems.join("; "), "Fix the values with 'aigit config set <key> <value>'")
+}
+
+fn check_repository(repo: &Repository) -> CheckResult {
+    match repo.verify_integrity() {
+        Ok(()) => CheckResult::pass("repository", repo.git_dir.display().to_string()),
+        Err(e) => CheckResult::fail("repository", e.to_string(),
+                                    "Run 'aigit fsck --full'; restore .aigit from a backup or a fresh clone if it reports damage"),
+    }
+}
+
+/// Loads the index through the same version and hash checks every command
+/// uses.
+fn check_index(repo: &Repository) -> CheckResult {
+    if !repo.git_dir.join("index").exists() {
+        return CheckResult::pass("index", "no index yet");
+    }
+    match Index::load(repo) {
+        Ok(index) => CheckResult::pass("index", format!("version {}, {} entries", index.version, index.entries.len())),
+        Err(e) => CheckResult::fail("index", e.to_string(),
+                                    "The index was written by an incompatible version; remove .aigit/index and re-add your files"),
+    }
+}
+
+async fn check_ai_call(config: &Config) -> CheckResult {
+    if !ai_enabled(config) {
+        return CheckResult::warn("ai request", "skipped: AI features are disabled",
+                                 "Drop --no-ai or set ai.enabled = true to test the provider");
+    }
+    let provider = match provider_from_config(config) {
+        Ok(provider) => provider,
+        Err(e) => return CheckResult::fail("ai request", e.to_string(), "Fix the ai check above first"),
+    };
+    match provider.generate_text(AI_TEST_PROMPT).await {
+        Ok(_) => CheckResult::pass("ai request", "the provider answered"),
+        Err(e) => CheckResult::fail("ai request", e.to_string(),
+                                    "Check the API key, ai.endpoint and your network connection"),
+    }
+}
+
+fn print_report(report: &DoctorReport) {
+    println!("{}", "=== aigit doctor ===".cyan().bold());
+    let width = report.checks.iter().map(|check| check.name.len()).max().unwrap_or(0);
+
+    for check in &report.checks {
+        let label = match check.status {
+            CheckStatus::Pass => "[ ok ]".green(),
+            CheckStatus::Warn => "[warn]".yellow(),
+            CheckStatus::Fail => "[FAIL]".red().bold(),
+        };
+        println!("{} {:<width$}  {}", label, check.name, check.detail, width = width);
+        if let Some(hint) = &check.hint {
+            println!("       {:<width$}  {} {}", "", "hint:".bright_black(), hint.bright_black(), width = width);
+        }
+    }
+
+    println!("\n{} passed, {} warning{}, {} failed",
+            report.count(CheckStatus::Pass).to_string().green(),
+            report.count(CheckStatus::Warn).to_string().yellow(),
+            if report.count(CheckStatus::Warn) == 1 { "" } else { "s" },
+            report.count(CheckStatus::Fail).to_string().red());
+}
+
+async fn audit_doctor(report: &DoctorReport) -> Result<(), Box<dyn std::error::Error>> {
+    let audit_file = std::path::PathBuf::from(".aigit/logs/audit.log");
+    if !audit_file.exists() {
+        return Ok(());
+    }
+
+    let timestamp = chrono::Utc::now().to_rfc3339();
+    let user = whoami::username();
+    let details = format!("pass={};warn={};fail={}",
+                          report.count(CheckStatus::Pass), report.count(CheckStatus::Warn), report.count(CheckStatus::Fail));
+
+    let entry = format!("{},doctor,{},{},repository\n", timestamp, user, details);
+    std::fs::OpenOptions::new()
+        .append(true)
+        .open(audit_file)?
+        .write_all(entry.as_bytes())?;
+
+    Ok(())
+}
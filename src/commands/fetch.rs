@@ -0,0 +1,66 @@
+use crate::core::Repository;
+use crate::core::transport::{self, Remote};
+use crate::commands::push::print_transfer_report;
+use colored::*;
+use indicatif::{ProgressBar, ProgressStyle};
+use std::io::Write;
+
+/// Fetches every branch of `remote` (origin by default) into its tracking
+/// refs. With `prune`, tracking refs for branches the remote no longer has
+/// are deleted.
+pub async fn run(remote: Option<String>, prune: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let repo = Repository::discover().ok_or("Not in a repository")?;
+    let config = repo.config();
+    let remote = Remote::open(config, remote.as_deref().unwrap_or("origin"))?;
+
+    let pb = ProgressBar::new_spinner();
+    pb.set_style(ProgressStyle::default_spinner().template("{spinner} {msg}").unwrap());
+    pb.set_message(format!("Fetching from '{}'...", remote.name));
+    pb.enable_steady_tick(std::time::Duration::from_millis(100));
+
+    let result = transport::fetch(&repo, &remote);
+    pb.finish_and_clear();
+    let (report, heads) = result?;
+
+    print_transfer_report(&report);
+    for (branch, hash) in &heads {
+        println!("  {} {} -> {}/{}",
+                hash[..8.min(hash.len())].bright_yellow(),
+                branch,
+                remote.name.bright_cyan(),
+                branch);
+    }
+
+    if prune {
+        let pruned = transport::prune_tracking_refs(&repo, &remote.name, &heads)?;
+        print_pruned(&remote.name, &pruned);
+    }
+
+    audit_fetch(&remote.name, report.transferred).await?;
+    Ok(())
+}
+
+pub fn print_pruned(remote: &str, pruned: &[String]) {
+    for branch in pruned {
+        println!("  {} {}/{}", "[deleted]".red(), remote.bright_cyan(), branch);
+    }
+}
+
+async fn audit_fetch(remote: &str, transferred: usize) -> Result<(), Box<dyn std::error::Error>> {
+    let audit_file = std::path::PathBuf::from(".aigit/logs/audit.log");
+    if !audit_file.exists() {
+        return Ok(());
+    }
+
+    let timestamp = chrono::Utc::now().to_rfc3339();
+    let user = whoami::username();
+    let details = format!("remote:{},objects:{}", remote, transferred);
+
+    let entry = format!("{},fetch,{},{},operation\n", timestamp, user, details);
+    std::fs::OpenOptions::new()
+        .append(true)
+        .open(audit_file)?
+        .write_all(entry.as_bytes())?;
+
+    Ok(())
+}
@@ -0,0 +1,70 @@
+use crate::core::{Repository, Object, Commit};
+use crate::core::revlist::{rev_list, RevRange};
+use crate::utils::diff::commit_patch;
+use crate::utils::patch::format_mail;
+use colored::*;
+use std::path::{Path, PathBuf};
+
+const MAX_SLUG_LEN: usize = 52;
+
+pub async fn run(revision: String, output_dir: Option<String>) -> Result<(), Box<dyn std::error::Error>> {
+    let repo = Repository::discover().ok_or("Not in a repository")?;
+    let dir = PathBuf::from(output_dir.unwrap_or_else(|| ".".to_string()));
+
+    let written = write_patches(&repo, &revision, &dir)?;
+    if written.is_empty() {
+        println!("{}", "No commits to format".yellow());
+    }
+    for path in &written {
+        println!("{}", path.display());
+    }
+
+    Ok(())
+}
+
+/// Writes one `NNNN-<subject>.patch` mail per commit into `dir`, oldest
+/// first. `<since>` means `<since>..HEAD`; merges are skipped.
+pub fn write_patches(repo: &Repository, revision: &str, dir: &Path) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let spec = if revision.contains("..") { revision.to_string() } else { format!("{}..HEAD", revision) };
+    let range = RevRange::parse(repo, &spec)?;
+
+    let mut commits = Vec::new();
+    for hash in rev_list(repo, &range)?.into_iter().rev() {
+        let commit: Commit = serde_json::from_slice(&Object::read(repo, &hash)?)?;
+        if !commit.is_merge() {
+            commits.push((hash, commit));
+        }
+    }
+
+    std::fs::create_dir_all(dir)?;
+    let total = commits.len();
+    let mut written = Vec::with_capacity(total);
+
+    for (i, (hash, commit)) in commits.iter().enumerate() {
+        let diff = commit_patch(repo, commit)?;
+        let path = dir.join(format!("{:04}-{}.patch", i + 1, slug(&commit.short_message())));
+        std::fs::write(&path, format_mail(hash, commit, i + 1, total, &diff))?;
+        written.push(path);
+    }
+
+    Ok(written)
+}
+
+/// The subject reduced to lowercase words joined by dashes, as in git's
+/// patch file names.
+fn slug(subject: &str) -> String {
+    let words: Vec<String> = subject
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect();
+    let mut slug = words.join("-");
+    if slug.len() > MAX_SLUG_LEN {
+        slug.truncate(MAX_SLUG_LEN);
+        slug = slug.trim_end_matches('-').to_string();
+    }
+    if slug.is_empty() {
+        slug.push_str("patch");
+    }
+    slug
+}
@@ -0,0 +1,331 @@
+use crate::core::{Repository, Object, ObjectType, Commit, Tree, Index, Refs, Branch, Stash, Tag};
+use crate::core::{gc, pack};
+use crate::core::revlist::parents_of;
+use crate::core::revision::all_object_hashes;
+use crate::commands::gc::print_size_repairs;
+use crate::utils::progress::object_progress;
+use colored::*;
+use indicatif::ProgressBar;
+use ring::digest;
+use std::collections::HashSet;
+use std::io::Write;
+
+/// Everything `aigit fsck` found, grouped the way it is printed.
+#[derive(Default, Debug)]
+pub struct FsckReport {
+    /// Objects whose stored bytes no longer hash to their name.
+    pub corrupted: Vec<String>,
+    /// Loose objects whose header size disagrees with their content;
+    /// `fsck --repair` and `gc` fix or quarantine them.
+    pub size_mismatches: Vec<String>,
+    /// `(hash, referrer)` for objects that a ref, commit or tree names but
+    /// that are not in the object store.
+    pub missing: Vec<(String, String)>,
+    /// `(ref, reason)` for refs that do not point at a readable commit.
+    pub broken_refs: Vec<(String, String)>,
+    /// Index paths whose recorded checksum does not match the staged blob.
+    pub index_mismatches: Vec<String>,
+    /// `(pack, hash, reason)` for pack index entries that do not match the
+    /// bytes in their pack.
+    pub pack_mismatches: Vec<(String, String, String)>,
+    /// `(type, hash)` for unreachable objects nothing else refers to. Only
+    /// commits are listed unless the check was run with `full`.
+    pub dangling: Vec<(String, String)>,
+}
+
+impl FsckReport {
+    pub fn is_broken(&self) -> bool {
+        !self.corrupted.is_empty()
+            || !self.size_mismatches.is_empty()
+            || !self.missing.is_empty()
+            || !self.broken_refs.is_empty()
+            || !self.index_mismatches.is_empty()
+            || !self.pack_mismatches.is_empty()
+    }
+}
+
+pub async fn run(full: bool, lost_found: bool, repair: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let repo = Repository::discover().ok_or("Not in a repository")?;
+    if repair {
+        print_size_repairs(&gc::repair_size_mismatches(&repo)?);
+    }
+    let pb = object_progress("Checking objects");
+    let report = check_with_progress(&repo, full, &pb);
+    pb.finish_and_clear();
+    let report = report?;
+
+    for hash in &report.corrupted {
+        println!("{} {}", "corrupted object".red(), hash);
+    }
+    for hash in &report.size_mismatches {
+        println!("{} {} {}", "size mismatch".red(), hash, "(run fsck --repair)".bright_black());
+    }
+    for (hash, referrer) in &report.missing {
+        println!("{} {} {}", "missing object".red(), hash, format!("(referenced by {})", referrer).bright_black());
+    }
+    for (name, reason) in &report.broken_refs {
+        println!("{} {}: {}", "broken ref".red(), name, reason);
+    }
+    for path in &report.index_mismatches {
+        println!("{} {}", "index checksum mismatch".red(), path);
+    }
+    for (pack, hash, reason) in &report.pack_mismatches {
+        println!("{} {} in {}: {}", "pack mismatch".red(), hash, pack, reason);
+    }
+    for (kind, hash) in &report.dangling {
+        println!("{} {}", format!("dangling {}", kind).yellow(), hash);
+    }
+
+    let dangling_commits: Vec<String> = report.dangling.iter()
+        .filter(|(kind, _)| kind == "commit")
+        .map(|(_, hash)| hash.clone())
+        .collect();
+
+    if lost_found && !dangling_commits.is_empty() {
+        gc::write_lost_found(&repo, &dangling_commits)?;
+        println!("{} {} commit(s) to {}",
+                "Saved".green(),
+                dangling_commits.len(),
+                "refs/lost-found/".bright_cyan());
+        println!("{}", "They are now kept by gc until the refs are removed".bright_black());
+    } else if report.dangling.is_empty() && !report.is_broken() {
+        println!("{}", "No problems found".green());
+    }
+
+    audit_fsck(&report, lost_found).await?;
+
+    if report.is_broken() {
+        return Err(format!("fsck found {} corrupted, {} size-mismatched, {} missing, {} broken ref(s), {} index mismatch(es) and {} pack mismatch(es)",
+                           report.corrupted.len(),
+                           report.size_mismatches.len(),
+                           report.missing.len(),
+                           report.broken_refs.len(),
+                           report.index_mismatches.len(),
+                           report.pack_mismatches.len()).into());
+    }
+    Ok(())
+}
+
+/// Checks object integrity, pack indexes against their packs, ref targets,
+/// commit/tree connectivity and index checksums. With `full`, unreachable trees and blobs are reported as
+/// dangling too, not just commits.
+pub fn check(repo: &Repository, full: bool) -> Result<FsckReport, Box<dyn std::error::Error>> {
+    check_with_progress(repo, full, &ProgressBar::hidden())
+}
+
+/// Like `check`, advancing `progress` once per loose object verified.
+pub fn check_with_progress(repo: &Repository, full: bool, progress: &ProgressBar) -> Result<FsckReport, Box<dyn std::error::Error>> {
+    let objects = Object::verify_repository_objects_with(repo, progress)?;
+    let mut report = FsckReport {
+        corrupted: objects.corrupted,
+        size_mismatches: objects.size_mismatches,
+        ..Default::default()
+    };
+    report.pack_mismatches = pack_mismatches(repo);
+    let corrupted: HashSet<String> = report.corrupted.iter().chain(&report.size_mismatches).cloned().collect();
+
+    let refs = Refs::load(repo)?;
+    let mut tips = Vec::new();
+    let mut named: Vec<(String, String)> = Vec::new();
+    named.extend(refs.heads.iter().map(|(name, hash)| (format!("refs/heads/{}", name), hash.clone())));
+    named.extend(refs.tags.iter().map(|(name, hash)| (format!("refs/tags/{}", name), hash.clone())));
+    named.extend(refs.remotes.iter().map(|(name, hash)| (format!("refs/remotes/{}", name), hash.clone())));
+    named.sort();
+
+    for (name, hash) in named {
+        match ref_target_problem(repo, &hash, &corrupted) {
+            Some(reason) => report.broken_refs.push((name, reason)),
+            None => tips.push((hash, name)),
+        }
+    }
+
+    if let Some(head) = Branch::get_current_commit(repo) {
+        tips.push((head, "HEAD".to_string()));
+    }
+    for (i, hash) in Stash::load(repo)?.entries.into_iter().enumerate() {
+        tips.push((hash, format!("stash@{{{}}}", i)));
+    }
+
+    check_connectivity(repo, tips, &corrupted, &mut report);
+    report.index_mismatches = index_mismatches(repo, &corrupted, &mut report.missing);
+    report.dangling = dangling_objects(repo, full)?;
+
+    Ok(report)
+}
+
+fn ref_target_problem(repo: &Repository, hash: &str, corrupted: &HashSet<String>) -> Option<String> {
+    if !Object::exists(repo, hash) {
+        return Some(format!("points to missing object {}", hash));
+    }
+    if corrupted.contains(hash) {
+        return Some(format!("points to corrupted object {}", hash));
+    }
+    match Object::read_with_type(repo, hash) {
+        Ok((ObjectType::Commit, _)) | Ok((ObjectType::Tag, _)) => None,
+        Ok((other, _)) => Some(format!("points to a {}, not a commit", other.as_str())),
+        Err(e) => Some(format!("cannot read {}: {}", hash, e)),
+    }
+}
+
+/// Walks every commit and tree reachable from `tips`, recording any object
+/// that is named but absent. Corrupted objects are already reported and are
+/// not descended into.
+fn check_connectivity(
+    repo: &Repository,
+    tips: Vec<(String, String)>,
+    corrupted: &HashSet<String>,
+    report: &mut FsckReport,
+) {
+    let mut seen = HashSet::new();
+    let mut to_visit = tips;
+
+    while let Some((hash, referrer)) = to_visit.pop() {
+        if hash.is_empty() || !seen.insert(hash.clone()) {
+            continue;
+        }
+        if !Object::exists(repo, &hash) {
+            report.missing.push((hash, referrer));
+            continue;
+        }
+        if corrupted.contains(&hash) {
+            continue;
+        }
+
+        match Object::read_with_type(repo, &hash) {
+            Ok((ObjectType::Commit, content)) => match serde_json::from_slice::<Commit>(&content) {
+                Ok(commit) => {
+                    let label = format!("commit {}", hash);
+                    to_visit.push((commit.tree.clone(), label.clone()));
+                    to_visit.extend(parents_of(&commit).into_iter().map(|parent| (parent, label.clone())));
+                },
+                Err(_) => report.corrupted.push(hash),
+            },
+            Ok((ObjectType::Tree, content)) => match serde_json::from_slice::<Tree>(&content) {
+                Ok(tree) => {
+                    for entry in tree.entries {
+                        to_visit.push((entry.hash, format!("tree {} ({})", hash, entry.name)));
+                    }
+                },
+                Err(_) => report.corrupted.push(hash),
+            },
+            Ok((ObjectType::Tag, content)) => match serde_json::from_slice::<Tag>(&content) {
+                Ok(tag) => to_visit.push((tag.object, format!("tag {}", hash))),
+                Err(_) => report.corrupted.push(hash),
+            },
+            Ok(_) => {},
+            Err(_) => report.corrupted.push(hash),
+        }
+    }
+}
+
+fn index_mismatches(
+    repo: &Repository,
+    corrupted: &HashSet<String>,
+    missing: &mut Vec<(String, String)>,
+) -> Vec<String> {
+    let index = Index::load(repo).unwrap_or_default();
+    let mut paths: Vec<&String> = index.entries.keys().collect();
+    paths.sort();
+
+    let mut mismatches = Vec::new();
+    for path in paths {
+        let hash = &index.entries[path];
+        if !Object::exists(repo, hash) {
+            missing.push((hash.clone(), format!("index ({})", path)));
+            continue;
+        }
+        if corrupted.contains(hash) {
+            continue;
+        }
+
+        let checksum = match index.metadata.get(path) {
+            Some(entry) if !entry.checksum.is_empty() => &entry.checksum,
+            _ => continue,
+        };
+        let matches = Object::read(repo, hash)
+            .map(|content| hex::encode(digest::digest(&digest::SHA256, &content).as_ref()) == *checksum)
+            .unwrap_or(false);
+        if !matches {
+            mismatches.push(path.trim_start_matches("./").to_string());
+        }
+    }
+    mismatches
+}
+
+fn pack_mismatches(repo: &Repository) -> Vec<(String, String, String)> {
+    let mut mismatches = Vec::new();
+    for index in pack::indexes(repo).iter() {
+        let name = index.pack_path.file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        mismatches.extend(index.verify(repo).into_iter().map(|(hash, reason)| (name.clone(), hash, reason)));
+    }
+    mismatches
+}
+
+/// Unreachable objects that no other unreachable object refers to.
+fn dangling_objects(repo: &Repository, full: bool) -> Result<Vec<(String, String)>, Box<dyn std::error::Error>> {
+    if !full {
+        return Ok(gc::dangling_commits(repo)?
+            .into_iter()
+            .map(|hash| ("commit".to_string(), hash))
+            .collect());
+    }
+
+    let reachable = gc::reachable_objects(repo)?;
+    let mut unreachable = Vec::new();
+    let mut referenced = HashSet::new();
+
+    for hash in all_object_hashes(repo) {
+        if reachable.contains(&hash) {
+            continue;
+        }
+        if let Ok((obj_type, content)) = Object::read_with_type(repo, &hash) {
+            match obj_type {
+                ObjectType::Commit => if let Ok(commit) = serde_json::from_slice::<Commit>(&content) {
+                    referenced.insert(commit.tree.clone());
+                    referenced.extend(parents_of(&commit));
+                },
+                ObjectType::Tree => if let Ok(tree) = serde_json::from_slice::<Tree>(&content) {
+                    referenced.extend(tree.entries.into_iter().map(|entry| entry.hash));
+                },
+                ObjectType::Tag => if let Ok(tag) = serde_json::from_slice::<Tag>(&content) {
+                    referenced.insert(tag.object);
+                },
+                _ => {},
+            }
+            unreachable.push((obj_type.as_str().to_string(), hash));
+        }
+    }
+
+    unreachable.retain(|(_, hash)| !referenced.contains(hash));
+    unreachable.sort();
+    Ok(unreachable)
+}
+
+async fn audit_fsck(report: &FsckReport, lost_found: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let audit_file = std::path::PathBuf::from(".aigit/logs/audit.log");
+    if !audit_file.exists() {
+        return Ok(());
+    }
+
+    let timestamp = chrono::Utc::now().to_rfc3339();
+    let user = whoami::username();
+    let details = format!("dangling:{},corrupted:{},size_mismatches:{},missing:{},broken_refs:{},index_mismatches:{},pack_mismatches:{},lost_found:{}",
+                          report.dangling.len(),
+                          report.corrupted.len(),
+                          report.size_mismatches.len(),
+                          report.missing.len(),
+                          report.broken_refs.len(),
+                          report.index_mismatches.len(),
+                          report.pack_mismatches.len(),
+                          lost_found);
+
+    let entry = format!("{},fsck,{},{},maintenance\n", timestamp, user, details);
+    std::fs::OpenOptions::new()
+        .append(true)
+        .open(audit_file)?
+        .write_all(entry.as_bytes())?;
+
+    Ok(())
+}
@@ -0,0 +1,146 @@
+use crate::core::{Repository, Config};
+use crate::core::gc::{self, GcReport, DEFAULT_PRUNE_GRACE};
+use crate::core::object::SizeRepair;
+use colored::*;
+use crate::utils::progress::object_progress;
+use std::io::Write;
+use std::time::Duration;
+
+/// Prunes unreachable objects and packs the rest. With `prune_packed` only
+/// the loose copies of already packed objects are removed.
+pub async fn run(auto: bool, prune_now: bool, prune_packed: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let repo = Repository::discover().ok_or("Not in a repository")?;
+    let config = repo.config();
+
+    if prune_packed {
+        let mut report = GcReport::default();
+        let pb = object_progress("Removing loose copies of packed objects");
+        let pruned = gc::prune_packed_with(&repo, &mut report, &pb);
+        pb.finish_and_clear();
+        pruned?;
+        if report.pruned_packed == 0 {
+            println!("{}", "No loose objects are packed".bright_black());
+        }
+        print_report(&report);
+        audit_gc(&report, false).await?;
+        return Ok(());
+    }
+
+    if auto {
+        if !run_auto(&repo, config).await? {
+            println!("{}", "Nothing to do".bright_black());
+        }
+        return Ok(());
+    }
+
+    let grace = if prune_now { Duration::ZERO } else { DEFAULT_PRUNE_GRACE };
+    let report = collect_with_progress(&repo, grace)?;
+    print_report(&report);
+    audit_gc(&report, false).await?;
+
+    Ok(())
+}
+
+pub async fn run_auto(repo: &Repository, config: &Config) -> Result<bool, Box<dyn std::error::Error>> {
+    if !gc::needs_auto_gc(repo, config) {
+        return Ok(false);
+    }
+
+    println!("{}", "Loose objects exceed gc.auto, running garbage collection...".bright_black());
+    let report = collect_with_progress(repo, DEFAULT_PRUNE_GRACE)?;
+    print_report(&report);
+    audit_gc(&report, true).await?;
+
+    Ok(true)
+}
+
+fn collect_with_progress(repo: &Repository, grace: Duration) -> Result<GcReport, Box<dyn std::error::Error>> {
+    let pb = object_progress("Scanning reachable objects");
+
+    let mut report = match gc::collect_with(repo, grace, &pb) {
+        Ok(report) => report,
+        Err(e) => {
+            pb.finish_and_clear();
+            return Err(e);
+        }
+    };
+
+    if let Err(e) = gc::prune_packed_with(repo, &mut report, &pb) {
+        pb.finish_and_clear();
+        return Err(e);
+    }
+
+    pb.set_message("Packing reachable objects");
+    let packed = gc::repack(repo);
+    pb.finish_and_clear();
+
+    report.packed = packed?;
+    Ok(report)
+}
+
+fn print_report(report: &GcReport) {
+    print_size_repairs(&report.size_repairs);
+
+    if report.loose_before > 0 {
+        println!("{} {} loose objects ({} reachable)",
+                "Scanned".green(),
+                report.loose_before.to_string().bright_yellow(),
+                report.reachable.to_string().bright_blue());
+    }
+
+    if report.pruned > 0 {
+        println!("{} {} unreachable objects ({} bytes freed)",
+                "Pruned".green(),
+                report.pruned.to_string().bright_yellow(),
+                report.freed_bytes.to_string().bright_blue());
+    }
+
+    if report.pruned_packed > 0 {
+        println!("{} {} loose objects already in a pack",
+                "Removed".green(),
+                report.pruned_packed.to_string().bright_yellow());
+    }
+
+    if report.packed > 0 {
+        println!("{} {} objects into a pack",
+                "Packed".green(),
+                report.packed.to_string().bright_yellow());
+    }
+}
+
+/// Shared with `fsck --repair`.
+pub fn print_size_repairs(repairs: &[(String, SizeRepair)]) {
+    for (hash, repair) in repairs {
+        match repair {
+            SizeRepair::Rewritten => println!("{} {} {}",
+                                              "Repaired".green(), hash, "(size header rewritten)".bright_black()),
+            SizeRepair::Quarantined(path) => println!("{} {} {}",
+                                                      "Quarantined".yellow(), hash,
+                                                      format!("(size header wrong and content does not match its hash; moved to {})", path.display()).bright_black()),
+            SizeRepair::Kept => println!("{} {} {}",
+                                         "Size mismatch".yellow(), hash,
+                                         "(content does not match its hash; left in place because it is still reachable)".bright_black()),
+        }
+    }
+}
+
+async fn audit_gc(report: &GcReport, auto: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let audit_file = std::path::PathBuf::from(".aigit/logs/audit.log");
+    if !audit_file.exists() {
+        return Ok(());
+    }
+
+    let timestamp = chrono::Utc::now().to_rfc3339();
+    let user = whoami::username();
+    let action = if auto { "gc_auto" } else { "gc" };
+    let details = format!("scanned:{},pruned:{},pruned_packed:{},packed:{}",
+                          report.loose_before, report.pruned, report.pruned_packed, report.packed);
+
+    let entry = format!("{},{},{},{},gc\n", timestamp, action, user, details);
+    std::fs::OpenOptions::new()
+        .append(true)
+        .open(audit_file)?
+        .write_all(entry.as_bytes())?;
+
+    Ok(())
+}
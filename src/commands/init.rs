@@ -1,18 +1,64 @@
+use crate::core::{Config, Repository};
 use std::fs;
 use std::path::Path;
 use colored::*;
 use ring::digest;
 use rand::RngCore;
 
-pub async fn run(bare: bool) -> Result<(), Box<dyn std::error::Error>> {
+#[derive(Default, Clone)]
+pub struct InitOptions {
+    pub bare: bool,
+    /// Initialize over a partial or damaged repository, rewriting its
+    /// metadata files.
+    pub force: bool,
+    /// Create only what is missing and keep everything else.
+    pub reinitialize: bool,
+    /// Let `force` start over in an intact repository as well, resetting
+    /// HEAD, the index and the audit log.
+    pub overwrite_existing: bool,
+}
+
+pub async fn run(bare: bool, force: bool, reinitialize: bool) -> Result<(), Box<dyn std::error::Error>> {
+    run_with(InitOptions { bare, force, reinitialize, ..Default::default() }).await
+}
+
+pub async fn run_with(options: InitOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let InitOptions { bare, force, reinitialize, overwrite_existing } = options;
     let repo_dir = if bare { "." } else { ".aigit" };
     
-    if Path::new(repo_dir).join("HEAD").exists() {
+    if Path::new(repo_dir).join("HEAD").exists() && !force && !reinitialize {
         println!("{}", "Repository already initialized".yellow());
         return Ok(());
     }
 
-    create_secure_repo_structure(repo_dir, bare)?;
+    let found = existing_content(repo_dir)?;
+    if !found.is_empty() && !Path::new(repo_dir).join("HEAD").exists() && !force && !reinitialize {
+        let what = if bare { "Directory is not empty" } else { "Found a partial .aigit without HEAD" };
+        return Err(format!(
+            "{} ({}). Use --reinitialize to repair it or --force to initialize over it",
+            what,
+            found.join(", ")
+        ).into());
+    }
+
+    // --force is for repairing a broken layout; an intact repository would
+    // lose its current branch and staged state.
+    if force && !overwrite_existing && is_intact_repo(repo_dir) {
+        return Err("The repository is intact; --force would reset HEAD, the index and the audit log.\nUse --reinitialize to restore missing files, or add --overwrite-existing to start over anyway".into());
+    }
+
+    if reinitialize && !force {
+        let repaired = create_secure_repo_structure(repo_dir, bare, false)?;
+        repair_security_settings(repo_dir)?;
+        if repaired.is_empty() {
+            println!("{}", "Reinitialized repository; nothing was missing".green());
+        } else {
+            println!("{} {}", "Reinitialized repository, restored:".green(), repaired.join(", "));
+        }
+        return Ok(());
+    }
+
+    create_secure_repo_structure(repo_dir, bare, true)?;
     initialize_security_settings(repo_dir)?;
     
     let msg = if bare {
@@ -25,7 +71,38 @@ pub async fn run(bare: bool) -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-fn create_secure_repo_structure(repo_dir: &str, bare: bool) -> Result<(), Box<dyn std::error::Error>> {
+/// Whether `repo_dir` holds a repository that passes the integrity check.
+fn is_intact_repo(repo_dir: &str) -> bool {
+    Repository::new(repo_dir).is_some_and(|repo| repo.verify_integrity().is_ok())
+}
+
+/// Lists what is already present where the repository would be created.
+fn existing_content(repo_dir: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    if !Path::new(repo_dir).is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut found: Vec<String> = fs::read_dir(repo_dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .collect();
+    found.sort();
+    Ok(found)
+}
+
+/// Writes `content` to `path` unless the file exists and `overwrite` is off.
+/// Returns whether the file was written.
+fn write_file(path: &str, content: &str, overwrite: bool) -> Result<bool, Box<dyn std::error::Error>> {
+    if !overwrite && Path::new(path).exists() {
+        return Ok(false);
+    }
+    fs::write(path, content)?;
+    Ok(true)
+}
+
+/// Creates the repository layout. With `overwrite` off, existing files are
+/// kept and only missing pieces are created; their names are returned.
+fn create_secure_repo_structure(repo_dir: &str, bare: bool, overwrite: bool) -> Result<Vec<String>, Box<dyn std::error::Error>> {
     let dirs = vec![
         format!("{}/objects", repo_dir),
         format!("{}/refs/heads", repo_dir),
@@ -36,26 +113,37 @@ fn create_secure_repo_structure(repo_dir: &str, bare: bool) -> Result<(), Box<dy
         format!("{}/info", repo_dir),
     ];
 
+    let mut created = Vec::new();
     for dir in dirs {
+        if !Path::new(&dir).is_dir() {
+            created.push(dir.clone());
+        }
         fs::create_dir_all(&dir)?;
         set_secure_permissions(&dir)?;
     }
     
-    let repo_id = generate_secure_repo_id();
-    fs::write(format!("{}/HEAD", repo_dir), "ref: refs/heads/main\n")?;
-    fs::write(format!("{}/description", repo_dir), "Secure AI repository\n")?;
-    fs::write(format!("{}/config", repo_dir), create_secure_config(bare))?;
-    fs::write(format!("{}/info/repo-id", repo_dir), repo_id)?;
-    fs::write(format!("{}/info/exclude", repo_dir), create_default_excludes())?;
+    let mut files = vec![
+        (format!("{}/HEAD", repo_dir), "ref: refs/heads/main\n".to_string()),
+        (format!("{}/description", repo_dir), "Secure AI repository\n".to_string()),
+        (format!("{}/config.json", repo_dir), serde_json::to_string_pretty(&Config::repo_defaults(bare))?),
+        (format!("{}/info/repo-id", repo_dir), generate_secure_repo_id()),
+        (format!("{}/info/exclude", repo_dir), create_default_excludes().to_string()),
+    ];
     
     if !bare {
-        fs::write(format!("{}/index", repo_dir), "")?;
-        fs::write(".gitignore", create_default_gitignore())?;
+        files.push((format!("{}/index", repo_dir), String::new()));
+        files.push((".gitignore".to_string(), create_default_gitignore().to_string()));
+    }
+
+    for (path, content) in files {
+        if write_file(&path, &content, overwrite)? {
+            created.push(path);
+        }
     }
 
-    create_security_hooks(repo_dir)?;
+    create_security_hooks(repo_dir, overwrite)?;
     
-    Ok(())
+    Ok(created)
 }
 
 fn set_secure_permissions(path: &str) -> Result<(), Box<dyn std::error::Error>> {
@@ -77,50 +165,6 @@ fn generate_secure_repo_id() -> String {
     hex::encode(digest::digest(&digest::SHA256, &bytes).as_ref())
 }
 
-fn create_secure_config(bare: bool) -> String {
-    format!(
-        r#"[core]
-    repositoryformatversion = 0
-    filemode = true
-    bare = {}
-    logallrefupdates = true
-    precomposeunicode = true
-    protectHFS = true
-    protectNTFS = true
-    ignorecase = false
-    trustctime = false
-    checkStat = minimal
-    autocrlf = false
-    safecrlf = true
-    quotepath = false
-
-[security]
-    enabled = true
-    requireSignature = true
-    auditLog = true
-    encryptObjects = false
-    hashAlgorithm = sha256
-    compressionLevel = 6
-
-[ai]
-    enabled = true
-    model = gemini-pro
-    maxTokens = 2048
-    temperature = 0.7
-    requireReview = false
-    autoCommitMessage = true
-
-[user]
-    signingkey = ""
-    
-[commit]
-    gpgsign = false
-    template = ""
-"#,
-        bare
-    )
-}
-
 fn create_default_excludes() -> &'static str {
     r#"*.o
 *.a
@@ -163,17 +207,20 @@ build/
 !Cargo.lock"#
 }
 
-fn create_security_hooks(repo_dir: &str) -> Result<(), Box<dyn std::error::Error>> {
+fn create_security_hooks(repo_dir: &str, overwrite: bool) -> Result<(), Box<dyn std::error::Error>> {
     let hooks = vec![
         ("pre-commit", PRE_COMMIT_HOOK),
         ("commit-msg", COMMIT_MSG_HOOK),
+        ("post-commit", POST_COMMIT_HOOK),
         ("pre-receive", PRE_RECEIVE_HOOK),
         ("post-receive", POST_RECEIVE_HOOK),
     ];
 
     for (hook_name, hook_content) in hooks {
         let hook_path = format!("{}/hooks/{}", repo_dir, hook_name);
-        fs::write(&hook_path, hook_content)?;
+        if !write_file(&hook_path, hook_content, overwrite)? {
+            continue;
+        }
         
         #[cfg(unix)]
         {
@@ -201,6 +248,13 @@ fi
 exit 0
 "#;
 
+const POST_COMMIT_HOOK: &str = r#"#!/bin/sh
+if [ -f ".aigit/security/post-commit-hooks" ]; then
+    exec ".aigit/security/post-commit-hooks"
+fi
+exit 0
+"#;
+
 const PRE_RECEIVE_HOOK: &str = r#"#!/bin/sh
 if [ -f ".aigit/security/pre-receive-checks" ]; then
     exec ".aigit/security/pre-receive-checks"
@@ -216,6 +270,14 @@ exit 0
 "#;
 
 fn initialize_security_settings(repo_dir: &str) -> Result<(), Box<dyn std::error::Error>> {
+    write_security_settings(repo_dir, true)
+}
+
+fn repair_security_settings(repo_dir: &str) -> Result<(), Box<dyn std::error::Error>> {
+    write_security_settings(repo_dir, false)
+}
+
+fn write_security_settings(repo_dir: &str, overwrite: bool) -> Result<(), Box<dyn std::error::Error>> {
     let security_config = r#"{
     "audit_log": true,
     "require_signature": false,
@@ -230,11 +292,11 @@ fn initialize_security_settings(repo_dir: &str) -> Result<(), Box<dyn std::error
     }
 }"#;
 
-    fs::write(format!("{}/security/config.json", repo_dir), security_config)?;
+    write_file(&format!("{}/security/config.json", repo_dir), security_config, overwrite)?;
     
     let audit_log_header = r#"timestamp,action,user,details,hash
 "#;
-    fs::write(format!("{}/logs/audit.log", repo_dir), audit_log_header)?;
+    write_file(&format!("{}/logs/audit.log", repo_dir), audit_log_header, overwrite)?;
     
     Ok(())
 }
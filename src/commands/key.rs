@@ -0,0 +1,39 @@
+use crate::core::Repository;
+use crate::core::signing::{self, keyring_dir};
+use colored::*;
+use std::io::Write;
+
+/// Creates the Ed25519 signing key `key_id` in the keyring and prints its
+/// public key, so commits can be signed with `commit -S<key_id>`.
+pub async fn generate(key_id: String) -> Result<(), Box<dyn std::error::Error>> {
+    let repo = Repository::discover().ok_or("Not in a repository")?;
+    let public_key = signing::generate_key(&repo, &key_id)?;
+
+    println!("{} {} in {}",
+             "Generated signing key".green(),
+             key_id.bright_cyan(),
+             keyring_dir(&repo).display());
+    println!("{} {}", "Public key:".cyan(), public_key);
+
+    audit_key_generate(&key_id).await?;
+    Ok(())
+}
+
+async fn audit_key_generate(key_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let audit_file = std::path::PathBuf::from(".aigit/logs/audit.log");
+    if !audit_file.exists() {
+        return Ok(());
+    }
+
+    let timestamp = chrono::Utc::now().to_rfc3339();
+    let user = whoami::username();
+    let details = format!("key:{}", key_id);
+
+    let entry = format!("{},key-generate,{},{},security\n", timestamp, user, details);
+    std::fs::OpenOptions::new()
+        .append(true)
+        .open(audit_file)?
+        .write_all(entry.as_bytes())?;
+
+    Ok(())
+}
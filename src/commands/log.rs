@@ -1,31 +1,102 @@
-use crate::core::{Repository, Commit, Object, Config};
-use crate::ai::gemini::GeminiClient;
+use crate::core::{Repository, Commit, Object, Config, Refs, CommitGraph};
+use crate::core::reflog;
+use crate::core::revlist::parents_of;
+use crate::core::tag::peel;
+use crate::core::revision::{min_unique_abbrev, DEFAULT_ABBREV, FULL_HASH_LEN, MIN_ABBREV};
+use crate::ai::{ai_enabled, provider_with_progress};
+use crate::utils::diff::{commit_changes, commit_file_stats, commit_patch_for_paths, format_stat_block, print_diff, PathScope};
 use colored::*;
-use chrono::{DateTime, Local, TimeZone};
+use chrono::{DateTime, Local, NaiveDate, TimeZone, Utc};
 use indicatif::{ProgressBar, ProgressStyle};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::io::IsTerminal;
 
-pub async fn run(oneline: bool, graph: bool, ai_summary: bool) -> Result<(), Box<dyn std::error::Error>> {
-    let repo = Repository::new(".aigit").ok_or("Not in a repository")?;
-    let config = Config::load_repo(&repo).unwrap_or_else(|_| Config::load_global().unwrap_or_default());
-    let mut commits = Vec::new();
-    
-    if let Some(head_hash) = get_head_commit(&repo) {
-        collect_commits(&repo, &head_hash, &mut commits, &mut HashMap::new()).await?;
+#[derive(Default, Clone)]
+pub struct LogOptions {
+    pub oneline: bool,
+    pub graph: bool,
+    pub ai_summary: bool,
+    pub stat: bool,
+    pub max_count: Option<usize>,
+    pub abbrev: Option<usize>,
+    /// `Some("no")` disables decorations; `None` decorates the full format
+    /// only when writing to a terminal.
+    pub decorate: Option<String>,
+    pub walk_reflogs: bool,
+    /// Start from every branch, tag and remote-tracking ref, not just HEAD.
+    pub all: bool,
+    /// `-p`: print each commit's diff against its first parent.
+    pub patch: bool,
+    /// Only show commits touching these paths, and only their part of `-p`.
+    pub paths: Vec<String>,
+    /// `--format=<template>`: print each commit through `format_commit`.
+    pub format: Option<String>,
+    /// `--author-date-order[=first|last]`: list contributors by their first
+    /// or last commit instead of by commit count.
+    pub author_date_order: Option<String>,
+    /// `--author <substr>`: only commits whose author name or email contains it.
+    pub author: Option<String>,
+    /// `--committer <substr>`: the same, matched against the committer.
+    pub committer: Option<String>,
+    /// `--since`/`--until`: RFC 3339 timestamps or `YYYY-MM-DD` dates,
+    /// compared with the author date. Both ends are inclusive.
+    pub since: Option<String>,
+    pub until: Option<String>,
+}
+
+pub async fn run(options: LogOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let LogOptions { oneline, graph, ai_summary, stat, max_count, abbrev, decorate, walk_reflogs, all, patch, paths, format, author_date_order, author, committer, since, until } = options;
+    let repo = Repository::discover().ok_or("Not in a repository")?;
+    let config = repo.config();
+    let contributor_order = ContributorOrder::parse(author_date_order.as_deref())?;
+    let filter = CommitFilter::new(author, committer, since.as_deref(), until.as_deref())?;
+    let paths = PathScope::new(&paths)?;
+
+    if walk_reflogs {
+        return print_reflog_walk(&repo, config, oneline, max_count, abbrev);
     }
 
-    if commits.is_empty() {
+    let tips = log_tips(&repo, all)?;
+    let hashes = if config.get_bool("core.commitGraph").unwrap_or(true) {
+        let mut commit_graph = CommitGraph::load(&repo);
+        let hashes = commit_graph.walk(&repo, &tips)?;
+        if let Err(e) = commit_graph.save(&repo) {
+            eprintln!("{} could not update the commit-graph cache: {}", "Warning:".yellow(), e);
+        }
+        hashes
+    } else {
+        collect_commits(&repo, &tips)?.into_iter().map(|(hash, _)| hash).collect()
+    };
+    let hashes = if filter.is_empty() { hashes } else { filter.apply(&repo, hashes)? };
+    let hashes = if paths.is_empty() {
+        hashes
+    } else {
+        // With an explicit limit there is no need to diff history past the last match shown.
+        let limit = max_count.map(|n| if ai_summary { n.max(20) } else { n });
+        commits_touching(&repo, hashes, &paths, limit)?
+    };
+
+    if hashes.is_empty() {
         println!("{}", "No commits found".yellow());
         return Ok(());
     }
 
-    if ai_summary && commits.len() > 1 {
+    let max_display = max_count.unwrap_or(if oneline { 50 } else { 25 });
+    // Only the commits that get printed (or summarized) are read in full.
+    let load_count = if ai_summary { max_display.max(20) } else { max_display };
+    let commits = hashes.iter()
+        .take(load_count)
+        .map(|hash| Ok((hash.clone(), serde_json::from_slice(&Object::read(&repo, hash)?)?)))
+        .collect::<Result<Vec<(String, Commit)>, Box<dyn std::error::Error>>>()?;
+
+    if ai_summary && !ai_enabled(repo.config()) {
+        println!("{}", "Skipping AI summary: AI features are disabled".bright_black());
+    } else if ai_summary && commits.len() > 1 {
         let pb = ProgressBar::new_spinner();
         pb.set_style(ProgressStyle::default_spinner().template("{spinner} {msg}").unwrap());
         pb.set_message("AI analyzing commit history...");
         pb.enable_steady_tick(std::time::Duration::from_millis(100));
 
-        let gemini = GeminiClient::new();
         let commit_messages: Vec<String> = commits.iter()
             .take(20)
             .map(|(_, commit)| commit.message.clone())
@@ -33,52 +104,334 @@ pub async fn run(oneline: bool, graph: bool, ai_summary: bool) -> Result<(), Box
         let summary_prompt = format!("Summarize this commit history and identify patterns:\n{}", 
                                    commit_messages.join("\n---\n"));
         
-        match gemini.generate_text(&summary_prompt).await {
+        let summary = match provider_with_progress(repo.config(), &pb) {
+            Ok(provider) => provider.generate_text(&summary_prompt).await,
+            Err(e) => Err(e.into()),
+        };
+        match summary {
             Ok(summary) => {
                 pb.finish_and_clear();
                 println!("{}", "AI Summary of Recent Changes:".cyan().bold());
                 println!("{}\n", summary);
                 println!("{}", "─".repeat(80).bright_black());
             },
-            Err(_) => {
+            Err(e) => {
                 pb.finish_and_clear();
-                println!("{}", "Failed to generate AI summary".red());
+                println!("{} {}", "Failed to generate AI summary:".red(), e);
             }
         }
     }
 
-    let max_display = if oneline { 50 } else { 25 };
     let displayed_commits = commits.iter().take(max_display);
+    let displayed_hashes: Vec<String> = commits.iter()
+        .take(max_display)
+        .flat_map(|(hash, commit)| std::iter::once(hash.clone()).chain(commit.parents.iter().cloned()))
+        .collect();
+    let abbrev = abbrev_len(&repo, config, abbrev, &displayed_hashes)?;
+    let decorations = if should_decorate(decorate.as_deref(), oneline)? {
+        Refs::load(&repo)?.decorations(&repo)
+    } else {
+        HashMap::new()
+    };
 
     for (i, (hash, commit)) in displayed_commits.enumerate() {
-        if oneline {
-            print_oneline_commit(hash, commit, i == 0);
+        let decoration = format_decoration(decorations.get(hash));
+        if let Some(template) = &format {
+            println!("{}", format_commit(template, hash, commit, abbrev));
+        } else if oneline {
+            print_oneline_commit(hash, commit, i == 0, abbrev, &decoration);
         } else {
-            print_full_commit(hash, commit, graph, i == 0, &config);
+            print_full_commit(hash, commit, graph, i == 0, config, abbrev, &decoration);
         }
+
+        if stat {
+            match commit_file_stats(&repo, commit).await {
+                Ok(stats) if !stats.is_empty() => {
+                    print!("{}", format_stat_block(&stats));
+                    println!();
+                },
+                Ok(_) => {},
+                Err(e) => println!("    {} {}", "Could not compute stat:".red(), e),
+            }
+        }
+
+        if patch {
+            match commit_patch_for_paths(&repo, commit, &paths) {
+                Ok(diff) if !diff.is_empty() => {
+                    print_diff(&diff);
+                    println!();
+                },
+                Ok(_) => {},
+                Err(e) => println!("    {} {}", "Could not compute diff:".red(), e),
+            }
+        }
+    }
+
+    // Templated output is meant for scripts, so it gets nothing extra.
+    if format.is_some() {
+        return Ok(());
     }
 
-    if commits.len() > max_display {
+    if max_count.is_none() && hashes.len() > max_display {
         println!("\n{} ({} more commits)", 
                 "...".bright_black(), 
-                (commits.len() - max_display).to_string().bright_yellow());
+                (hashes.len() - max_display).to_string().bright_yellow());
         println!("{}", "Use 'aigit log --oneline' for more compact view".bright_black());
     }
 
-    print_log_statistics(&commits);
+    print_log_statistics(hashes.len(), &commits[..commits.len().min(max_display)], contributor_order);
     
     Ok(())
 }
 
-fn print_oneline_commit(hash: &str, commit: &Commit, is_head: bool) {
+/// The `--author`, `--committer`, `--since` and `--until` limits. Every
+/// limit given must match for a commit to be shown.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CommitFilter {
+    pub author: Option<String>,
+    pub committer: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl CommitFilter {
+    pub fn new(
+        author: Option<String>,
+        committer: Option<String>,
+        since: Option<&str>,
+        until: Option<&str>
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(CommitFilter {
+            author,
+            committer,
+            since: since.map(|value| parse_log_date(value, false)).transpose()?,
+            until: until.map(|value| parse_log_date(value, true)).transpose()?,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.author.is_none() && self.committer.is_none() && self.since.is_none() && self.until.is_none()
+    }
+
+    pub fn matches(&self, commit: &Commit) -> bool {
+        let identity_matches = |needle: &Option<String>, who: &crate::core::Author| needle.as_ref()
+            .is_none_or(|needle| who.name.contains(needle.as_str()) || who.email.contains(needle.as_str()));
+        let timestamp = commit.author.timestamp;
+        identity_matches(&self.author, &commit.author)
+            && identity_matches(&self.committer, &commit.committer)
+            && self.since.is_none_or(|since| timestamp >= since)
+            && self.until.is_none_or(|until| timestamp <= until)
+    }
+
+    /// The commits among `hashes` that match, keeping their order.
+    fn apply(&self, repo: &Repository, hashes: Vec<String>) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let mut matching = Vec::new();
+        for hash in hashes {
+            let commit: Commit = serde_json::from_slice(&Object::read(repo, &hash)?)?;
+            if self.matches(&commit) {
+                matching.push(hash);
+            }
+        }
+        Ok(matching)
+    }
+}
+
+/// Parses a `--since`/`--until` value. A bare `YYYY-MM-DD` means the start
+/// of that day in UTC, or its last second when `end_of_day` is set, so
+/// `--until` includes the whole day.
+pub fn parse_log_date(value: &str, end_of_day: bool) -> Result<DateTime<Utc>, Box<dyn std::error::Error>> {
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(value) {
+        return Ok(timestamp.with_timezone(&Utc));
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| format!("Invalid date '{}': expected RFC 3339 or YYYY-MM-DD", value))?;
+    let time = if end_of_day { date.and_hms_opt(23, 59, 59) } else { date.and_hms_opt(0, 0, 0) };
+    Ok(time.ok_or_else(|| format!("Invalid date '{}'", value))?.and_utc())
+}
+
+/// The commits among `hashes` that changed a file at or under `paths`,
+/// keeping their order. Stops diffing once `limit` matches are found.
+fn commits_touching(
+    repo: &Repository,
+    hashes: Vec<String>,
+    paths: &PathScope,
+    limit: Option<usize>
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let limit = limit.unwrap_or(usize::MAX);
+    let mut touching = Vec::new();
+    for hash in hashes {
+        if touching.len() >= limit {
+            break;
+        }
+        let commit: Commit = serde_json::from_slice(&Object::read(repo, &hash)?)?;
+        if commit_changes(repo, &commit)?.iter().any(|change| paths.matches(&change.path)) {
+            touching.push(hash);
+        }
+    }
+    Ok(touching)
+}
+
+/// Resolves the hash abbreviation length from `--abbrev`, then `core.abbrev`
+/// (a number or `auto`). Auto picks the shortest length that keeps every
+/// displayed hash unambiguous.
+pub fn abbrev_len(
+    repo: &Repository,
+    config: &Config,
+    flag: Option<usize>,
+    hashes: &[String]
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let requested = match (flag, config.get("core.abbrev")) {
+        (Some(n), _) => Some(n),
+        (None, Some(value)) if value != "auto" => Some(value.parse::<usize>()
+            .map_err(|_| format!("Invalid core.abbrev '{}': expected a number or 'auto'", value))?),
+        _ => None,
+    };
+
+    Ok(match requested {
+        Some(n) => n.clamp(MIN_ABBREV, FULL_HASH_LEN),
+        None => min_unique_abbrev(repo, hashes, DEFAULT_ABBREV),
+    })
+}
+
+/// `log -g`: walks HEAD's reflog instead of the commit graph, newest first.
+fn print_reflog_walk(
+    repo: &Repository,
+    config: &Config,
+    oneline: bool,
+    max_count: Option<usize>,
+    abbrev: Option<usize>
+) -> Result<(), Box<dyn std::error::Error>> {
+    let entries = reflog::read(repo)?;
+    if entries.is_empty() {
+        println!("{}", "No reflog entries found".yellow());
+        return Ok(());
+    }
+
+    let entries: Vec<_> = entries.into_iter().enumerate().take(max_count.unwrap_or(usize::MAX)).collect();
+    let hashes: Vec<String> = entries.iter().map(|(_, entry)| entry.new.clone()).collect();
+    let abbrev = abbrev_len(repo, config, abbrev, &hashes)?;
+
+    for (n, entry) in entries {
+        let selector = format!("HEAD@{{{}}}", n);
+        if oneline {
+            println!("{} {}: {}", abbreviate(&entry.new, abbrev).yellow(), selector.bright_cyan(), entry.message);
+            continue;
+        }
+
+        let commit: Commit = match Object::read(repo, &entry.new).ok().and_then(|c| serde_json::from_slice(&c).ok()) {
+            Some(commit) => commit,
+            None => {
+                println!("{}: {} {}", selector.bright_cyan(), entry.message, "(commit missing)".red());
+                continue;
+            }
+        };
+        println!("{}: {} {}", selector.bright_cyan(), entry.message, format!("({})", entry.user).bright_black());
+        print_full_commit(&entry.new, &commit, false, n == 0, config, abbrev, "");
+    }
+
+    Ok(())
+}
+
+fn should_decorate(mode: Option<&str>, oneline: bool) -> Result<bool, Box<dyn std::error::Error>> {
+    match mode {
+        Some("no") => Ok(false),
+        Some("short") | Some("full") | Some("yes") => Ok(true),
+        Some(other) => Err(format!("Invalid --decorate value '{}': expected short, full or no", other).into()),
+        None => Ok(!oneline && std::io::stdout().is_terminal()),
+    }
+}
+
+/// ` (HEAD -> main, tag: v1.0)`, or an empty string for undecorated commits.
+pub fn format_decoration(refs: Option<&Vec<String>>) -> String {
+    match refs {
+        Some(refs) if !refs.is_empty() => format!(" ({})", refs.join(", ")),
+        _ => String::new(),
+    }
+}
+
+/// Expands a `--format` template for one commit. Supported placeholders:
+/// `%H`/`%h` commit hash (full/abbreviated), `%T`/`%t` tree, `%P`/`%p`
+/// parents, `%an`/`%ae`/`%ad`/`%aI` author name, email, date and ISO date,
+/// `%cn`/`%ce`/`%cd`/`%cI` the same for the committer, `%s` subject, `%b`
+/// body, `%B` raw message, `%n` newline and `%%`. Anything else is copied
+/// through unchanged.
+pub fn format_commit(template: &str, hash: &str, commit: &Commit, abbrev: usize) -> String {
+    let subject = commit.short_message();
+    let body = commit.message.split_once('\n')
+        .map(|(_, rest)| rest.trim_start_matches('\n').trim_end().to_string())
+        .unwrap_or_default();
+    let parents = |len: usize| commit.parents.iter()
+        .map(|parent| abbreviate(parent, len))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let date = |author: &crate::core::Author| Local.timestamp_opt(author.timestamp.timestamp(), 0)
+        .single()
+        .unwrap_or_else(Local::now)
+        .format("%a %b %d %H:%M:%S %Y %z")
+        .to_string();
+
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(pos) = rest.find('%') {
+        out.push_str(&rest[..pos]);
+        rest = &rest[pos + 1..];
+
+        let expansion = [
+            ("H", hash.to_string()),
+            ("h", abbreviate(hash, abbrev).to_string()),
+            ("T", commit.tree.clone()),
+            ("t", abbreviate(&commit.tree, abbrev).to_string()),
+            ("P", parents(FULL_HASH_LEN)),
+            ("p", parents(abbrev)),
+            ("an", commit.author.name.clone()),
+            ("ae", commit.author.email.clone()),
+            ("ad", date(&commit.author)),
+            ("aI", commit.author.timestamp.to_rfc3339()),
+            ("cn", commit.committer.name.clone()),
+            ("ce", commit.committer.email.clone()),
+            ("cd", date(&commit.committer)),
+            ("cI", commit.committer.timestamp.to_rfc3339()),
+            ("s", subject.clone()),
+            ("b", body.clone()),
+            ("B", commit.message.clone()),
+            ("n", "\n".to_string()),
+            ("%", "%".to_string()),
+        ].into_iter().find(|(placeholder, _)| rest.starts_with(placeholder));
+
+        match expansion {
+            Some((placeholder, value)) => {
+                out.push_str(&value);
+                rest = &rest[placeholder.len()..];
+            },
+            None => out.push('%'),
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn abbreviate(hash: &str, len: usize) -> &str {
+    &hash[..len.min(hash.len())]
+}
+
+fn print_oneline_commit(hash: &str, commit: &Commit, is_head: bool, abbrev: usize, decoration: &str) {
     let prefix = if is_head { "* " } else { "  " };
-    let hash_color = if is_head { hash[..8].bright_yellow() } else { hash[..8].yellow() };
+    let short = abbreviate(hash, abbrev);
+    let hash_color = if is_head { short.bright_yellow() } else { short.yellow() };
     let message_color = if is_head { commit.short_message().bright_white() } else { commit.short_message().white() };
     
-    println!("{}{} {}", prefix, hash_color, message_color);
+    println!("{}{}{} {}", prefix, hash_color, decoration.bright_cyan(), message_color);
 }
 
-fn print_full_commit(hash: &str, commit: &Commit, graph: bool, is_head: bool, config: &Config) {
+pub fn print_full_commit(
+    hash: &str,
+    commit: &Commit,
+    graph: bool,
+    is_head: bool,
+    config: &Config,
+    abbrev: usize,
+    decoration: &str
+) {
     let prefix = if graph { 
         if is_head { "* " } else { "| " }
     } else { 
@@ -87,31 +440,33 @@ fn print_full_commit(hash: &str, commit: &Commit, graph: bool, is_head: bool, co
     
     let hash_display = if is_head { hash.bright_yellow() } else { hash.yellow() };
     
-    println!("{}{} {}", prefix, "commit".yellow(), hash_display);
+    println!("{}{} {}{}", prefix, "commit".yellow(), hash_display, decoration.bright_cyan());
     
     if commit.is_merge() {
-        println!("{}Merge: {} {}", 
-                "    ", 
-                commit.parents.get(0).map(|h| &h[..8]).unwrap_or("unknown").bright_blue(),
-                commit.parents.get(1).map(|h| &h[..8]).unwrap_or("unknown").bright_blue());
+        println!("    Merge: {} {}", 
+                commit.parents.first().map(|h| abbreviate(h, abbrev)).unwrap_or("unknown").bright_blue(),
+                commit.parents.get(1).map(|h| abbreviate(h, abbrev)).unwrap_or("unknown").bright_blue());
     }
     
-    println!("{}Author: {} <{}>", 
-            "    ", 
+    println!("    Author: {} <{}>", 
             commit.author.name.bright_white(), 
             commit.author.email.cyan());
+    if (&commit.committer.name, &commit.committer.email) != (&commit.author.name, &commit.author.email) {
+        println!("    Commit: {} <{}>",
+                commit.committer.name.bright_white(),
+                commit.committer.email.cyan());
+    }
     
     let local_time: DateTime<Local> = Local.timestamp_opt(commit.author.timestamp.timestamp(), 0)
         .single()
-        .unwrap_or_else(|| Local::now());
+        .unwrap_or_else(Local::now);
     
-    println!("{}Date:   {}", 
-            "    ", 
+    println!("    Date:   {}", 
             local_time.format("%a %b %d %H:%M:%S %Y %z"));
     
     if let Some(signature) = &commit.signature {
-        if config.get("security.requireSignature").map(|v| v == "true").unwrap_or(false) {
-            println!("{}Signature: {} ✓", "    ", signature.chars().take(16).collect::<String>().bright_green());
+        if config.get_bool("security.requireSignature").unwrap_or(false) {
+            println!("    Signature: {} ✓", signature.chars().take(16).collect::<String>().bright_green());
         }
     }
     
@@ -126,35 +481,44 @@ fn print_full_commit(hash: &str, commit: &Commit, graph: bool, is_head: bool, co
     println!();
 }
 
-use std::pin::Pin;
-use std::future::Future;
+/// Every commit reachable from `tips`, newest author date first. The walk
+/// is an explicit worklist so long histories cannot exhaust the stack; ties
+/// keep the order the walk found them in.
+pub fn collect_commits(repo: &Repository, tips: &[String]) -> Result<Vec<(String, Commit)>, Box<dyn std::error::Error>> {
+    let mut commits = Vec::new();
+    let mut visited: HashMap<String, bool> = HashMap::new();
+    let mut queue: VecDeque<String> = tips.iter().cloned().collect();
 
-fn collect_commits<'a>(
-    repo: &'a Repository, 
-    start_hash: &'a str, 
-    commits: &'a mut Vec<(String, Commit)>,
-    visited: &'a mut HashMap<String, bool>
-) -> Pin<Box<dyn Future<Output = Result<(), Box<dyn std::error::Error>>> + 'a>> {
-    Box::pin(async move {
-        if visited.contains_key(start_hash) {
-            return Ok(());
+    while let Some(hash) = queue.pop_front() {
+        if hash.is_empty() || visited.contains_key(&hash) {
+            continue;
         }
-        
-        visited.insert(start_hash.to_string(), true);
-        
-        let content = Object::read(repo, start_hash)?;
-        let commit: Commit = serde_json::from_slice(&content)?;
-        
-        commits.push((start_hash.to_string(), commit.clone()));
-        
-        for parent_hash in &commit.parents {
-            if !parent_hash.is_empty() && !visited.contains_key(parent_hash) {
-                collect_commits(repo, parent_hash, commits, visited).await?;
-            }
-        }
-        
-        Ok(())
-    })
+        visited.insert(hash.clone(), true);
+
+        let commit: Commit = serde_json::from_slice(&Object::read(repo, &hash)?)?;
+        queue.extend(parents_of(&commit).into_iter().filter(|parent| !visited.contains_key(parent)));
+        commits.push((hash, commit));
+    }
+
+    commits.sort_by_key(|(_, commit)| std::cmp::Reverse(commit.author.timestamp));
+    Ok(commits)
+}
+
+/// Starting points for the walk: HEAD, plus every ref with `--all`.
+fn log_tips(repo: &Repository, all: bool) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let mut tips: Vec<String> = get_head_commit(repo).into_iter().collect();
+    if all {
+        let refs = Refs::load(repo)?;
+        let mut ref_tips: Vec<String> = refs.heads.values()
+            .chain(refs.tags.values())
+            .chain(refs.remotes.values())
+            .map(|hash| peel(repo, hash))
+            .collect();
+        ref_tips.sort();
+        tips.extend(ref_tips);
+    }
+    tips.dedup();
+    Ok(tips)
 }
 
 fn get_head_commit(repo: &Repository) -> Option<String> {
@@ -172,31 +536,92 @@ fn get_head_commit(repo: &Repository) -> Option<String> {
         .filter(|s| !s.is_empty() && s.len() >= 8)
 }
 
-fn print_log_statistics(commits: &[(String, Commit)]) {
+/// `total` counts every commit walked; message and author figures cover the
+/// displayed `commits` only, since the rest are never read in full.
+fn print_log_statistics(total: usize, commits: &[(String, Commit)], order: ContributorOrder) {
     if commits.is_empty() {
         return;
     }
     
-    let mut authors = HashMap::new();
-    let mut total_lines = 0;
-    
-    for (_, commit) in commits {
-        *authors.entry(commit.author.name.clone()).or_insert(0) += 1;
-        total_lines += commit.message.lines().count();
-    }
+    let total_lines: usize = commits.iter().map(|(_, commit)| commit.message.lines().count()).sum();
+    let authors = contributors(commits, order);
     
     println!("{}", "─".repeat(80).bright_black());
     println!("{}", "Repository Statistics:".cyan().bold());
-    println!("Total commits: {}", commits.len().to_string().bright_yellow());
+    println!("Total commits: {}", total.to_string().bright_yellow());
     println!("Average message length: {} lines", (total_lines / commits.len()).to_string().bright_blue());
     
     if authors.len() > 1 {
         println!("\nTop contributors:");
-        let mut sorted_authors: Vec<_> = authors.iter().collect();
-        sorted_authors.sort_by(|a, b| b.1.cmp(a.1));
-        
-        for (author, count) in sorted_authors.iter().take(5) {
-            println!("  {} - {} commits", author.bright_white(), count.to_string().bright_green());
+        for author in authors.iter().take(5) {
+            let since = match order {
+                ContributorOrder::Count => String::new(),
+                ContributorOrder::FirstCommit => format!(" (first {})", author.first.format("%Y-%m-%d")),
+                ContributorOrder::LastCommit => format!(" (last {})", author.last.format("%Y-%m-%d")),
+            };
+            println!("  {} - {} commits{}", author.name.bright_white(), author.commits.to_string().bright_green(), since.bright_black());
         }
     }
 }
+
+/// How the contributor list is ordered. Ties always fall back to name, then
+/// email, so the same history prints the same list every time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ContributorOrder {
+    /// Most commits first.
+    #[default]
+    Count,
+    /// Earliest first commit first.
+    FirstCommit,
+    /// Most recent last commit first.
+    LastCommit,
+}
+
+impl ContributorOrder {
+    /// Parses the `--author-date-order` value; `None` means the flag was not given.
+    pub fn parse(value: Option<&str>) -> Result<Self, Box<dyn std::error::Error>> {
+        match value {
+            None => Ok(ContributorOrder::Count),
+            Some("first") => Ok(ContributorOrder::FirstCommit),
+            Some("last") => Ok(ContributorOrder::LastCommit),
+            Some(other) => Err(format!("Invalid --author-date-order value '{}': expected first or last", other).into()),
+        }
+    }
+}
+
+/// One author's tally over a set of commits.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Contributor {
+    pub name: String,
+    pub email: String,
+    pub commits: usize,
+    pub first: DateTime<Utc>,
+    pub last: DateTime<Utc>,
+}
+
+/// Tallies `commits` by author name and email, sorted by `order`.
+pub fn contributors(commits: &[(String, Commit)], order: ContributorOrder) -> Vec<Contributor> {
+    let mut tally: BTreeMap<(String, String), Contributor> = BTreeMap::new();
+    for (_, commit) in commits {
+        let author = &commit.author;
+        let entry = tally.entry((author.name.clone(), author.email.clone())).or_insert_with(|| Contributor {
+            name: author.name.clone(),
+            email: author.email.clone(),
+            commits: 0,
+            first: author.timestamp,
+            last: author.timestamp,
+        });
+        entry.commits += 1;
+        entry.first = entry.first.min(author.timestamp);
+        entry.last = entry.last.max(author.timestamp);
+    }
+
+    // The map is already in name/email order, and the sort below is stable.
+    let mut contributors: Vec<Contributor> = tally.into_values().collect();
+    match order {
+        ContributorOrder::Count => contributors.sort_by_key(|c| std::cmp::Reverse(c.commits)),
+        ContributorOrder::FirstCommit => contributors.sort_by_key(|c| c.first),
+        ContributorOrder::LastCommit => contributors.sort_by_key(|c| std::cmp::Reverse(c.last)),
+    }
+    contributors
+}
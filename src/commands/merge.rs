@@ -1,26 +1,45 @@
-use crate::core::{Repository, Branch, Config, Commit, Object};
-use crate::ai::gemini::GeminiClient;
+use crate::core::{Repository, Branch, Config, Commit, Object, ObjectType, Index, Tree, TreeEntry, resolve_revision};
+use crate::core::object::hash_object;
+use crate::core::reflog;
+use crate::ai::{ai_enabled, provider_with_progress, AiProvider};
+use crate::commands::checkout::update_worktree;
+use crate::utils::conflicts::{has_conflict_markers, parse_conflicts, apply_resolutions, format_hunk_for_prompt, parse_resolution, print_three_columns, merge_text};
+use crate::utils::diff::is_binary;
+use crate::utils::worktree::{read_entry_content, write_entry, remove_file_and_empty_parents};
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs;
 use colored::*;
 use indicatif::{ProgressBar, ProgressStyle};
 use ring::digest;
-use std::io::Write;
+use std::io::{BufRead, Write};
+
+/// Records the commit being merged while a merge is in progress.
+pub const MERGE_HEAD: &str = "MERGE_HEAD";
+/// The message prepared for the commit that concludes a stopped revert.
+pub const MERGE_MSG: &str = "MERGE_MSG";
+/// Records where HEAD was before the last merge started.
+pub const ORIG_HEAD: &str = "ORIG_HEAD";
 
 pub async fn run(branch: String, ai_resolve: bool) -> Result<(), Box<dyn std::error::Error>> {
-    let repo = Repository::new(".aigit").ok_or("Not in a repository")?;
-    let config = Config::load_repo(&repo).unwrap_or_else(|_| Config::load_global().unwrap_or_default());
+    let repo = Repository::discover().ok_or("Not in a repository")?;
+    let config = repo.config();
     let current_branch = Branch::get_current_branch(&repo).ok_or("Not on a branch")?;
     
     if current_branch == branch {
         return Err("Cannot merge branch into itself".into());
     }
 
-    let branch_ref = repo.heads_dir().join(&branch);
-    if !branch_ref.exists() {
-        return Err(format!("Branch '{}' not found", branch).into());
+    if merge_head(&repo).is_some() {
+        return Err("You have not concluded your merge (MERGE_HEAD exists).\nFix the conflicts and commit, or run 'aigit merge --abort'.".into());
     }
 
-    let branch_commit = fs::read_to_string(&branch_ref)?.trim().to_string();
+    // Anything naming a commit can be merged, including an abbreviated hash.
+    let branch_ref = repo.heads_dir().join(&branch);
+    let branch_commit = if branch_ref.exists() {
+        fs::read_to_string(&branch_ref)?.trim().to_string()
+    } else {
+        resolve_revision(&repo, &branch).map_err(|e| format!("Cannot merge '{}': {}", branch, e))?
+    };
     let current_commit = Branch::get_current_commit(&repo);
 
     security_pre_merge_checks(&repo, &branch_commit, &current_commit).await?;
@@ -37,11 +56,23 @@ pub async fn run(branch: String, ai_resolve: bool) -> Result<(), Box<dyn std::er
                     "into".green(), 
                     current_branch.bright_cyan());
             
-            if ai_resolve {
-                perform_ai_assisted_merge(&repo, &current, &branch_commit, &branch, &config).await?;
+            fs::write(repo.git_dir.join(ORIG_HEAD), &current)?;
+            fs::write(repo.git_dir.join(MERGE_HEAD), &branch_commit)?;
+
+            if ai_resolve && !ai_enabled(config) {
+                println!("{}", "AI features are disabled; merging without --ai-resolve".bright_black());
+            }
+            let result = if ai_resolve && ai_enabled(config) {
+                perform_ai_assisted_merge(&repo, &current, &branch_commit, &branch, config).await
             } else {
-                perform_merge(&repo, &current, &branch_commit, &branch, &config).await?;
+                perform_merge(&repo, &current, &branch_commit, &branch, config).await
+            };
+
+            // Only a merge stopped on conflicts stays in progress.
+            if result.is_err() || !Index::load(&repo)?.has_conflicts() {
+                fs::remove_file(repo.git_dir.join(MERGE_HEAD)).ok();
             }
+            result?;
         },
         None => {
             update_head(&repo, &branch_commit);
@@ -50,13 +81,87 @@ pub async fn run(branch: String, ai_resolve: bool) -> Result<(), Box<dyn std::er
                     branch.bright_cyan(),
                     "(no previous commits)".bright_black());
             
-            audit_merge_operation("fast_forward", &branch, &branch_commit, &config).await?;
+            audit_merge_operation("fast_forward", &branch, &branch_commit, config).await?;
         }
     }
     
+    crate::commands::gc::run_auto(&repo, config).await?;
+    Ok(())
+}
+
+/// Abandons a merge stopped on conflicts: the current branch goes back to
+/// `ORIG_HEAD`, and the index and working tree to that commit's tree.
+pub async fn abort() -> Result<(), Box<dyn std::error::Error>> {
+    let repo = Repository::discover().ok_or("Not in a repository")?;
+    if merge_head(&repo).is_none() {
+        return Err("There is no merge to abort (MERGE_HEAD missing)".into());
+    }
+    let orig_head = fs::read_to_string(repo.git_dir.join(ORIG_HEAD))
+        .map_err(|_| "Cannot abort the merge: ORIG_HEAD is missing")?
+        .trim()
+        .to_string();
+
+    reset_index_and_worktree(&repo, &orig_head)?;
+
+    let head = Branch::get_current_commit(&repo);
+    if head.as_deref() != Some(orig_head.as_str()) {
+        update_head(&repo, &orig_head);
+        reflog::append(&repo, head.as_deref(), &orig_head, "merge: abort")?;
+    }
+    fs::remove_file(repo.git_dir.join(MERGE_HEAD))?;
+    fs::remove_file(repo.git_dir.join(MERGE_MSG)).ok();
+
+    println!("{} {}", "Merge aborted; HEAD is back at".yellow(), orig_head[..8].bright_yellow());
+    audit_merge_operation("abort", "", &orig_head, repo.config()).await?;
     Ok(())
 }
 
+/// Puts the index and working tree back to `commit`'s tree. Paths whose
+/// staged content already matches are left alone, so unstaged edits to them
+/// survive; conflicted entries are always reset. Shared by `merge --abort`
+/// and `revert --abort`.
+pub fn reset_index_and_worktree(repo: &Repository, commit: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let target: BTreeMap<String, TreeEntry> = flatten_commit_entries(repo, commit)?
+        .into_iter()
+        .map(|(path, entry)| (path.strip_prefix("./").map(str::to_string).unwrap_or(path), entry))
+        .collect();
+
+    let mut index = Index::load(repo)?;
+    let staged: BTreeMap<String, (String, u8)> = index.metadata.iter()
+        .map(|(path, entry)| (path.clone(), (entry.hash.clone(), entry.stage)))
+        .collect();
+
+    // Conflicted entries are reset even when their content already matches.
+    let paths: BTreeSet<&String> = staged.keys().chain(target.keys()).collect();
+    for path in paths {
+        let current = staged.get(path);
+        let wanted = target.get(path);
+        if current.is_some_and(|(_, stage)| *stage == 0) && current.map(|(hash, _)| hash) == wanted.map(|e| &e.hash) {
+            continue;
+        }
+
+        index.remove_entry(path);
+        match wanted {
+            Some(entry) => {
+                let content = Object::read(repo, &entry.hash)?;
+                write_entry(path, &content, &entry.mode)?;
+                let checksum = hex::encode(digest::digest(&digest::SHA256, &content).as_ref());
+                index.add_entry_secure(path.clone(), entry.hash.clone(), entry.mode.clone(), content.len() as u64, checksum);
+            },
+            None => remove_file_and_empty_parents(path)?,
+        }
+    }
+    index.save(repo)?;
+    Ok(())
+}
+
+/// The commit being merged, if a merge stopped on conflicts is in progress.
+pub fn merge_head(repo: &Repository) -> Option<String> {
+    fs::read_to_string(repo.git_dir.join(MERGE_HEAD)).ok()
+        .map(|content| content.trim().to_string())
+        .filter(|hash| !hash.is_empty())
+}
+
 async fn security_pre_merge_checks(
     repo: &Repository,
     branch_commit: &str,
@@ -93,10 +198,16 @@ async fn perform_ai_assisted_merge(
     pb.set_message("AI analyzing merge strategy...");
     pb.enable_steady_tick(std::time::Duration::from_millis(100));
 
-    let gemini = GeminiClient::new();
+    let provider = match provider_with_progress(repo.config(), &pb) {
+        Ok(provider) => provider,
+        Err(e) => {
+            pb.finish_and_clear();
+            return Err(e.to_string().into());
+        }
+    };
     let merge_context = create_merge_context(repo, current, branch_commit, branch_name).await?;
 
-    match gemini.analyze_merge(&merge_context).await {
+    match provider.analyze_merge(&merge_context).await {
         Ok(analysis) => {
             pb.finish_and_clear();
             println!("{}", "=== AI Merge Analysis ===".cyan().bold());
@@ -128,9 +239,99 @@ async fn perform_ai_assisted_merge(
         }
     }
     
+    preview_ai_resolutions(repo, provider.as_ref()).await
+}
+
+/// Asks the AI for a resolution of every conflicted hunk and lets the user
+/// accept or reject each proposal before anything is written.
+async fn preview_ai_resolutions(repo: &Repository, provider: &dyn AiProvider) -> Result<(), Box<dyn std::error::Error>> {
+    let index = Index::load(repo)?;
+    let mut paths = index.get_conflicted_files();
+    paths.extend(index.entries.keys()
+        .filter(|path| fs::read_to_string(path).map(|c| has_conflict_markers(&c)).unwrap_or(false))
+        .cloned());
+    paths.sort();
+    paths.dedup();
+
+    for path in paths {
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(_) => continue,
+        };
+
+        let mut responses = Vec::new();
+        for hunk in parse_conflicts(&content) {
+            let pb = ProgressBar::new_spinner();
+            pb.set_style(ProgressStyle::default_spinner().template("{spinner} {msg}").unwrap());
+            pb.set_message(format!("AI proposing resolution for {}...", path));
+            pb.enable_steady_tick(std::time::Duration::from_millis(100));
+
+            let response = provider.resolve_conflict(&format_hunk_for_prompt(&path, &hunk)).await;
+            pb.finish_and_clear();
+
+            responses.push(response.unwrap_or_else(|e| {
+                println!("{} {}", "AI resolution failed:".yellow(), e);
+                String::new()
+            }));
+        }
+
+        let stdin = std::io::stdin();
+        review_conflict_resolutions(&path, &content, &responses, &mut stdin.lock())?;
+    }
+
     Ok(())
 }
 
+/// Shows each hunk's proposed resolution next to both sides and writes back
+/// only the ones accepted. Returns how many hunks were resolved.
+pub fn review_conflict_resolutions(
+    path: &str,
+    content: &str,
+    responses: &[String],
+    input: &mut dyn BufRead
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let hunks = parse_conflicts(content);
+    let mut resolutions = vec![None; hunks.len()];
+
+    for (n, (hunk, response)) in hunks.iter().zip(responses).enumerate() {
+        println!("\n{} {} ({}/{})", "Conflict in".cyan().bold(), path.bright_white(), n + 1, hunks.len());
+
+        let proposed = match parse_resolution(response) {
+            Some(proposed) => proposed,
+            None => {
+                println!("{}", "No usable resolution proposed; leaving this hunk unresolved".yellow());
+                continue;
+            }
+        };
+
+        print_three_columns(hunk, &proposed);
+        println!("{} {}", "Confidence:".bright_black(), proposed.confidence);
+
+        print!("{} ", "Accept this resolution [y,n]?".cyan());
+        std::io::stdout().flush()?;
+
+        let mut reply = String::new();
+        input.read_line(&mut reply)?;
+        if matches!(reply.trim().to_lowercase().as_str(), "y" | "yes") {
+            resolutions[n] = Some(proposed.lines);
+        }
+    }
+
+    let accepted = resolutions.iter().filter(|r| r.is_some()).count();
+    if accepted > 0 {
+        fs::write(path, apply_resolutions(content, &hunks, &resolutions))?;
+    }
+
+    let remaining = hunks.len() - accepted;
+    if remaining == 0 {
+        println!("{} {} {}", "Resolved".green(), path.bright_white(), "- use 'aigit add' to mark it resolved".bright_black());
+    } else {
+        println!("{} {} hunk(s) left unresolved in {}", "!".yellow(), remaining, path.bright_white());
+    }
+
+    Ok(accepted)
+}
+
 async fn perform_merge(
     repo: &Repository,
     current: &str,
@@ -142,7 +343,7 @@ async fn perform_merge(
     
     match merge_base {
         Some(base) if base == current => {
-            fast_forward_merge(repo, branch_commit, branch_name, config).await?;
+            fast_forward_merge(repo, current, branch_commit, branch_name, config).await?;
         },
         Some(base) if base == branch_commit => {
             println!("{}", "Already up to date".green());
@@ -160,11 +361,15 @@ async fn perform_merge(
 
 async fn fast_forward_merge(
     repo: &Repository,
+    current: &str,
     branch_commit: &str,
     branch_name: &str,
     config: &Config
 ) -> Result<(), Box<dyn std::error::Error>> {
+    // Same rules as checkout: local edits to files the merge brings in stop it.
+    update_worktree(repo, branch_commit, false)?;
     update_head(repo, branch_commit);
+    reflog::append(repo, Some(current), branch_commit, &format!("merge {}: Fast-forward", branch_name))?;
     println!("{} {} {}", 
             "Fast-forward merge:".green(),
             branch_name.bright_cyan(),
@@ -182,7 +387,80 @@ async fn three_way_merge(
     config: &Config
 ) -> Result<(), Box<dyn std::error::Error>> {
     println!("{}", "Performing three-way merge...".yellow());
-    
+
+    // Unrelated histories merge against an empty base.
+    let base = find_merge_base(repo, current, branch_commit).await?;
+    let merge = merge_commits(repo, base.as_deref(), current, branch_commit, branch_name)?;
+    let ours = flatten_commit_entries(repo, current)?;
+
+    let paths: BTreeSet<&String> = merge.files.keys().chain(ours.keys()).collect();
+    let touched: Vec<String> = paths.into_iter()
+        .filter(|path| merge.files.get(*path).map(|e| &e.hash) != ours.get(*path).map(|e| &e.hash))
+        .cloned()
+        .collect();
+
+    if touched.is_empty() && merge.conflicts.is_empty() {
+        println!("{}", "Already up to date (both branches made the same changes)".green());
+        audit_merge_operation("same_content", branch_name, current, config).await?;
+        return Ok(());
+    }
+
+    let dirty: Vec<&String> = touched.iter()
+        .filter(|path| {
+            let worktree = read_entry_content(path).ok().map(|content| hash_object(&ObjectType::Blob, &content));
+            worktree.as_ref() != ours.get(*path).map(|e| &e.hash)
+        })
+        .collect();
+    if !dirty.is_empty() {
+        let names: Vec<&str> = dirty.iter().map(|path| path.as_str()).collect();
+        return Err(format!("Your local changes to the following files would be overwritten by merge:\n  {}\nCommit or stash them before you merge.",
+                           names.join("\n  ")).into());
+    }
+
+    let mut index = Index::load(repo)?;
+    let staged: BTreeSet<&String> = touched.iter().chain(merge.conflicts.iter()).collect();
+    for path in staged {
+        index.remove_entry(path);
+        match merge.files.get(path) {
+            Some(entry) => {
+                let content = Object::read(repo, &entry.hash)?;
+                if touched.contains(path) {
+                    write_entry(path, &content, &entry.mode)?;
+                }
+                let checksum = hex::encode(digest::digest(&digest::SHA256, &content).as_ref());
+                index.add_entry_secure(path.clone(), entry.hash.clone(), entry.mode.clone(), content.len() as u64, checksum);
+            },
+            None => remove_file_and_empty_parents(path)?,
+        }
+
+        // A conflicted path is indexed as our version (theirs if we deleted
+        // it) next to all three stages; the working tree keeps the markers.
+        if let Some(versions) = merge.stages.get(path) {
+            if let Some((_, staged)) = versions.iter().find(|(stage, _)| *stage == 2).or(versions.last()) {
+                index.add_entry_secure(path.clone(), staged.hash.clone(), staged.mode.clone(), 0, String::new());
+            }
+            index.add_conflict(path, versions.iter()
+                .map(|(stage, entry)| (*stage, entry.hash.clone(), entry.mode.clone()))
+                .collect());
+        }
+    }
+    index.save(repo)?;
+
+    if !merge.conflicts.is_empty() {
+        for path in &merge.conflicts {
+            println!("{} {}", "CONFLICT (content): Merge conflict in".red(), path.bright_white());
+        }
+        println!("{}", "Automatic merge failed; fix conflicts and then commit the result.".yellow());
+        audit_merge_operation("conflict", branch_name, current, config).await?;
+        return Ok(());
+    }
+
+    let mut snapshot = Index::new();
+    for (path, entry) in &merge.files {
+        snapshot.add_entry_secure(path.clone(), entry.hash.clone(), entry.mode.clone(), 0, String::new());
+    }
+    let tree = Tree::create_from_index(repo, &snapshot)?;
+
     let merge_message = format!("Merge branch '{}' into {}", 
                                branch_name, 
                                Branch::get_current_branch(repo).unwrap_or_else(|| "HEAD".to_string()));
@@ -192,7 +470,7 @@ async fn three_way_merge(
     let author_email = config.get_user_email();
     
     let merge_commit = Commit::new_merge(
-        "temp_tree".to_string(),
+        tree,
         parents,
         author_name,
         author_email,
@@ -201,15 +479,134 @@ async fn three_way_merge(
     );
 
     let commit_content = serde_json::to_string(&merge_commit)?;
-    let commit_hash = Object::create(repo, crate::core::ObjectType::Commit, commit_content.as_bytes())?;
+    let commit_hash = Object::create(repo, ObjectType::Commit, commit_content.as_bytes())?;
     
     update_head(repo, &commit_hash);
+    reflog::append(repo, Some(current), &commit_hash, &format!("merge {}: Merge made by the 'three-way' strategy.", branch_name))?;
     println!("{} {}", "Merge commit created:".green(), commit_hash[..8].bright_yellow());
     
     audit_merge_operation("three_way", branch_name, &commit_hash, config).await?;
     Ok(())
 }
 
+/// The outcome of merging two commits file by file: the merged tree, with
+/// conflicted files holding their conflict-marked content, and the paths
+/// that still need resolving.
+pub struct TreeMerge {
+    pub files: BTreeMap<String, TreeEntry>,
+    pub conflicts: Vec<String>,
+    /// The base, ours and theirs versions (stages 1-3) of each conflicted
+    /// path; a side without the file is left out.
+    pub stages: BTreeMap<String, Vec<(u8, TreeEntry)>>,
+}
+
+/// Three-way merges the trees of `ours` and `theirs` against `base` (an empty
+/// tree when `None`). Paths changed on one side take that side; text files
+/// changed on both sides are merged line by line, with conflict markers
+/// around hunks that cannot be combined. Binary files changed on both sides
+/// and files modified on one side but deleted on the other keep the version
+/// that still has content and are reported as conflicts.
+pub fn merge_commits(
+    repo: &Repository,
+    base: Option<&str>,
+    ours: &str,
+    theirs: &str,
+    theirs_label: &str
+) -> Result<TreeMerge, Box<dyn std::error::Error>> {
+    let base = match base {
+        Some(base) => flatten_commit_entries(repo, base)?,
+        None => BTreeMap::new(),
+    };
+    let ours = flatten_commit_entries(repo, ours)?;
+    let theirs = flatten_commit_entries(repo, theirs)?;
+
+    let paths: BTreeSet<&String> = base.keys().chain(ours.keys()).chain(theirs.keys()).collect();
+    let mut merge = TreeMerge { files: BTreeMap::new(), conflicts: Vec::new(), stages: BTreeMap::new() };
+
+    for path in paths {
+        let (base_entry, our_entry, their_entry) = (base.get(path), ours.get(path), theirs.get(path));
+        let hash = |entry: Option<&TreeEntry>| entry.map(|e| e.hash.clone());
+
+        let resolved = if hash(our_entry) == hash(their_entry) || hash(their_entry) == hash(base_entry) {
+            our_entry.cloned()
+        } else if hash(our_entry) == hash(base_entry) {
+            their_entry.cloned()
+        } else {
+            match (our_entry, their_entry) {
+                (Some(our_entry), Some(their_entry)) => {
+                    let (entry, clean) = merge_file(repo, path, base_entry, our_entry, their_entry, theirs_label)?;
+                    if !clean {
+                        merge.conflicts.push(path.clone());
+                    }
+                    Some(entry)
+                },
+                (kept, deleted) => {
+                    merge.conflicts.push(path.clone());
+                    kept.or(deleted).cloned()
+                },
+            }
+        };
+
+        if merge.conflicts.last() == Some(path) {
+            let versions = [(1, base_entry), (2, our_entry), (3, their_entry)].into_iter()
+                .filter_map(|(stage, entry)| entry.map(|entry| (stage, entry.clone())))
+                .collect();
+            merge.stages.insert(path.clone(), versions);
+        }
+        if let Some(entry) = resolved {
+            merge.files.insert(path.clone(), entry);
+        }
+    }
+
+    Ok(merge)
+}
+
+/// Merges one file changed on both sides. Returns the merged entry and
+/// whether it merged cleanly; binary files are never merged and keep ours.
+fn merge_file(
+    repo: &Repository,
+    path: &str,
+    base: Option<&TreeEntry>,
+    ours: &TreeEntry,
+    theirs: &TreeEntry,
+    theirs_label: &str
+) -> Result<(TreeEntry, bool), Box<dyn std::error::Error>> {
+    let base_content = match base {
+        Some(base) => Object::read(repo, &base.hash)?,
+        None => Vec::new(),
+    };
+    let our_content = Object::read(repo, &ours.hash)?;
+    let their_content = Object::read(repo, &theirs.hash)?;
+    if is_binary(&base_content) || is_binary(&our_content) || is_binary(&their_content) {
+        return Ok((ours.clone(), false));
+    }
+
+    let (merged, conflicts) = merge_text(
+        &String::from_utf8_lossy(&base_content),
+        &String::from_utf8_lossy(&our_content),
+        &String::from_utf8_lossy(&their_content),
+        "HEAD",
+        theirs_label,
+    );
+    let mode = match base {
+        Some(base) if base.mode == ours.mode => theirs.mode.clone(),
+        _ => ours.mode.clone(),
+    };
+
+    let entry = TreeEntry {
+        mode,
+        name: path.to_string(),
+        hash: Object::create(repo, ObjectType::Blob, merged.as_bytes())?,
+        entry_type: "blob".to_string(),
+    };
+    Ok((entry, conflicts == 0))
+}
+
+fn flatten_commit_entries(repo: &Repository, commit_hash: &str) -> Result<BTreeMap<String, TreeEntry>, Box<dyn std::error::Error>> {
+    let commit: Commit = serde_json::from_slice(&Object::read(repo, commit_hash)?)?;
+    Tree::from_hash(repo, &commit.tree)?.flatten_entries(repo, "")
+}
+
 async fn unrelated_histories_merge(
     repo: &Repository,
     current: &str,
@@ -247,7 +644,7 @@ async fn create_merge_context(
          Potential conflicts: {}\n\
          Merge complexity: {}",
         branch_name,
-        branch_commit.get(..8).unwrap_or(&branch_commit),
+        branch_commit.get(..8).unwrap_or(branch_commit),
         branch_commit_info,
         &current[..8],
         current_commit_info,
@@ -320,9 +717,7 @@ async fn is_ancestor(
 }
 
 fn commit_exists(repo: &Repository, hash: &str) -> Result<bool, Box<dyn std::error::Error>> {
-    let (dir, file) = hash.split_at(2);
-    let obj_path = repo.objects_dir().join(dir).join(file);
-    Ok(obj_path.exists())
+    Ok(Object::exists(repo, hash))
 }
 
 async fn verify_commit_integrity(
@@ -355,7 +750,7 @@ async fn audit_merge_operation(
     commit_hash: &str,
     config: &Config
 ) -> Result<(), Box<dyn std::error::Error>> {
-    if !config.get("security.auditLog").map(|v| v == "true").unwrap_or(false) {
+    if !config.get_bool("security.auditLog").unwrap_or(false) {
         return Ok(());
     }
     
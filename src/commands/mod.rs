@@ -4,10 +4,30 @@ pub mod add;
 pub mod commit;
 pub mod status;
 pub mod log;
+pub mod reflog;
+pub mod show;
 pub mod branch;
+pub mod tag;
 pub mod checkout;
+pub mod switch;
 pub mod diff;
 pub mod merge;
 pub mod review;
 pub mod suggest;
 pub mod push;
+pub mod quarantine;
+pub mod gc;
+pub mod restore;
+pub mod stash;
+pub mod fetch;
+pub mod rev_list;
+pub mod fsck;
+pub mod doctor;
+pub mod apply;
+pub mod rm;
+pub mod format_patch;
+pub mod am;
+pub mod verify_commit;
+pub mod key;
+pub mod revert;
+pub mod remote;
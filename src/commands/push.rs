@@ -1,10 +1,20 @@
-use crate::core::{Repository};
+use crate::core::Repository;
+use crate::core::revlist;
+use crate::core::transport::{self, Remote, TransferReport};
 use colored::*;
 use indicatif::{ProgressBar, ProgressStyle};
 use std::io::Write;
 
-pub async fn run(branch: String) -> Result<(), Box<dyn std::error::Error>> {
-    let repo = Repository::new(".aigit").ok_or("Not in a repository")?;
+pub async fn run(remote: Option<String>, branch: String) -> Result<(), Box<dyn std::error::Error>> {
+    let repo = Repository::discover().ok_or("Not in a repository")?;
+    let config = repo.config();
+    
+    let remote_name = remote.clone().unwrap_or_else(|| "origin".to_string());
+    if remote.is_some() || config.get(&format!("remote.{}.url", remote_name)).is_some() {
+        validate_branch_exists(&repo, &branch)?;
+        let remote = Remote::open(config, &remote_name)?;
+        return push_to_remote(&repo, &remote, &branch).await;
+    }
     
     let pb = ProgressBar::new_spinner();
     pb.set_style(ProgressStyle::default_spinner().template("{spinner} {msg}").unwrap());
@@ -50,6 +60,43 @@ pub async fn run(branch: String) -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+async fn push_to_remote(repo: &Repository, remote: &Remote, branch: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let pb = ProgressBar::new_spinner();
+    pb.set_style(ProgressStyle::default_spinner().template("{spinner} {msg}").unwrap());
+    pb.set_message(format!("Negotiating objects with '{}'...", remote.name));
+    pb.enable_steady_tick(std::time::Duration::from_millis(100));
+    
+    let result = transport::push(repo, remote, branch);
+    pb.finish_and_clear();
+    
+    match result {
+        Ok(report) => {
+            print_transfer_report(&report);
+            println!("{} {} -> {}/{}", 
+                    "✓".green().bold(), branch.bright_yellow(), remote.name.bright_cyan(), branch.bright_yellow());
+            audit_push_operation(branch, report.transferred, true).await?;
+            Ok(())
+        },
+        Err(e) => {
+            println!("{} Failed to push '{}' to '{}': {}", 
+                    "✗".red().bold(), branch.bright_yellow(), remote.name.bright_cyan(), e);
+            audit_push_operation(branch, 0, false).await?;
+            Err(e)
+        }
+    }
+}
+
+pub fn print_transfer_report(report: &TransferReport) {
+    if report.transferred == 0 {
+        println!("{}", "Everything up-to-date, no objects transferred".bright_black());
+    } else {
+        println!("{} {} objects ({} bytes)", 
+                "Transferred".green(), 
+                report.transferred.to_string().bright_cyan(), 
+                report.bytes);
+    }
+}
+
 fn validate_branch_exists(repo: &Repository, branch: &str) -> Result<(), Box<dyn std::error::Error>> {
     let branch_file = repo.git_dir.join("refs").join("heads").join(branch);
     if !branch_file.exists() {
@@ -79,41 +126,8 @@ fn get_branch_commit_count(repo: &Repository, branch: &str) -> Result<usize, Box
         return Ok(0);
     }
     
-    let mut count = 0;
-    let mut current_hash = std::fs::read_to_string(&branch_file)?.trim().to_string();
-    
-    while !current_hash.is_empty() {
-        let commit_file = repo.git_dir.join("objects").join(&current_hash[..2]).join(&current_hash[2..]);
-        if !commit_file.exists() {
-            break;
-        }
-        
-        count += 1;
-        
-        let commit_data = std::fs::read(&commit_file)?;
-        let mut decoder = flate2::read::ZlibDecoder::new(&commit_data[..]);
-        let mut decompressed = Vec::new();
-        
-        if std::io::Read::read_to_end(&mut decoder, &mut decompressed).is_ok() {
-            if let Ok(commit_str) = String::from_utf8(decompressed) {
-                if let Ok(commit_obj) = serde_json::from_str::<serde_json::Value>(&commit_str) {
-                    if let Some(parent) = commit_obj.get("parent").and_then(|p| p.as_str()) {
-                        current_hash = parent.to_string();
-                    } else {
-                        break;
-                    }
-                } else {
-                    break;
-                }
-            } else {
-                break;
-            }
-        } else {
-            break;
-        }
-    }
-    
-    Ok(count)
+    let tip = std::fs::read_to_string(&branch_file)?.trim().to_string();
+    revlist::count_history(repo, &tip)
 }
 
 async fn execute_branch_sync(_repo: &Repository, branch: &str, commit_count: usize) -> Result<usize, Box<dyn std::error::Error>> {
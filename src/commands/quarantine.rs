@@ -0,0 +1,55 @@
+use crate::core::{Repository, Quarantine};
+use colored::*;
+use std::io::Write;
+
+pub async fn list() -> Result<(), Box<dyn std::error::Error>> {
+    let repo = Repository::discover().ok_or("Not in a repository")?;
+    let quarantine = Quarantine::load(&repo)?;
+
+    if quarantine.is_empty() {
+        println!("{}", "No quarantined files".green());
+        return Ok(());
+    }
+
+    println!("{}", "Quarantined files:".red().bold());
+    for entry in &quarantine.entries {
+        println!("  {} {}",
+                entry.path.red(),
+                entry.timestamp.format("%Y-%m-%d %H:%M:%S").to_string().bright_black());
+        println!("    {} {}", "pattern:".bright_black(), entry.pattern);
+    }
+
+    Ok(())
+}
+
+pub async fn clear() -> Result<(), Box<dyn std::error::Error>> {
+    let repo = Repository::discover().ok_or("Not in a repository")?;
+    let mut quarantine = Quarantine::load(&repo)?;
+
+    let cleared = quarantine.clear();
+    quarantine.save(&repo)?;
+
+    println!("{} {} quarantine entries", "Cleared".green(), cleared.to_string().bright_yellow());
+
+    audit_quarantine_clear(cleared).await?;
+    Ok(())
+}
+
+async fn audit_quarantine_clear(cleared: usize) -> Result<(), Box<dyn std::error::Error>> {
+    let audit_file = std::path::PathBuf::from(".aigit/logs/audit.log");
+    if !audit_file.exists() {
+        return Ok(());
+    }
+
+    let timestamp = chrono::Utc::now().to_rfc3339();
+    let user = whoami::username();
+    let details = format!("cleared:{}", cleared);
+
+    let entry = format!("{},quarantine_clear,{},{},quarantine\n", timestamp, user, details);
+    std::fs::OpenOptions::new()
+        .append(true)
+        .open(audit_file)?
+        .write_all(entry.as_bytes())?;
+
+    Ok(())
+}
@@ -0,0 +1,7 @@
+use crate::commands::log::{self, LogOptions};
+
+/// Lists where HEAD has been, newest first, as `<hash> HEAD@{n}: <action>:
+/// <message>`. The same walk as `log -g --oneline`.
+pub async fn run(max_count: Option<usize>) -> Result<(), Box<dyn std::error::Error>> {
+    log::run(LogOptions { walk_reflogs: true, oneline: true, max_count, ..Default::default() }).await
+}
@@ -0,0 +1,42 @@
+use crate::core::Repository;
+use crate::core::transport::{self, Remote};
+use crate::commands::fetch::print_pruned;
+use colored::*;
+use std::io::Write;
+
+/// Deletes the tracking refs of `remote` whose branches no longer exist
+/// there, without fetching anything.
+pub async fn prune(remote: String) -> Result<(), Box<dyn std::error::Error>> {
+    let repo = Repository::discover().ok_or("Not in a repository")?;
+    let remote = Remote::open(repo.config(), &remote)?;
+
+    let pruned = transport::prune_tracking_refs(&repo, &remote.name, &remote.heads()?)?;
+    if pruned.is_empty() {
+        println!("{} {}", "Nothing to prune for".yellow(), remote.name.bright_cyan());
+    } else {
+        println!("{} {}", "Pruning".green(), remote.name.bright_cyan());
+        print_pruned(&remote.name, &pruned);
+    }
+
+    audit_prune(&remote.name, pruned.len()).await?;
+    Ok(())
+}
+
+async fn audit_prune(remote: &str, pruned: usize) -> Result<(), Box<dyn std::error::Error>> {
+    let audit_file = std::path::PathBuf::from(".aigit/logs/audit.log");
+    if !audit_file.exists() {
+        return Ok(());
+    }
+
+    let timestamp = chrono::Utc::now().to_rfc3339();
+    let user = whoami::username();
+    let details = format!("remote:{},pruned:{}", remote, pruned);
+
+    let entry = format!("{},remote-prune,{},{},operation\n", timestamp, user, details);
+    std::fs::OpenOptions::new()
+        .append(true)
+        .open(audit_file)?
+        .write_all(entry.as_bytes())?;
+
+    Ok(())
+}
@@ -0,0 +1,81 @@
+use crate::core::{Repository, Index, Object, Commit, Tree, resolve_revision};
+use crate::utils::worktree::write_entry;
+use colored::*;
+use ring::digest;
+use std::io::Write;
+
+pub async fn run(files: Vec<String>) -> Result<(), Box<dyn std::error::Error>> {
+    run_from(None, files).await
+}
+
+/// Restores `files` from the index, or from the commit named by `source`
+/// (in which case the restored content is staged as well).
+pub async fn run_from(source: Option<String>, files: Vec<String>) -> Result<(), Box<dyn std::error::Error>> {
+    let repo = Repository::discover().ok_or("Not in a repository")?;
+    let mut index = Index::load(&repo)?;
+
+    if files.is_empty() {
+        return Err("Please specify the files to restore".into());
+    }
+
+    let source_files = match &source {
+        Some(spec) => {
+            let hash = resolve_revision(&repo, spec)?;
+            let commit: Commit = serde_json::from_slice(&Object::read(&repo, &hash)?)?;
+            Some(Tree::from_hash(&repo, &commit.tree)?.flatten_entries(&repo, "")?)
+        },
+        None => None,
+    };
+
+    let mut restored = Vec::new();
+
+    for file in &files {
+        let (hash, mode) = match (&source_files, &source) {
+            (Some(tree_files), Some(spec)) => tree_files.get(file)
+                .map(|entry| (entry.hash.clone(), entry.mode.clone()))
+                .ok_or_else(|| format!("'{}' does not exist in {}", file, spec))?,
+            _ => {
+                let hash = index.entries.get(file)
+                    .cloned()
+                    .ok_or_else(|| format!("'{}' is not staged, nothing to restore from", file))?;
+                let mode = index.metadata.get(file)
+                    .map(|m| m.mode.clone())
+                    .unwrap_or_else(|| "100644".to_string());
+                (hash, mode)
+            },
+        };
+
+        let content = Object::read(&repo, &hash)?;
+        write_entry(file, &content, &mode)?;
+
+        let checksum = hex::encode(digest::digest(&digest::SHA256, &content).as_ref());
+        index.add_entry_secure(file.clone(), hash, mode, content.len() as u64, checksum);
+
+        println!("{} {}", "Restored".green(), file);
+        restored.push(file.clone());
+    }
+
+    index.save(&repo)?;
+    audit_restore(&restored).await?;
+
+    Ok(())
+}
+
+async fn audit_restore(files: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let audit_file = std::path::PathBuf::from(".aigit/logs/audit.log");
+    if !audit_file.exists() {
+        return Ok(());
+    }
+
+    let timestamp = chrono::Utc::now().to_rfc3339();
+    let user = whoami::username();
+    let details = format!("files:{}", files.len());
+
+    let entry = format!("{},restore,{},{},files\n", timestamp, user, details);
+    std::fs::OpenOptions::new()
+        .append(true)
+        .open(audit_file)?
+        .write_all(entry.as_bytes())?;
+
+    Ok(())
+}
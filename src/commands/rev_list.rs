@@ -0,0 +1,17 @@
+use crate::core::Repository;
+use crate::core::revlist::{self, RevRange};
+
+pub async fn run(spec: String, count: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let repo = Repository::discover().ok_or("Not in a repository")?;
+    let range = RevRange::parse(&repo, &spec)?;
+
+    if count {
+        println!("{}", revlist::count_commits(&repo, &range)?);
+    } else {
+        for hash in revlist::rev_list(&repo, &range)? {
+            println!("{}", hash);
+        }
+    }
+
+    Ok(())
+}
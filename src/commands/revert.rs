@@ -0,0 +1,387 @@
+use crate::core::{Repository, Object, ObjectType, Commit, Tree, TreeEntry, Index, Branch, resolve_revision};
+use crate::core::object::hash_object;
+use crate::core::reflog;
+use crate::core::revlist::{rev_list, parents_of, RevRange};
+use crate::commands::commit::{cleanup_message, edit_commit_message, CleanupMode};
+use crate::commands::merge::{merge_head, reset_index_and_worktree, MERGE_MSG, ORIG_HEAD};
+use crate::utils::conflicts::merge_text;
+use crate::utils::diff::{diff_text, is_binary};
+use crate::utils::patch::{apply_file_patch, parse_patch};
+use crate::utils::worktree::{read_entry_content, write_entry, remove_file_and_empty_parents};
+use colored::*;
+use ring::digest;
+use std::collections::BTreeMap;
+use std::io::Write;
+
+#[derive(Default, Clone)]
+pub struct RevertOptions {
+    pub target: String,
+    pub no_edit: bool,
+    pub mainline: Option<usize>,
+    /// `--no-commit`: apply the inverse to the working tree and index but
+    /// leave committing it to the user.
+    pub no_commit: bool,
+}
+
+/// How a revert ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RevertOutcome {
+    /// The revert was committed as this hash.
+    Committed(String),
+    /// `--no-commit`: the revert is staged but not committed.
+    Staged,
+    /// These files could not be reverted cleanly. They hold conflict markers
+    /// and are staged as conflicts; nothing was committed.
+    Conflicted(Vec<String>),
+}
+
+pub async fn run(target: String, no_edit: bool, mainline: Option<usize>) -> Result<(), Box<dyn std::error::Error>> {
+    run_with(RevertOptions { target, no_edit, mainline, ..Default::default() }).await
+}
+
+/// Reverts `target`, a single commit or an `<a>..<b>` range. Each commit gets
+/// its own `Revert "..."` commit, newest first, so every step applies on top
+/// of the ones before it. Merges need `mainline`, the 1-based parent whose
+/// side is kept. Unless `no_edit` is set the message is opened in the editor.
+/// A commit that conflicts stops the run with the conflicts staged.
+pub async fn run_with(options: RevertOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let RevertOptions { target, no_edit, mainline, no_commit } = options;
+    let repo = Repository::discover().ok_or("Not in a repository")?;
+    let orig_head = Branch::get_current_commit(&repo).ok_or("Cannot revert before the first commit")?;
+    if Index::load(&repo)?.has_conflicts() {
+        return Err("You have unresolved conflicts.\nFix them and commit, or restore the files, before reverting.".into());
+    }
+
+    let commits = if target.contains("..") {
+        rev_list(&repo, &RevRange::parse(&repo, &target)?)?
+    } else {
+        vec![resolve_revision(&repo, &target)?]
+    };
+    if commits.is_empty() {
+        println!("{}", "Nothing to revert".yellow());
+        return Ok(());
+    }
+
+    for hash in &commits {
+        let commit = read_commit(&repo, hash)?;
+        let parent = mainline_parent(hash, &commit, mainline)?;
+
+        let mut message = revert_message(hash, &commit, parent.as_deref(), mainline.is_some());
+        if !no_edit && !no_commit {
+            message = cleanup_message(&edit_commit_message(&message, repo.config())?, CleanupMode::Strip);
+            if message.trim().is_empty() {
+                return Err("Aborting revert due to empty commit message".into());
+            }
+        }
+
+        match revert_commit(&repo, hash, &commit, parent.as_deref(), &message, no_commit)? {
+            RevertOutcome::Committed(new_hash) => {
+                println!("{} {} {}",
+                        "Reverted".green(),
+                        hash[..8].bright_yellow(),
+                        format!("as {}", &new_hash[..8]).bright_black());
+                audit_revert(hash, &new_hash).await?;
+            },
+            RevertOutcome::Staged => {
+                println!("{} {} {}",
+                        "Reverted".green(),
+                        hash[..8].bright_yellow(),
+                        "(staged, not committed)".bright_black());
+                audit_revert(hash, "staged").await?;
+            },
+            RevertOutcome::Conflicted(paths) => {
+                for path in &paths {
+                    println!("{} {}", "CONFLICT (content): Revert conflict in".red(), path.bright_white());
+                }
+                println!("{} {} \"{}\"", "Could not revert".yellow(), &hash[..8], commit.short_message());
+                println!("{}", "Fix the conflicts, stage the files and run 'aigit commit' to record the revert,".yellow());
+                println!("{}", "or run 'aigit revert --abort' to give up.".yellow());
+                std::fs::write(repo.git_dir.join(ORIG_HEAD), &orig_head)?;
+                audit_revert(hash, "conflict").await?;
+                return Ok(());
+            },
+        }
+    }
+    if no_commit {
+        std::fs::write(repo.git_dir.join(ORIG_HEAD), &orig_head)?;
+        println!("{}", "Run 'aigit commit' to record the revert, or 'aigit revert --abort' to drop it.".bright_black());
+    }
+
+    Ok(())
+}
+
+/// Abandons a revert stopped by `--no-commit` or a conflict: the branch, the
+/// index and the working tree go back to where the revert started, and the
+/// prepared `MERGE_MSG` is removed.
+pub async fn abort() -> Result<(), Box<dyn std::error::Error>> {
+    let repo = Repository::discover().ok_or("Not in a repository")?;
+    let message_path = repo.git_dir.join(MERGE_MSG);
+    if !message_path.exists() || merge_head(&repo).is_some() {
+        return Err("There is no revert to abort (MERGE_MSG missing)".into());
+    }
+    let head = Branch::get_current_commit(&repo).ok_or("Cannot abort a revert before the first commit")?;
+    let orig_head = std::fs::read_to_string(repo.git_dir.join(ORIG_HEAD))
+        .map(|content| content.trim().to_string())
+        .unwrap_or_else(|_| head.clone());
+
+    reset_index_and_worktree(&repo, &orig_head)?;
+    if head != orig_head {
+        update_head(&repo, &orig_head)?;
+        reflog::append(&repo, Some(&head), &orig_head, "revert: abort")?;
+    }
+    std::fs::remove_file(message_path)?;
+
+    println!("{} {}", "Revert aborted; HEAD is back at".yellow(), orig_head[..8].bright_yellow());
+    audit_revert("abort", &orig_head).await?;
+    Ok(())
+}
+
+/// The parent a revert of `commit` goes back to: the only parent of a normal
+/// commit, or parent number `mainline` of a merge.
+pub fn mainline_parent(hash: &str, commit: &Commit, mainline: Option<usize>) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let parents = parents_of(commit);
+    match (parents.len(), mainline) {
+        (0 | 1, Some(_)) => Err(format!("Commit {} is not a merge, but -m was given", &hash[..8]).into()),
+        (0 | 1, None) => Ok(parents.into_iter().next().filter(|parent| !parent.is_empty())),
+        (_, None) => Err(format!("Commit {} is a merge; use -m <parent> to pick the mainline", &hash[..8]).into()),
+        (count, Some(n)) if n == 0 || n > count => {
+            Err(format!("Commit {} has {} parents; -m must be between 1 and {}", &hash[..8], count, count).into())
+        },
+        (_, Some(n)) => Ok(Some(parents[n - 1].clone())),
+    }
+}
+
+/// `Revert "<subject>"` plus a body naming the reverted commit, as git writes it.
+pub fn revert_message(hash: &str, commit: &Commit, parent: Option<&str>, is_merge: bool) -> String {
+    let mut message = format!("Revert \"{}\"\n\nThis reverts commit {}", commit.short_message(), hash);
+    match parent {
+        Some(parent) if is_merge => message.push_str(&format!(", reversing\nchanges made to {}.\n", parent)),
+        _ => message.push_str(".\n"),
+    }
+    message
+}
+
+/// Applies the inverse of `commit` against `parent` to the working tree and
+/// index and, unless `no_commit` is set, commits it on top of HEAD. Files
+/// that changed since `commit` are merged three ways; any conflict leaves
+/// markers in the working tree and the file staged as a conflict, the way
+/// `merge` does, and nothing is committed. Either way the message is kept in
+/// `MERGE_MSG` for the `commit` that finishes the revert.
+pub fn revert_commit(
+    repo: &Repository,
+    hash: &str,
+    commit: &Commit,
+    parent: Option<&str>,
+    message: &str,
+    no_commit: bool
+) -> Result<RevertOutcome, Box<dyn std::error::Error>> {
+    let head = Branch::get_current_commit(repo).ok_or("Cannot revert before the first commit")?;
+    let parent_tree = match parent {
+        Some(parent) => Some(read_commit(repo, parent)?.tree),
+        None => None,
+    };
+    let parent_files = match &parent_tree {
+        Some(tree) => Tree::from_hash(repo, tree)?.flatten_entries(repo, "")?,
+        None => BTreeMap::new(),
+    };
+    let their_label = format!("parent of {} ({})", &hash[..8], commit.short_message());
+
+    let mut results: BTreeMap<String, Option<(Vec<u8>, String)>> = BTreeMap::new();
+    let mut conflicts: BTreeMap<String, RevertConflict> = BTreeMap::new();
+
+    for change in Tree::diff(repo, Some(&commit.tree), parent_tree.as_deref())? {
+        let path = change.path.clone();
+        let current = read_entry_content(&path).ok();
+        let current_hash = current.as_deref().map(|content| hash_object(&ObjectType::Blob, content));
+        let mode = parent_files.get(&path).map(|entry| entry.mode.clone()).unwrap_or_else(|| "100644".to_string());
+
+        if current_hash == change.new_hash {
+            continue;
+        }
+        if current_hash == change.old_hash {
+            let restored = match &change.new_hash {
+                Some(blob) => Some((Object::read(repo, blob)?, mode)),
+                None => None,
+            };
+            results.insert(path, restored);
+            continue;
+        }
+
+        let reverted = read_blob(repo, change.old_hash.as_deref())?;
+        let restored = read_blob(repo, change.new_hash.as_deref())?;
+        let ours = current.clone().unwrap_or_default();
+        let conflict = |content: Option<Vec<u8>>| RevertConflict {
+            content,
+            mode: mode.clone(),
+            base: change.old_hash.clone(),
+            ours: current.clone(),
+            theirs: change.new_hash.clone(),
+        };
+        if is_binary(&reverted) || is_binary(&restored) || is_binary(&ours) {
+            conflicts.insert(path, conflict(None));
+            continue;
+        }
+
+        let relative = path.strip_prefix("./").unwrap_or(&path);
+        let old_name = if change.old_hash.is_some() { format!("a/{}", relative) } else { "/dev/null".to_string() };
+        let new_name = if change.new_hash.is_some() { format!("b/{}", relative) } else { "/dev/null".to_string() };
+        let text = format!("--- {}\n+++ {}\n{}", old_name, new_name,
+                           diff_text(&String::from_utf8_lossy(&reverted), &String::from_utf8_lossy(&restored)));
+        let patch = parse_patch(&text)?.remove(0);
+        let original = current_hash.map(|_| String::from_utf8_lossy(&ours).to_string());
+
+        match apply_file_patch(original.as_deref(), &patch) {
+            Ok(content) => {
+                results.insert(path, content.map(|content| (content.into_bytes(), mode)));
+            },
+            Err(_) => {
+                let (merged, count) = merge_text(
+                    &String::from_utf8_lossy(&reverted),
+                    &String::from_utf8_lossy(&ours),
+                    &String::from_utf8_lossy(&restored),
+                    "HEAD",
+                    &their_label,
+                );
+                if count == 0 {
+                    results.insert(path, Some((merged.into_bytes(), mode)));
+                } else {
+                    conflicts.insert(path, conflict(Some(merged.into_bytes())));
+                }
+            },
+        }
+    }
+
+    if results.is_empty() && conflicts.is_empty() {
+        return Err(format!("Reverting {} would not change anything", &hash[..8]).into());
+    }
+
+    // The revert commit is HEAD's tree with the reverted files swapped in, so
+    // unrelated staged changes stay out of it.
+    let mut files: BTreeMap<String, TreeEntry> = Tree::from_hash(repo, &read_commit(repo, &head)?.tree)?
+        .flatten_entries(repo, "")?;
+    let mut index = Index::load(repo)?;
+
+    for (path, result) in &results {
+        index.remove_entry(path);
+        match result {
+            Some((content, mode)) => {
+                write_entry(path, content, mode)?;
+                let blob = Object::create(repo, ObjectType::Blob, content)?;
+                let checksum = hex::encode(digest::digest(&digest::SHA256, content).as_ref());
+                index.add_entry_secure(path.clone(), blob.clone(), mode.clone(), content.len() as u64, checksum);
+                files.insert(path.clone(), TreeEntry {
+                    mode: mode.clone(),
+                    name: path.clone(),
+                    hash: blob,
+                    entry_type: "blob".to_string(),
+                });
+            },
+            None => {
+                remove_file_and_empty_parents(path)?;
+                files.remove(path);
+            },
+        }
+    }
+    for (path, conflict) in &conflicts {
+        stage_conflict(repo, &mut index, path, conflict)?;
+    }
+    index.save(repo)?;
+
+    if no_commit || !conflicts.is_empty() {
+        std::fs::write(repo.git_dir.join(MERGE_MSG), message)?;
+        return Ok(if conflicts.is_empty() {
+            RevertOutcome::Staged
+        } else {
+            RevertOutcome::Conflicted(conflicts.into_keys().collect())
+        });
+    }
+
+    let mut snapshot = Index::new();
+    for (path, entry) in files {
+        snapshot.add_entry_secure(path, entry.hash, entry.mode, 0, String::new());
+    }
+    let tree = Tree::create_from_index(repo, &snapshot)?;
+
+    let config = repo.config();
+    let revert = Commit::new(tree, Some(head.clone()), config.get_user_name(), config.get_user_email(), message.to_string());
+    let new_hash = Object::create(repo, ObjectType::Commit, &serde_json::to_vec(&revert)?)?;
+
+    update_head(repo, &new_hash)?;
+    reflog::append(repo, Some(&head), &new_hash, &format!("revert: {}", revert.short_message()))?;
+    Ok(RevertOutcome::Committed(new_hash))
+}
+
+/// A file changed both by the reverted commit and since then.
+struct RevertConflict {
+    /// The merge with conflict markers; `None` for binary files, which keep
+    /// the working-tree version.
+    content: Option<Vec<u8>>,
+    mode: String,
+    /// The file as the reverted commit left it (stage 1).
+    base: Option<String>,
+    /// The current working-tree content (stage 2).
+    ours: Option<Vec<u8>>,
+    /// The file as the commit's parent had it (stage 3).
+    theirs: Option<String>,
+}
+
+/// Writes the conflict-marked file and indexes it as our version next to all
+/// three stages, as `merge` does.
+fn stage_conflict(repo: &Repository, index: &mut Index, path: &str, conflict: &RevertConflict) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(content) = &conflict.content {
+        write_entry(path, content, &conflict.mode)?;
+    }
+    let ours = match &conflict.ours {
+        Some(content) => Some(Object::create(repo, ObjectType::Blob, content)?),
+        None => None,
+    };
+
+    index.remove_entry(path);
+    let versions: Vec<(u8, String, String)> = [(1, &conflict.base), (2, &ours), (3, &conflict.theirs)].into_iter()
+        .filter_map(|(stage, hash)| hash.clone().map(|hash| (stage, hash, conflict.mode.clone())))
+        .collect();
+    if let Some((_, staged, mode)) = versions.iter().find(|(stage, _, _)| *stage == 2).or(versions.last()) {
+        index.add_entry_secure(path.to_string(), staged.clone(), mode.clone(), 0, String::new());
+    }
+    index.add_conflict(path, versions);
+    Ok(())
+}
+
+fn read_commit(repo: &Repository, hash: &str) -> Result<Commit, Box<dyn std::error::Error>> {
+    Ok(serde_json::from_slice(&Object::read(repo, hash)?)?)
+}
+
+fn read_blob(repo: &Repository, hash: Option<&str>) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    match hash {
+        Some(hash) => Object::read(repo, hash),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn update_head(repo: &Repository, commit_hash: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let head_content = std::fs::read_to_string(repo.git_dir.join("HEAD"))?;
+    match head_content.trim().strip_prefix("ref: ") {
+        Some(ref_path) => std::fs::write(repo.git_dir.join(ref_path), commit_hash)?,
+        None => std::fs::write(repo.git_dir.join("HEAD"), commit_hash)?,
+    }
+    Ok(())
+}
+
+async fn audit_revert(reverted: &str, commit_hash: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let audit_file = std::path::PathBuf::from(".aigit/logs/audit.log");
+    if !audit_file.exists() {
+        return Ok(());
+    }
+
+    let timestamp = chrono::Utc::now().to_rfc3339();
+    let user = whoami::username();
+    let details = format!("{}:{}", reverted, commit_hash);
+
+    let entry = format!("{},revert,{},{},commit\n", timestamp, user, details);
+    std::fs::OpenOptions::new()
+        .append(true)
+        .open(audit_file)?
+        .write_all(entry.as_bytes())?;
+
+    Ok(())
+}
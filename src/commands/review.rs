@@ -1,50 +1,88 @@
 use crate::core::{Repository, Index};
-use crate::ai::gemini::GeminiClient;
-use crate::utils::diff::get_staged_diff;
+use crate::ai::{provider_with_progress, require_ai, AiProvider};
+use crate::utils::diff::{generate_diff_for_paths, PathScope};
 use crate::utils::analyzer::analyze_diff_complexity;
 use colored::*;
 use indicatif::{ProgressBar, ProgressStyle};
+use std::io::Write;
+
+#[derive(Default, Clone)]
+pub struct ReviewOptions {
+    pub full: bool,
+    /// Paths or globs after `--`; only staged changes to matching files are
+    /// sent for review.
+    pub paths: Vec<String>,
+}
 
 pub async fn run(full: bool) -> Result<(), Box<dyn std::error::Error>> {
-    let repo = Repository::new(".aigit").ok_or("Not in a repository")?;
-    let index = Index::load(&repo)?;
+    run_with(ReviewOptions { full, ..Default::default() }).await
+}
+
+pub async fn run_with(options: ReviewOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let ReviewOptions { full, paths } = options;
+    let repo = Repository::discover().ok_or("Not in a repository")?;
+    require_ai(repo.config())?;
+    let paths = PathScope::new(&paths)?;
+    let mut index = Index::load(&repo)?;
+    index.entries.retain(|path, _| paths.matches(path));
+    index.metadata.retain(|path, _| paths.matches(path));
 
     if index.entries.is_empty() {
         println!("{}", "No changes staged for review".yellow());
         return Ok(());
     }
 
+    let diff_content = generate_diff_for_paths(&repo, &index, true, false, &paths).await?;
+    if diff_content.is_empty() && !paths.is_empty() {
+        println!("{}", "No staged changes under the given paths".yellow());
+        return Ok(());
+    }
+
     let pb = ProgressBar::new_spinner();
     pb.set_style(ProgressStyle::default_spinner().template("{spinner} {msg}").unwrap());
     pb.set_message("AI analyzing staged changes...");
     pb.enable_steady_tick(std::time::Duration::from_millis(100));
 
-    let diff_content = get_staged_diff(&repo, &index).await;
     let complexity_score = analyze_diff_complexity(&diff_content).await;
     
-    let gemini = GeminiClient::new();
+    let provider = match provider_with_progress(repo.config(), &pb) {
+        Ok(provider) => provider,
+        Err(e) => {
+            pb.finish_and_clear();
+            return Err(e.to_string().into());
+        }
+    };
 
-    match gemini.comprehensive_review(&diff_content, full).await {
-        Ok(review) => {
+    // The spinner runs until the first piece of the review arrives, which is
+    // then printed as it streams in.
+    let mut started = false;
+    let mut print_chunk = |chunk: &str| {
+        if !started {
             pb.finish_and_clear();
-            
             print_review_header(&index, complexity_score);
-            println!("{}", review);
+            started = true;
+        }
+        print!("{}", chunk);
+        let _ = std::io::stdout().flush();
+    };
+    match provider.comprehensive_review_streaming(&diff_content, full, &mut print_chunk).await {
+        Ok(_) => {
+            println!();
             
             if full {
                 println!("\n{}", "Generating additional insights...".yellow());
                 
-                if let Ok(suggestions) = gemini.suggest_improvements(&diff_content).await {
+                if let Ok(suggestions) = provider.suggest_improvements(&diff_content).await {
                     println!("\n{}", "=== Improvement Suggestions ===".green().bold());
                     println!("{}", suggestions);
                 }
                 
-                if let Ok(security_analysis) = analyze_security_implications(&diff_content, &gemini).await {
+                if let Ok(security_analysis) = analyze_security_implications(&diff_content, provider.as_ref()).await {
                     println!("\n{}", "=== Security Analysis ===".red().bold());
                     println!("{}", security_analysis);
                 }
                 
-                if let Ok(performance_analysis) = analyze_performance_implications(&diff_content, &gemini).await {
+                if let Ok(performance_analysis) = analyze_performance_implications(&diff_content, provider.as_ref()).await {
                     println!("\n{}", "=== Performance Analysis ===".blue().bold());
                     println!("{}", performance_analysis);
                 }
@@ -54,6 +92,9 @@ pub async fn run(full: bool) -> Result<(), Box<dyn std::error::Error>> {
         },
         Err(e) => {
             pb.finish_and_clear();
+            if started {
+                println!();
+            }
             return Err(format!("Review failed: {}", e).into());
         }
     }
@@ -102,7 +143,7 @@ fn print_review_summary(index: &Index, complexity_score: f32) {
 
 async fn analyze_security_implications(
     diff_content: &str,
-    gemini: &GeminiClient
+    provider: &dyn AiProvider
 ) -> Result<String, Box<dyn std::error::Error>> {
     let security_prompt = format!(
         "Analyze these code changes for potential security vulnerabilities, \
@@ -111,12 +152,12 @@ async fn analyze_security_implications(
         diff_content.chars().take(3000).collect::<String>()
     );
     
-    gemini.generate_text(&security_prompt).await
+    provider.generate_text(&security_prompt).await
 }
 
 async fn analyze_performance_implications(
     diff_content: &str,
-    gemini: &GeminiClient
+    provider: &dyn AiProvider
 ) -> Result<String, Box<dyn std::error::Error>> {
     let performance_prompt = format!(
         "Analyze these code changes for performance implications, \
@@ -125,5 +166,5 @@ async fn analyze_performance_implications(
         diff_content.chars().take(3000).collect::<String>()
     );
     
-    gemini.generate_text(&performance_prompt).await
+    provider.generate_text(&performance_prompt).await
 }
@@ -0,0 +1,122 @@
+use crate::core::{Repository, Index, ObjectType};
+use crate::core::object::hash_object;
+use crate::utils::diff::head_blob_hash;
+use crate::utils::ignore::GitIgnore;
+use crate::utils::worktree::{read_entry_content, remove_file_and_empty_parents};
+use colored::*;
+use ring::digest;
+use std::collections::BTreeSet;
+use std::io::Write;
+
+/// Removes `files` from the index and, unless `cached`, from the working
+/// tree. A directory removes every tracked path under it, and `*`/`?`
+/// patterns are matched against tracked paths. Paths with changes that are
+/// not committed yet are refused unless `force` is set.
+pub async fn run(files: Vec<String>, cached: bool, force: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let repo = Repository::discover().ok_or("Not in a repository")?;
+    let mut index = Index::load(&repo)?;
+
+    if files.is_empty() {
+        return Err("Please specify the files to remove".into());
+    }
+
+    let mut targets = BTreeSet::new();
+    for spec in &files {
+        let matched = matching_entries(&index, spec)?;
+        if matched.is_empty() {
+            return Err(format!("pathspec '{}' did not match any tracked files", spec).into());
+        }
+        targets.extend(matched);
+    }
+
+    if !force {
+        let blocked: Vec<&String> = targets.iter()
+            .filter(|path| has_uncommitted_changes(&repo, &index, path, cached))
+            .collect();
+        if !blocked.is_empty() {
+            println!("{}", "The following files have changes that are not committed:".red());
+            for path in &blocked {
+                println!("  {}", path);
+            }
+            println!("{}", "Commit them first, use --cached to keep the files, or --force to remove anyway".yellow());
+            return Err("Refusing to remove files with uncommitted changes".into());
+        }
+    }
+
+    for path in &targets {
+        index.remove_entry(path);
+        if !cached {
+            remove_file_and_empty_parents(path)?;
+        }
+        println!("{} '{}'", "rm".red(), path);
+    }
+
+    index.save(&repo)?;
+    audit_rm(targets.len(), cached, force).await?;
+
+    Ok(())
+}
+
+/// Index keys named by `spec`: the path itself, every path under it when it
+/// is a directory, or the paths a glob pattern matches.
+fn matching_entries(index: &Index, spec: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let spec = spec.strip_prefix("./").unwrap_or(spec).trim_end_matches('/');
+    let pattern = if spec.contains(['*', '?', '[']) {
+        Some(regex::Regex::new(&GitIgnore::glob_to_regex(spec))?)
+    } else {
+        None
+    };
+
+    let mut matched: Vec<String> = index.entries.keys()
+        .filter(|key| match &pattern {
+            Some(pattern) => pattern.is_match(key),
+            None => spec.is_empty() || spec == "." || key.as_str() == spec
+                || key.strip_prefix(spec).is_some_and(|rest| rest.starts_with('/')),
+        })
+        .cloned()
+        .collect();
+    matched.sort();
+    Ok(matched)
+}
+
+/// Whether removing `path` would lose work: its staged content differs from
+/// HEAD, or the working-tree file differs from what is staged. With `cached`
+/// the file stays on disk, so only both at once would lose anything.
+fn has_uncommitted_changes(repo: &Repository, index: &Index, path: &str, cached: bool) -> bool {
+    let staged_hash = &index.entries[path];
+    let staged_differs = head_blob_hash(repo, path).as_ref() != Some(staged_hash);
+
+    let worktree_differs = match (read_entry_content(path), index.metadata.get(path)) {
+        (Ok(content), Some(entry)) if !entry.checksum.is_empty() => {
+            hex::encode(digest::digest(&digest::SHA256, &content).as_ref()) != entry.checksum
+        },
+        (Ok(content), _) => hash_object(&ObjectType::Blob, &content) != *staged_hash,
+        // Already deleted from the working tree: nothing left to lose there.
+        (Err(_), _) => false,
+    };
+
+    if cached {
+        staged_differs && worktree_differs
+    } else {
+        staged_differs || worktree_differs
+    }
+}
+
+async fn audit_rm(files: usize, cached: bool, force: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let audit_file = std::path::PathBuf::from(".aigit/logs/audit.log");
+    if !audit_file.exists() {
+        return Ok(());
+    }
+
+    let timestamp = chrono::Utc::now().to_rfc3339();
+    let user = whoami::username();
+    let details = format!("files:{},cached:{},force:{}", files, cached, force);
+
+    let entry = format!("{},rm,{},{},files\n", timestamp, user, details);
+    std::fs::OpenOptions::new()
+        .append(true)
+        .open(audit_file)?
+        .write_all(entry.as_bytes())?;
+
+    Ok(())
+}
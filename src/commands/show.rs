@@ -0,0 +1,70 @@
+use crate::core::{Repository, Object, ObjectType, Commit, Tree, resolve_revision};
+use crate::commands::log::{abbrev_len, format_commit, print_full_commit};
+use crate::utils::diff::{commit_patch, print_diff};
+use std::io::Write;
+
+/// Shows one commit (HEAD by default): its header and message as in `log`,
+/// followed by the diff against its first parent. `no_patch` leaves the diff
+/// out, and `format` replaces the header with a `log --format` template.
+/// A tree is shown as a listing of its entries and a blob as its raw content.
+pub async fn run(
+    revision: Option<String>,
+    no_patch: bool,
+    format: Option<String>
+) -> Result<(), Box<dyn std::error::Error>> {
+    let repo = Repository::discover().ok_or("Not in a repository")?;
+    let config = repo.config();
+    let revision = revision.unwrap_or_else(|| "HEAD".to_string());
+
+    let hash = resolve_revision(&repo, &revision)?;
+    let commit: Commit = match Object::read_with_type(&repo, &hash)? {
+        (ObjectType::Commit, content) => serde_json::from_slice(&content)?,
+        (ObjectType::Tree, _) => {
+            print!("{}", format_tree_listing(&revision, &Tree::from_hash(&repo, &hash)?));
+            return Ok(());
+        },
+        (ObjectType::Blob, content) => {
+            std::io::stdout().write_all(&content)?;
+            return Ok(());
+        },
+        (other, _) => return Err(format!("'{}' is a {}, which cannot be shown", revision, other.as_str()).into()),
+    };
+
+    let mut hashes = vec![hash.clone()];
+    hashes.extend(commit.parents.iter().cloned());
+    let abbrev = abbrev_len(&repo, config, None, &hashes)?;
+
+    match &format {
+        Some(template) => println!("{}", format_commit(template, &hash, &commit, abbrev)),
+        None => print_full_commit(&hash, &commit, false, false, config, abbrev, ""),
+    }
+
+    if !no_patch {
+        let diff = commit_patch(&repo, &commit)?;
+        if !diff.is_empty() {
+            if format.is_some() {
+                println!();
+            }
+            print_diff(&diff);
+        }
+    }
+
+    Ok(())
+}
+
+/// `tree <name>`, a blank line, then one entry per line in name order, with
+/// subtrees marked by a trailing `/`, as `git show` lists a tree.
+pub fn format_tree_listing(name: &str, tree: &Tree) -> String {
+    let mut entries: Vec<_> = tree.entries.iter().collect();
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut listing = format!("tree {}\n\n", name);
+    for entry in entries {
+        listing.push_str(&entry.name);
+        if entry.entry_type == "tree" {
+            listing.push('/');
+        }
+        listing.push('\n');
+    }
+    listing
+}
@@ -0,0 +1,102 @@
+use crate::core::{Repository, Stash};
+use crate::core::stash::parse_stash_index;
+use crate::utils::diff::{commit_file_stats, format_stat_block};
+use colored::*;
+use std::io::Write;
+
+pub async fn push(message: Option<String>) -> Result<(), Box<dyn std::error::Error>> {
+    let repo = Repository::discover().ok_or("Not in a repository")?;
+
+    match Stash::push(&repo, message.as_deref())? {
+        Some(hash) => {
+            let stash = Stash::load(&repo)?;
+            let entry = stash.get(&repo, 0)?;
+            println!("{} {}", "Saved working directory and index state".green(), entry.commit.short_message());
+            audit_stash("push", &hash).await?;
+        },
+        None => println!("{}", "No local changes to save".bright_black()),
+    }
+
+    Ok(())
+}
+
+pub async fn list() -> Result<(), Box<dyn std::error::Error>> {
+    let repo = Repository::discover().ok_or("Not in a repository")?;
+    let stash = Stash::load(&repo)?;
+
+    for n in 0..stash.len() {
+        let entry = stash.get(&repo, n)?;
+        println!("{}: {}", format!("stash@{{{}}}", n).bright_yellow(), entry.commit.short_message());
+    }
+
+    Ok(())
+}
+
+pub async fn show(spec: Option<String>) -> Result<(), Box<dyn std::error::Error>> {
+    let repo = Repository::discover().ok_or("Not in a repository")?;
+    let stash = Stash::load(&repo)?;
+    let n = stash_index(spec.as_deref())?;
+    let entry = stash.get(&repo, n)?;
+
+    let stats = commit_file_stats(&repo, &entry.commit).await?;
+    print!("{}", format_stat_block(&stats));
+
+    Ok(())
+}
+
+pub async fn apply(spec: Option<String>) -> Result<(), Box<dyn std::error::Error>> {
+    let repo = Repository::discover().ok_or("Not in a repository")?;
+    let stash = Stash::load(&repo)?;
+    let n = stash_index(spec.as_deref())?;
+
+    let restored = stash.apply(&repo, n)?;
+    for path in &restored {
+        println!("{} {}", "Restored".green(), path);
+    }
+
+    audit_stash("apply", &stash.entries[n]).await?;
+    Ok(())
+}
+
+pub async fn drop(spec: Option<String>) -> Result<(), Box<dyn std::error::Error>> {
+    let repo = Repository::discover().ok_or("Not in a repository")?;
+    let mut stash = Stash::load(&repo)?;
+    let n = stash_index(spec.as_deref())?;
+
+    let hash = stash.remove(n)?;
+    stash.save(&repo)?;
+    println!("{} stash@{{{}}} ({})", "Dropped".green(), n, hash[..8].bright_yellow());
+
+    audit_stash("drop", &hash).await?;
+    Ok(())
+}
+
+pub async fn pop(spec: Option<String>) -> Result<(), Box<dyn std::error::Error>> {
+    apply(spec.clone()).await?;
+    drop(spec).await
+}
+
+fn stash_index(spec: Option<&str>) -> Result<usize, Box<dyn std::error::Error>> {
+    match spec {
+        Some(spec) => parse_stash_index(spec).ok_or_else(|| format!("Invalid stash reference '{}'", spec).into()),
+        None => Ok(0),
+    }
+}
+
+async fn audit_stash(action: &str, hash: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let audit_file = std::path::PathBuf::from(".aigit/logs/audit.log");
+    if !audit_file.exists() {
+        return Ok(());
+    }
+
+    let timestamp = chrono::Utc::now().to_rfc3339();
+    let user = whoami::username();
+
+    let entry = format!("{},stash_{},{},{},stash\n", timestamp, action, user, hash);
+    std::fs::OpenOptions::new()
+        .append(true)
+        .open(audit_file)?
+        .write_all(entry.as_bytes())?;
+
+    Ok(())
+}
@@ -1,130 +1,379 @@
-use crate::core::{Repository, Index, Config};
+use crate::core::{Repository, Index, IndexEntry, Config, Quarantine, ObjectType, Branch, crypto};
+use crate::core::object::hash_object;
+use crate::core::index::index_path;
+use crate::core::revlist::{count_commits, count_history, RevRange};
+use crate::utils::diff::head_files;
 use crate::utils::ignore::GitIgnore;
-use std::collections::{HashMap};
-use walkdir::WalkDir;
+use crate::utils::worktree::{kind_of, read_entry_content, walk_worktree, EntryKind};
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
 use colored::*;
 use ring::digest;
+use serde::Serialize;
 
-pub async fn run(porcelain: bool) -> Result<(), Box<dyn std::error::Error>> {
-    let repo = Repository::new(".aigit").ok_or("Not in a repository")?;
-    let index = Index::load(&repo)?;
+pub enum EntryState {
+    Unchanged,
+    Modified,
+    Inconsistent(IntegrityIssue),
+}
+
+pub struct IntegrityIssue {
+    pub path: String,
+    pub stored_hash: String,
+    pub current_hash: String,
+    pub stored_checksum: String,
+    pub current_checksum: String,
+}
+
+impl IntegrityIssue {
+    pub fn explanation(&self) -> String {
+        if self.stored_hash == self.current_hash {
+            "content matches the staged blob, but the recorded checksum is stale".to_string()
+        } else {
+            format!("content matches the recorded checksum, but not the staged blob {}", 
+                    &self.stored_hash[..8.min(self.stored_hash.len())])
+        }
+    }
+
+    pub fn suggestions(&self) -> Vec<String> {
+        vec![
+            format!("aigit add {}", self.path),
+            format!("aigit restore {}", self.path),
+        ]
+    }
+}
+
+// The blob hash and the metadata checksum should agree on whether a file changed;
+// when they don't, the index entry is out of sync rather than the file being corrupt.
+pub fn check_index_entry(
+    path: &str,
+    staged_hash: &str,
+    metadata: Option<&IndexEntry>,
+    current_content: &[u8],
+) -> EntryState {
+    let current_hash = hash_object(&ObjectType::Blob, current_content);
+    let hash_matches = current_hash == staged_hash;
+
+    let metadata = match metadata {
+        Some(metadata) if !metadata.checksum.is_empty() => metadata,
+        _ => return if hash_matches { EntryState::Unchanged } else { EntryState::Modified },
+    };
+
+    let current_checksum = hex::encode(digest::digest(&digest::SHA256, current_content).as_ref());
+    let checksum_matches = metadata.checksum == current_checksum;
+
+    match (hash_matches, checksum_matches) {
+        (true, true) => EntryState::Unchanged,
+        (false, false) => EntryState::Modified,
+        _ => EntryState::Inconsistent(IntegrityIssue {
+            path: path.to_string(),
+            stored_hash: staged_hash.to_string(),
+            current_hash,
+            stored_checksum: metadata.checksum.clone(),
+            current_checksum,
+        }),
+    }
+}
+
+/// How a staged path differs from HEAD.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StagedChange {
+    Added,
+    Modified,
+    Deleted,
+}
+
+#[derive(Default)]
+pub struct StatusReport {
+    /// Paths whose index entry differs from HEAD's tree.
+    pub staged: BTreeMap<String, StagedChange>,
+    pub modified: Vec<String>,
+    pub typechanged: Vec<String>,
+    pub deleted: Vec<String>,
+    pub untracked: Vec<String>,
+    pub conflicted: Vec<String>,
+    pub corrupted: Vec<IntegrityIssue>,
+    /// Directories holding their own repository, shown as one entry each.
+    pub nested_repos: Vec<String>,
+}
+
+impl StatusReport {
+    pub fn is_clean(&self) -> bool {
+        self.staged.is_empty()
+            && self.modified.is_empty()
+            && self.typechanged.is_empty()
+            && self.deleted.is_empty()
+            && self.untracked.is_empty()
+            && self.conflicted.is_empty()
+            && self.nested_repos.is_empty()
+    }
+}
+
+/// Setting `AIGIT_TRACE` to `1` turns on the `status --verbose` trace.
+pub const TRACE_ENV: &str = "AIGIT_TRACE";
+
+/// Phase timings and file counts gathered while collecting status, printed
+/// to stderr by `status --verbose` to show where the time goes.
+#[derive(Default)]
+pub struct StatusTrace {
+    pub index_load: Duration,
+    pub tree_walk: Duration,
+    pub ignore_matching: Duration,
+    pub hashing: Duration,
+    pub files_walked: usize,
+    pub files_hashed: usize,
+    /// Walked files that were ignored or could not be looked up.
+    pub files_skipped: usize,
+}
+
+impl StatusTrace {
+    pub fn render(&self) -> String {
+        let phases = [
+            ("index load", self.index_load),
+            ("tree walk", self.tree_walk),
+            ("ignore matching", self.ignore_matching),
+            ("hashing", self.hashing),
+        ];
+
+        let mut out = String::new();
+        for (label, elapsed) in phases {
+            out.push_str(&format!("trace: {:<16} {:>10.3}ms\n", label, elapsed.as_secs_f64() * 1000.0));
+        }
+        out.push_str(&format!("trace: files walked {}, hashed {}, skipped {}\n",
+                              self.files_walked, self.files_hashed, self.files_skipped));
+        out
+    }
+}
+
+fn trace_enabled(verbose: bool) -> bool {
+    verbose || std::env::var(TRACE_ENV).is_ok_and(|value| value == "1")
+}
+
+/// The `status --json` document. Paths are sorted and relative to the
+/// repository root.
+#[derive(Serialize)]
+pub struct JsonStatus {
+    pub branch: Option<String>,
+    pub detached: bool,
+    pub upstream: Option<String>,
+    pub ahead: usize,
+    pub behind: usize,
+    pub staged: Vec<String>,
+    pub modified: Vec<String>,
+    pub typechanged: Vec<String>,
+    pub deleted: Vec<String>,
+    pub untracked: Vec<String>,
+    pub conflicted: Vec<String>,
+    pub corrupted: Vec<String>,
+    pub nested_repos: Vec<String>,
+}
+
+impl JsonStatus {
+    pub fn from_report(repo: &Repository, report: &StatusReport) -> Self {
+        let branch = Branch::get_current_branch(repo);
+        let (upstream, ahead, behind) = branch.as_deref()
+            .and_then(|name| upstream_divergence(repo, name))
+            .map(|(upstream, ahead, behind)| (Some(upstream), ahead, behind))
+            .unwrap_or((None, 0, 0));
+
+        Self {
+            detached: branch.is_none(),
+            branch,
+            upstream,
+            ahead,
+            behind,
+            staged: sorted_paths(report.staged.keys()),
+            modified: sorted_paths(&report.modified),
+            typechanged: sorted_paths(&report.typechanged),
+            deleted: sorted_paths(&report.deleted),
+            untracked: sorted_paths(&report.untracked),
+            conflicted: sorted_paths(&report.conflicted),
+            corrupted: sorted_paths(report.corrupted.iter().map(|issue| &issue.path)),
+            nested_repos: report.nested_repos.clone(),
+        }
+    }
+}
+
+fn sorted_paths<'a>(paths: impl IntoIterator<Item = &'a String>) -> Vec<String> {
+    let mut paths: Vec<String> = paths.into_iter().cloned().collect();
+    paths.sort();
+    paths.dedup();
+    paths
+}
+
+/// Commits ahead of and behind `branch.<name>.remote`/`branch.<name>.merge`,
+/// or `None` when no upstream is configured or fetched.
+fn upstream_divergence(repo: &Repository, branch: &str) -> Option<(String, usize, usize)> {
+    let config = repo.config();
+    let remote = config.get(&format!("branch.{}.remote", branch))?;
+    let merge = config.get(&format!("branch.{}.merge", branch))?;
+    let merge = merge.strip_prefix("refs/heads/").unwrap_or(merge);
+
+    let upstream = format!("{}/{}", remote, merge);
+    let upstream_tip = std::fs::read_to_string(repo.refs_dir().join("remotes").join(remote).join(merge)).ok()?;
+    let upstream_tip = upstream_tip.trim().to_string();
+    let local_tip = Branch::get_current_commit(repo).unwrap_or_default();
+
+    let ahead = count_commits(repo, &RevRange { include: vec![local_tip.clone()], exclude: vec![upstream_tip.clone()] }).ok()?;
+    let behind = count_commits(repo, &RevRange { include: vec![upstream_tip], exclude: vec![local_tip] }).ok()?;
+    Some((upstream, ahead, behind))
+}
+
+/// Prints the working tree status. With `verbose` (or `AIGIT_TRACE=1`) the
+/// time spent in each phase and the files walked, hashed and skipped are
+/// written to stderr as well.
+pub async fn run(porcelain: bool, json: bool, verbose: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let repo = Repository::discover().ok_or("Not in a repository")?;
+    let quarantine = Quarantine::load(&repo).unwrap_or_default();
+    let config = repo.config();
+    
+    let mut trace = StatusTrace::default();
+    let report = collect_status_traced(&repo, &mut trace)?;
+    if trace_enabled(verbose) {
+        eprint!("{}", trace.render());
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&JsonStatus::from_report(&repo, &report))?);
+    } else if porcelain {
+        print_porcelain_status(&report);
+    } else {
+        print_human_status(&report, &repo, config).await;
+        print_quarantine_status(&quarantine);
+    }
+    
+    Ok(())
+}
+
+pub fn collect_status(repo: &Repository) -> Result<StatusReport, Box<dyn std::error::Error>> {
+    collect_status_traced(repo, &mut StatusTrace::default())
+}
+
+/// `collect_status`, recording phase timings and file counts into `trace`.
+pub fn collect_status_traced(repo: &Repository, trace: &mut StatusTrace) -> Result<StatusReport, Box<dyn std::error::Error>> {
+    let started = Instant::now();
+    let index = Index::load(repo)?;
+    trace.index_load = started.elapsed();
     let ignore = GitIgnore::new(&repo.path);
-    let config = Config::load_repo(&repo).unwrap_or_else(|_| Config::load_global().unwrap_or_default());
     
-    let mut staged: HashMap<String, String> = index.entries.clone();
-    let mut modified = Vec::new();
-    let mut deleted = Vec::new();
-    let mut untracked = Vec::new();
-    let mut conflicted = Vec::new();
-    let mut corrupted = Vec::new();
-
-    for file in index.get_conflicted_files() {
-        conflicted.push(file);
+    let mut report = StatusReport {
+        conflicted: index.get_conflicted_files(),
+        ..Default::default()
+    };
+
+    let head = head_files(repo);
+    for (path, hash) in &index.entries {
+        match head.get(path) {
+            None => { report.staged.insert(path.clone(), StagedChange::Added); },
+            Some(head_hash) if head_hash != hash => { report.staged.insert(path.clone(), StagedChange::Modified); },
+            _ => {},
+        }
+
+        let staged_kind = index.metadata.get(path)
+            .map(|m| EntryKind::from_mode(&m.mode))
+            .unwrap_or(EntryKind::File);
+        match kind_of(path) {
+            Some(kind) if kind != staged_kind => report.typechanged.push(path.clone()),
+            Some(_) => {},
+            None => report.deleted.push(path.clone()),
+        }
+    }
+    for path in head.keys().filter(|path| !index.entries.contains_key(*path)) {
+        report.staged.insert(path.clone(), StagedChange::Deleted);
     }
 
-    for entry in WalkDir::new(".")
-        .into_iter()
-        .filter_map(|e| e.ok())
-        .filter(|e| e.file_type().is_file())
-        .filter(|e| !e.path().starts_with(".aigit"))
-    {
-        let path = entry.path();
-        if ignore.is_ignored(path) {
+    let started = Instant::now();
+    let walk = walk_worktree(".");
+    trace.tree_walk = started.elapsed();
+    trace.files_walked = walk.files.len();
+    report.nested_repos = walk.nested_repos;
+
+    for path in &walk.files {
+        let path = path.as_path();
+        let started = Instant::now();
+        let ignored = ignore.is_ignored(path);
+        trace.ignore_matching += started.elapsed();
+        if ignored {
+            trace.files_skipped += 1;
             continue;
         }
         
-        let path_str = path.to_str().unwrap();
+        let path_str = match path.to_str() {
+            Some(path_str) => index_path(path_str),
+            None => {
+                eprintln!("{} {} (path is not valid UTF-8)", "Skipping".yellow(), path.to_string_lossy());
+                trace.files_skipped += 1;
+                continue;
+            }
+        };
+        let path_str = path_str.as_str();
         
         if let Some(staged_hash) = index.entries.get(path_str) {
-            match std::fs::read(path) {
-                Ok(current_content) => {
-                    let current_hash = crate::core::object::hash_content(&current_content);
-                    
-                    if &current_hash != staged_hash {
-                        if let Some(metadata) = index.metadata.get(path_str) {
-                            let current_checksum = hex::encode(digest::digest(&digest::SHA256, &current_content).as_ref());
-                            if metadata.checksum != current_checksum {
-                                modified.push(path_str.to_string());
-                            } else {
-                                corrupted.push(path_str.to_string());
-                            }
-                        } else {
-                            modified.push(path_str.to_string());
-                        }
-                    }
-                },
-                Err(_) => deleted.push(path_str.to_string()),
+            if report.typechanged.iter().any(|p| p == path_str) {
+                continue;
+            }
+            let started = Instant::now();
+            let state = read_entry_content(path).map(|current_content| {
+                trace.files_hashed += 1;
+                check_index_entry(path_str, staged_hash, index.metadata.get(path_str), &current_content)
+            });
+            trace.hashing += started.elapsed();
+            match state {
+                Ok(EntryState::Unchanged) => {},
+                Ok(EntryState::Modified) => report.modified.push(path_str.to_string()),
+                Ok(EntryState::Inconsistent(issue)) => report.corrupted.push(issue),
+                Err(_) => {},
             }
-            staged.remove(path_str);
         } else {
-            untracked.push(path_str.to_string());
+            report.untracked.push(path_str.to_string());
         }
     }
 
-    for file in staged.keys() {
-        if !std::path::Path::new(file).exists() {
-            deleted.push(file.clone());
-        }
-    }
-
-    if porcelain {
-        print_porcelain_status(&staged, &modified, &deleted, &untracked, &conflicted, &corrupted);
-    } else {
-        print_human_status(&staged, &modified, &deleted, &untracked, &conflicted, &corrupted, &repo, &config).await;
-    }
-    
-    Ok(())
+    report.typechanged.sort();
+    report.deleted.sort();
+    Ok(report)
 }
 
-fn print_porcelain_status(
-    staged: &HashMap<String, String>,
-    modified: &[String],
-    deleted: &[String],
-    untracked: &[String],
-    conflicted: &[String],
-    corrupted: &[String],
-) {
-    for file in conflicted {
+fn print_porcelain_status(report: &StatusReport) {
+    for file in &report.conflicted {
         println!("UU {}", file);
     }
     
-    for file in corrupted {
-        println!("XX {}", file);
+    for issue in &report.corrupted {
+        println!("XX {}", issue.path);
     }
     
-    for file in staged.keys() {
-        if deleted.contains(file) {
-            println!("D  {}", file);
-        } else {
-            println!("A  {}", file);
-        }
+    // One `XY path` line per path: X is the index against HEAD, Y the
+    // working tree against the index.
+    let mut codes: BTreeMap<&String, (char, char)> = BTreeMap::new();
+    for (file, change) in &report.staged {
+        codes.entry(file).or_insert((' ', ' ')).0 = match change {
+            StagedChange::Added => 'A',
+            StagedChange::Modified => 'M',
+            StagedChange::Deleted => 'D',
+        };
     }
-
-    for file in modified {
-        println!(" M {}", file);
+    for (files, code) in [(&report.modified, 'M'), (&report.typechanged, 'T'), (&report.deleted, 'D')] {
+        for file in files {
+            codes.entry(file).or_insert((' ', ' ')).1 = code;
+        }
     }
-
-    for file in deleted {
-        if !staged.contains_key(file) {
-            println!(" D {}", file);
+    for (file, (staged, worktree)) in codes {
+        if !report.conflicted.contains(file) {
+            println!("{}{} {}", staged, worktree, file);
         }
     }
 
-    for file in untracked {
+    for file in &report.untracked {
         println!("?? {}", file);
     }
+
+    for repo in &report.nested_repos {
+        println!("?? {}", repo);
+    }
 }
 
-async fn print_human_status(
-    staged: &HashMap<String, String>,
-    modified: &[String],
-    deleted: &[String],
-    untracked: &[String],
-    conflicted: &[String],
-    corrupted: &[String],
-    repo: &Repository,
-    config: &Config,
-) {
+async fn print_human_status(report: &StatusReport, repo: &Repository, config: &Config) {
     let current_branch = get_current_branch(repo);
     let commit_count = get_commit_count(repo);
     let repo_id = get_repo_id(repo);
@@ -132,67 +381,80 @@ async fn print_human_status(
     println!("On branch {} {}", current_branch.bright_cyan(), format!("({})", repo_id).bright_black());
     println!("Total commits: {}", commit_count.to_string().bright_yellow());
     
-    if config.get("security.auditLog").map(|v| v == "true").unwrap_or(false) {
+    if config.get_bool("security.auditLog").unwrap_or(false) {
         println!("{} Security audit logging enabled", "🔒".green());
     }
 
-    if !corrupted.is_empty() {
-        println!("\n{}", "Files with integrity issues:".red().bold());
-        for file in corrupted {
-            println!("  {} {}", "corrupted:".red(), file);
+    if !report.corrupted.is_empty() {
+        println!("\n{}", "Staged files with inconsistent index entries:".yellow().bold());
+        for issue in &report.corrupted {
+            println!("  {} {}", "checksum mismatch:".yellow(), issue.path);
+            println!("    {}", issue.explanation().bright_black());
+            println!("    {} {}", "stored checksum: ".bright_black(), issue.stored_checksum);
+            println!("    {} {}", "current checksum:".bright_black(), issue.current_checksum);
+            let suggestions = issue.suggestions();
+            println!("    {} {}", "to re-stage:".bright_black(), suggestions[0].cyan());
+            println!("    {} {}", "to revert:  ".bright_black(), suggestions[1].cyan());
         }
-        println!("{}", "Run 'aigit fsck' to verify repository integrity".red());
     }
 
-    if !conflicted.is_empty() {
+    if !report.conflicted.is_empty() {
         println!("\n{}", "You have unmerged paths.".red().bold());
         println!("{}", "Unmerged paths:".red());
-        for file in conflicted {
+        for file in &report.conflicted {
             println!("  {} {}", "both modified:".red(), file);
         }
         println!("{}", "Use 'aigit add/rm <file>...' to mark resolution".red());
     }
 
-    if !staged.is_empty() {
+    if !report.staged.is_empty() {
         println!("\n{}", "Changes to be committed:".green());
-        for file in staged.keys() {
-            if deleted.contains(file) {
-                println!("  {} {}", "deleted:".red(), file);
-            } else {
-                println!("  {} {}", "new file:".green(), file);
+        for (file, change) in &report.staged {
+            match change {
+                StagedChange::Added => println!("  {} {}", "new file:".green(), file),
+                StagedChange::Modified => println!("  {} {}", "modified:".green(), file),
+                StagedChange::Deleted => println!("  {} {}", "deleted:".red(), file),
             }
         }
     }
 
-    if !modified.is_empty() || (!deleted.is_empty() && staged.is_empty()) {
+    if !report.modified.is_empty() || !report.typechanged.is_empty() || !report.deleted.is_empty() {
         println!("\n{}", "Changes not staged for commit:".yellow());
-        for file in modified {
+        for file in &report.modified {
             println!("  {} {}", "modified:".yellow(), file);
         }
-        for file in deleted {
-            if !staged.contains_key(file) {
-                println!("  {} {}", "deleted:".red(), file);
-            }
+        for file in &report.typechanged {
+            println!("  {} {}", "typechange:".yellow(), file);
+        }
+        for file in &report.deleted {
+            println!("  {} {}", "deleted:".red(), file);
         }
         println!("{}", "Use 'aigit add <file>...' to update what will be committed".yellow());
     }
 
-    if !untracked.is_empty() {
+    if !report.untracked.is_empty() {
         println!("\n{}", "Untracked files:".bright_black());
         let mut shown = 0;
-        for file in untracked {
+        for file in &report.untracked {
             if shown < 20 {
                 println!("  {}", file.bright_black());
                 shown += 1;
             } else {
-                println!("  {} ({} more files)", "...".bright_black(), untracked.len() - shown);
+                println!("  {} ({} more files)", "...".bright_black(), report.untracked.len() - shown);
                 break;
             }
         }
         println!("\n{}", "Use 'aigit add <file>...' to include in what will be committed".bright_black());
     }
 
-    if staged.is_empty() && modified.is_empty() && deleted.is_empty() && untracked.is_empty() && conflicted.is_empty() {
+    if !report.nested_repos.is_empty() {
+        println!("\n{}", "Nested repositories (not tracked):".bright_black());
+        for repo in &report.nested_repos {
+            println!("  {} {}", "nested repo:".bright_black(), repo.bright_black());
+        }
+    }
+
+    if report.is_clean() {
         println!("\n{}", "Working tree clean".green());
         
         if let Some(last_commit) = get_last_commit_info(repo) {
@@ -213,17 +475,12 @@ fn get_current_branch(repo: &Repository) -> String {
         .unwrap_or_else(|| "detached HEAD".to_string())
 }
 
-fn get_commit_count(repo: &Repository) -> usize {
-    let objects_dir = repo.objects_dir();
-    if !objects_dir.exists() {
-        return 0;
-    }
-    
-    walkdir::WalkDir::new(&objects_dir)
-        .into_iter()
-        .filter_map(|e| e.ok())
-        .filter(|e| e.file_type().is_file())
-        .count()
+/// The number of commits reachable from HEAD, or 0 on an unborn branch or
+/// when the history cannot be read.
+pub fn get_commit_count(repo: &Repository) -> usize {
+    Branch::get_current_commit(repo)
+        .and_then(|head| count_history(repo, &head).ok())
+        .unwrap_or(0)
 }
 
 fn get_repo_id(repo: &Repository) -> String {
@@ -251,6 +508,21 @@ fn get_last_commit_info(repo: &Repository) -> Option<String> {
     Some(format!("{} ({})", &last_hash[..8], "timestamp unavailable"))
 }
 
+fn print_quarantine_status(quarantine: &Quarantine) {
+    if quarantine.is_empty() {
+        return;
+    }
+
+    println!("\n{}", "Quarantined files:".red().bold());
+    for entry in &quarantine.entries {
+        println!("  {} {} {}", 
+                entry.path.red(), 
+                entry.timestamp.format("%Y-%m-%d %H:%M:%S").to_string().bright_black(),
+                format!("(matched {})", entry.pattern).bright_black());
+    }
+    println!("{}", "Remove the secrets and re-add, then run 'aigit quarantine clear'".red());
+}
+
 async fn print_security_status(repo: &Repository) {
     let security_file = repo.git_dir.join("security/config.json");
     if security_file.exists() {
@@ -259,10 +531,10 @@ async fn print_security_status(repo: &Repository) {
                 if config.get("audit_log").and_then(|v| v.as_bool()).unwrap_or(false) {
                     println!("\n{} Audit logging active", "🔍".cyan());
                 }
-                if config.get("encrypt_objects").and_then(|v| v.as_bool()).unwrap_or(false) {
-                    println!("{} Object encryption enabled", "🔐".green());
-                }
             }
         }
     }
+    if crypto::is_enabled(repo) {
+        println!("{} Object encryption enabled", "🔐".green());
+    }
 }
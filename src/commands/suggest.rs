@@ -1,20 +1,47 @@
 use crate::core::Repository;
-use crate::ai::gemini::GeminiClient;
-use crate::utils::analyzer::analyze_codebase;
+use crate::ai::{provider_with_progress, require_ai};
+use crate::utils::analyzer::analyze_codebase_with;
+use crate::utils::diff::{diff_text, print_diff};
 use colored::*;
 use indicatif::{ProgressBar, ProgressStyle};
+use std::io::{BufRead, Write};
+
+/// Options shared by the `suggest` subcommands.
+#[derive(Debug, Clone, Default)]
+pub struct SuggestOptions {
+    /// Recompute the codebase analysis instead of reusing the cached one.
+    pub refresh: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RefactorDecision {
+    Rejected,
+    Applied,
+    AppliedAndStaged,
+}
 
 pub async fn commit() -> Result<(), Box<dyn std::error::Error>> {
-    let repo = Repository::new(".aigit").ok_or("Not in a repository")?;
+    commit_with(&SuggestOptions::default()).await
+}
+
+pub async fn commit_with(options: &SuggestOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let repo = Repository::discover().ok_or("Not in a repository")?;
+    require_ai(repo.config())?;
     let pb = ProgressBar::new_spinner();
     pb.set_style(ProgressStyle::default_spinner().template("{spinner} {msg}").unwrap());
     pb.set_message("Analyzing project context...");
     pb.enable_steady_tick(std::time::Duration::from_millis(100));
 
-    let context = analyze_codebase(&repo).await;
-    let gemini = GeminiClient::new();
+    let context = analyze_codebase_with(&repo, options.refresh).await;
+    let provider = match provider_with_progress(repo.config(), &pb) {
+        Ok(provider) => provider,
+        Err(e) => {
+            pb.finish_and_clear();
+            return Err(e.to_string().into());
+        }
+    };
 
-    match gemini.suggest_next_commit(&context).await {
+    match provider.suggest_next_commit(&context).await {
         Ok(suggestion) => {
             pb.finish_and_clear();
             println!("{}", "AI Suggests Next Steps:".cyan().bold());
@@ -31,23 +58,34 @@ pub async fn commit() -> Result<(), Box<dyn std::error::Error>> {
 }
 
 pub async fn branch() -> Result<(), Box<dyn std::error::Error>> {
-    let repo = Repository::new(".aigit").ok_or("Not in a repository")?;
+    branch_with(&SuggestOptions::default()).await
+}
+
+pub async fn branch_with(options: &SuggestOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let repo = Repository::discover().ok_or("Not in a repository")?;
+    require_ai(repo.config())?;
     let pb = ProgressBar::new_spinner();
     pb.set_style(ProgressStyle::default_spinner().template("{spinner} {msg}").unwrap());
     pb.set_message("Analyzing project for branch opportunities...");
     pb.enable_steady_tick(std::time::Duration::from_millis(100));
 
-    let context = analyze_codebase(&repo).await;
-    let gemini = GeminiClient::new();
+    let context = analyze_codebase_with(&repo, options.refresh).await;
+    let provider = match provider_with_progress(repo.config(), &pb) {
+        Ok(provider) => provider,
+        Err(e) => {
+            pb.finish_and_clear();
+            return Err(e.to_string().into());
+        }
+    };
 
-    match gemini.suggest_branch_name(&context).await {
+    match provider.suggest_branch_name(&context).await {
         Ok(suggestions) => {
             pb.finish_and_clear();
             println!("{}", "AI Suggested Branch Names:".cyan().bold());
             
             for (i, name) in suggestions.iter().enumerate() {
-                let category = categorize_branch(&name);
-                let icon = get_branch_icon(&category);
+                let category = categorize_branch(name);
+                let icon = get_branch_icon(category);
                 println!("{}. {} {} {}", 
                         i + 1, 
                         icon,
@@ -66,17 +104,34 @@ pub async fn branch() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-pub async fn refactor() -> Result<(), Box<dyn std::error::Error>> {
-    let repo = Repository::new(".aigit").ok_or("Not in a repository")?;
+pub async fn refactor(target: Option<String>, diff: bool) -> Result<(), Box<dyn std::error::Error>> {
+    refactor_with(target, diff, &SuggestOptions::default()).await
+}
+
+pub async fn refactor_with(target: Option<String>, diff: bool, options: &SuggestOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let repo = Repository::discover().ok_or("Not in a repository")?;
+    require_ai(repo.config())?;
+
+    if diff {
+        let path = target.ok_or("--diff requires --for <file>")?;
+        return refactor_file(&repo, &path).await;
+    }
+
     let pb = ProgressBar::new_spinner();
     pb.set_style(ProgressStyle::default_spinner().template("{spinner} {msg}").unwrap());
     pb.set_message("Scanning codebase for refactoring opportunities...");
     pb.enable_steady_tick(std::time::Duration::from_millis(100));
 
-    let context = analyze_codebase(&repo).await;
-    let gemini = GeminiClient::new();
+    let context = analyze_codebase_with(&repo, options.refresh).await;
+    let provider = match provider_with_progress(repo.config(), &pb) {
+        Ok(provider) => provider,
+        Err(e) => {
+            pb.finish_and_clear();
+            return Err(e.to_string().into());
+        }
+    };
 
-    match gemini.suggest_refactoring(&context).await {
+    match provider.suggest_refactoring(&context).await {
         Ok(suggestions) => {
             pb.finish_and_clear();
             println!("{}", "Refactoring Opportunities:".cyan().bold());
@@ -92,17 +147,120 @@ pub async fn refactor() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+async fn refactor_file(repo: &Repository, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let current = std::fs::read_to_string(path)
+        .map_err(|e| format!("Cannot read {}: {}", path, e))?;
+
+    let pb = ProgressBar::new_spinner();
+    pb.set_style(ProgressStyle::default_spinner().template("{spinner} {msg}").unwrap());
+    pb.set_message(format!("Asking AI to refactor {}...", path));
+    pb.enable_steady_tick(std::time::Duration::from_millis(100));
+
+    let provider = match provider_with_progress(repo.config(), &pb) {
+        Ok(provider) => provider,
+        Err(e) => {
+            pb.finish_and_clear();
+            return Err(e.to_string().into());
+        }
+    };
+    let response = match provider.propose_refactor(path, &current).await {
+        Ok(response) => {
+            pb.finish_and_clear();
+            response
+        },
+        Err(e) => {
+            pb.finish_and_clear();
+            return Err(format!("Failed to generate refactor: {}", e).into());
+        }
+    };
+
+    let stdin = std::io::stdin();
+    let decision = review_refactor_proposal(path, &current, &response, &mut stdin.lock())?;
+
+    if decision == RefactorDecision::AppliedAndStaged {
+        crate::commands::add::run(vec![path.to_string()], false).await?;
+    }
+
+    Ok(())
+}
+
+pub fn review_refactor_proposal(
+    path: &str,
+    current: &str,
+    response: &str,
+    input: &mut dyn BufRead
+) -> Result<RefactorDecision, Box<dyn std::error::Error>> {
+    let proposed = extract_code_block(response);
+    let patch = diff_text(current, &proposed);
+
+    if patch.is_empty() {
+        println!("{} No changes proposed for {}", "✓".green(), path.bright_white());
+        return Ok(RefactorDecision::Rejected);
+    }
+
+    println!("{} {}", "Proposed refactor for".cyan().bold(), path.bright_white());
+    println!("{}", format!("--- a/{}\n+++ b/{}", path, path).bold());
+    print_diff(&patch);
+
+    print!("{} ", "Apply this refactor [y,n,s(tage)]?".cyan());
+    std::io::stdout().flush()?;
+
+    let mut reply = String::new();
+    input.read_line(&mut reply)?;
+
+    let decision = match reply.trim().to_lowercase().as_str() {
+        "y" | "yes" => RefactorDecision::Applied,
+        "s" | "stage" => RefactorDecision::AppliedAndStaged,
+        _ => RefactorDecision::Rejected,
+    };
+
+    if decision == RefactorDecision::Rejected {
+        println!("{}", "Refactor discarded".yellow());
+    } else {
+        std::fs::write(path, &proposed)?;
+        println!("{} {}", "Applied refactor to".green(), path.bright_white());
+    }
+
+    Ok(decision)
+}
+
+fn extract_code_block(response: &str) -> String {
+    let mut lines = response.lines().skip_while(|line| !line.trim_start().starts_with("```"));
+
+    if lines.next().is_none() {
+        return ensure_trailing_newline(response.trim());
+    }
+
+    let body: Vec<&str> = lines.take_while(|line| !line.trim_start().starts_with("```")).collect();
+    ensure_trailing_newline(&body.join("\n"))
+}
+
+fn ensure_trailing_newline(content: &str) -> String {
+    format!("{}\n", content.trim_end_matches('\n'))
+}
+
 pub async fn tests() -> Result<(), Box<dyn std::error::Error>> {
-    let repo = Repository::new(".aigit").ok_or("Not in a repository")?;
+    tests_with(&SuggestOptions::default()).await
+}
+
+pub async fn tests_with(options: &SuggestOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let repo = Repository::discover().ok_or("Not in a repository")?;
+    require_ai(repo.config())?;
     let pb = ProgressBar::new_spinner();
     pb.set_style(ProgressStyle::default_spinner().template("{spinner} {msg}").unwrap());
     pb.set_message("Analyzing test coverage and opportunities...");
     pb.enable_steady_tick(std::time::Duration::from_millis(100));
 
-    let context = analyze_codebase(&repo).await;
-    let gemini = GeminiClient::new();
+    let context = analyze_codebase_with(&repo, options.refresh).await;
+    let provider = match provider_with_progress(repo.config(), &pb) {
+        Ok(provider) => provider,
+        Err(e) => {
+            pb.finish_and_clear();
+            return Err(e.to_string().into());
+        }
+    };
 
-    match gemini.suggest_tests(&context).await {
+    match provider.suggest_tests(&context).await {
         Ok(suggestions) => {
             pb.finish_and_clear();
             println!("{}", "Testing Suggestions:".cyan().bold());
@@ -119,14 +277,25 @@ pub async fn tests() -> Result<(), Box<dyn std::error::Error>> {
 }
 
 pub async fn cleanup() -> Result<(), Box<dyn std::error::Error>> {
-    let repo = Repository::new(".aigit").ok_or("Not in a repository")?;
+    cleanup_with(&SuggestOptions::default()).await
+}
+
+pub async fn cleanup_with(options: &SuggestOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let repo = Repository::discover().ok_or("Not in a repository")?;
+    require_ai(repo.config())?;
     let pb = ProgressBar::new_spinner();
     pb.set_style(ProgressStyle::default_spinner().template("{spinner} {msg}").unwrap());
     pb.set_message("Identifying cleanup opportunities...");
     pb.enable_steady_tick(std::time::Duration::from_millis(100));
 
-    let context = analyze_codebase(&repo).await;
-    let gemini = GeminiClient::new();
+    let context = analyze_codebase_with(&repo, options.refresh).await;
+    let provider = match provider_with_progress(repo.config(), &pb) {
+        Ok(provider) => provider,
+        Err(e) => {
+            pb.finish_and_clear();
+            return Err(e.to_string().into());
+        }
+    };
     
     let cleanup_prompt = format!(
         "Analyze this codebase and suggest cleanup tasks like removing dead code, \
@@ -135,7 +304,7 @@ pub async fn cleanup() -> Result<(), Box<dyn std::error::Error>> {
         context
     );
 
-    match gemini.generate_text(&cleanup_prompt).await {
+    match provider.generate_text(&cleanup_prompt).await {
         Ok(suggestions) => {
             pb.finish_and_clear();
             println!("{}", "Cleanup Suggestions:".cyan().bold());
@@ -0,0 +1,132 @@
+use crate::core::{Repository, Branch, Object, Commit, resolve_revision};
+use crate::commands::branch::validate_ref_name;
+use crate::commands::checkout::{switch_to_branch, update_worktree};
+use colored::*;
+use std::io::Write;
+use std::path::Path;
+
+/// How `switch` should treat its target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SwitchMode {
+    /// Switch to an existing branch.
+    #[default]
+    Branch,
+    /// `-c`: create the branch, refusing if it exists.
+    Create,
+    /// `-C`: create the branch, or reset it if it exists.
+    ForceCreate,
+    /// `--detach`: check out a commit without a branch.
+    Detach,
+}
+
+/// `switch` only ever moves HEAD between branches (or detaches it on request);
+/// unlike `checkout` it never falls back to restoring files or to treating a
+/// hash as a commit, so a typo cannot silently do something else.
+pub async fn run(target: String, start_point: Option<String>, mode: SwitchMode) -> Result<(), Box<dyn std::error::Error>> {
+    let repo = Repository::discover().ok_or("Not in a repository")?;
+
+    match mode {
+        SwitchMode::Branch => {
+            if start_point.is_some() {
+                return Err("A start point can only be given with -c, -C or --detach".into());
+            }
+            if !repo.heads_dir().join(&target).exists() {
+                return Err(not_a_branch(&repo, &target).into());
+            }
+            switch_to_branch(&repo, &target, false)?;
+        },
+        SwitchMode::Create | SwitchMode::ForceCreate => {
+            create_and_switch(&repo, &target, start_point.as_deref(), mode == SwitchMode::ForceCreate)?;
+        },
+        SwitchMode::Detach => {
+            if start_point.is_some() {
+                return Err("--detach takes a single commit".into());
+            }
+            let commit = resolve_revision(&repo, &target)?;
+            update_worktree(&repo, &commit, false)?;
+            Branch::checkout(&repo, &commit)?;
+            let subject = Object::read(&repo, &commit).ok()
+                .and_then(|content| serde_json::from_slice::<Commit>(&content).ok())
+                .map(|commit| commit.short_message())
+                .unwrap_or_default();
+            println!("{} HEAD is now at {} {}", "✓".green(), commit[..8].bright_yellow(), subject);
+        },
+    }
+
+    audit_switch(&target).await?;
+    Ok(())
+}
+
+/// `-c`/`-C`: creates `name` at `start_point` (HEAD by default), updates the
+/// working tree and switches to it. With `reset`, an existing branch is moved
+/// instead of refused.
+fn create_and_switch(
+    repo: &Repository,
+    name: &str,
+    start_point: Option<&str>,
+    reset: bool
+) -> Result<(), Box<dyn std::error::Error>> {
+    validate_ref_name(name, "Branch")?;
+    let branch_path = repo.heads_dir().join(name);
+    let exists = branch_path.exists();
+    if exists && !reset {
+        return Err(format!("A branch named '{}' already exists (use -C to reset it)", name).into());
+    }
+
+    if Branch::get_current_commit(repo).is_none() && start_point.is_none() && !exists {
+        // Nothing to branch from yet: move HEAD and let the first commit create the ref.
+        Branch::start_unborn(repo, name)?;
+        println!("{} Switched to a new branch '{}' (no commits yet)", "✓".green(), name);
+        return Ok(());
+    }
+
+    let commit = match start_point {
+        Some(spec) => resolve_revision(repo, spec)?,
+        None => Branch::get_current_commit(repo)
+            .ok_or_else(|| format!("Cannot create branch '{}': HEAD has no commits yet", name))?,
+    };
+    update_worktree(repo, &commit, false)?;
+    if exists {
+        std::fs::write(&branch_path, &commit)?;
+    } else {
+        Branch::create(repo, name, Some(&commit))?;
+    }
+    Branch::checkout(repo, name)?;
+
+    if exists {
+        println!("{} Reset branch '{}' and switched to it", "✓".green(), name);
+    } else {
+        println!("{} Switched to a new branch '{}'", "✓".green(), name);
+    }
+    Ok(())
+}
+
+/// Explains why `target` cannot be switched to, pointing at the command
+/// that does what was probably meant.
+fn not_a_branch(repo: &Repository, target: &str) -> String {
+    if Path::new(target).exists() {
+        format!("'{}' is a path, not a branch. Use 'aigit restore {}' to restore files", target, target)
+    } else if resolve_revision(repo, target).is_ok() {
+        format!("'{}' is not a branch. Use 'aigit switch --detach {}' to check out a commit", target, target)
+    } else {
+        format!("Branch '{}' does not exist. Use 'aigit switch -c {}' to create it", target, target)
+    }
+}
+
+async fn audit_switch(target: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let audit_file = std::path::PathBuf::from(".aigit/logs/audit.log");
+    if !audit_file.exists() {
+        return Ok(());
+    }
+
+    let timestamp = chrono::Utc::now().to_rfc3339();
+    let user = whoami::username();
+
+    let entry = format!("{},switch,{},{},branch\n", timestamp, user, target);
+    std::fs::OpenOptions::new()
+        .append(true)
+        .open(audit_file)?
+        .write_all(entry.as_bytes())?;
+
+    Ok(())
+}
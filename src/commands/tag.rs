@@ -0,0 +1,107 @@
+use crate::core::{Repository, Branch, Refs, Object, Commit, Tag};
+use crate::core::tag::peel;
+use crate::commands::branch::validate_ref_name;
+use colored::*;
+use std::io::Write;
+
+/// Creates a tag `name` at HEAD, deletes the tag named by `delete`, or lists
+/// every tag when neither is given. With `annotate` or a `message` the tag is
+/// an annotated tag object carrying the tagger and message; otherwise the ref
+/// points straight at the commit.
+pub async fn run(
+    name: Option<String>,
+    delete: Option<String>,
+    annotate: bool,
+    message: Option<String>
+) -> Result<(), Box<dyn std::error::Error>> {
+    let repo = Repository::discover().ok_or("Not in a repository")?;
+    let mut refs = Refs::load(&repo)?;
+
+    if let Some(name) = delete {
+        let hash = refs.get_tag(&name).cloned()
+            .ok_or_else(|| format!("Tag '{}' not found", name))?;
+        refs.delete_tag(&repo, &name)?;
+        println!("{} Deleted tag '{}' (was {})", "✓".green(), name, &hash[..8]);
+        audit_tag("tag-delete", &name, &hash).await?;
+        return Ok(());
+    }
+
+    match name {
+        Some(name) => {
+            validate_ref_name(&name, "Tag")?;
+            // Tags are stored flat under refs/tags, so a '/' would create a
+            // tag that Refs::load never reads back.
+            if name.contains('/') {
+                return Err("Tag name contains invalid characters".into());
+            }
+            if refs.get_tag(&name).is_some() {
+                return Err(format!("Tag '{}' already exists", name).into());
+            }
+
+            let hash = Branch::get_current_commit(&repo)
+                .ok_or("Cannot create a tag: HEAD does not point to a commit")?;
+            let target = if annotate || message.is_some() {
+                let message = message.filter(|m| !m.trim().is_empty())
+                    .ok_or("Annotated tags need a message (-m <msg>)")?;
+                let config = repo.config();
+                Tag::new(hash.clone(), name.clone(), config.get_user_name(), config.get_user_email(), message)
+                    .create(&repo)?
+            } else {
+                hash.clone()
+            };
+
+            std::fs::create_dir_all(repo.tags_dir())?;
+            refs.create_tag(&repo, &name, &target)?;
+            println!("{} Created tag '{}' at {}", "✓".green(), name.bright_cyan(), hash[..8].bright_yellow());
+            audit_tag("tag-create", &name, &target).await?;
+        },
+        None => list_tags(&repo, &refs),
+    }
+
+    Ok(())
+}
+
+fn list_tags(repo: &Repository, refs: &Refs) {
+    let mut tags: Vec<(&String, &String)> = refs.tags.iter().collect();
+    tags.sort();
+
+    if tags.is_empty() {
+        println!("{}", "No tags found".yellow());
+        return;
+    }
+
+    println!("{}", "Tags:".cyan().bold());
+    for (name, hash) in tags {
+        // Annotated tags show their own message against the commit they tag.
+        let commit_hash = peel(repo, hash);
+        let summary = match Tag::read(repo, hash) {
+            Ok(Some(tag)) => Some(tag.short_message()),
+            _ => Object::read(repo, &commit_hash).ok()
+                .and_then(|data| serde_json::from_slice::<Commit>(&data).ok())
+                .map(|commit| commit.short_message()),
+        }.unwrap_or_else(|| "invalid commit".to_string());
+        println!("  {} {} {}",
+                name.white(),
+                commit_hash.get(..8).unwrap_or(&commit_hash).bright_yellow(),
+                summary.bright_black());
+    }
+}
+
+async fn audit_tag(operation: &str, name: &str, hash: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let audit_file = std::path::PathBuf::from(".aigit/logs/audit.log");
+    if !audit_file.exists() {
+        return Ok(());
+    }
+
+    let timestamp = chrono::Utc::now().to_rfc3339();
+    let user = whoami::username();
+    let details = format!("{}:{}", name, hash);
+
+    let entry = format!("{},{},{},{},tag\n", timestamp, operation, user, details);
+    std::fs::OpenOptions::new()
+        .append(true)
+        .open(audit_file)?
+        .write_all(entry.as_bytes())?;
+
+    Ok(())
+}
@@ -0,0 +1,33 @@
+use crate::core::{Repository, Object, Commit, resolve_revision};
+use crate::core::signing;
+use colored::*;
+
+/// Checks the signature of each commit in `revisions` against the public key
+/// recorded in the commit. Fails if any commit is unsigned or does not verify.
+pub async fn run(revisions: Vec<String>) -> Result<(), Box<dyn std::error::Error>> {
+    let repo = Repository::discover().ok_or("Not in a repository")?;
+    let revisions = if revisions.is_empty() { vec!["HEAD".to_string()] } else { revisions };
+
+    let mut failures = 0;
+    for revision in &revisions {
+        let hash = resolve_revision(&repo, revision)?;
+        let commit: Commit = serde_json::from_slice(&Object::read(&repo, &hash)?)?;
+
+        match signing::verify_commit(&repo, &commit) {
+            Ok(key_id) => println!("{} {} Good signature from key '{}' ({})",
+                                   "✓".green(),
+                                   hash[..8].bright_yellow(),
+                                   key_id.bright_cyan(),
+                                   commit.author.format_signature()),
+            Err(e) => {
+                failures += 1;
+                println!("{} {} {}", "✗".red(), hash[..8].bright_yellow(), e.to_string().red());
+            },
+        }
+    }
+
+    if failures > 0 {
+        return Err(format!("{} commit(s) failed signature verification", failures).into());
+    }
+    Ok(())
+}
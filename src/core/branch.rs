@@ -1,4 +1,6 @@
-use crate::core::Repository;
+use crate::core::{Repository, Object, Commit};
+use crate::core::reflog;
+use crate::core::revision::resolve_revision;
 use std::fs;
 
 pub struct Branch {
@@ -11,7 +13,10 @@ impl Branch {
         Self { name, hash }
     }
 
-    pub fn create(repo: &Repository, name: &str, start_point: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    /// Creates `name` at `start_point` (any revision), or at HEAD. Refuses to
+    /// write a ref that does not name a readable commit, so a branch never
+    /// exists without a tip; use `start_unborn` for a branch with no commits.
+    pub fn create(repo: &Repository, name: &str, start_point: Option<&str>) -> Result<String, Box<dyn std::error::Error>> {
         let branch_path = repo.heads_dir().join(name);
         
         if branch_path.exists() {
@@ -19,11 +24,28 @@ impl Branch {
         }
 
         let commit_hash = match start_point {
-            Some(hash) => hash.to_string(),
-            None => Self::get_current_commit(repo).unwrap_or_default(),
+            Some(spec) => resolve_revision(repo, spec)?,
+            None => Self::get_current_commit(repo)
+                .ok_or_else(|| format!("Cannot create branch '{}': HEAD has no commits yet", name))?,
         };
+        let is_commit = Object::read(repo, &commit_hash).ok()
+            .and_then(|content| serde_json::from_slice::<Commit>(&content).ok())
+            .is_some();
+        if !is_commit {
+            return Err(format!("Cannot create branch '{}': {} is not a commit", name, commit_hash).into());
+        }
+
+        fs::write(branch_path, &commit_hash)?;
+        Ok(commit_hash)
+    }
 
-        fs::write(branch_path, commit_hash)?;
+    /// Points HEAD at `name` without creating its ref, the same unborn state
+    /// a fresh repository's `main` is in. The first commit creates the ref.
+    pub fn start_unborn(repo: &Repository, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+        if repo.heads_dir().join(name).exists() {
+            return Err(format!("Branch '{}' already exists", name).into());
+        }
+        fs::write(repo.git_dir.join("HEAD"), format!("ref: refs/heads/{}", name))?;
         Ok(())
     }
 
@@ -94,6 +116,10 @@ impl Branch {
 
     pub fn checkout(repo: &Repository, target: &str) -> Result<(), Box<dyn std::error::Error>> {
         let branch_path = repo.heads_dir().join(target);
+        let old_commit = Self::get_current_commit(repo);
+        let from = Self::get_current_branch(repo)
+            .or_else(|| old_commit.clone())
+            .unwrap_or_else(|| "HEAD".to_string());
         
         if branch_path.exists() {
             let new_head = format!("ref: refs/heads/{}", target);
@@ -104,6 +130,10 @@ impl Branch {
             return Err(format!("Branch or commit '{}' not found", target).into());
         }
 
+        if let Some(new_commit) = Self::get_current_commit(repo) {
+            reflog::append(repo, old_commit.as_deref(), &new_commit, &format!("checkout: moving from {} to {}", from, target))?;
+        }
+
         Ok(())
     }
 }
@@ -10,6 +10,9 @@ pub struct Commit {
     pub committer: Author,
     pub message: String,
     pub signature: Option<String>,
+    /// Id of the keyring key that produced `signature`, for signed commits.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signing_key: Option<String>,
     pub timestamp: DateTime<Utc>,
 }
 
@@ -49,6 +52,7 @@ impl Commit {
             committer: author,
             message,
             signature: None,
+            signing_key: None,
             timestamp,
         }
     }
@@ -91,6 +95,7 @@ impl Commit {
             committer: author,
             message,
             signature: Some(signature),
+            signing_key: None,
             timestamp,
         }
     }
@@ -0,0 +1,144 @@
+use crate::core::{Repository, Object, Commit};
+use crate::core::revlist::parents_of;
+use serde::{Deserialize, Serialize};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::path::PathBuf;
+
+/// What a history walk needs to know about a commit without parsing it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct GraphEntry {
+    pub parents: Vec<String>,
+    /// 1 for root commits, otherwise one more than the highest parent.
+    pub generation: u32,
+    pub timestamp: i64,
+}
+
+/// Cache of commit parents, generation numbers and timestamps, stored in
+/// `.aigit/info/commit-graph`. Commits never change, so entries only need
+/// adding, never invalidating.
+#[derive(Serialize, Deserialize, Default)]
+pub struct CommitGraph {
+    entries: HashMap<String, GraphEntry>,
+    #[serde(skip)]
+    dirty: bool,
+}
+
+impl CommitGraph {
+    pub fn path(repo: &Repository) -> PathBuf {
+        repo.git_dir.join("info/commit-graph")
+    }
+
+    /// Loads the cache; a missing or unreadable file yields an empty graph
+    /// that is rebuilt on demand.
+    pub fn load(repo: &Repository) -> Self {
+        std::fs::read(Self::path(repo))
+            .ok()
+            .and_then(|content| serde_json::from_slice(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Writes the cache if entries were added since it was loaded.
+    pub fn save(&mut self, repo: &Repository) -> Result<(), Box<dyn std::error::Error>> {
+        if !self.dirty {
+            return Ok(());
+        }
+        let path = Self::path(repo);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_vec(self)?)?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    pub fn get(&self, hash: &str) -> Option<&GraphEntry> {
+        self.entries.get(hash)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Adds every commit reachable from `tips` that is not cached yet. Only
+    /// those commits are read from the object store.
+    pub fn extend(&mut self, repo: &Repository, tips: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+        let mut parsed: HashMap<String, (Vec<String>, i64)> = HashMap::new();
+        let mut stack: Vec<String> = tips.iter()
+            .filter(|tip| !tip.is_empty() && !self.entries.contains_key(*tip))
+            .cloned()
+            .collect();
+
+        // Post-order walk so parents get their generation before children.
+        while let Some(hash) = stack.last().cloned() {
+            if self.entries.contains_key(&hash) {
+                stack.pop();
+                continue;
+            }
+
+            if !parsed.contains_key(&hash) {
+                let commit: Commit = serde_json::from_slice(&Object::read(repo, &hash)?)?;
+                parsed.insert(hash.clone(), (parents_of(&commit), commit.timestamp.timestamp()));
+            }
+            let (parents, timestamp) = &parsed[&hash];
+
+            let pending: Vec<String> = parents.iter()
+                .filter(|parent| !parent.is_empty() && !self.entries.contains_key(*parent))
+                .cloned()
+                .collect();
+            if !pending.is_empty() {
+                stack.extend(pending);
+                continue;
+            }
+
+            let generation = parents.iter()
+                .filter_map(|parent| self.entries.get(parent))
+                .map(|entry| entry.generation)
+                .max()
+                .unwrap_or(0) + 1;
+            self.entries.insert(hash.clone(), GraphEntry {
+                parents: parents.clone(),
+                generation,
+                timestamp: *timestamp,
+            });
+            self.dirty = true;
+            stack.pop();
+        }
+
+        Ok(())
+    }
+
+    /// Every commit reachable from `tips`, newest first. Commits with the same
+    /// timestamp are ordered by generation, so children still precede parents.
+    pub fn walk(&mut self, repo: &Repository, tips: &[String]) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        self.extend(repo, tips)?;
+
+        let mut seen = HashSet::new();
+        let mut queue = BinaryHeap::new();
+        let mut commits = Vec::new();
+
+        for tip in tips {
+            if let Some(entry) = self.entries.get(tip) {
+                if seen.insert(tip.clone()) {
+                    queue.push((entry.timestamp, entry.generation, tip.clone()));
+                }
+            }
+        }
+
+        while let Some((_, _, hash)) = queue.pop() {
+            for parent in &self.entries[&hash].parents {
+                if let Some(entry) = self.entries.get(parent) {
+                    if seen.insert(parent.clone()) {
+                        queue.push((entry.timestamp, entry.generation, parent.clone()));
+                    }
+                }
+            }
+            commits.push(hash);
+        }
+
+        Ok(commits)
+    }
+}
@@ -4,7 +4,44 @@ use serde::{Serialize, Deserialize};
 use dirs::home_dir;
 use std::path::PathBuf;
 
-#[derive(Serialize, Deserialize, Default)]
+pub const GLOBAL_CONFIG_ENV: &str = "AIGIT_CONFIG_GLOBAL";
+
+/// What `init` writes into a new repository's `config.json`, besides
+/// `core.bare`. Only settings that describe the repository itself belong
+/// here; anything else would shadow the user's global config.
+const REPO_DEFAULTS: &[(&str, &str)] = &[
+    ("core.repositoryformatversion", "0"),
+];
+
+/// Fallbacks for keys that neither the global nor the repository config sets.
+const BUILTIN_DEFAULTS: &[(&str, &str)] = &[
+    ("core.filemode", "true"),
+    ("core.logallrefupdates", "true"),
+    ("core.precomposeunicode", "true"),
+    ("core.protectHFS", "true"),
+    ("core.protectNTFS", "true"),
+    ("core.ignorecase", "false"),
+    ("core.trustctime", "false"),
+    ("core.checkStat", "minimal"),
+    ("core.autocrlf", "false"),
+    ("core.safecrlf", "true"),
+    ("core.quotepath", "false"),
+    ("security.enabled", "true"),
+    ("security.requireSignature", "false"),
+    ("security.auditLog", "true"),
+    ("security.encryptObjects", "false"),
+    ("security.hashAlgorithm", "sha256"),
+    ("security.compressionLevel", "6"),
+    ("ai.enabled", "true"),
+    ("ai.provider", "gemini"),
+    ("ai.maxTokens", "4096"),
+    ("ai.temperature", "0.7"),
+    ("ai.requireReview", "false"),
+    ("ai.autoCommitMessage", "true"),
+    ("commit.gpgsign", "false"),
+];
+
+#[derive(Serialize, Deserialize, Default, Clone)]
 pub struct Config {
     settings: HashMap<String, String>,
 }
@@ -14,10 +51,36 @@ impl Config {
         Self::default()
     }
 
+    /// `~/.aigitconfig`, unless `AIGIT_CONFIG_GLOBAL` names another file.
+    pub fn global_path() -> Result<PathBuf, Box<dyn std::error::Error>> {
+        if let Ok(path) = std::env::var(GLOBAL_CONFIG_ENV) {
+            return Ok(PathBuf::from(path));
+        }
+        Ok(home_dir().ok_or("Cannot find home directory")?.join(".aigitconfig"))
+    }
+
+    /// The settings `init` writes into a new repository's `config.json`.
+    pub fn repo_defaults(bare: bool) -> Self {
+        let mut config = Self::from_pairs(REPO_DEFAULTS);
+        config.set("core.bare", &bare.to_string());
+        config
+    }
+
+    /// The values used for keys no config file sets.
+    pub fn builtin_defaults() -> Self {
+        Self::from_pairs(BUILTIN_DEFAULTS)
+    }
+
+    fn from_pairs(pairs: &[(&str, &str)]) -> Self {
+        let mut config = Self::new();
+        for (key, value) in pairs {
+            config.set(key, value);
+        }
+        config
+    }
+
     pub fn load_global() -> Result<Self, Box<dyn std::error::Error>> {
-        let config_path = home_dir()
-            .ok_or("Cannot find home directory")?
-            .join(".aigitconfig");
+        let config_path = Self::global_path()?;
         
         if config_path.exists() {
             let content = std::fs::read_to_string(config_path)?;
@@ -38,6 +101,18 @@ impl Config {
         }
     }
 
+    /// Global settings with the repository's keys layered on top; keys the
+    /// repository leaves unset fall through to the global value, and keys
+    /// neither sets to the built-in defaults.
+    pub fn load_effective(repo: &Repository) -> Self {
+        let mut config = Self::builtin_defaults();
+        config.overlay(&Self::load_global().unwrap_or_default());
+        if let Ok(repo_config) = Self::load_repo(repo) {
+            config.overlay(&repo_config);
+        }
+        config
+    }
+
     pub fn load_from_file(path: &PathBuf) -> Result<Self, Box<dyn std::error::Error>> {
         if path.exists() {
             let content = std::fs::read_to_string(path)?;
@@ -48,9 +123,7 @@ impl Config {
     }
 
     pub fn save_global(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let config_path = home_dir()
-            .ok_or("Cannot find home directory")?
-            .join(".aigitconfig");
+        let config_path = Self::global_path()?;
         
         let content = serde_json::to_string_pretty(&self)?;
         std::fs::write(config_path, content)?;
@@ -78,6 +151,17 @@ impl Config {
         self.settings.get(key)
     }
 
+    pub fn get_bool(&self, key: &str) -> Option<bool> {
+        self.get(key).and_then(|value| parse_bool(value))
+    }
+
+    /// Copies every key of `other` over this config.
+    pub fn overlay(&mut self, other: &Config) {
+        for (key, value) in other.iter() {
+            self.set(key, value);
+        }
+    }
+
     pub fn remove(&mut self, key: &str) -> Option<String> {
         self.settings.remove(key)
     }
@@ -112,3 +196,11 @@ impl Config {
         format!("{} <{}>", self.get_user_name(), self.get_user_email())
     }
 }
+
+pub fn parse_bool(value: &str) -> Option<bool> {
+    match value.trim().to_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => Some(true),
+        "false" | "no" | "off" | "0" => Some(false),
+        _ => None,
+    }
+}
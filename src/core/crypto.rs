@@ -0,0 +1,129 @@
+use crate::core::Repository;
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use rand::RngCore;
+use rand::rngs::OsRng;
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Where the object encryption key lives, relative to the repository dir.
+pub const KEY_FILE: &str = "security/object.key";
+
+/// Prefix of every encrypted object file. Zlib streams never start with it, so
+/// plaintext and encrypted objects can live side by side.
+pub const MAGIC: &[u8] = b"AIGITENC1\0";
+
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+
+/// Whether new objects should be encrypted: either `security.encryptObjects`
+/// or `encrypt_objects` in `security/config.json` turns it on.
+pub fn is_enabled(repo: &Repository) -> bool {
+    *repo.encrypt_objects.get_or_init(|| {
+        repo.config().get_bool("security.encryptObjects").unwrap_or(false)
+            || security_setting(repo, "encrypt_objects")
+    })
+}
+
+pub fn is_encrypted(stored: &[u8]) -> bool {
+    stored.starts_with(MAGIC)
+}
+
+pub fn key_path(repo: &Repository) -> PathBuf {
+    repo.git_dir.join(KEY_FILE)
+}
+
+/// Encrypts the compressed bytes of object `hash` for storage when encryption
+/// is enabled; otherwise returns them unchanged. The hash is bound in as
+/// associated data so an encrypted object cannot be swapped for another.
+pub fn seal(repo: &Repository, hash: &str, compressed: Vec<u8>) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    if !is_enabled(repo) {
+        return Ok(compressed);
+    }
+
+    let key = object_key(repo, true)?;
+    let mut nonce = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut nonce);
+
+    let ciphertext = cipher(&key)
+        .encrypt(Nonce::from_slice(&nonce), Payload { msg: &compressed, aad: hash.as_bytes() })
+        .map_err(|_| format!("Failed to encrypt object {}", hash))?;
+
+    let mut stored = Vec::with_capacity(MAGIC.len() + NONCE_LEN + ciphertext.len());
+    stored.extend_from_slice(MAGIC);
+    stored.extend_from_slice(&nonce);
+    stored.extend_from_slice(&ciphertext);
+    Ok(stored)
+}
+
+/// Returns the compressed bytes of object `hash` from what is stored on disk,
+/// decrypting if the object was written encrypted.
+pub fn open(repo: &Repository, hash: &str, stored: Vec<u8>) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    if !is_encrypted(&stored) {
+        return Ok(stored);
+    }
+
+    let body = &stored[MAGIC.len()..];
+    if body.len() < NONCE_LEN {
+        return Err(format!("Encrypted object {} is truncated", hash).into());
+    }
+    let (nonce, ciphertext) = body.split_at(NONCE_LEN);
+
+    let key = object_key(repo, false)?;
+    let compressed = cipher(&key)
+        .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad: hash.as_bytes() })
+        .map_err(|_| format!("Failed to decrypt object {}: wrong key or corrupted data", hash))?;
+    Ok(compressed)
+}
+
+fn cipher(key: &[u8; KEY_LEN]) -> Aes256Gcm {
+    Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key))
+}
+
+/// Loads the key from `security/object.key`, generating one on first use when
+/// `create` is set. Reading an encrypted object never invents a key.
+fn object_key(repo: &Repository, create: bool) -> Result<[u8; KEY_LEN], Box<dyn std::error::Error>> {
+    if let Some(key) = repo.object_key.get() {
+        return Ok(*key);
+    }
+
+    let path = key_path(repo);
+    let key = match std::fs::read(&path) {
+        Ok(bytes) => <[u8; KEY_LEN]>::try_from(bytes.as_slice())
+            .map_err(|_| format!("{} must hold exactly {} bytes", path.display(), KEY_LEN))?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound && create => generate_key(&path)?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(format!("Object is encrypted but the key file {} is missing", path.display()).into());
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let _ = repo.object_key.set(key);
+    Ok(key)
+}
+
+fn generate_key(path: &std::path::Path) -> Result<[u8; KEY_LEN], Box<dyn std::error::Error>> {
+    let mut key = [0u8; KEY_LEN];
+    OsRng.fill_bytes(&mut key);
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)?.write_all(&key)?;
+    Ok(key)
+}
+
+fn security_setting(repo: &Repository, name: &str) -> bool {
+    std::fs::read_to_string(repo.git_dir.join("security/config.json")).ok()
+        .and_then(|content| serde_json::from_str::<serde_json::Value>(&content).ok())
+        .and_then(|config| config.get(name).and_then(|v| v.as_bool()))
+        .unwrap_or(false)
+}
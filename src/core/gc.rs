@@ -0,0 +1,375 @@
+use crate::core::{Repository, Object, ObjectType, Commit, Tree, Index, Refs, Branch, Config, Stash, Tag, pack};
+use crate::core::object::SizeRepair;
+use crate::core::revision::all_object_hashes;
+use crate::core::reflog::{self, NULL_HASH};
+use indicatif::ProgressBar;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+pub const DEFAULT_AUTO_THRESHOLD: usize = 6700;
+pub const DEFAULT_PRUNE_GRACE: Duration = Duration::from_secs(14 * 24 * 60 * 60);
+pub const DEFAULT_REFLOG_EXPIRE: Duration = Duration::from_secs(90 * 24 * 60 * 60);
+
+#[derive(Default)]
+pub struct GcReport {
+    pub loose_before: usize,
+    pub reachable: usize,
+    pub pruned: usize,
+    pub freed_bytes: u64,
+    pub packed: usize,
+    /// Loose objects removed because an intact copy is already packed.
+    pub pruned_packed: usize,
+    /// Loose objects whose size header was wrong, and what was done about it.
+    pub size_repairs: Vec<(String, SizeRepair)>,
+}
+
+pub fn auto_threshold(config: &Config) -> usize {
+    config.get("gc.auto")
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(DEFAULT_AUTO_THRESHOLD)
+}
+
+/// Parses a `gc.reflogExpire` value: `never`, `now`, or a number of days
+/// written as `30`, `30d`, `30 days` or `30.days`. `Some(None)` means entries
+/// never expire.
+pub fn parse_reflog_expire(value: &str) -> Option<Option<Duration>> {
+    let value = value.trim().to_lowercase();
+    match value.as_str() {
+        "never" | "false" => return Some(None),
+        "now" => return Some(Some(Duration::ZERO)),
+        _ => {}
+    }
+
+    let days = value.trim_end_matches("days")
+        .trim_end_matches("day")
+        .trim_end_matches('d')
+        .trim_end_matches(['.', ' '])
+        .parse::<u64>()
+        .ok()?;
+    Some(Some(Duration::from_secs(days * 24 * 60 * 60)))
+}
+
+/// How long reflog entries keep their commits alive; `None` means forever.
+pub fn reflog_expire(config: &Config) -> Option<Duration> {
+    config.get("gc.reflogExpire")
+        .and_then(|v| parse_reflog_expire(v))
+        .unwrap_or(Some(DEFAULT_REFLOG_EXPIRE))
+}
+
+pub fn loose_object_count(repo: &Repository) -> usize {
+    Object::list_objects(repo).map(|objects| objects.len()).unwrap_or(0)
+}
+
+pub fn needs_auto_gc(repo: &Repository, config: &Config) -> bool {
+    let threshold = auto_threshold(config);
+    threshold > 0 && loose_object_count(repo) > threshold
+}
+
+pub fn reachable_objects(repo: &Repository) -> Result<HashSet<String>, Box<dyn std::error::Error>> {
+    reachable_objects_with(repo, &ProgressBar::hidden(), None)
+}
+
+fn reachable_objects_with(
+    repo: &Repository,
+    progress: &ProgressBar,
+    repairs: Option<&mut Vec<(String, SizeRepair)>>
+) -> Result<HashSet<String>, Box<dyn std::error::Error>> {
+    let mut roots = Vec::new();
+
+    let refs = Refs::load(repo)?;
+    roots.extend(refs.heads.values().cloned());
+    roots.extend(refs.tags.values().cloned());
+    roots.extend(refs.remotes.values().cloned());
+    roots.extend(Stash::load(repo)?.entries);
+    roots.extend(lost_found_refs(repo));
+
+    if let Some(head) = Branch::get_current_commit(repo) {
+        roots.push(head);
+    }
+
+    let mut reachable = HashSet::new();
+
+    let index = Index::load(repo).unwrap_or_default();
+    for hash in index.entries.values() {
+        reachable.insert(hash.clone());
+    }
+
+    walk_reachable_with(repo, roots, &mut reachable, progress, repairs)?;
+    Ok(reachable)
+}
+
+/// Objects gc must keep: everything reachable from refs, plus everything
+/// reachable from reflog entries that have not expired, so history moved
+/// away from by a reset or checkout stays recoverable.
+pub fn retained_objects(repo: &Repository) -> Result<HashSet<String>, Box<dyn std::error::Error>> {
+    retained_objects_with(repo, &ProgressBar::hidden())
+}
+
+/// Like `retained_objects`, advancing `progress` once per object found. Its
+/// length is set to the number of objects in the store.
+pub fn retained_objects_with(repo: &Repository, progress: &ProgressBar) -> Result<HashSet<String>, Box<dyn std::error::Error>> {
+    retained_objects_repairing(repo, progress, None)
+}
+
+/// Like `retained_objects_with`, but with `repairs` set, loose objects met on
+/// the walk whose size header is wrong are repaired and recorded there, so
+/// what a damaged commit or tree names is still found.
+fn retained_objects_repairing(
+    repo: &Repository,
+    progress: &ProgressBar,
+    mut repairs: Option<&mut Vec<(String, SizeRepair)>>
+) -> Result<HashSet<String>, Box<dyn std::error::Error>> {
+    progress.set_length(all_object_hashes(repo).len() as u64);
+    progress.set_position(0);
+    let mut retained = reachable_objects_with(repo, progress, repairs.as_deref_mut())?;
+    walk_reachable_with(repo, reflog_roots(repo, reflog_expire(repo.config())), &mut retained, progress, repairs)?;
+    Ok(retained)
+}
+
+/// Old and new hashes of every reflog entry in `logs/HEAD` and `logs/refs/**`
+/// that is younger than `expire`.
+pub fn reflog_roots(repo: &Repository, expire: Option<Duration>) -> Vec<String> {
+    let cutoff = expire
+        .and_then(|expire| chrono::Duration::from_std(expire).ok())
+        .and_then(|expire| chrono::Utc::now().checked_sub_signed(expire));
+
+    let mut roots = Vec::new();
+    for path in reflog::log_files(repo) {
+        for entry in reflog::read_file(&path).unwrap_or_default() {
+            if cutoff.is_some_and(|cutoff| entry.timestamp < cutoff) {
+                continue;
+            }
+            roots.extend([entry.old, entry.new].into_iter().filter(|hash| hash != NULL_HASH));
+        }
+    }
+    roots
+}
+
+pub fn lost_found_dir(repo: &Repository) -> PathBuf {
+    repo.refs_dir().join("lost-found")
+}
+
+fn lost_found_refs(repo: &Repository) -> Vec<String> {
+    std::fs::read_dir(lost_found_dir(repo))
+        .map(|entries| entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| std::fs::read_to_string(entry.path()).ok())
+            .map(|hash| hash.trim().to_string())
+            .collect())
+        .unwrap_or_default()
+}
+
+/// Commits that no ref reaches and that no other unreachable commit names as
+/// a parent, i.e. the tips of lost history.
+pub fn dangling_commits(repo: &Repository) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let reachable = reachable_objects(repo)?;
+    let mut unreachable = Vec::new();
+
+    for hash in all_object_hashes(repo) {
+        if reachable.contains(&hash) {
+            continue;
+        }
+        if let Ok((ObjectType::Commit, content)) = Object::read_with_type(repo, &hash) {
+            if let Ok(commit) = serde_json::from_slice::<Commit>(&content) {
+                unreachable.push((hash, commit));
+            }
+        }
+    }
+
+    let referenced: HashSet<&String> = unreachable.iter()
+        .flat_map(|(_, commit)| commit.parents.iter())
+        .collect();
+
+    Ok(unreachable.iter()
+        .filter(|(hash, _)| !referenced.contains(hash))
+        .map(|(hash, _)| hash.clone())
+        .collect())
+}
+
+/// Points `refs/lost-found/<hash>` at each commit so it is kept by gc and can
+/// be re-attached with `aigit branch`.
+pub fn write_lost_found(repo: &Repository, hashes: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let dir = lost_found_dir(repo);
+    std::fs::create_dir_all(&dir)?;
+    for hash in hashes {
+        std::fs::write(dir.join(hash), hash)?;
+    }
+    Ok(())
+}
+
+/// Adds every object reachable from `roots` to `reachable`, skipping anything
+/// already in the set.
+pub fn walk_reachable(repo: &Repository, roots: Vec<String>, reachable: &mut HashSet<String>) {
+    // Nothing is written without `repairs`, so the walk cannot fail.
+    walk_reachable_with(repo, roots, reachable, &ProgressBar::hidden(), None).ok();
+}
+
+fn walk_reachable_with(
+    repo: &Repository,
+    roots: Vec<String>,
+    reachable: &mut HashSet<String>,
+    progress: &ProgressBar,
+    mut repairs: Option<&mut Vec<(String, SizeRepair)>>
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut to_visit = roots;
+    while let Some(hash) = to_visit.pop() {
+        if hash.is_empty() || !Object::exists(repo, &hash) || !reachable.insert(hash.clone()) {
+            continue;
+        }
+        progress.inc(1);
+
+        let object = match (Object::read_with_type(repo, &hash), repairs.as_deref_mut()) {
+            (Err(_), Some(repairs)) if Object::has_size_mismatch(repo, &hash) => {
+                repairs.push((hash.clone(), Object::rewrite_size_header(repo, &hash)?));
+                Object::read_with_type(repo, &hash)
+            },
+            (object, _) => object,
+        };
+        match object {
+            Ok((ObjectType::Commit, content)) => {
+                if let Ok(commit) = serde_json::from_slice::<Commit>(&content) {
+                    to_visit.push(commit.tree.clone());
+                    to_visit.extend(commit.parents.iter().cloned());
+                }
+            },
+            Ok((ObjectType::Tree, content)) => {
+                if let Ok(tree) = serde_json::from_slice::<Tree>(&content) {
+                    to_visit.extend(tree.entries.iter().map(|entry| entry.hash.clone()));
+                }
+            },
+            Ok((ObjectType::Tag, content)) => {
+                if let Ok(tag) = serde_json::from_slice::<Tag>(&content) {
+                    to_visit.push(tag.object);
+                }
+            },
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Fixes every loose object whose header size disagrees with its content.
+/// Reachable objects are repaired in place during a reachability walk and
+/// never moved; the rest are rewritten or quarantined, see
+/// `Object::repair_size_header`.
+pub fn repair_size_mismatches(repo: &Repository) -> Result<Vec<(String, SizeRepair)>, Box<dyn std::error::Error>> {
+    let mut repairs = Vec::new();
+    let retained = retained_objects_repairing(repo, &ProgressBar::hidden(), Some(&mut repairs))?;
+    for hash in Object::verify_repository_objects(repo)?.size_mismatches {
+        if !retained.contains(&hash) {
+            let repair = Object::repair_size_header(repo, &hash)?;
+            repairs.push((hash, repair));
+        }
+    }
+    Ok(repairs)
+}
+
+pub fn collect(repo: &Repository, prune_grace: Duration) -> Result<GcReport, Box<dyn std::error::Error>> {
+    collect_with(repo, prune_grace, &ProgressBar::hidden())
+}
+
+/// Like `collect`, reporting the reachability scan and then the pruning pass
+/// on `progress`, each counted in objects.
+pub fn collect_with(repo: &Repository, prune_grace: Duration, progress: &ProgressBar) -> Result<GcReport, Box<dyn std::error::Error>> {
+    // A broken header would otherwise hide what the object names from the
+    // walk, so reachable objects are repaired as they are read.
+    let mut size_repairs = Vec::new();
+    progress.set_message("Scanning reachable objects");
+    let reachable = retained_objects_repairing(repo, progress, Some(&mut size_repairs))?;
+    let objects = Object::list_objects(repo)?;
+    let cutoff = SystemTime::now().checked_sub(prune_grace).unwrap_or(SystemTime::UNIX_EPOCH);
+
+    let mut report = GcReport {
+        loose_before: objects.len(),
+        size_repairs,
+        ..Default::default()
+    };
+
+    progress.set_message("Pruning unreachable objects");
+    progress.set_length(objects.len() as u64);
+    progress.set_position(0);
+
+    for hash in objects {
+        progress.inc(1);
+        if reachable.contains(&hash) {
+            report.reachable += 1;
+            continue;
+        }
+
+        let obj_path = match repo.find_object_path(&hash) {
+            Some(path) => path,
+            None => continue,
+        };
+        let metadata = std::fs::metadata(&obj_path)?;
+
+        if metadata.modified().map(|m| m > cutoff).unwrap_or(true) {
+            continue;
+        }
+
+        std::fs::remove_file(&obj_path)?;
+        report.pruned += 1;
+        report.freed_bytes += metadata.len();
+    }
+
+    Ok(report)
+}
+
+/// Deletes loose objects that also exist in a pack, once the packed copy is
+/// confirmed to read back intact. Adds the removals to `report`.
+pub fn prune_packed(repo: &Repository, report: &mut GcReport) -> Result<(), Box<dyn std::error::Error>> {
+    prune_packed_with(repo, report, &ProgressBar::hidden())
+}
+
+/// Like `prune_packed`, advancing `progress` once per loose object checked.
+pub fn prune_packed_with(repo: &Repository, report: &mut GcReport, progress: &ProgressBar) -> Result<(), Box<dyn std::error::Error>> {
+    let indexes = pack::indexes(repo);
+    if indexes.is_empty() {
+        return Ok(());
+    }
+
+    let objects = Object::list_objects(repo)?;
+    progress.set_message("Removing loose copies of packed objects");
+    progress.set_length(objects.len() as u64);
+    progress.set_position(0);
+
+    for hash in objects {
+        progress.inc(1);
+        let packed_intact = indexes.iter().any(|index| matches!(
+            index.read_raw(&hash),
+            Ok(Some(raw)) if Object::verify_raw(repo, &raw, &hash).is_ok()
+        ));
+        if !packed_intact {
+            continue;
+        }
+        if let Some(path) = repo.find_object_path(&hash) {
+            let size = std::fs::metadata(&path)?.len();
+            std::fs::remove_file(path)?;
+            report.pruned_packed += 1;
+            report.freed_bytes += size;
+        }
+    }
+    Ok(())
+}
+
+/// Moves every retained loose object into a new pack and removes the loose
+/// copies. Unreachable objects still inside the prune grace period stay loose.
+pub fn repack(repo: &Repository) -> Result<usize, Box<dyn std::error::Error>> {
+    let reachable = retained_objects(repo)?;
+    let loose: Vec<String> = Object::list_objects(repo)?
+        .into_iter()
+        .filter(|hash| reachable.contains(hash))
+        .collect();
+
+    if pack::write_pack(repo, &loose)?.is_none() {
+        return Ok(0);
+    }
+
+    for hash in &loose {
+        if let Some(path) = repo.find_object_path(hash) {
+            std::fs::remove_file(path)?;
+        }
+    }
+
+    Ok(loose.len())
+}
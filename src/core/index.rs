@@ -1,9 +1,23 @@
 use crate::core::Repository;
+use crate::utils::worktree::read_entry_content;
 use std::collections::HashMap;
 use serde::{Serialize, Deserialize};
 use chrono::{DateTime, Utc};
 use ring::digest;
 
+// Set on entries staged from a subset of hunks, where the working tree is expected to differ.
+pub const FLAG_PARTIAL: u16 = 0x1;
+
+/// The form every index key is stored in: relative to the working tree root,
+/// `/`-separated and without `.` segments, so `add x`, `add .` and `add src`
+/// all name a file the same way.
+pub fn index_path(path: &str) -> String {
+    path.split('/')
+        .filter(|segment| !segment.is_empty() && *segment != ".")
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
 #[derive(Serialize, Deserialize, Default, Clone)]
 pub struct IndexEntry {
     pub hash: String,
@@ -20,6 +34,10 @@ pub struct IndexEntry {
 pub struct Index {
     pub entries: HashMap<String, String>,
     pub metadata: HashMap<String, IndexEntry>,
+    /// Stage 1-3 (base, ours, theirs) versions of paths a merge left
+    /// conflicted. Staging the path again drops them.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub unmerged: HashMap<String, Vec<IndexEntry>>,
     pub version: u32,
     pub timestamp: DateTime<Utc>,
     pub signature: Option<String>,
@@ -30,6 +48,7 @@ impl Index {
         Self {
             entries: HashMap::new(),
             metadata: HashMap::new(),
+            unmerged: HashMap::new(),
             version: 3,
             timestamp: Utc::now(),
             signature: None,
@@ -44,10 +63,11 @@ impl Index {
             if content.trim().is_empty() {
                 return Ok(Index::new());
             }
-            let index: Index = serde_json::from_str(&content)
+            let mut index: Index = serde_json::from_str(&content)
                 .unwrap_or_else(|_| Index::new());
             
             index.verify_integrity()?;
+            index.normalize_keys();
             Ok(index)
         } else {
             Ok(Index::new())
@@ -71,9 +91,10 @@ impl Index {
 
     pub fn add_entry(&mut self, path: String, hash: String, mode: String) {
         let now = Utc::now();
+        let path = index_path(&path);
         
-        let metadata = if let Ok(file_metadata) = std::fs::metadata(&path) {
-            let content = std::fs::read(&path).unwrap_or_default();
+        let metadata = if let Ok(file_metadata) = std::fs::symlink_metadata(&path) {
+            let content = read_entry_content(&path).unwrap_or_default();
             let checksum = hex::encode(digest::digest(&digest::SHA256, &content).as_ref());
             
             IndexEntry {
@@ -99,6 +120,7 @@ impl Index {
             }
         };
         
+        self.unmerged.remove(&path);
         self.entries.insert(path.clone(), hash);
         self.metadata.insert(path, metadata);
         self.timestamp = now;
@@ -106,6 +128,7 @@ impl Index {
 
     pub fn add_entry_secure(&mut self, path: String, hash: String, mode: String, size: u64, checksum: String) {
         let now = Utc::now();
+        let path = index_path(&path);
         
         let metadata = IndexEntry {
             hash: hash.clone(),
@@ -118,20 +141,56 @@ impl Index {
             flags: 0,
         };
         
+        self.unmerged.remove(&path);
         self.entries.insert(path.clone(), hash);
         self.metadata.insert(path, metadata);
         self.timestamp = now;
     }
 
+    pub fn mark_partial(&mut self, path: &str) {
+        if let Some(entry) = self.metadata.get_mut(&index_path(path)) {
+            entry.flags |= FLAG_PARTIAL;
+        }
+    }
+
+    pub fn is_partial(&self, path: &str) -> bool {
+        self.metadata.get(&index_path(path)).map(|entry| entry.flags & FLAG_PARTIAL != 0).unwrap_or(false)
+    }
+
     pub fn remove_entry(&mut self, path: &str) {
-        self.entries.remove(path);
-        self.metadata.remove(path);
+        let path = index_path(path);
+        self.entries.remove(&path);
+        self.metadata.remove(&path);
+        self.unmerged.remove(&path);
         self.timestamp = Utc::now();
     }
 
+    /// Records the versions of a conflicted `path` as `(stage, hash, mode)`,
+    /// with 1 = base, 2 = ours and 3 = theirs; a side without the file is
+    /// left out. The path's own entry is marked unmerged until it is staged
+    /// again.
+    pub fn add_conflict(&mut self, path: &str, versions: Vec<(u8, String, String)>) {
+        let now = Utc::now();
+        let path = index_path(path);
+        let stages = versions.into_iter()
+            .map(|(stage, hash, mode)| IndexEntry { hash, mode, mtime: now, ctime: now, stage, ..Default::default() })
+            .collect();
+        if let Some(entry) = self.metadata.get_mut(&path) {
+            entry.stage = 2;
+        }
+        self.unmerged.insert(path, stages);
+        self.timestamp = now;
+    }
+
+    /// The stage 1-3 versions recorded for a conflicted `path`.
+    pub fn conflict_stages(&self, path: &str) -> &[IndexEntry] {
+        self.unmerged.get(&index_path(path)).map(Vec::as_slice).unwrap_or(&[])
+    }
+
     pub fn clear(&mut self, _repo: &Repository) -> Result<(), Box<dyn std::error::Error>> {
         self.entries.clear();
         self.metadata.clear();
+        self.unmerged.clear();
         self.timestamp = Utc::now();
         self.signature = None;
         Ok(())
@@ -142,15 +201,50 @@ impl Index {
     }
 
     pub fn has_conflicts(&self) -> bool {
-        self.metadata.values().any(|entry| entry.stage != 0)
+        !self.unmerged.is_empty() || self.metadata.values().any(|entry| entry.stage != 0)
+    }
+
+    pub fn is_conflicted(&self, path: &str) -> bool {
+        let path = index_path(path);
+        self.unmerged.contains_key(&path) || self.metadata.get(&path).is_some_and(|entry| entry.stage != 0)
     }
 
     pub fn get_conflicted_files(&self) -> Vec<String> {
-        self.metadata
+        let mut paths: Vec<String> = self.metadata
             .iter()
             .filter(|(_, entry)| entry.stage != 0)
             .map(|(path, _)| path.clone())
-            .collect()
+            .chain(self.unmerged.keys().cloned())
+            .collect();
+        paths.sort();
+        paths.dedup();
+        paths
+    }
+
+    /// Rewrites keys saved by older versions, which stored files added by
+    /// directory as "./x", into `index_path` form. If both spellings of a
+    /// path were staged, the more recently staged one is kept.
+    fn normalize_keys(&mut self) {
+        let stale: Vec<String> = self.entries.keys()
+            .chain(self.unmerged.keys())
+            .filter(|path| index_path(path) != **path)
+            .cloned()
+            .collect();
+
+        for path in stale {
+            let key = index_path(&path);
+            if let Some(stages) = self.unmerged.remove(&path) {
+                self.unmerged.entry(key.clone()).or_insert(stages);
+            }
+            let (Some(hash), Some(entry)) = (self.entries.remove(&path), self.metadata.remove(&path)) else {
+                continue;
+            };
+            if self.metadata.get(&key).is_some_and(|existing| existing.mtime >= entry.mtime) {
+                continue;
+            }
+            self.entries.insert(key.clone(), hash);
+            self.metadata.insert(key, entry);
+        }
     }
 
     fn verify_integrity(&self) -> Result<(), Box<dyn std::error::Error>> {
@@ -178,6 +272,12 @@ impl Index {
             }
         }
 
+        for (path, stages) in &self.unmerged {
+            if stages.iter().any(|entry| !(1..=3).contains(&entry.stage)) {
+                return Err(format!("Invalid stage number for {}", path).into());
+            }
+        }
+
         Ok(())
     }
 
@@ -6,12 +6,29 @@ pub mod tree;
 pub mod branch;
 pub mod refs;
 pub mod config;
+pub mod quarantine;
+pub mod gc;
+pub mod stash;
+pub mod revision;
+pub mod revlist;
+pub mod commit_graph;
+pub mod reflog;
+pub mod transport;
+pub mod pack;
+pub mod signing;
+pub mod crypto;
+pub mod tag;
 
 pub use repository::Repository;
 pub use object::{Object, ObjectType};
 pub use index::{Index, IndexEntry};
 pub use commit::{Commit, Author};
-pub use tree::Tree;
+pub use tree::{Tree, TreeEntry, TreeChange, ChangeKind};
 pub use branch::Branch;
 pub use refs::Refs;
 pub use config::Config;
+pub use quarantine::{Quarantine, QuarantineEntry};
+pub use stash::{Stash, StashEntry};
+pub use revision::resolve_revision;
+pub use commit_graph::CommitGraph;
+pub use tag::Tag;
@@ -1,8 +1,40 @@
-use crate::core::Repository;
+use crate::core::{Repository, crypto, pack};
+use crate::core::revision::MIN_ABBREV;
 use std::fs;
 use flate2::{Compression, write::ZlibEncoder, read::ZlibDecoder};
 use std::io::{Write, Read};
+use std::path::PathBuf;
+use indicatif::ProgressBar;
 use ring::digest;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum PrefixError {
+    #[error("'{0}' is not a hash prefix (expected at least {} hex digits)", MIN_ABBREV)]
+    Invalid(String),
+    #[error("No object matches prefix '{0}'")]
+    NotFound(String),
+    #[error("ambiguous prefix '{prefix}' matches {}:\n  {}", .candidates.len(), .candidates.join("\n  "))]
+    Ambiguous { prefix: String, candidates: Vec<String> },
+}
+
+/// Where loose objects that cannot be repaired are moved, relative to the
+/// repository dir, so they no longer break reads but are not thrown away.
+pub const QUARANTINE_DIR: &str = "quarantine/objects";
+
+/// What `Object::repair_size_header` did with an object whose header size
+/// disagreed with its content.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SizeRepair {
+    /// The content with a corrected header still hashes to the object's
+    /// name, so the object was rewritten in place.
+    Rewritten,
+    /// It does not, so the object was moved to this path.
+    Quarantined(PathBuf),
+    /// It does not, but the object is still reachable, so it was left in
+    /// place rather than taken out from under the history that names it.
+    Kept,
+}
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum ObjectType {
@@ -21,61 +53,110 @@ impl ObjectType {
             ObjectType::Tag => "tag",
         }
     }
+}
+
+impl std::str::FromStr for ObjectType {
+    type Err = String;
 
-    pub fn from_str(s: &str) -> Option<Self> {
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
-            "blob" => Some(ObjectType::Blob),
-            "tree" => Some(ObjectType::Tree),
-            "commit" => Some(ObjectType::Commit),
-            "tag" => Some(ObjectType::Tag),
-            _ => None,
+            "blob" => Ok(ObjectType::Blob),
+            "tree" => Ok(ObjectType::Tree),
+            "commit" => Ok(ObjectType::Commit),
+            "tag" => Ok(ObjectType::Tag),
+            _ => Err(format!("Unknown object type: {}", s)),
         }
     }
 }
 
 pub struct Object;
 
+type StagedObject = (String, Option<PathBuf>);
+
 impl Object {
     pub fn create(
         repo: &Repository, 
         obj_type: ObjectType, 
         content: &[u8]
     ) -> Result<String, Box<dyn std::error::Error>> {
-        let header = format!("{} {}\0", obj_type.as_str(), content.len());
-        let mut full_content = header.into_bytes();
-        full_content.extend_from_slice(content);
-        
+        let full_content = encode_object(&obj_type, content);
         let hash = hash_content(&full_content);
-        let (dir, file) = hash.split_at(2);
-        
-        let obj_dir = repo.objects_dir().join(dir);
-        fs::create_dir_all(&obj_dir)?;
         
-        let obj_path = obj_dir.join(file);
-        if !obj_path.exists() {
-            let compressed = compress_data(&full_content)?;
-            fs::write(&obj_path, compressed)?;
+        if !Self::exists(repo, &hash) {
+            let obj_path = repo.object_path(&hash);
+            if let Some(obj_dir) = obj_path.parent() {
+                fs::create_dir_all(obj_dir)?;
+            }
+            
+            let stored = crypto::seal(repo, &hash, compress_data(&full_content)?)?;
+            fs::write(&obj_path, stored)?;
             
             Self::set_object_permissions(&obj_path)?;
-            Self::verify_object_integrity(&obj_path, &hash)?;
+            Self::verify_object_integrity(repo, &obj_path, &hash)?;
         }
         
         Ok(hash)
     }
 
+    pub fn create_batch(
+        repo: &Repository,
+        objects: &[(ObjectType, Vec<u8>)]
+    ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let staging_dir = repo.objects_dir().join(format!("tmp-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&staging_dir)?;
+
+        let staged = Self::stage_batch(repo, &staging_dir, objects);
+        let result = staged.and_then(|staged| {
+            let mut hashes = Vec::with_capacity(staged.len());
+            for (hash, temp_path) in staged {
+                if let Some(temp_path) = temp_path {
+                    let obj_path = repo.object_path(&hash);
+                    if let Some(obj_dir) = obj_path.parent() {
+                        fs::create_dir_all(obj_dir)?;
+                    }
+                    fs::rename(&temp_path, &obj_path)?;
+                    Self::set_object_permissions(&obj_path)?;
+                }
+                hashes.push(hash);
+            }
+            Ok(hashes)
+        });
+
+        fs::remove_dir_all(&staging_dir).ok();
+        result
+    }
+
+    fn stage_batch(
+        repo: &Repository,
+        staging_dir: &std::path::Path,
+        objects: &[(ObjectType, Vec<u8>)]
+    ) -> Result<Vec<StagedObject>, Box<dyn std::error::Error>> {
+        let mut staged = Vec::with_capacity(objects.len());
+
+        for (obj_type, content) in objects {
+            let full_content = encode_object(obj_type, content);
+            let hash = hash_content(&full_content);
+
+            if Self::exists(repo, &hash) || staged.iter().any(|(h, _)| h == &hash) {
+                staged.push((hash, None));
+                continue;
+            }
+
+            let temp_path = staging_dir.join(&hash);
+            fs::write(&temp_path, crypto::seal(repo, &hash, compress_data(&full_content)?)?)?;
+            Self::verify_object_integrity(repo, &temp_path, &hash)?;
+            staged.push((hash, Some(temp_path)));
+        }
+
+        Ok(staged)
+    }
+
     pub fn read(repo: &Repository, hash: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
         if hash.len() < 8 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
             return Err("Invalid object hash format".into());
         }
         
-        let (dir, file) = hash.split_at(2);
-        let obj_path = repo.objects_dir().join(dir).join(file);
-        
-        if !obj_path.exists() {
-            return Err(format!("Object {} not found", hash).into());
-        }
-        
-        let compressed_data = fs::read(&obj_path)?;
+        let compressed_data = Self::read_compressed(repo, hash)?;
         let decompressed = decompress_data(&compressed_data)?;
         
         Self::verify_decompressed_data(&decompressed, hash)?;
@@ -91,10 +172,7 @@ impl Object {
         repo: &Repository, 
         hash: &str
     ) -> Result<(ObjectType, Vec<u8>), Box<dyn std::error::Error>> {
-        let (dir, file) = hash.split_at(2);
-        let obj_path = repo.objects_dir().join(dir).join(file);
-        
-        let compressed_data = fs::read(&obj_path)?;
+        let compressed_data = Self::read_compressed(repo, hash)?;
         let decompressed = decompress_data(&compressed_data)?;
         
         Self::verify_decompressed_data(&decompressed, hash)?;
@@ -104,8 +182,7 @@ impl Object {
             let parts: Vec<&str> = header.splitn(2, ' ').collect();
             
             if parts.len() == 2 {
-                let obj_type = ObjectType::from_str(parts[0])
-                    .ok_or("Unknown object type")?;
+                let obj_type: ObjectType = parts[0].parse()?;
                 let expected_size: usize = parts[1].parse()
                     .map_err(|_| "Invalid size in object header")?;
                 
@@ -129,9 +206,63 @@ impl Object {
             return false;
         }
         
-        let (dir, file) = hash.split_at(2);
-        let obj_path = repo.objects_dir().join(dir).join(file);
-        obj_path.exists()
+        repo.find_object_path(hash).is_some() || pack::contains(repo, hash)
+    }
+
+    /// Expands an abbreviated hash to the one object, loose or packed, whose
+    /// hash starts with `prefix`. Only the `objects/<2-char>/` directory the
+    /// prefix points at is scanned.
+    pub fn resolve_prefix(repo: &Repository, prefix: &str) -> Result<String, PrefixError> {
+        let prefix = prefix.to_ascii_lowercase();
+        if prefix.len() < MIN_ABBREV || !prefix.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(PrefixError::Invalid(prefix));
+        }
+
+        let mut candidates = Self::with_prefix(repo, &prefix);
+        match candidates.len() {
+            0 => Err(PrefixError::NotFound(prefix)),
+            1 => Ok(candidates.remove(0)),
+            _ => Err(PrefixError::Ambiguous { prefix, candidates }),
+        }
+    }
+
+    /// Every object hash, loose or packed, that starts with `prefix` (at
+    /// least two characters), sorted. Only the `objects/<2-char>/` directory
+    /// the prefix points at is scanned.
+    pub fn with_prefix(repo: &Repository, prefix: &str) -> Vec<String> {
+        let mut hashes = Vec::new();
+        let shard = &prefix[..2];
+        let shard_dir = repo.objects_dir().join(shard);
+        if shard_dir.is_dir() {
+            Self::collect_object_names(&shard_dir, shard, &mut hashes).ok();
+        }
+        for index in pack::indexes(repo).iter() {
+            hashes.extend(index.objects.range(prefix.to_string()..)
+                .map(|(hash, _)| hash)
+                .take_while(|hash| hash.starts_with(prefix))
+                .cloned());
+        }
+        hashes.retain(|hash| hash.starts_with(prefix));
+        hashes.sort();
+        hashes.dedup();
+        hashes
+    }
+
+    /// Returns the compressed bytes of an object, decrypting them if the object
+    /// was stored encrypted.
+    pub fn read_compressed(repo: &Repository, hash: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        crypto::open(repo, hash, Self::read_raw(repo, hash)?)
+    }
+
+    /// Returns the stored bytes of an object, whether it is loose or packed.
+    /// These are encrypted when the repository encrypts objects.
+    pub fn read_raw(repo: &Repository, hash: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        if let Some(obj_path) = repo.find_object_path(hash) {
+            return Ok(fs::read(&obj_path)?);
+        }
+
+        pack::read_raw(repo, hash)?
+            .ok_or_else(|| format!("Object {} not found", hash).into())
     }
 
     pub fn list_objects(repo: &Repository) -> Result<Vec<String>, Box<dyn std::error::Error>> {
@@ -142,27 +273,34 @@ impl Object {
             return Ok(objects);
         }
         
-        for entry in fs::read_dir(&objects_dir)? {
+        Self::collect_object_names(&objects_dir, "", &mut objects)?;
+        
+        objects.sort();
+        Ok(objects)
+    }
+
+    fn collect_object_names(
+        dir: &std::path::Path,
+        prefix: &str,
+        objects: &mut Vec<String>
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        for entry in fs::read_dir(dir)? {
             let entry = entry?;
+            let name = match entry.file_name().to_str() {
+                Some(name) if name.chars().all(|c| c.is_ascii_hexdigit()) => name.to_string(),
+                _ => continue,
+            };
+            
             if entry.file_type()?.is_dir() {
-                let dir_name = entry.file_name();
-                if let Some(prefix) = dir_name.to_str() {
-                    if prefix.len() == 2 && prefix.chars().all(|c| c.is_ascii_hexdigit()) {
-                        for obj_entry in fs::read_dir(entry.path())? {
-                            let obj_entry = obj_entry?;
-                            if let Some(suffix) = obj_entry.file_name().to_str() {
-                                if suffix.chars().all(|c| c.is_ascii_hexdigit()) {
-                                    objects.push(format!("{}{}", prefix, suffix));
-                                }
-                            }
-                        }
-                    }
+                if name.len() == 2 {
+                    Self::collect_object_names(&entry.path(), &format!("{}{}", prefix, name), objects)?;
                 }
+            } else if !prefix.is_empty() {
+                objects.push(format!("{}{}", prefix, name));
             }
         }
         
-        objects.sort();
-        Ok(objects)
+        Ok(())
     }
 
     fn set_object_permissions(obj_path: &std::path::Path) -> Result<(), Box<dyn std::error::Error>> {
@@ -177,10 +315,11 @@ impl Object {
     }
 
     fn verify_object_integrity(
+        repo: &Repository,
         obj_path: &std::path::Path, 
         expected_hash: &str
     ) -> Result<(), Box<dyn std::error::Error>> {
-        let compressed_data = fs::read(obj_path)?;
+        let compressed_data = crypto::open(repo, expected_hash, fs::read(obj_path)?)?;
         let decompressed = decompress_data(&compressed_data)?;
         let actual_hash = hash_content(&decompressed);
         
@@ -203,26 +342,133 @@ impl Object {
         Ok(())
     }
 
+    /// Checks that stored bytes decrypt and decompress to the object `hash`.
+    pub fn verify_raw(repo: &Repository, raw: &[u8], hash: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let compressed = crypto::open(repo, hash, raw.to_vec())?;
+        Self::verify_decompressed_data(&decompress_data(&compressed)?, hash)
+    }
+
+    /// Whether the loose object `hash` decompresses to a well-formed header
+    /// whose size differs from the length of the content that follows.
+    pub fn has_size_mismatch(repo: &Repository, hash: &str) -> bool {
+        Self::parse_loose(repo, hash)
+            .is_some_and(|(_, declared, content)| declared != content.len())
+    }
+
+    fn declares_wrong_size(decompressed: &[u8]) -> bool {
+        split_header(decompressed).is_some_and(|(_, declared, content)| declared != content.len())
+    }
+
+    /// Fixes a loose object whose header size is wrong. If the content hashes
+    /// to `hash` once the header carries the right size, the object is
+    /// rewritten with that header; otherwise it is moved to `QUARANTINE_DIR`.
+    pub fn repair_size_header(repo: &Repository, hash: &str) -> Result<SizeRepair, Box<dyn std::error::Error>> {
+        if Self::rewrite_size_header(repo, hash)? == SizeRepair::Rewritten {
+            return Ok(SizeRepair::Rewritten);
+        }
+
+        let obj_path = repo.find_object_path(hash).ok_or_else(|| format!("Object {} is not a loose object", hash))?;
+        let quarantine_dir = repo.git_dir.join(QUARANTINE_DIR);
+        fs::create_dir_all(&quarantine_dir)?;
+        let target = quarantine_dir.join(hash);
+        fs::rename(&obj_path, &target)?;
+        Ok(SizeRepair::Quarantined(target))
+    }
+
+    /// Like `repair_size_header`, but an object that cannot be rewritten is
+    /// left where it is and reported as `SizeRepair::Kept`. Used for
+    /// reachable objects.
+    pub fn rewrite_size_header(repo: &Repository, hash: &str) -> Result<SizeRepair, Box<dyn std::error::Error>> {
+        let obj_path = repo.find_object_path(hash).ok_or_else(|| format!("Object {} is not a loose object", hash))?;
+        let (obj_type, declared, content) = Self::parse_loose(repo, hash)
+            .ok_or_else(|| format!("Object {} has no readable header", hash))?;
+        if declared == content.len() {
+            return Err(format!("Object {} has a correct size header", hash).into());
+        }
+
+        if hash_object(&obj_type, &content) == hash {
+            let stored = crypto::seal(repo, hash, compress_data(&encode_object(&obj_type, &content))?)?;
+            let temp_path = obj_path.with_extension("tmp");
+            fs::write(&temp_path, stored)?;
+            Self::verify_object_integrity(repo, &temp_path, hash)?;
+            fs::rename(&temp_path, &obj_path)?;
+            Self::set_object_permissions(&obj_path)?;
+            return Ok(SizeRepair::Rewritten);
+        }
+        Ok(SizeRepair::Kept)
+    }
+
+    /// The type, declared size and content of a loose object, without any
+    /// integrity checks.
+    fn parse_loose(repo: &Repository, hash: &str) -> Option<(ObjectType, usize, Vec<u8>)> {
+        let stored = fs::read(repo.find_object_path(hash)?).ok()?;
+        let decompressed = decompress_data(&crypto::open(repo, hash, stored).ok()?).ok()?;
+        let (obj_type, declared, content) = split_header(&decompressed)?;
+        Some((obj_type, declared, content.to_vec()))
+    }
+
     pub fn get_size(repo: &Repository, hash: &str) -> Result<u64, Box<dyn std::error::Error>> {
         let (_, content) = Self::read_with_type(repo, hash)?;
         Ok(content.len() as u64)
     }
 
-    pub fn verify_repository_objects(repo: &Repository) -> Result<Vec<String>, Box<dyn std::error::Error>> {
-        let mut corrupted = Vec::new();
+    pub fn verify_repository_objects(repo: &Repository) -> Result<ObjectCheck, Box<dyn std::error::Error>> {
+        Self::verify_repository_objects_with(repo, &ProgressBar::hidden())
+    }
+
+    /// Like `verify_repository_objects`, advancing `progress` once per loose
+    /// object; its length is set to the number of loose objects. Each object
+    /// is decompressed once, which also tells size mismatches apart.
+    pub fn verify_repository_objects_with(repo: &Repository, progress: &ProgressBar) -> Result<ObjectCheck, Box<dyn std::error::Error>> {
+        let mut check = ObjectCheck::default();
         let objects = Self::list_objects(repo)?;
+        progress.set_length(objects.len() as u64);
+        progress.set_position(0);
         
         for hash in objects {
-            match Self::read(repo, &hash) {
-                Ok(_) => {},
-                Err(_) => corrupted.push(hash),
+            progress.inc(1);
+            match Self::read_compressed(repo, &hash).and_then(|data| decompress_data(&data)) {
+                Ok(data) if Self::declares_wrong_size(&data) => check.size_mismatches.push(hash),
+                Ok(data) if hash.len() >= 8
+                    && data.contains(&0)
+                    && Self::verify_decompressed_data(&data, &hash).is_ok() => {},
+                _ => check.corrupted.push(hash),
             }
         }
         
-        Ok(corrupted)
+        Ok(check)
     }
 }
 
+/// Loose objects that failed `Object::verify_repository_objects`.
+#[derive(Debug, Default)]
+pub struct ObjectCheck {
+    /// Objects that cannot be read back as the object they are named for.
+    pub corrupted: Vec<String>,
+    /// Objects whose header size disagrees with their content; see
+    /// `Object::repair_size_header`.
+    pub size_mismatches: Vec<String>,
+}
+
+/// The type, declared size and content of a decompressed object.
+fn split_header(decompressed: &[u8]) -> Option<(ObjectType, usize, &[u8])> {
+    let null_pos = decompressed.iter().position(|&b| b == 0)?;
+    let header = std::str::from_utf8(&decompressed[..null_pos]).ok()?;
+    let (obj_type, size) = header.split_once(' ')?;
+    Some((obj_type.parse().ok()?, size.parse().ok()?, &decompressed[null_pos + 1..]))
+}
+
+pub fn hash_object(obj_type: &ObjectType, content: &[u8]) -> String {
+    hash_content(&encode_object(obj_type, content))
+}
+
+fn encode_object(obj_type: &ObjectType, content: &[u8]) -> Vec<u8> {
+    let header = format!("{} {}\0", obj_type.as_str(), content.len());
+    let mut full_content = header.into_bytes();
+    full_content.extend_from_slice(content);
+    full_content
+}
+
 pub fn hash_content(content: &[u8]) -> String {
     let digest_result = digest::digest(&digest::SHA256, content);
     hex::encode(digest_result.as_ref())
@@ -0,0 +1,194 @@
+use crate::core::{Repository, Object};
+use crate::core::object::hash_content;
+use serde::{Serialize, Deserialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+
+pub const PACK_EXTENSION: &str = "aigitpack";
+pub const PACK_INDEX_EXTENSION: &str = "idx";
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct PackEntry {
+    pub offset: u64,
+    pub length: u64,
+}
+
+/// Companion index for a pack file. Each object is stored in the pack exactly
+/// as its compressed loose representation, concatenated back to back.
+#[derive(Serialize, Deserialize, Default)]
+pub struct PackIndex {
+    pub objects: BTreeMap<String, PackEntry>,
+    #[serde(skip)]
+    pub pack_path: PathBuf,
+}
+
+impl PackIndex {
+    pub fn load(idx_path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut index: PackIndex = serde_json::from_str(&fs::read_to_string(idx_path)?)?;
+        index.pack_path = idx_path.with_extension(PACK_EXTENSION);
+        Ok(index)
+    }
+
+    pub fn contains(&self, hash: &str) -> bool {
+        self.objects.contains_key(hash)
+    }
+
+    pub fn read_raw(&self, hash: &str) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
+        let entry = match self.objects.get(hash) {
+            Some(entry) => *entry,
+            None => return Ok(None),
+        };
+
+        let mut file = fs::File::open(&self.pack_path)?;
+        file.seek(SeekFrom::Start(entry.offset))?;
+        let mut raw = vec![0u8; entry.length as usize];
+        file.read_exact(&mut raw)?;
+        Ok(Some(raw))
+    }
+
+    /// Checks every entry against the pack it indexes: the recorded range must
+    /// lie inside the pack and its bytes must decrypt and decompress to the named object.
+    /// Returns `(hash, reason)` for each entry that fails.
+    pub fn verify(&self, repo: &Repository) -> Vec<(String, String)> {
+        let pack = match fs::read(&self.pack_path) {
+            Ok(pack) => pack,
+            Err(e) => {
+                let reason = format!("cannot read {}: {}", self.pack_path.display(), e);
+                return self.objects.keys().map(|hash| (hash.clone(), reason.clone())).collect();
+            }
+        };
+
+        let mut problems = Vec::new();
+        for (hash, entry) in &self.objects {
+            let raw = usize::try_from(entry.offset).ok()
+                .zip(usize::try_from(entry.length).ok())
+                .and_then(|(offset, length)| pack.get(offset..offset.checked_add(length)?));
+            match raw {
+                None => problems.push((hash.clone(), format!(
+                    "entry {}+{} lies outside the {} byte pack", entry.offset, entry.length, pack.len()))),
+                Some(raw) => if Object::verify_raw(repo, raw, hash).is_err() {
+                    problems.push((hash.clone(), "packed content does not match its hash".to_string()));
+                },
+            }
+        }
+        problems
+    }
+}
+
+pub fn pack_dir(repo: &Repository) -> PathBuf {
+    repo.objects_dir().join("pack")
+}
+
+pub fn load_indexes(repo: &Repository) -> Vec<PackIndex> {
+    let entries = match fs::read_dir(pack_dir(repo)) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut indexes: Vec<PackIndex> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().and_then(|ext| ext.to_str()) == Some(PACK_INDEX_EXTENSION))
+        .filter_map(|path| PackIndex::load(&path).ok())
+        .collect();
+    indexes.sort_by(|a, b| a.pack_path.cmp(&b.pack_path));
+    indexes
+}
+
+/// The pack indexes a `Repository` has read, so object lookups do not parse
+/// every `.idx` file again. Invalidated by `write_pack`, and by a change to
+/// the pack directory's modification time when another process adds a pack.
+#[derive(Default)]
+pub struct PackCache {
+    loaded: RwLock<Option<CachedIndexes>>,
+}
+
+struct CachedIndexes {
+    modified: Option<SystemTime>,
+    indexes: Arc<Vec<PackIndex>>,
+}
+
+impl PackCache {
+    fn reset(&self) {
+        if let Ok(mut loaded) = self.loaded.write() {
+            *loaded = None;
+        }
+    }
+}
+
+/// Every pack index of `repo`, read from disk only the first time or after
+/// the set of packs changed.
+pub fn indexes(repo: &Repository) -> Arc<Vec<PackIndex>> {
+    let modified = fs::metadata(pack_dir(repo)).and_then(|meta| meta.modified()).ok();
+    if let Ok(loaded) = repo.pack_cache.loaded.read() {
+        if let Some(cached) = loaded.as_ref().filter(|cached| cached.modified == modified) {
+            return Arc::clone(&cached.indexes);
+        }
+    }
+
+    let indexes = Arc::new(load_indexes(repo));
+    if let Ok(mut loaded) = repo.pack_cache.loaded.write() {
+        *loaded = Some(CachedIndexes { modified, indexes: Arc::clone(&indexes) });
+    }
+    indexes
+}
+
+pub fn read_raw(repo: &Repository, hash: &str) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
+    for index in indexes(repo).iter() {
+        if let Some(raw) = index.read_raw(hash)? {
+            return Ok(Some(raw));
+        }
+    }
+    Ok(None)
+}
+
+pub fn contains(repo: &Repository, hash: &str) -> bool {
+    indexes(repo).iter().any(|index| index.contains(hash))
+}
+
+/// Writes the given loose objects into `pack-<hash>.aigitpack` plus its index
+/// and returns the pack path. Loose files are left for the caller to remove.
+pub fn write_pack(repo: &Repository, hashes: &[String]) -> Result<Option<PathBuf>, Box<dyn std::error::Error>> {
+    let mut pack = Vec::new();
+    let mut index = PackIndex::default();
+
+    for hash in hashes {
+        let loose_path = match repo.find_object_path(hash) {
+            Some(path) => path,
+            None => continue,
+        };
+        let raw = fs::read(&loose_path)?;
+        index.objects.insert(hash.clone(), PackEntry {
+            offset: pack.len() as u64,
+            length: raw.len() as u64,
+        });
+        pack.extend_from_slice(&raw);
+    }
+
+    if index.objects.is_empty() {
+        return Ok(None);
+    }
+
+    let dir = pack_dir(repo);
+    fs::create_dir_all(&dir)?;
+
+    let name = format!("pack-{}", hash_content(&pack));
+    let pack_path = dir.join(format!("{}.{}", name, PACK_EXTENSION));
+    let idx_path = dir.join(format!("{}.{}", name, PACK_INDEX_EXTENSION));
+
+    // The pack must be complete before its index makes the objects visible.
+    let temp_pack = pack_path.with_extension("tmp");
+    fs::write(&temp_pack, &pack)?;
+    fs::rename(&temp_pack, &pack_path)?;
+
+    let temp_idx = idx_path.with_extension("idx-tmp");
+    fs::write(&temp_idx, serde_json::to_string(&index)?)?;
+    fs::rename(&temp_idx, &idx_path)?;
+    repo.pack_cache.reset();
+
+    Ok(Some(pack_path))
+}
@@ -0,0 +1,64 @@
+use crate::core::Repository;
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Utc};
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct QuarantineEntry {
+    pub path: String,
+    pub pattern: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct Quarantine {
+    pub entries: Vec<QuarantineEntry>,
+}
+
+impl Quarantine {
+    pub fn load(repo: &Repository) -> Result<Self, Box<dyn std::error::Error>> {
+        let quarantine_path = repo.git_dir.join("quarantine.json");
+
+        if quarantine_path.exists() {
+            let content = std::fs::read_to_string(&quarantine_path)?;
+            if content.trim().is_empty() {
+                return Ok(Self::default());
+            }
+            Ok(serde_json::from_str(&content)?)
+        } else {
+            Ok(Self::default())
+        }
+    }
+
+    pub fn save(&self, repo: &Repository) -> Result<(), Box<dyn std::error::Error>> {
+        let quarantine_path = repo.git_dir.join("quarantine.json");
+        let content = serde_json::to_string_pretty(self)?;
+
+        let temp_path = quarantine_path.with_extension("tmp");
+        std::fs::write(&temp_path, content)?;
+        std::fs::rename(&temp_path, &quarantine_path)?;
+        Ok(())
+    }
+
+    /// Records `path` as quarantined, replacing any earlier record for it.
+    pub fn record(&mut self, path: &str, pattern: &str) {
+        let entry = QuarantineEntry {
+            path: path.to_string(),
+            pattern: pattern.to_string(),
+            timestamp: Utc::now(),
+        };
+        match self.entries.iter_mut().find(|existing| existing.path == path) {
+            Some(existing) => *existing = entry,
+            None => self.entries.push(entry),
+        }
+    }
+
+    pub fn clear(&mut self) -> usize {
+        let count = self.entries.len();
+        self.entries.clear();
+        count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
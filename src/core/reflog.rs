@@ -0,0 +1,122 @@
+use crate::core::Repository;
+use chrono::{DateTime, Utc};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+pub const NULL_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// One line of `.aigit/logs/HEAD`:
+/// `<old-hash> <new-hash> <user> <timestamp> <action>: <message>`.
+#[derive(Debug, Clone)]
+pub struct ReflogEntry {
+    pub old: String,
+    pub new: String,
+    pub user: String,
+    pub timestamp: DateTime<Utc>,
+    pub message: String,
+}
+
+impl ReflogEntry {
+    fn parse(line: &str) -> Option<Self> {
+        let mut parts = line.splitn(5, ' ');
+        Some(Self {
+            old: parts.next()?.to_string(),
+            new: parts.next()?.to_string(),
+            user: parts.next()?.to_string(),
+            timestamp: DateTime::parse_from_rfc3339(parts.next()?).ok()?.with_timezone(&Utc),
+            message: parts.next().unwrap_or("").to_string(),
+        })
+    }
+}
+
+pub fn head_log_path(repo: &Repository) -> PathBuf {
+    repo.git_dir.join("logs").join("HEAD")
+}
+
+/// Records that HEAD moved from `old` to `new`. `message` is `<action>: <details>`.
+pub fn append(repo: &Repository, old: Option<&str>, new: &str, message: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let path = head_log_path(repo);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let line = format!("{} {} {} {} {}\n",
+                       old.filter(|hash| !hash.is_empty()).unwrap_or(NULL_HASH),
+                       new,
+                       whoami::username().replace(' ', "_"),
+                       Utc::now().to_rfc3339(),
+                       message.lines().next().unwrap_or(""));
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(line.as_bytes())?;
+    Ok(())
+}
+
+/// HEAD reflog entries, newest first, so index `n` is `HEAD@{n}`.
+pub fn read(repo: &Repository) -> Result<Vec<ReflogEntry>, Box<dyn std::error::Error>> {
+    let path = head_log_path(repo);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut entries = read_file(&path)?;
+    entries.reverse();
+    Ok(entries)
+}
+
+/// The `n` of `HEAD@{n}` (or the `@{n}` shorthand).
+pub fn parse_head_selector(spec: &str) -> Option<usize> {
+    spec.trim()
+        .strip_prefix("HEAD")
+        .unwrap_or(spec.trim())
+        .strip_prefix("@{")?
+        .strip_suffix('}')?
+        .parse()
+        .ok()
+}
+
+/// Where HEAD pointed `n` moves ago: the new hash of reflog entry `n`.
+pub fn resolve_head_selector(repo: &Repository, n: usize) -> Result<String, Box<dyn std::error::Error>> {
+    let entries = read(repo)?;
+    entries.get(n)
+        .map(|entry| entry.new.clone())
+        .ok_or_else(|| format!("HEAD@{{{}}} does not exist: the HEAD reflog has only {} entries", n, entries.len()).into())
+}
+
+/// Entries of a single reflog file, oldest first.
+pub fn read_file(path: &Path) -> Result<Vec<ReflogEntry>, Box<dyn std::error::Error>> {
+    Ok(std::fs::read_to_string(path)?
+        .lines()
+        .filter_map(ReflogEntry::parse)
+        .collect())
+}
+
+/// `logs/HEAD` plus every file under `logs/refs/`.
+pub fn log_files(repo: &Repository) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let head = head_log_path(repo);
+    if head.is_file() {
+        files.push(head);
+    }
+
+    let mut dirs = vec![repo.git_dir.join("logs").join("refs")];
+    while let Some(dir) = dirs.pop() {
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let path = entry.path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else {
+                files.push(path);
+            }
+        }
+    }
+
+    files.sort();
+    files
+}
@@ -1,10 +1,12 @@
 use crate::core::Repository;
+use crate::core::tag::peel;
 use std::fs;
 use std::collections::HashMap;
 
 pub struct Refs {
     pub heads: HashMap<String, String>,
     pub tags: HashMap<String, String>,
+    pub remotes: HashMap<String, String>,
 }
 
 impl Refs {
@@ -12,6 +14,7 @@ impl Refs {
         let mut refs = Refs {
             heads: HashMap::new(),
             tags: HashMap::new(),
+            remotes: HashMap::new(),
         };
 
         let heads_dir = repo.heads_dir();
@@ -44,9 +47,72 @@ impl Refs {
             }
         }
 
+        let remotes_dir = repo.refs_dir().join("remotes");
+        if remotes_dir.exists() {
+            for remote in fs::read_dir(&remotes_dir)? {
+                let remote = remote?;
+                let remote_name = match remote.file_name().to_str() {
+                    Some(name) if remote.path().is_dir() => name.to_string(),
+                    _ => continue,
+                };
+                for entry in fs::read_dir(remote.path())? {
+                    let entry = entry?;
+                    if let Some(name) = entry.file_name().to_str() {
+                        if let Ok(hash) = fs::read_to_string(entry.path()) {
+                            let hash = hash.trim();
+                            if !hash.is_empty() {
+                                refs.remotes.insert(format!("{}/{}", remote_name, name), hash.to_string());
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
         Ok(refs)
     }
 
+    /// Maps each commit hash to the refs pointing at it, formatted for
+    /// `log --decorate`: `HEAD -> <branch>` first, then tags, branches and
+    /// remote-tracking branches, each sorted by name.
+    pub fn decorations(&self, repo: &Repository) -> HashMap<String, Vec<String>> {
+        let mut decorations: HashMap<String, Vec<String>> = HashMap::new();
+        let head = fs::read_to_string(repo.git_dir.join("HEAD")).unwrap_or_default();
+        let current_branch = head.trim().strip_prefix("ref: refs/heads/").map(str::to_string);
+
+        match &current_branch {
+            Some(branch) => {
+                if let Some(hash) = self.heads.get(branch) {
+                    decorations.entry(hash.clone()).or_default().push(format!("HEAD -> {}", branch));
+                }
+            },
+            None if !head.trim().is_empty() => {
+                decorations.entry(head.trim().to_string()).or_default().push("HEAD".to_string());
+            },
+            None => {},
+        }
+
+        let sorted = |map: &HashMap<String, String>| {
+            let mut entries: Vec<(String, String)> = map.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+            entries.sort();
+            entries
+        };
+
+        for (name, hash) in sorted(&self.tags) {
+            decorations.entry(peel(repo, &hash)).or_default().push(format!("tag: {}", name));
+        }
+        for (name, hash) in sorted(&self.heads) {
+            if current_branch.as_deref() != Some(name.as_str()) {
+                decorations.entry(hash).or_default().push(name);
+            }
+        }
+        for (name, hash) in sorted(&self.remotes) {
+            decorations.entry(hash).or_default().push(name);
+        }
+
+        decorations
+    }
+
     pub fn get_head(&self, name: &str) -> Option<&String> {
         self.heads.get(name)
     }
@@ -56,7 +122,9 @@ impl Refs {
     }
 
     pub fn resolve(&self, name: &str) -> Option<&String> {
-        self.get_head(name).or_else(|| self.get_tag(name))
+        self.get_head(name)
+            .or_else(|| self.get_tag(name))
+            .or_else(|| self.remotes.get(name))
     }
 
     pub fn create_tag(&mut self, repo: &Repository, name: &str, commit_hash: &str) -> Result<(), Box<dyn std::error::Error>> {
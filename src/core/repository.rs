@@ -1,4 +1,7 @@
+use crate::core::Config;
+use crate::core::pack::PackCache;
 use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 use thiserror::Error;
 use ring::digest;
 
@@ -16,10 +19,17 @@ pub enum RepoError {
     Corrupted(String),
 }
 
+pub const MAX_OBJECT_SHARDING: usize = 2;
+
 pub struct Repository {
     pub path: PathBuf,
     pub git_dir: PathBuf,
     pub repo_id: String,
+    pub object_sharding: usize,
+    config: OnceLock<Config>,
+    pub(crate) encrypt_objects: OnceLock<bool>,
+    pub(crate) object_key: OnceLock<[u8; 32]>,
+    pub(crate) pack_cache: PackCache,
 }
 
 impl Repository {
@@ -43,14 +53,40 @@ impl Repository {
         };
         
         let repo_id = Self::load_repo_id(&git_dir).unwrap_or_else(|| "unknown".to_string());
+        let object_sharding = Self::load_object_sharding(&git_dir);
         
         Some(Repository { 
             path, 
             git_dir, 
-            repo_id 
+            repo_id,
+            object_sharding,
+            config: OnceLock::new(),
+            encrypt_objects: OnceLock::new(),
+            object_key: OnceLock::new(),
+            pack_cache: PackCache::default(),
         })
     }
 
+    /// Opens the repository containing the current directory, looking for a
+    /// `.aigit` with a valid `HEAD` in it and then in each parent in turn.
+    /// `path` is set to the root of that working tree.
+    pub fn discover() -> Option<Self> {
+        let current = std::env::current_dir().ok()?;
+        let root = Self::discover_root(&current)?;
+        if root == current {
+            // Keep the relative paths commands have always seen at the root.
+            return Self::new(".aigit");
+        }
+        Self::new(root.join(".aigit"))
+    }
+
+    /// The nearest of `start` and its parents that holds a repository.
+    pub fn discover_root(start: &Path) -> Option<PathBuf> {
+        start.ancestors()
+            .find(|dir| Self::is_valid_repo(&dir.join(".aigit")))
+            .map(Path::to_path_buf)
+    }
+
     pub fn init<P: AsRef<Path>>(path: P, bare: bool) -> Result<Self, RepoError> {
         let path = path.as_ref();
         let git_dir = if bare {
@@ -65,6 +101,7 @@ impl Repository {
 
         Self::create_repo_structure(&git_dir, bare)?;
         let repo_id = Self::generate_repo_id(&git_dir);
+        std::fs::write(git_dir.join("info/repo-id"), &repo_id)?;
         
         let work_dir = if bare { git_dir.clone() } else { path.to_path_buf() };
         
@@ -72,6 +109,11 @@ impl Repository {
             path: work_dir,
             git_dir,
             repo_id,
+            object_sharding: 1,
+            config: OnceLock::new(),
+            encrypt_objects: OnceLock::new(),
+            object_key: OnceLock::new(),
+            pack_cache: PackCache::default(),
         })
     }
 
@@ -100,8 +142,9 @@ impl Repository {
         let head_content = "ref: refs/heads/main\n";
         std::fs::write(git_dir.join("HEAD"), head_content)?;
         
-        let config_content = Self::create_repo_config(bare);
-        std::fs::write(git_dir.join("config"), config_content)?;
+        let config_content = serde_json::to_string_pretty(&Config::repo_defaults(bare))
+            .map_err(std::io::Error::other)?;
+        std::fs::write(git_dir.join("config.json"), config_content)?;
         
         let description = "AI-powered secure repository\n";
         std::fs::write(git_dir.join("description"), description)?;
@@ -113,48 +156,15 @@ impl Repository {
         Ok(())
     }
 
-    fn create_repo_config(bare: bool) -> String {
-        format!(
-            r#"[core]
-    repositoryformatversion = 0
-    filemode = true
-    bare = {}
-    logallrefupdates = true
-    ignorecase = false
-    precomposeunicode = true
-    protectHFS = true
-    protectNTFS = true
-    quotepath = false
-
-[security]
-    enabled = true
-    auditLog = true
-    requireSignature = false
-    encryptObjects = false
-    hashAlgorithm = sha256
-
-[ai]
-    enabled = true
-    model = gemini-pro
-    autoCommitMessage = true
-    reviewRequired = false
-"#,
-            bare
-        )
-    }
-
+    /// A new id for the repository at `git_dir`. Every call gives a
+    /// different one, so it is only used by `init`, which persists it to
+    /// `info/repo-id`; checks compare against that file instead.
     fn generate_repo_id(git_dir: &Path) -> String {
         let content = format!("{}{}", 
                              git_dir.to_string_lossy(), 
                              chrono::Utc::now().to_rfc3339());
         let digest = digest::digest(&digest::SHA256, content.as_bytes());
-        let id = hex::encode(digest.as_ref())[..16].to_string();
-        
-        if let Err(_) = std::fs::write(git_dir.join("info/repo-id"), &id) {
-            return "fallback".to_string();
-        }
-        
-        id
+        hex::encode(digest.as_ref())[..16].to_string()
     }
 
     fn load_repo_id(git_dir: &Path) -> Option<String> {
@@ -163,6 +173,14 @@ impl Repository {
             .map(|s| s.trim().to_string())
     }
 
+    fn load_object_sharding(git_dir: &Path) -> usize {
+        crate::core::Config::load_from_file(&git_dir.join("config.json"))
+            .ok()
+            .and_then(|config| config.get("core.objectSharding").and_then(|v| v.parse::<usize>().ok()))
+            .filter(|depth| (1..=MAX_OBJECT_SHARDING).contains(depth))
+            .unwrap_or(1)
+    }
+
     fn set_secure_permissions(path: &Path) -> Result<(), RepoError> {
         #[cfg(unix)]
         {
@@ -175,10 +193,43 @@ impl Repository {
         Ok(())
     }
 
+    /// The global config with this repository's settings layered on top,
+    /// loaded on first use and cached for the lifetime of this handle.
+    pub fn config(&self) -> &Config {
+        self.config.get_or_init(|| Config::load_effective(self))
+    }
+
     pub fn objects_dir(&self) -> PathBuf {
         self.git_dir.join("objects")
     }
 
+    pub fn object_path(&self, hash: &str) -> PathBuf {
+        Self::sharded_path(&self.objects_dir(), hash, self.object_sharding)
+    }
+
+    // New objects use the configured depth; lookups fall back to the other
+    // layouts so objects written before a sharding change stay readable.
+    pub fn find_object_path(&self, hash: &str) -> Option<PathBuf> {
+        std::iter::once(self.object_sharding)
+            .chain((1..=MAX_OBJECT_SHARDING).filter(|depth| *depth != self.object_sharding))
+            .map(|depth| Self::sharded_path(&self.objects_dir(), hash, depth))
+            .find(|path| path.exists())
+    }
+
+    fn sharded_path(objects_dir: &Path, hash: &str, depth: usize) -> PathBuf {
+        let mut path = objects_dir.to_path_buf();
+        let mut rest = hash;
+        for _ in 0..depth {
+            if rest.len() <= 2 {
+                break;
+            }
+            let (dir, remaining) = rest.split_at(2);
+            path.push(dir);
+            rest = remaining;
+        }
+        path.join(rest)
+    }
+
     pub fn refs_dir(&self) -> PathBuf {
         self.git_dir.join("refs")
     }
@@ -203,6 +254,8 @@ impl Repository {
         self.path == self.git_dir
     }
 
+    /// Checks the repository layout and that `info/repo-id` still holds the
+    /// id this handle was opened with. Nothing is written.
     pub fn verify_integrity(&self) -> Result<(), RepoError> {
         if !self.git_dir.join("HEAD").exists() {
             return Err(RepoError::Corrupted("Missing HEAD file".to_string()));
@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+use crate::core::{Repository, Object, Refs, Branch, Stash};
+use crate::core::object::PrefixError;
+use crate::core::pack;
+use crate::core::reflog;
+use crate::core::stash::parse_stash_index;
+use crate::core::tag::peel;
+
+/// Resolves a revision (`HEAD`, a branch or tag name, `stash@{n}`,
+/// `HEAD@{n}`, or a full or abbreviated commit hash) to the commit hash it
+/// names. Annotated tags
+/// are followed to the commit they tag.
+pub fn resolve_revision(repo: &Repository, spec: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let spec = spec.trim();
+
+    if spec == "HEAD" {
+        return Branch::get_current_commit(repo).ok_or_else(|| "HEAD does not point to a commit".into());
+    }
+
+    if spec == "stash" || spec.starts_with("stash@{") {
+        let n = parse_stash_index(if spec == "stash" { "0" } else { spec })
+            .ok_or_else(|| format!("Invalid stash reference '{}'", spec))?;
+        return Stash::load(repo)?.entries.get(n)
+            .cloned()
+            .ok_or_else(|| format!("{} does not exist", spec).into());
+    }
+
+    if let Some(n) = reflog::parse_head_selector(spec) {
+        return reflog::resolve_head_selector(repo, n);
+    }
+
+    let refs = Refs::load(repo)?;
+    if let Some(hash) = refs.resolve(spec) {
+        return Ok(peel(repo, hash));
+    }
+
+    if !spec.is_empty() && spec.chars().all(|c| c.is_ascii_hexdigit()) {
+        match Object::resolve_prefix(repo, spec) {
+            Ok(hash) => return Ok(peel(repo, &hash)),
+            Err(e @ PrefixError::Ambiguous { .. }) => return Err(e.into()),
+            Err(_) => {},
+        }
+    }
+
+    Err(format!("Unknown revision '{}'", spec).into())
+}
+
+pub const MIN_ABBREV: usize = 4;
+pub const DEFAULT_ABBREV: usize = 7;
+pub const FULL_HASH_LEN: usize = 64;
+
+/// Every object hash in the repository, loose or packed, sorted.
+pub fn all_object_hashes(repo: &Repository) -> Vec<String> {
+    let mut hashes = Object::list_objects(repo).unwrap_or_default();
+    for index in pack::indexes(repo).iter() {
+        hashes.extend(index.objects.keys().cloned());
+    }
+    hashes.sort();
+    hashes.dedup();
+    hashes
+}
+
+/// Shortest prefix of `hash` that no other entry of the sorted `all` shares.
+pub fn unique_prefix_len(all: &[String], hash: &str) -> usize {
+    let position = all.partition_point(|other| other.as_str() < hash);
+    let neighbours = [
+        position.checked_sub(1).and_then(|i| all.get(i)),
+        all.get(position).filter(|other| other.as_str() != hash),
+        all.get(position + 1),
+    ];
+
+    neighbours.iter()
+        .flatten()
+        .filter(|other| other.as_str() != hash)
+        .map(|other| other.chars().zip(hash.chars()).take_while(|(a, b)| a == b).count() + 1)
+        .max()
+        .unwrap_or(1)
+        .min(hash.len())
+}
+
+/// The abbreviation length at which every hash in `hashes` is unambiguous
+/// among all objects in the repository, but never shorter than `floor`.
+/// Only the objects sharing a hash's two-character shard are compared.
+pub fn min_unique_abbrev(repo: &Repository, hashes: &[String], floor: usize) -> usize {
+    let mut shards: HashMap<&str, Vec<String>> = HashMap::new();
+    hashes.iter()
+        .filter(|hash| hash.len() > 2)
+        .map(|hash| {
+            let neighbours = shards.entry(&hash[..2]).or_insert_with(|| Object::with_prefix(repo, &hash[..2]));
+            unique_prefix_len(neighbours, hash)
+        })
+        .fold(floor, usize::max)
+        .min(FULL_HASH_LEN)
+}
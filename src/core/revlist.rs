@@ -0,0 +1,96 @@
+use crate::core::{Repository, Object, Commit};
+use crate::core::revision::resolve_revision;
+use chrono::{DateTime, Utc};
+use std::collections::{BinaryHeap, HashSet};
+
+/// A revision range: commits reachable from `include` but not from `exclude`.
+pub struct RevRange {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+impl RevRange {
+    /// Parses `<rev>` or `<a>..<b>`; an empty side of a range means `HEAD`.
+    pub fn parse(repo: &Repository, spec: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        match spec.split_once("..") {
+            Some((from, to)) => {
+                let from = if from.is_empty() { "HEAD" } else { from };
+                let to = if to.is_empty() { "HEAD" } else { to };
+                Ok(Self {
+                    include: vec![resolve_revision(repo, to)?],
+                    exclude: vec![resolve_revision(repo, from)?],
+                })
+            },
+            None => Ok(Self {
+                include: vec![resolve_revision(repo, spec)?],
+                exclude: Vec::new(),
+            }),
+        }
+    }
+}
+
+fn read_commit(repo: &Repository, hash: &str) -> Result<Commit, Box<dyn std::error::Error>> {
+    Ok(serde_json::from_slice(&Object::read(repo, hash)?)?)
+}
+
+/// A commit's parents, falling back to the single `parent` field.
+pub fn parents_of(commit: &Commit) -> Vec<String> {
+    if commit.parents.is_empty() {
+        commit.parent.iter().cloned().collect()
+    } else {
+        commit.parents.clone()
+    }
+}
+
+/// Every commit reachable from `tips` through all parents.
+pub fn reachable_commits(repo: &Repository, tips: &[String]) -> Result<HashSet<String>, Box<dyn std::error::Error>> {
+    let mut seen = HashSet::new();
+    let mut to_visit: Vec<String> = tips.to_vec();
+
+    while let Some(hash) = to_visit.pop() {
+        if hash.is_empty() || !seen.insert(hash.clone()) {
+            continue;
+        }
+        to_visit.extend(parents_of(&read_commit(repo, &hash)?));
+    }
+
+    Ok(seen)
+}
+
+/// Lists the commits in `range` newest first, each exactly once.
+pub fn rev_list(repo: &Repository, range: &RevRange) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let excluded = reachable_commits(repo, &range.exclude)?;
+    let mut seen = HashSet::new();
+    let mut queue: BinaryHeap<(DateTime<Utc>, String)> = BinaryHeap::new();
+    let mut commits = Vec::new();
+
+    for tip in &range.include {
+        if !tip.is_empty() && !excluded.contains(tip) && seen.insert(tip.clone()) {
+            queue.push((read_commit(repo, tip)?.timestamp, tip.clone()));
+        }
+    }
+
+    while let Some((_, hash)) = queue.pop() {
+        let commit = read_commit(repo, &hash)?;
+        for parent in parents_of(&commit) {
+            if !excluded.contains(&parent) && seen.insert(parent.clone()) {
+                queue.push((read_commit(repo, &parent)?.timestamp, parent));
+            }
+        }
+        commits.push(hash);
+    }
+
+    Ok(commits)
+}
+
+/// How many commits make up the history of `tip`, counting each shared
+/// ancestor of a merge once. An empty `tip` (an unborn branch) has none.
+pub fn count_history(repo: &Repository, tip: &str) -> Result<usize, Box<dyn std::error::Error>> {
+    count_commits(repo, &RevRange { include: vec![tip.to_string()], exclude: Vec::new() })
+}
+
+pub fn count_commits(repo: &Repository, range: &RevRange) -> Result<usize, Box<dyn std::error::Error>> {
+    let included = reachable_commits(repo, &range.include)?;
+    let excluded = reachable_commits(repo, &range.exclude)?;
+    Ok(included.difference(&excluded).count())
+}
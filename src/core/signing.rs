@@ -0,0 +1,93 @@
+use crate::core::{Repository, Commit};
+use ring::rand::SystemRandom;
+use ring::signature::{Ed25519KeyPair, KeyPair, UnparsedPublicKey, ED25519};
+use std::path::PathBuf;
+
+/// Where signing keys live unless `security.keyring` names another directory.
+pub const DEFAULT_KEYRING: &str = "security/keys";
+
+/// The keyring directory. Each key is an Ed25519 PKCS#8 file named after its
+/// key id, with an optional `<keyid>.pub` holding the raw public key.
+pub fn keyring_dir(repo: &Repository) -> PathBuf {
+    match repo.config().get("security.keyring") {
+        Some(dir) => repo.path.join(dir),
+        None => repo.git_dir.join(DEFAULT_KEYRING),
+    }
+}
+
+/// Generates a new key pair named `key_id` in the keyring and returns its
+/// public key, hex encoded.
+pub fn generate_key(repo: &Repository, key_id: &str) -> Result<String, Box<dyn std::error::Error>> {
+    validate_key_id(key_id)?;
+    let dir = keyring_dir(repo);
+    let path = dir.join(key_id);
+    if path.exists() {
+        return Err(format!("Signing key '{}' already exists", key_id).into());
+    }
+
+    let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new())
+        .map_err(|_| "Failed to generate signing key")?;
+    let key_pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref())
+        .map_err(|_| "Failed to generate signing key")?;
+
+    std::fs::create_dir_all(&dir)?;
+    std::fs::write(&path, pkcs8.as_ref())?;
+    std::fs::write(dir.join(format!("{}.pub", key_id)), key_pair.public_key().as_ref())?;
+    Ok(hex::encode(key_pair.public_key().as_ref()))
+}
+
+/// Signs `commit` with the key `key_id`, recording the key id so the
+/// signature can be checked later.
+pub fn sign_commit(repo: &Repository, commit: &mut Commit, key_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let key_pair = load_key_pair(repo, key_id)?;
+    commit.signing_key = Some(key_id.to_string());
+    let payload = signed_payload(commit)?;
+    commit.signature = Some(hex::encode(key_pair.sign(&payload).as_ref()));
+    Ok(())
+}
+
+/// Checks a signed commit against the public key of the key it names.
+/// Returns the key id on success.
+pub fn verify_commit(repo: &Repository, commit: &Commit) -> Result<String, Box<dyn std::error::Error>> {
+    let key_id = commit.signing_key.clone().ok_or("Commit is not signed with a key")?;
+    let signature = commit.signature.as_deref().ok_or("Commit has no signature")?;
+    let signature = hex::decode(signature).map_err(|_| "Malformed signature")?;
+
+    let public_key = load_public_key(repo, &key_id)?;
+    UnparsedPublicKey::new(&ED25519, public_key)
+        .verify(&signed_payload(commit)?, &signature)
+        .map_err(|_| format!("Bad signature from key '{}'", key_id))?;
+    Ok(key_id)
+}
+
+/// The bytes a signature covers: the commit with its signature left out.
+fn signed_payload(commit: &Commit) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut unsigned = commit.clone();
+    unsigned.signature = None;
+    Ok(serde_json::to_vec(&unsigned)?)
+}
+
+fn load_key_pair(repo: &Repository, key_id: &str) -> Result<Ed25519KeyPair, Box<dyn std::error::Error>> {
+    validate_key_id(key_id)?;
+    let path = keyring_dir(repo).join(key_id);
+    let pkcs8 = std::fs::read(&path)
+        .map_err(|_| format!("Signing key '{}' not found in {}", key_id, keyring_dir(repo).display()))?;
+    Ed25519KeyPair::from_pkcs8_maybe_unchecked(&pkcs8)
+        .map_err(|_| format!("Signing key '{}' is not an Ed25519 PKCS#8 key", key_id).into())
+}
+
+fn load_public_key(repo: &Repository, key_id: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    validate_key_id(key_id)?;
+    let public_path = keyring_dir(repo).join(format!("{}.pub", key_id));
+    if let Ok(public_key) = std::fs::read(&public_path) {
+        return Ok(public_key);
+    }
+    Ok(load_key_pair(repo, key_id)?.public_key().as_ref().to_vec())
+}
+
+fn validate_key_id(key_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+    if key_id.is_empty() || !key_id.chars().all(|c| c.is_ascii_alphanumeric() || "-_.@".contains(c)) || key_id.starts_with('.') {
+        return Err(format!("Invalid signing key id '{}'", key_id).into());
+    }
+    Ok(())
+}
@@ -0,0 +1,325 @@
+use crate::core::{Repository, Object, ObjectType, Commit, Tree, TreeEntry, Index, Branch};
+use crate::core::object::hash_object;
+use crate::core::index::index_path;
+use crate::utils::conflicts::merge_text;
+use crate::utils::worktree::{kind_of, read_entry_content, write_entry, EntryKind, SYMLINK_MODE};
+use ring::digest;
+use std::collections::BTreeMap;
+
+/// The content and mode `apply` writes to a path, or `None` to remove it.
+type PendingWrite = Option<(Vec<u8>, String)>;
+
+pub struct StashEntry {
+    pub hash: String,
+    pub commit: Commit,
+}
+
+pub struct Stash {
+    pub entries: Vec<String>,
+}
+
+impl Stash {
+    pub fn load(repo: &Repository) -> Result<Self, Box<dyn std::error::Error>> {
+        let stash_path = repo.git_dir.join("refs/stash");
+
+        if !stash_path.exists() {
+            return Ok(Self { entries: Vec::new() });
+        }
+
+        let entries = std::fs::read_to_string(&stash_path)?
+            .lines()
+            .map(|line| line.trim().to_string())
+            .filter(|line| !line.is_empty())
+            .collect();
+
+        Ok(Self { entries })
+    }
+
+    pub fn save(&self, repo: &Repository) -> Result<(), Box<dyn std::error::Error>> {
+        let stash_path = repo.git_dir.join("refs/stash");
+
+        if self.entries.is_empty() {
+            if stash_path.exists() {
+                std::fs::remove_file(&stash_path)?;
+            }
+            return Ok(());
+        }
+
+        let mut content = self.entries.join("\n");
+        content.push('\n');
+
+        let temp_path = stash_path.with_extension("tmp");
+        std::fs::write(&temp_path, content)?;
+        std::fs::rename(&temp_path, &stash_path)?;
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn get(&self, repo: &Repository, n: usize) -> Result<StashEntry, Box<dyn std::error::Error>> {
+        let hash = self.entries.get(n)
+            .cloned()
+            .ok_or_else(|| format!("stash@{{{}}} does not exist", n))?;
+        let commit: Commit = serde_json::from_slice(&Object::read(repo, &hash)?)?;
+        Ok(StashEntry { hash, commit })
+    }
+
+    pub fn remove(&mut self, n: usize) -> Result<String, Box<dyn std::error::Error>> {
+        if n >= self.entries.len() {
+            return Err(format!("stash@{{{}}} does not exist", n).into());
+        }
+        Ok(self.entries.remove(n))
+    }
+
+    /// Snapshots tracked files from the working tree into a stash commit whose
+    /// parents are HEAD and a commit of the current index, then resets the
+    /// working tree and index back to HEAD.
+    pub fn push(repo: &Repository, message: Option<&str>) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let head = Branch::get_current_commit(repo).ok_or("Cannot stash before the first commit")?;
+        let head_files = commit_files(repo, &head)?;
+        let mut index = Index::load(repo)?;
+
+        let mut tracked: BTreeMap<String, String> = head_files.iter()
+            .map(|(path, entry)| (path.clone(), entry.hash.clone()))
+            .collect();
+        tracked.extend(index.entries.iter().map(|(path, hash)| (path.clone(), hash.clone())));
+
+        let mut snapshot = Index::new();
+        let mut changed = !index.is_empty();
+        for (path, base_hash) in &tracked {
+            let content = match read_entry_content(path) {
+                Ok(content) => content,
+                Err(_) => {
+                    changed = true;
+                    continue;
+                }
+            };
+            let hash = Object::create(repo, ObjectType::Blob, &content)?;
+            changed |= &hash != base_hash || !head_files.contains_key(path);
+            let mode = match kind_of(path) {
+                Some(EntryKind::Symlink) => SYMLINK_MODE,
+                _ => "100644",
+            };
+            snapshot.add_entry(path.clone(), hash, mode.to_string());
+        }
+
+        if !changed {
+            return Ok(None);
+        }
+
+        let config = repo.config();
+        let branch = Branch::get_current_branch(repo).unwrap_or_else(|| "(no branch)".to_string());
+        let subject = head_subject(repo, &head);
+        let message = match message {
+            Some(message) => format!("On {}: {}", branch, message),
+            None => format!("WIP on {}: {} {}", branch, &head[..head.len().min(8)], subject),
+        };
+
+        let index_tree = Tree::create_from_index(repo, &index)?;
+        let index_commit = Commit::new(
+            index_tree,
+            Some(head.clone()),
+            config.get_user_name(),
+            config.get_user_email(),
+            format!("index on {}: {} {}", branch, &head[..head.len().min(8)], subject),
+        );
+        let index_commit_hash = Object::create(repo, ObjectType::Commit, &serde_json::to_vec(&index_commit)?)?;
+
+        let work_tree = Tree::create_from_index(repo, &snapshot)?;
+        let mut stash_commit = Commit::new_merge(
+            work_tree,
+            vec![head.clone(), index_commit_hash],
+            config.get_user_name(),
+            config.get_user_email(),
+            message,
+            String::new(),
+        );
+        stash_commit.signature = None;
+        let stash_hash = Object::create(repo, ObjectType::Commit, &serde_json::to_vec(&stash_commit)?)?;
+
+        for path in tracked.keys() {
+            match head_files.get(path) {
+                Some(entry) => write_entry(path, &Object::read(repo, &entry.hash)?, &entry.mode)?,
+                None => {
+                    if kind_of(path).is_some() {
+                        std::fs::remove_file(path)?;
+                    }
+                }
+            }
+        }
+
+        index.clear(repo)?;
+        stage_files(repo, &mut index, &head_files)?;
+        index.save(repo)?;
+
+        let mut stash = Self::load(repo)?;
+        stash.entries.insert(0, stash_hash.clone());
+        stash.save(repo)?;
+
+        Ok(Some(stash_hash))
+    }
+
+    /// Replays the stash on top of the current working tree and index. Only
+    /// the paths the stash changed relative to the commit it was made on are
+    /// touched: each one is written (or removed) when the working tree still
+    /// matches that commit, merged line by line when both sides edited a text
+    /// file, and refused otherwise, before anything is written. Staged changes
+    /// are restored for paths whose index entry still matches the base. The
+    /// stack itself is left untouched.
+    pub fn apply(&self, repo: &Repository, n: usize) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let entry = self.get(repo, n)?;
+        let base = match entry.commit.parents.first() {
+            Some(base) => commit_files(repo, base)?,
+            None => BTreeMap::new(),
+        };
+        let work = Tree::from_hash(repo, &entry.commit.tree)?.flatten_entries(repo, "")?;
+        let staged = match entry.commit.parents.get(1) {
+            Some(hash) => Some(commit_files(repo, hash)?),
+            None => None,
+        };
+
+        let hash_of = |files: &BTreeMap<String, TreeEntry>, path: &str| files.get(path).map(|file| file.hash.clone());
+        let mut paths: Vec<&String> = base.keys().chain(work.keys()).collect();
+        if let Some(staged) = &staged {
+            paths.extend(staged.keys());
+        }
+        paths.sort();
+        paths.dedup();
+
+        // Work out every write first so a conflict leaves nothing half applied.
+        let mut writes: Vec<(String, PendingWrite)> = Vec::new();
+        let mut conflicts = Vec::new();
+        for path in &paths {
+            let stashed = hash_of(&work, path);
+            let original = hash_of(&base, path);
+            if stashed == original {
+                continue;
+            }
+            let current = read_entry_content(path).ok();
+            let current_hash = current.as_deref().map(|content| hash_object(&ObjectType::Blob, content));
+            if current_hash == stashed {
+                continue;
+            }
+
+            let stashed_entry = work.get(*path);
+            if current_hash == original {
+                let content = match stashed_entry {
+                    Some(file) => Some((Object::read(repo, &file.hash)?, file.mode.clone())),
+                    None => None,
+                };
+                writes.push((path.to_string(), content));
+                continue;
+            }
+
+            match (base.get(*path), current, stashed_entry) {
+                (Some(original), Some(current), Some(stashed)) => {
+                    let texts = (
+                        String::from_utf8(Object::read(repo, &original.hash)?),
+                        String::from_utf8(current),
+                        String::from_utf8(Object::read(repo, &stashed.hash)?),
+                    );
+                    match texts {
+                        (Ok(original), Ok(current), Ok(stashed_text)) => {
+                            let (merged, conflicted) = merge_text(&original, &current, &stashed_text, "Updated upstream", "Stashed changes");
+                            if conflicted == 0 {
+                                writes.push((path.to_string(), Some((merged.into_bytes(), stashed.mode.clone()))));
+                            } else {
+                                conflicts.push(path.to_string());
+                            }
+                        },
+                        _ => conflicts.push(path.to_string()),
+                    }
+                },
+                _ => conflicts.push(path.to_string()),
+            }
+        }
+
+        if !conflicts.is_empty() {
+            return Err(format!(
+                "Local changes to the following files would be overwritten by the stash:\n  {}\nCommit or stash them first",
+                conflicts.join("\n  ")
+            ).into());
+        }
+
+        let mut restored = Vec::new();
+        for (path, content) in &writes {
+            match content {
+                Some((content, mode)) => write_entry(path, content, mode)?,
+                None => {
+                    if kind_of(path).is_some() {
+                        std::fs::remove_file(path)?;
+                    }
+                },
+            }
+            restored.push(path.clone());
+        }
+
+        if let Some(staged) = &staged {
+            let mut index = Index::load(repo)?;
+            let mut changed = false;
+            for path in &paths {
+                let wanted = hash_of(staged, path);
+                let original = hash_of(&base, path);
+                if wanted == original || index_hash(&index, path) != original {
+                    continue;
+                }
+                index.remove_entry(path);
+                if let Some(file) = staged.get(*path) {
+                    let content = Object::read(repo, &file.hash)?;
+                    let checksum = hex::encode(digest::digest(&digest::SHA256, &content).as_ref());
+                    index.add_entry_secure(path.to_string(), file.hash.clone(), file.mode.clone(), content.len() as u64, checksum);
+                }
+                changed = true;
+            }
+            if changed {
+                index.save(repo)?;
+            }
+        }
+
+        Ok(restored)
+    }
+}
+
+/// The blob staged for `path`, which may come from a tree written before
+/// index keys dropped their "./" prefix.
+fn index_hash(index: &Index, path: &str) -> Option<String> {
+    index.entries.get(&index_path(path)).cloned()
+}
+
+/// Adds every file in `files` to `index` as it is stored in the repository.
+fn stage_files(repo: &Repository, index: &mut Index, files: &BTreeMap<String, TreeEntry>) -> Result<(), Box<dyn std::error::Error>> {
+    for (path, file) in files {
+        let content = Object::read(repo, &file.hash)?;
+        let checksum = hex::encode(digest::digest(&digest::SHA256, &content).as_ref());
+        index.add_entry_secure(path.clone(), file.hash.clone(), file.mode.clone(), content.len() as u64, checksum);
+    }
+    Ok(())
+}
+
+pub fn parse_stash_index(spec: &str) -> Option<usize> {
+    let spec = spec.trim();
+    spec.strip_prefix("stash@{")
+        .and_then(|rest| rest.strip_suffix('}'))
+        .unwrap_or(spec)
+        .parse()
+        .ok()
+}
+
+fn commit_files(repo: &Repository, hash: &str) -> Result<BTreeMap<String, TreeEntry>, Box<dyn std::error::Error>> {
+    let commit: Commit = serde_json::from_slice(&Object::read(repo, hash)?)?;
+    Tree::from_hash(repo, &commit.tree)?.flatten_entries(repo, "")
+}
+
+fn head_subject(repo: &Repository, hash: &str) -> String {
+    Object::read(repo, hash)
+        .ok()
+        .and_then(|content| serde_json::from_slice::<Commit>(&content).ok())
+        .map(|commit| commit.short_message())
+        .unwrap_or_default()
+}
@@ -0,0 +1,67 @@
+use crate::core::{Repository, Object, ObjectType, Author};
+use chrono::Utc;
+use ring::digest;
+use serde::{Deserialize, Serialize};
+
+/// An annotated tag: a named, messaged pointer at another object, stored as
+/// an `ObjectType::Tag` object that `refs/tags/<name>` points at.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Tag {
+    /// Hash of the tagged object, usually a commit.
+    pub object: String,
+    pub name: String,
+    pub tagger: Author,
+    pub message: String,
+    pub signature: Option<String>,
+}
+
+impl Tag {
+    pub fn new(object: String, name: String, tagger_name: String, tagger_email: String, message: String) -> Self {
+        let tagger = Author {
+            name: tagger_name,
+            email: tagger_email,
+            timestamp: Utc::now(),
+        };
+        let content = format!("{}\n{}\n{}\n{}", object, name, message, tagger.timestamp.to_rfc3339());
+        let signature = hex::encode(digest::digest(&digest::SHA256, content.as_bytes()).as_ref());
+
+        Self {
+            object,
+            name,
+            tagger,
+            message,
+            signature: Some(signature),
+        }
+    }
+
+    /// Writes the tag object and returns its hash.
+    pub fn create(&self, repo: &Repository) -> Result<String, Box<dyn std::error::Error>> {
+        Object::create(repo, ObjectType::Tag, &serde_json::to_vec(self)?)
+    }
+
+    /// The tag object `hash`, or `None` when `hash` names another kind of object.
+    pub fn read(repo: &Repository, hash: &str) -> Result<Option<Self>, Box<dyn std::error::Error>> {
+        match Object::read_with_type(repo, hash)? {
+            (ObjectType::Tag, content) => Ok(Some(serde_json::from_slice(&content)?)),
+            _ => Ok(None),
+        }
+    }
+
+    pub fn short_message(&self) -> String {
+        self.message.lines().next().unwrap_or("").to_string()
+    }
+}
+
+/// Follows tag objects from `hash` to the object they finally point at.
+/// Anything that is not a readable tag object is returned unchanged.
+pub fn peel(repo: &Repository, hash: &str) -> String {
+    let mut current = hash.to_string();
+    // Bounded so a hand-crafted tag cycle cannot loop forever.
+    for _ in 0..16 {
+        match Tag::read(repo, &current) {
+            Ok(Some(tag)) => current = tag.object,
+            _ => break,
+        }
+    }
+    current
+}
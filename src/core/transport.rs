@@ -0,0 +1,193 @@
+use crate::core::{Repository, Object, Refs, Config, crypto};
+use crate::core::gc::walk_reachable;
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+
+pub const DEFAULT_TRANSFER_WORKERS: usize = 4;
+
+#[derive(Default)]
+pub struct TransferReport {
+    pub wanted: usize,
+    pub transferred: usize,
+    pub bytes: u64,
+}
+
+/// A remote repository reachable over the local filesystem, configured as
+/// `remote.<name>.url`.
+pub struct Remote {
+    pub name: String,
+    pub repo: Repository,
+}
+
+impl Remote {
+    pub fn open(config: &Config, name: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let url = config.get(&format!("remote.{}.url", name))
+            .ok_or_else(|| format!("Remote '{}' is not configured (set remote.{}.url)", name, name))?;
+        Ok(Self {
+            name: name.to_string(),
+            repo: open_repository(url)?,
+        })
+    }
+
+    pub fn heads(&self) -> Result<BTreeMap<String, String>, Box<dyn std::error::Error>> {
+        Ok(Refs::load(&self.repo)?.heads.into_iter().collect())
+    }
+}
+
+fn open_repository(url: &str) -> Result<Repository, Box<dyn std::error::Error>> {
+    let path = Path::new(url.strip_prefix("file://").unwrap_or(url));
+    Repository::new(path.join(".aigit"))
+        .or_else(|| Repository::new(path))
+        .ok_or_else(|| format!("'{}' is not an aigit repository", url).into())
+}
+
+/// Have/want negotiation: everything reachable from `tips` in `from`, minus
+/// what `to` can already reach from its own refs or already stores.
+pub fn missing_objects(
+    from: &Repository,
+    to: &Repository,
+    tips: &[String]
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let mut want = HashSet::new();
+    walk_reachable(from, tips.to_vec(), &mut want);
+
+    let to_refs = Refs::load(to)?;
+    let mut have = HashSet::new();
+    let roots: Vec<String> = to_refs.heads.values()
+        .chain(to_refs.tags.values())
+        .cloned()
+        .collect();
+    walk_reachable(to, roots, &mut have);
+
+    let mut missing: Vec<String> = want.into_iter()
+        .filter(|hash| !have.contains(hash) && !Object::exists(to, hash))
+        .collect();
+    missing.sort();
+    Ok(missing)
+}
+
+pub fn transfer(
+    from: &Repository,
+    to: &Repository,
+    tips: &[String],
+    workers: usize
+) -> Result<TransferReport, Box<dyn std::error::Error>> {
+    let missing = missing_objects(from, to, tips)?;
+    let mut report = TransferReport {
+        wanted: missing.len(),
+        ..Default::default()
+    };
+
+    if missing.is_empty() {
+        return Ok(report);
+    }
+
+    let chunk_size = missing.len().div_ceil(workers.max(1));
+    let results: Vec<Result<(usize, u64), String>> = std::thread::scope(|scope| {
+        let handles: Vec<_> = missing.chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || copy_objects(from, to, chunk).map_err(|e| e.to_string())))
+            .collect();
+        handles.into_iter()
+            .map(|handle| handle.join().unwrap_or_else(|_| Err("transfer worker panicked".to_string())))
+            .collect()
+    });
+
+    for result in results {
+        let (count, bytes) = result?;
+        report.transferred += count;
+        report.bytes += bytes;
+    }
+
+    Ok(report)
+}
+
+fn copy_objects(from: &Repository, to: &Repository, hashes: &[String]) -> Result<(usize, u64), Box<dyn std::error::Error>> {
+    let mut count = 0;
+    let mut bytes = 0;
+
+    for hash in hashes {
+        let raw = crypto::seal(to, hash, Object::read_compressed(from, hash)?)?;
+        let target = to.object_path(hash);
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let temp_path = target.with_extension(format!("tmp-{}", uuid::Uuid::new_v4()));
+        std::fs::write(&temp_path, &raw)?;
+        std::fs::rename(&temp_path, &target)?;
+        bytes += raw.len() as u64;
+        count += 1;
+    }
+
+    Ok((count, bytes))
+}
+
+pub fn push(local: &Repository, remote: &Remote, branch: &str) -> Result<TransferReport, Box<dyn std::error::Error>> {
+    let tip = std::fs::read_to_string(local.heads_dir().join(branch))?.trim().to_string();
+    if tip.is_empty() {
+        return Err(format!("Branch '{}' has no commits", branch).into());
+    }
+
+    if let Some(remote_tip) = remote.heads()?.get(branch) {
+        let mut ancestors = HashSet::new();
+        walk_reachable(local, vec![tip.clone()], &mut ancestors);
+        if !ancestors.contains(remote_tip) {
+            return Err(format!("Rejected non-fast-forward push to '{}/{}'", remote.name, branch).into());
+        }
+    }
+
+    let report = transfer(local, &remote.repo, std::slice::from_ref(&tip), DEFAULT_TRANSFER_WORKERS)?;
+
+    std::fs::write(remote.repo.heads_dir().join(branch), &tip)?;
+    write_tracking_ref(local, &remote.name, branch, &tip)?;
+
+    Ok(report)
+}
+
+pub fn fetch(local: &Repository, remote: &Remote) -> Result<(TransferReport, BTreeMap<String, String>), Box<dyn std::error::Error>> {
+    let heads = remote.heads()?;
+    let tips: Vec<String> = heads.values().cloned().collect();
+
+    let report = transfer(&remote.repo, local, &tips, DEFAULT_TRANSFER_WORKERS)?;
+
+    for (branch, hash) in &heads {
+        write_tracking_ref(local, &remote.name, branch, hash)?;
+    }
+
+    Ok((report, heads))
+}
+
+/// Deletes the tracking refs under `refs/remotes/<remote>/` for branches not
+/// in `heads`, the remote's current branches. Returns the pruned branch
+/// names, sorted.
+pub fn prune_tracking_refs(
+    local: &Repository,
+    remote: &str,
+    heads: &BTreeMap<String, String>
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let dir = local.refs_dir().join("remotes").join(remote);
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut pruned = Vec::new();
+    for entry in std::fs::read_dir(&dir)? {
+        let entry = entry?;
+        if let Some(name) = entry.file_name().to_str() {
+            if entry.path().is_file() && !heads.contains_key(name) {
+                std::fs::remove_file(entry.path())?;
+                pruned.push(name.to_string());
+            }
+        }
+    }
+
+    pruned.sort();
+    Ok(pruned)
+}
+
+fn write_tracking_ref(repo: &Repository, remote: &str, branch: &str, hash: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let dir = repo.refs_dir().join("remotes").join(remote);
+    std::fs::create_dir_all(&dir)?;
+    std::fs::write(dir.join(branch), hash)?;
+    Ok(())
+}
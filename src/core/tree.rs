@@ -1,6 +1,7 @@
 use crate::core::{Repository, Index, Object, ObjectType};
+use crate::core::object::hash_object;
 use serde::{Serialize, Deserialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, BTreeMap};
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct TreeEntry {
@@ -10,11 +11,29 @@ pub struct TreeEntry {
     pub entry_type: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChangeKind {
+    Added,
+    Modified,
+    Deleted,
+}
+
+#[derive(Debug, Clone)]
+pub struct TreeChange {
+    pub path: String,
+    pub kind: ChangeKind,
+    pub old_hash: Option<String>,
+    pub new_hash: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
 pub struct Tree {
     pub entries: Vec<TreeEntry>,
 }
 
+// Root tree hash plus the serialized content of every tree object, root last.
+pub type PreparedTree = (String, Vec<Vec<u8>>);
+
 impl Tree {
     pub fn new() -> Self {
         Self {
@@ -34,8 +53,19 @@ impl Tree {
     }
 
     pub fn create_from_index(repo: &Repository, index: &Index) -> Result<String, Box<dyn std::error::Error>> {
+        let (tree_hash, tree_objects) = Self::prepare_from_index(index)?;
+        let objects: Vec<(ObjectType, Vec<u8>)> = tree_objects
+            .into_iter()
+            .map(|content| (ObjectType::Tree, content))
+            .collect();
+        Object::create_batch(repo, &objects)?;
+        Ok(tree_hash)
+    }
+
+    pub fn prepare_from_index(index: &Index) -> Result<PreparedTree, Box<dyn std::error::Error>> {
         let mut tree = Tree::new();
         let mut directories = HashMap::new();
+        let mut objects = Vec::new();
 
         for (path, hash) in &index.entries {
             let parts: Vec<&str> = path.split('/').collect();
@@ -52,23 +82,30 @@ impl Tree {
                 }
                 
                 let remaining_path = parts[1..].join("/");
-                directories.get_mut(dir).unwrap().push((remaining_path, hash.clone()));
+                let mode = index.metadata.get(path)
+                    .map(|m| m.mode.clone())
+                    .unwrap_or_else(|| "100644".to_string());
+                directories.get_mut(dir).unwrap().push((remaining_path, hash.clone(), mode));
             }
         }
 
         for (dir_name, files) in directories {
             let mut subtree = Tree::new();
-            for (file_path, file_hash) in files {
-                subtree.add_entry("100644".to_string(), file_path, file_hash, "blob".to_string());
+            for (file_path, file_hash, mode) in files {
+                subtree.add_entry(mode, file_path, file_hash, "blob".to_string());
             }
             
-            let subtree_content = serde_json::to_string(&subtree)?;
-            let subtree_hash = Object::create(repo, ObjectType::Tree, subtree_content.as_bytes())?;
+            let subtree_content = serde_json::to_vec(&subtree)?;
+            let subtree_hash = hash_object(&ObjectType::Tree, &subtree_content);
+            objects.push(subtree_content);
             tree.add_entry("040000".to_string(), dir_name, subtree_hash, "tree".to_string());
         }
 
-        let tree_content = serde_json::to_string(&tree)?;
-        Object::create(repo, ObjectType::Tree, tree_content.as_bytes())
+        let tree_content = serde_json::to_vec(&tree)?;
+        let tree_hash = hash_object(&ObjectType::Tree, &tree_content);
+        objects.push(tree_content);
+
+        Ok((tree_hash, objects))
     }
 
     pub fn from_hash(repo: &Repository, hash: &str) -> Result<Self, Box<dyn std::error::Error>> {
@@ -101,4 +138,96 @@ impl Tree {
         
         Ok(files)
     }
+
+    pub fn flatten(&self, repo: &Repository, prefix: &str) -> Result<BTreeMap<String, String>, Box<dyn std::error::Error>> {
+        let mut files = BTreeMap::new();
+
+        for entry in &self.entries {
+            let full_path = if prefix.is_empty() {
+                entry.name.clone()
+            } else {
+                format!("{}/{}", prefix, entry.name)
+            };
+
+            if entry.entry_type == "tree" {
+                let subtree = Tree::from_hash(repo, &entry.hash)?;
+                files.extend(subtree.flatten(repo, &full_path)?);
+            } else {
+                files.insert(full_path, entry.hash.clone());
+            }
+        }
+
+        Ok(files)
+    }
+
+    /// Like `flatten`, but keeps each blob's mode. Entry names are full paths.
+    pub fn flatten_entries(&self, repo: &Repository, prefix: &str) -> Result<BTreeMap<String, TreeEntry>, Box<dyn std::error::Error>> {
+        let mut files = BTreeMap::new();
+
+        for entry in &self.entries {
+            let full_path = if prefix.is_empty() {
+                entry.name.clone()
+            } else {
+                format!("{}/{}", prefix, entry.name)
+            };
+
+            if entry.entry_type == "tree" {
+                let subtree = Tree::from_hash(repo, &entry.hash)?;
+                files.extend(subtree.flatten_entries(repo, &full_path)?);
+            } else {
+                files.insert(full_path.clone(), TreeEntry { name: full_path, ..entry.clone() });
+            }
+        }
+
+        Ok(files)
+    }
+
+    pub fn diff(
+        repo: &Repository,
+        old_tree: Option<&str>,
+        new_tree: Option<&str>
+    ) -> Result<Vec<TreeChange>, Box<dyn std::error::Error>> {
+        let old_files = match old_tree {
+            Some(hash) => Tree::from_hash(repo, hash)?.flatten(repo, "")?,
+            None => BTreeMap::new(),
+        };
+        let new_files = match new_tree {
+            Some(hash) => Tree::from_hash(repo, hash)?.flatten(repo, "")?,
+            None => BTreeMap::new(),
+        };
+
+        let mut changes = Vec::new();
+
+        for (path, new_hash) in &new_files {
+            match old_files.get(path) {
+                Some(old_hash) if old_hash == new_hash => {},
+                Some(old_hash) => changes.push(TreeChange {
+                    path: path.clone(),
+                    kind: ChangeKind::Modified,
+                    old_hash: Some(old_hash.clone()),
+                    new_hash: Some(new_hash.clone()),
+                }),
+                None => changes.push(TreeChange {
+                    path: path.clone(),
+                    kind: ChangeKind::Added,
+                    old_hash: None,
+                    new_hash: Some(new_hash.clone()),
+                }),
+            }
+        }
+
+        for (path, old_hash) in &old_files {
+            if !new_files.contains_key(path) {
+                changes.push(TreeChange {
+                    path: path.clone(),
+                    kind: ChangeKind::Deleted,
+                    old_hash: Some(old_hash.clone()),
+                    new_hash: None,
+                });
+            }
+        }
+
+        changes.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(changes)
+    }
 }
@@ -1,11 +1,18 @@
-use clap::{Parser, Subcommand};
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
 use aigit::commands;
+use aigit::core::Repository;
+use aigit::utils::worktree::root_relative;
+use std::path::{Path, PathBuf};
 
 #[derive(Parser)]
 #[command(name = "aigit")]
 #[command(about = "AI-powered version control system")]
 #[command(version = "0.1.0")]
 struct Cli {
+    /// Never call an AI provider; commands take their manual path instead
+    #[arg(long, global = true)]
+    no_ai: bool,
     #[command(subcommand)]
     command: Commands,
 }
@@ -15,6 +22,13 @@ enum Commands {
     Init {
         #[arg(long)]
         bare: bool,
+        #[arg(long)]
+        force: bool,
+        #[arg(long)]
+        reinitialize: bool,
+        /// With --force, also start over in an intact repository
+        #[arg(long, requires = "force")]
+        overwrite_existing: bool,
     },
     Config {
         #[command(subcommand)]
@@ -34,52 +48,258 @@ enum Commands {
         ai_review: bool,
         #[arg(short, long)]
         signoff: bool,
+        #[arg(short, long)]
+        patch: bool,
+        #[arg(long = "trailer", value_name = "KEY: VALUE")]
+        trailers: Vec<String>,
+        #[arg(short = 'F', long = "file", conflicts_with = "message")]
+        file: Option<String>,
+        #[arg(long, value_enum, default_value = "strip")]
+        cleanup: commands::commit::CleanupMode,
+        #[arg(short = 'S', long = "gpg-sign", value_name = "KEYID", num_args = 0..=1, default_missing_value = "")]
+        gpg_sign: Option<String>,
+        #[arg(long)]
+        include_changes: bool,
+        #[arg(long)]
+        short_stat: bool,
+        #[arg(short = 'n', long)]
+        no_verify: bool,
     },
     Status {
         #[arg(short, long)]
         porcelain: bool,
+        #[arg(long)]
+        json: bool,
+        #[arg(short, long)]
+        verbose: bool,
     },
     Log {
         #[arg(short, long)]
         oneline: bool,
-        #[arg(short, long)]
+        #[arg(long)]
         graph: bool,
+        #[arg(short = 'g', long)]
+        walk_reflogs: bool,
+        #[arg(long)]
+        all: bool,
         #[arg(long)]
         ai_summary: bool,
+        #[arg(long)]
+        stat: bool,
+        #[arg(short = 'n', long)]
+        max_count: Option<usize>,
+        #[arg(long)]
+        abbrev: Option<usize>,
+        #[arg(long, num_args = 0..=1, default_missing_value = "short", value_name = "short|full|no")]
+        decorate: Option<String>,
+        #[arg(short = 'p', long)]
+        patch: bool,
+        #[arg(long, value_name = "TEMPLATE")]
+        format: Option<String>,
+        #[arg(long, num_args = 0..=1, default_missing_value = "first", value_name = "first|last")]
+        author_date_order: Option<String>,
+        #[arg(long, value_name = "PATTERN")]
+        author: Option<String>,
+        #[arg(long, value_name = "PATTERN")]
+        committer: Option<String>,
+        #[arg(long, value_name = "DATE")]
+        since: Option<String>,
+        #[arg(long, value_name = "DATE")]
+        until: Option<String>,
+        paths: Vec<String>,
+    },
+    Show {
+        revision: Option<String>,
+        #[arg(short = 's', long)]
+        no_patch: bool,
+        #[arg(long, value_name = "TEMPLATE")]
+        format: Option<String>,
     },
     Branch {
         name: Option<String>,
+        new_name: Option<String>,
         #[arg(short, long)]
         delete: Option<String>,
         #[arg(long)]
         ai_suggest: bool,
+        #[arg(short = 'c', long)]
+        copy: bool,
+        #[arg(short = 'C')]
+        force_copy: bool,
+    },
+    Tag {
+        name: Option<String>,
+        #[arg(short, long)]
+        delete: Option<String>,
+        #[arg(short, long)]
+        annotate: bool,
+        #[arg(short, long)]
+        message: Option<String>,
     },
     Checkout {
-        target: String,
+        target: Option<String>,
         #[arg(short, long)]
         create: bool,
+        #[arg(short, long)]
+        force: bool,
+        #[arg(last = true)]
+        paths: Vec<String>,
+    },
+    Switch {
+        target: String,
+        start_point: Option<String>,
+        #[arg(short = 'c', long = "create")]
+        create: bool,
+        #[arg(short = 'C', long = "force-create", conflicts_with = "create")]
+        force_create: bool,
+        #[arg(long, conflicts_with_all = ["create", "force_create"])]
+        detach: bool,
     },
     Diff {
         #[arg(long)]
         cached: bool,
         #[arg(long)]
         ai_explain: bool,
+        #[arg(long)]
+        binary: bool,
+        #[arg(long)]
+        stat: bool,
+        #[arg(long, num_args = 0..=1, default_missing_value = "always", value_name = "always|never|auto")]
+        color: Option<String>,
+        #[arg(long)]
+        aigit_header: bool,
+        revisions: Vec<String>,
+        #[arg(last = true)]
+        paths: Vec<String>,
     },
     Merge {
-        branch: String,
+        #[arg(required_unless_present = "abort")]
+        branch: Option<String>,
         #[arg(long)]
         ai_resolve: bool,
+        #[arg(long, conflicts_with_all = ["branch", "ai_resolve"])]
+        abort: bool,
     },
     Review {
         #[arg(long)]
         full: bool,
+        #[arg(last = true)]
+        paths: Vec<String>,
     },
     Suggest {
+        /// Recompute the codebase analysis instead of using the cache
+        #[arg(long, global = true)]
+        refresh: bool,
         #[command(subcommand)]
         action: SuggestCommands,
     },
     Push {
-        branch: String,
+        remote_or_branch: String,
+        branch: Option<String>,
+    },
+    Fetch {
+        remote: Option<String>,
+        #[arg(short, long)]
+        prune: bool,
+    },
+    Remote {
+        #[command(subcommand)]
+        action: RemoteCommands,
+    },
+    Quarantine {
+        #[command(subcommand)]
+        action: QuarantineCommands,
+    },
+    Gc {
+        #[arg(long)]
+        auto: bool,
+        #[arg(long)]
+        prune_now: bool,
+        #[arg(long)]
+        prune_packed: bool,
+    },
+    Restore {
+        #[arg(short, long)]
+        source: Option<String>,
+        files: Vec<String>,
+    },
+    Rm {
+        files: Vec<String>,
+        #[arg(long)]
+        cached: bool,
+        #[arg(short, long)]
+        force: bool,
+    },
+    FormatPatch {
+        revision: String,
+        #[arg(short, long)]
+        output_directory: Option<String>,
+    },
+    Am {
+        files: Vec<String>,
+        #[arg(long = "continue")]
+        resume: bool,
+        #[arg(long)]
+        skip: bool,
+        #[arg(long)]
+        abort: bool,
+    },
+    VerifyCommit {
+        revisions: Vec<String>,
+    },
+    Key {
+        #[command(subcommand)]
+        action: KeyCommands,
+    },
+    Revert {
+        #[arg(required_unless_present = "abort")]
+        target: Option<String>,
+        #[arg(long)]
+        no_edit: bool,
+        #[arg(short = 'm', long = "mainline")]
+        mainline: Option<usize>,
+        #[arg(short = 'n', long)]
+        no_commit: bool,
+        #[arg(long, conflicts_with_all = ["target", "no_edit", "mainline", "no_commit"])]
+        abort: bool,
+    },
+    Apply {
+        patch: String,
+        #[arg(short = 'R', long)]
+        reverse: bool,
+        #[arg(long)]
+        check: bool,
+    },
+    Stash {
+        #[command(subcommand)]
+        action: Option<StashCommands>,
+    },
+    Fsck {
+        #[arg(long)]
+        full: bool,
+        #[arg(long)]
+        lost_found: bool,
+        #[arg(long)]
+        repair: bool,
+    },
+    Doctor {
+        #[arg(long)]
+        test_ai: bool,
+    },
+    Reflog {
+        #[arg(short = 'n', long)]
+        max_count: Option<usize>,
+    },
+    RevList {
+        #[arg(default_value = "HEAD")]
+        revision: String,
+        #[arg(long)]
+        count: bool,
+    },
+    /// Print a shell completion script to stdout
+    #[command(hide = true)]
+    Completions {
+        shell: Shell,
     },
 }
 
@@ -91,8 +311,13 @@ enum ConfigAction {
     },
     Get {
         key: String,
+        #[arg(long = "type", value_enum)]
+        value_type: Option<commands::config::ConfigType>,
+    },
+    List {
+        #[arg(long)]
+        name_only: bool,
     },
-    List,
     User {
         name: Option<String>,
         #[arg(long)]
@@ -104,27 +329,92 @@ enum ConfigAction {
 enum SuggestCommands {
     Commit,
     Branch,
-    Refactor,
+    Refactor {
+        #[arg(long = "for")]
+        file: Option<String>,
+        #[arg(long)]
+        diff: bool,
+    },
     Tests,
     Cleanup,
 }
 
+#[derive(Subcommand)]
+enum StashCommands {
+    #[command(alias = "save")]
+    Push {
+        #[arg(short, long)]
+        message: Option<String>,
+    },
+    Pop {
+        stash: Option<String>,
+    },
+    Apply {
+        stash: Option<String>,
+    },
+    Drop {
+        stash: Option<String>,
+    },
+    Show {
+        stash: Option<String>,
+    },
+    List,
+}
+
+#[derive(Subcommand)]
+enum QuarantineCommands {
+    List,
+    Clear,
+}
+
+#[derive(Subcommand)]
+enum KeyCommands {
+    Generate {
+        key_id: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum RemoteCommands {
+    Prune {
+        remote: String,
+    },
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
+    if cli.no_ai {
+        aigit::ai::disable_ai();
+    }
+    let prefix = match &cli.command {
+        Commands::Init { .. } | Commands::Completions { .. } => PathBuf::new(),
+        _ => enter_work_tree()?,
+    };
+    let resolve = |args: &Vec<String>| -> Vec<String> {
+        args.iter().map(|arg| root_relative(&prefix, arg)).collect()
+    };
 
     match &cli.command {
-        Commands::Init { bare } => commands::init::run(*bare).await?,
+        Commands::Init { bare, force, reinitialize, overwrite_existing } => {
+            commands::init::run_with(commands::init::InitOptions {
+                bare: *bare,
+                force: *force,
+                reinitialize: *reinitialize,
+                overwrite_existing: *overwrite_existing,
+            }).await?
+        },
         Commands::Config { action } => {
             let config_action = match action {
                 ConfigAction::Set { key, value } => commands::config::ConfigAction::Set {
                     key: key.clone(),
                     value: value.clone(),
                 },
-                ConfigAction::Get { key } => commands::config::ConfigAction::Get {
+                ConfigAction::Get { key, value_type } => commands::config::ConfigAction::Get {
                     key: key.clone(),
+                    value_type: *value_type,
                 },
-                ConfigAction::List => commands::config::ConfigAction::List,
+                ConfigAction::List { name_only } => commands::config::ConfigAction::List { name_only: *name_only },
                 ConfigAction::User { name, email } => commands::config::ConfigAction::User {
                     name: name.clone(),
                     email: email.clone(),
@@ -132,40 +422,196 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             };
             commands::config::run(&config_action).await?
         },
-        Commands::Add { files, all } => commands::add::run(files.clone(), *all).await?,
-        Commands::Commit { message, amend, ai_review, signoff } => {
-            commands::commit::run(message.clone(), *amend, *ai_review, *signoff).await?
+        Commands::Add { files, all } => commands::add::run(resolve(files), *all).await?,
+        Commands::Commit { message, amend, ai_review, signoff, patch, trailers, file, cleanup, gpg_sign, include_changes, short_stat, no_verify } => {
+            let message = match file.as_deref() {
+                Some("-") => Some(std::io::read_to_string(std::io::stdin())?),
+                Some(path) => Some(std::fs::read_to_string(root_relative(&prefix, path))?),
+                None => message.clone(),
+            };
+            commands::commit::run_with(commands::commit::CommitOptions {
+                message,
+                amend: *amend,
+                ai_review: *ai_review,
+                signoff: *signoff,
+                patch: *patch,
+                trailers: trailers.clone(),
+                cleanup: *cleanup,
+                gpg_sign: gpg_sign.clone(),
+                include_changes: *include_changes,
+                short_stat: *short_stat,
+                no_verify: *no_verify,
+            }).await?
         },
-        Commands::Status { porcelain } => commands::status::run(*porcelain).await?,
-        Commands::Log { oneline, graph, ai_summary } => {
-            commands::log::run(*oneline, *graph, *ai_summary).await?
+        Commands::Status { porcelain, json, verbose } => commands::status::run(*porcelain, *json, *verbose).await?,
+        Commands::Log { oneline, graph, walk_reflogs, all, ai_summary, stat, max_count, abbrev, decorate, patch, format, author_date_order, author, committer, since, until, paths } => {
+            commands::log::run(commands::log::LogOptions {
+                oneline: *oneline,
+                graph: *graph,
+                ai_summary: *ai_summary,
+                stat: *stat,
+                max_count: *max_count,
+                abbrev: *abbrev,
+                decorate: decorate.clone(),
+                walk_reflogs: *walk_reflogs,
+                all: *all,
+                patch: *patch,
+                paths: resolve(paths),
+                format: format.clone(),
+                author_date_order: author_date_order.clone(),
+                author: author.clone(),
+                committer: committer.clone(),
+                since: since.clone(),
+                until: until.clone(),
+            }).await?
         },
-        Commands::Branch { name, delete, ai_suggest } => {
-            commands::branch::run(name.clone(), delete.clone(), *ai_suggest).await?
+        Commands::Show { revision, no_patch, format } => {
+            commands::show::run(revision.clone(), *no_patch, format.clone()).await?
+        },
+        Commands::Branch { name, new_name, delete, ai_suggest, copy, force_copy } => {
+            if *copy || *force_copy {
+                let (source, target) = match (name, new_name) {
+                    (Some(source), Some(target)) => (Some(source.clone()), target.clone()),
+                    (Some(target), None) => (None, target.clone()),
+                    _ => return Err("Usage: aigit branch -c [<source>] <new-branch>".into()),
+                };
+                commands::branch::copy(source, target, *force_copy).await?
+            } else {
+                commands::branch::run(name.clone(), new_name.clone(), delete.clone(), *ai_suggest).await?
+            }
         },
-        Commands::Checkout { target, create } => {
-            commands::checkout::run(target.clone(), *create).await?
+        Commands::Tag { name, delete, annotate, message } => {
+            commands::tag::run(name.clone(), delete.clone(), *annotate, message.clone()).await?
         },
-        Commands::Diff { cached, ai_explain } => {
-            commands::diff::run(*cached, *ai_explain).await?
+        Commands::Checkout { target, create, force, paths } => {
+            commands::checkout::run(target.clone(), *create, *force, resolve(paths)).await?
         },
-        Commands::Merge { branch, ai_resolve } => {
-            commands::merge::run(branch.clone(), *ai_resolve).await?
+        Commands::Switch { target, start_point, create, force_create, detach } => {
+            let mode = match (create, force_create, detach) {
+                (true, _, _) => commands::switch::SwitchMode::Create,
+                (_, true, _) => commands::switch::SwitchMode::ForceCreate,
+                (_, _, true) => commands::switch::SwitchMode::Detach,
+                _ => commands::switch::SwitchMode::Branch,
+            };
+            commands::switch::run(target.clone(), start_point.clone(), mode).await?
+        },
+        Commands::Diff { cached, ai_explain, binary, stat, color, aigit_header, revisions, paths } => {
+            commands::diff::run_with(commands::diff::DiffOptions {
+                cached: *cached,
+                ai_explain: *ai_explain,
+                binary: *binary,
+                stat: *stat,
+                color: color.clone(),
+                revisions: revisions.clone(),
+                aigit_header: *aigit_header,
+                paths: resolve(paths),
+            }).await?
+        },
+        Commands::Merge { branch, ai_resolve, abort } => {
+            match branch {
+                Some(branch) if !*abort => commands::merge::run(branch.clone(), *ai_resolve).await?,
+                _ => commands::merge::abort().await?,
+            }
         },
-        Commands::Review { full } => commands::review::run(*full).await?,
-        Commands::Suggest { action } => {
+        Commands::Review { full, paths } => {
+            commands::review::run_with(commands::review::ReviewOptions {
+                full: *full,
+                paths: resolve(paths),
+            }).await?
+        },
+        Commands::Suggest { refresh, action } => {
+            let options = commands::suggest::SuggestOptions { refresh: *refresh };
             match action {
-                SuggestCommands::Commit => commands::suggest::commit().await?,
-                SuggestCommands::Branch => commands::suggest::branch().await?,
-                SuggestCommands::Refactor => commands::suggest::refactor().await?,
-                SuggestCommands::Tests => commands::suggest::tests().await?,
-                SuggestCommands::Cleanup => commands::suggest::cleanup().await?,
+                SuggestCommands::Commit => commands::suggest::commit_with(&options).await?,
+                SuggestCommands::Branch => commands::suggest::branch_with(&options).await?,
+                SuggestCommands::Refactor { file, diff } => {
+                    let file = file.as_ref().map(|file| root_relative(&prefix, file));
+                    commands::suggest::refactor_with(file, *diff, &options).await?
+                },
+                SuggestCommands::Tests => commands::suggest::tests_with(&options).await?,
+                SuggestCommands::Cleanup => commands::suggest::cleanup_with(&options).await?,
+            }
+        },
+        Commands::Push { remote_or_branch, branch } => {
+            match branch {
+                Some(branch) => commands::push::run(Some(remote_or_branch.clone()), branch.clone()).await?,
+                None => commands::push::run(None, remote_or_branch.clone()).await?,
             }
         },
-        Commands::Push { branch } => {
-            commands::push::run(branch.clone()).await?
+        Commands::Fetch { remote, prune } => commands::fetch::run(remote.clone(), *prune).await?,
+        Commands::Remote { action } => {
+            match action {
+                RemoteCommands::Prune { remote } => commands::remote::prune(remote.clone()).await?,
+            }
+        },
+        Commands::Quarantine { action } => {
+            match action {
+                QuarantineCommands::List => commands::quarantine::list().await?,
+                QuarantineCommands::Clear => commands::quarantine::clear().await?,
+            }
+        },
+        Commands::Gc { auto, prune_now, prune_packed } => commands::gc::run(*auto, *prune_now, *prune_packed).await?,
+        Commands::Restore { source, files } => commands::restore::run_from(source.clone(), resolve(files)).await?,
+        Commands::Stash { action } => {
+            match action {
+                None => commands::stash::push(None).await?,
+                Some(StashCommands::Push { message }) => commands::stash::push(message.clone()).await?,
+                Some(StashCommands::Pop { stash }) => commands::stash::pop(stash.clone()).await?,
+                Some(StashCommands::Apply { stash }) => commands::stash::apply(stash.clone()).await?,
+                Some(StashCommands::Drop { stash }) => commands::stash::drop(stash.clone()).await?,
+                Some(StashCommands::Show { stash }) => commands::stash::show(stash.clone()).await?,
+                Some(StashCommands::List) => commands::stash::list().await?,
+            }
+        },
+        Commands::Rm { files, cached, force } => commands::rm::run(resolve(files), *cached, *force).await?,
+        Commands::FormatPatch { revision, output_directory } => {
+            let output_directory = output_directory.as_ref().map(|dir| root_relative(&prefix, dir));
+            commands::format_patch::run(revision.clone(), output_directory).await?
+        },
+        Commands::Am { files, resume, skip, abort } => {
+            commands::am::run(resolve(files), *resume, *skip, *abort).await?
+        },
+        Commands::VerifyCommit { revisions } => commands::verify_commit::run(revisions.clone()).await?,
+        Commands::Key { action } => {
+            match action {
+                KeyCommands::Generate { key_id } => commands::key::generate(key_id.clone()).await?,
+            }
+        },
+        Commands::Revert { target, no_edit, mainline, no_commit, abort } => {
+            match target {
+                Some(target) if !*abort => commands::revert::run_with(commands::revert::RevertOptions {
+                    target: target.clone(),
+                    no_edit: *no_edit,
+                    mainline: *mainline,
+                    no_commit: *no_commit,
+                }).await?,
+                _ => commands::revert::abort().await?,
+            }
+        },
+        Commands::Apply { patch, reverse, check } => commands::apply::run(root_relative(&prefix, patch), *reverse, *check).await?,
+        Commands::Fsck { full, lost_found, repair } => commands::fsck::run(*full, *lost_found, *repair).await?,
+        Commands::Doctor { test_ai } => commands::doctor::run(*test_ai).await?,
+        Commands::Reflog { max_count } => commands::reflog::run(*max_count).await?,
+        Commands::RevList { revision, count } => commands::rev_list::run(revision.clone(), *count).await?,
+        Commands::Completions { shell } => {
+            clap_complete::generate(*shell, &mut Cli::command(), "aigit", &mut std::io::stdout());
         },
     }
 
     Ok(())
 }
+
+/// Moves to the root of the working tree when run from one of its
+/// subdirectories, so commands keep working with root-relative paths.
+/// Returns the original directory relative to that root, empty at the root
+/// or outside any repository.
+fn enter_work_tree() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let current = std::env::current_dir()?;
+    match Repository::discover_root(&current) {
+        Some(root) if root != current => {
+            std::env::set_current_dir(&root)?;
+            Ok(current.strip_prefix(&root).unwrap_or(Path::new("")).to_path_buf())
+        },
+        _ => Ok(PathBuf::new()),
+    }
+}
@@ -0,0 +1,99 @@
+use tree_sitter::{Node, Parser};
+
+/// Languages whose complexity is measured from a tree-sitter syntax tree
+/// rather than by matching keywords line by line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    Rust,
+    Python,
+    JavaScript,
+    Go,
+}
+
+impl Language {
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "rs" => Some(Language::Rust),
+            "py" | "pyi" => Some(Language::Python),
+            "js" | "jsx" | "mjs" | "cjs" => Some(Language::JavaScript),
+            "go" => Some(Language::Go),
+            _ => None,
+        }
+    }
+
+    fn grammar(self) -> tree_sitter::Language {
+        match self {
+            Language::Rust => tree_sitter_rust::LANGUAGE.into(),
+            Language::Python => tree_sitter_python::LANGUAGE.into(),
+            Language::JavaScript => tree_sitter_javascript::LANGUAGE.into(),
+            Language::Go => tree_sitter_go::LANGUAGE.into(),
+        }
+    }
+
+    /// Functions, closures and methods; each starts a path of its own.
+    fn is_function(self, kind: &str) -> bool {
+        match self {
+            Language::Rust => matches!(kind, "function_item" | "closure_expression"),
+            Language::Python => matches!(kind, "function_definition" | "lambda"),
+            Language::JavaScript => matches!(kind, "function_declaration" | "function_expression" | "arrow_function"
+                | "method_definition" | "generator_function_declaration" | "generator_function"),
+            Language::Go => matches!(kind, "function_declaration" | "method_declaration" | "func_literal"),
+        }
+    }
+
+    /// Nodes that add a branch to the control flow graph.
+    fn is_branch(self, kind: &str) -> bool {
+        match self {
+            Language::Rust => matches!(kind, "if_expression" | "while_expression" | "for_expression"
+                | "loop_expression" | "match_arm" | "try_expression"),
+            Language::Python => matches!(kind, "if_statement" | "elif_clause" | "for_statement" | "while_statement"
+                | "except_clause" | "conditional_expression" | "case_clause" | "for_in_clause" | "if_clause"
+                | "boolean_operator"),
+            Language::JavaScript => matches!(kind, "if_statement" | "for_statement" | "for_in_statement"
+                | "while_statement" | "do_statement" | "switch_case" | "catch_clause" | "ternary_expression"),
+            Language::Go => matches!(kind, "if_statement" | "for_statement" | "expression_case" | "type_case"
+                | "communication_case"),
+        }
+    }
+
+    /// Short-circuit operators, each of which is a hidden branch. Python has
+    /// its own `boolean_operator` node, counted in `is_branch`.
+    fn is_short_circuit(self, node: &Node) -> bool {
+        if self == Language::Python || node.kind() != "binary_expression" {
+            return false;
+        }
+        node.child_by_field_name("operator")
+            .is_some_and(|operator| matches!(operator.kind(), "&&" | "||" | "??"))
+    }
+}
+
+/// Cyclomatic complexity of `content` summed over the file: one for every
+/// function plus one for every branch and short-circuit operator inside it.
+/// `None` when the extension has no grammar or the parser gives up, so the
+/// caller can fall back to the keyword heuristic.
+pub fn ast_complexity(extension: &str, content: &str) -> Option<f32> {
+    let language = Language::from_extension(extension)?;
+    let mut parser = Parser::new();
+    parser.set_language(&language.grammar()).ok()?;
+    let tree = parser.parse(content, None)?;
+
+    let mut complexity = 0usize;
+    let mut cursor = tree.walk();
+    'walk: loop {
+        let node = cursor.node();
+        if language.is_function(node.kind()) || language.is_branch(node.kind()) || language.is_short_circuit(&node) {
+            complexity += 1;
+        }
+
+        if cursor.goto_first_child() {
+            continue;
+        }
+        while !cursor.goto_next_sibling() {
+            if !cursor.goto_parent() {
+                break 'walk;
+            }
+        }
+    }
+
+    Some(complexity as f32)
+}
@@ -0,0 +1,241 @@
+use colored::*;
+use similar::{DiffTag, TextDiff};
+
+const OURS_MARKER: &str = "<<<<<<<";
+const SEPARATOR_MARKER: &str = "=======";
+const THEIRS_MARKER: &str = ">>>>>>>";
+const COLUMN_WIDTH: usize = 32;
+
+/// One `<<<<<<< ... ======= ... >>>>>>>` region of a conflicted file, with the
+/// line range it occupies (marker lines included).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConflictHunk {
+    pub start: usize,
+    pub end: usize,
+    pub ours: Vec<String>,
+    pub theirs: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProposedResolution {
+    pub lines: Vec<String>,
+    pub confidence: String,
+}
+
+pub fn has_conflict_markers(content: &str) -> bool {
+    !parse_conflicts(content).is_empty()
+}
+
+pub fn parse_conflicts(content: &str) -> Vec<ConflictHunk> {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut hunks = Vec::new();
+    let mut i = 0;
+
+    while i < lines.len() {
+        if !lines[i].starts_with(OURS_MARKER) {
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        let separator = (start + 1..lines.len()).find(|&j| lines[j].starts_with(SEPARATOR_MARKER));
+        let end = separator.and_then(|sep| (sep + 1..lines.len()).find(|&j| lines[j].starts_with(THEIRS_MARKER)));
+
+        match (separator, end) {
+            (Some(separator), Some(end)) => {
+                hunks.push(ConflictHunk {
+                    start,
+                    end,
+                    ours: lines[start + 1..separator].iter().map(|l| l.to_string()).collect(),
+                    theirs: lines[separator + 1..end].iter().map(|l| l.to_string()).collect(),
+                });
+                i = end + 1;
+            },
+            _ => break,
+        }
+    }
+
+    hunks
+}
+
+/// Rebuilds `content` with each hunk replaced by its resolution. Hunks without
+/// one keep their markers so they can still be resolved by hand.
+pub fn apply_resolutions(content: &str, hunks: &[ConflictHunk], resolutions: &[Option<Vec<String>>]) -> String {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut result: Vec<String> = Vec::new();
+    let mut next_line = 0;
+
+    for (hunk, resolution) in hunks.iter().zip(resolutions) {
+        result.extend(lines[next_line..hunk.start].iter().map(|l| l.to_string()));
+        match resolution {
+            Some(resolved) => result.extend(resolved.iter().cloned()),
+            None => result.extend(lines[hunk.start..=hunk.end].iter().map(|l| l.to_string())),
+        }
+        next_line = hunk.end + 1;
+    }
+    result.extend(lines[next_line..].iter().map(|l| l.to_string()));
+
+    let mut output = result.join("\n");
+    if content.ends_with('\n') || output.is_empty() {
+        output.push('\n');
+    }
+    output
+}
+
+/// A region of the base replaced by one side: base lines `start..end`
+/// become `lines`.
+struct SideChange {
+    start: usize,
+    end: usize,
+    lines: Vec<String>,
+}
+
+fn side_changes(base: &[&str], side: &[&str]) -> Vec<SideChange> {
+    TextDiff::from_slices(base, side).ops().iter()
+        .filter(|op| op.tag() != DiffTag::Equal)
+        .map(|op| SideChange {
+            start: op.old_range().start,
+            end: op.old_range().end,
+            lines: side[op.new_range()].iter().map(|l| l.to_string()).collect(),
+        })
+        .collect()
+}
+
+/// Base lines `start..end` with `changes` (all inside that range) applied.
+fn apply_side(base: &[&str], start: usize, end: usize, changes: &[&SideChange]) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut pos = start;
+    for change in changes {
+        lines.extend(base[pos..change.start].iter().map(|l| l.to_string()));
+        lines.extend(change.lines.iter().cloned());
+        pos = change.end;
+    }
+    lines.extend(base[pos..end].iter().map(|l| l.to_string()));
+    lines
+}
+
+/// Line-based three-way merge of `ours` and `theirs` against `base`. Regions
+/// changed by one side take that side; regions both sides changed the same
+/// way are kept once; anything else is written between conflict markers
+/// labelled `ours_label` and `theirs_label`. Returns the merged text and the
+/// number of conflicted regions.
+pub fn merge_text(base: &str, ours: &str, theirs: &str, ours_label: &str, theirs_label: &str) -> (String, usize) {
+    let base_lines: Vec<&str> = base.lines().collect();
+    let our_lines: Vec<&str> = ours.lines().collect();
+    let their_lines: Vec<&str> = theirs.lines().collect();
+    let our_changes = side_changes(&base_lines, &our_lines);
+    let their_changes = side_changes(&base_lines, &their_lines);
+
+    let mut merged: Vec<String> = Vec::new();
+    let mut conflicts = 0;
+    let (mut i, mut j, mut pos) = (0, 0, 0);
+
+    while i < our_changes.len() || j < their_changes.len() {
+        let start = match (our_changes.get(i), their_changes.get(j)) {
+            (Some(a), Some(b)) => a.start.min(b.start),
+            (Some(a), None) => a.start,
+            (None, Some(b)) => b.start,
+            (None, None) => break,
+        };
+
+        // Grow the region until no change from either side touches it.
+        let (mut end, mut ours_group, mut theirs_group) = (start, Vec::new(), Vec::new());
+        loop {
+            if let Some(change) = our_changes.get(i).filter(|c| c.start <= end) {
+                end = end.max(change.end);
+                ours_group.push(change);
+                i += 1;
+            } else if let Some(change) = their_changes.get(j).filter(|c| c.start <= end) {
+                end = end.max(change.end);
+                theirs_group.push(change);
+                j += 1;
+            } else {
+                break;
+            }
+        }
+
+        merged.extend(base_lines[pos..start].iter().map(|l| l.to_string()));
+        let our_side = apply_side(&base_lines, start, end, &ours_group);
+        let their_side = apply_side(&base_lines, start, end, &theirs_group);
+
+        if theirs_group.is_empty() || our_side == their_side {
+            merged.extend(our_side);
+        } else if ours_group.is_empty() {
+            merged.extend(their_side);
+        } else {
+            conflicts += 1;
+            merged.push(format!("{} {}", OURS_MARKER, ours_label));
+            merged.extend(our_side);
+            merged.push(SEPARATOR_MARKER.to_string());
+            merged.extend(their_side);
+            merged.push(format!("{} {}", THEIRS_MARKER, theirs_label));
+        }
+        pos = end;
+    }
+    merged.extend(base_lines[pos..].iter().map(|l| l.to_string()));
+
+    let mut output = merged.join("\n");
+    let trailing_newline = [ours, theirs, base].iter().any(|text| text.ends_with('\n'));
+    if trailing_newline && !output.is_empty() {
+        output.push('\n');
+    }
+    (output, conflicts)
+}
+
+/// The text sent to the AI for a single hunk.
+pub fn format_hunk_for_prompt(path: &str, hunk: &ConflictHunk) -> String {
+    format!(
+        "File: {}\n{} ours\n{}\n{}\n{}\n{} theirs\n",
+        path,
+        OURS_MARKER,
+        hunk.ours.join("\n"),
+        SEPARATOR_MARKER,
+        hunk.theirs.join("\n"),
+        THEIRS_MARKER
+    )
+}
+
+/// Reads the resolved lines from the first fenced code block of an AI reply
+/// and the `Confidence:` note that accompanies it.
+pub fn parse_resolution(response: &str) -> Option<ProposedResolution> {
+    let mut lines = response.lines().skip_while(|line| !line.trim_start().starts_with("```"));
+    lines.next()?;
+
+    let resolved: Vec<String> = lines
+        .take_while(|line| !line.trim_start().starts_with("```"))
+        .map(|line| line.to_string())
+        .collect();
+
+    let confidence = response.lines()
+        .find_map(|line| line.trim().strip_prefix("Confidence:"))
+        .map(|note| note.trim().to_string())
+        .unwrap_or_else(|| "not stated".to_string());
+
+    Some(ProposedResolution { lines: resolved, confidence })
+}
+
+/// Prints ours, theirs and the proposed resolution side by side.
+pub fn print_three_columns(hunk: &ConflictHunk, proposed: &ProposedResolution) {
+    println!("{} {} {}",
+            column("ours").green().bold(),
+            column("theirs").red().bold(),
+            column("proposed").cyan().bold());
+
+    let rows = hunk.ours.len().max(hunk.theirs.len()).max(proposed.lines.len());
+    for row in 0..rows {
+        let cell = |lines: &[String]| column(lines.get(row).map(|l| l.as_str()).unwrap_or(""));
+        println!("{} {} {}",
+                cell(&hunk.ours).green(),
+                cell(&hunk.theirs).red(),
+                cell(&proposed.lines).cyan());
+    }
+}
+
+fn column(text: &str) -> String {
+    let text = text.replace('\t', "    ");
+    let mut cell: String = text.chars().take(COLUMN_WIDTH - 1).collect();
+    if text.chars().count() >= COLUMN_WIDTH {
+        cell.push('…');
+    }
+    format!("{:<width$}|", cell, width = COLUMN_WIDTH)
+}
@@ -1,65 +1,278 @@
-use crate::core::{Repository, Index, Object};
-use similar::{ChangeTag, TextDiff};
+use crate::core::{Repository, Index, Object, ObjectType, Commit, Tree, TreeEntry, TreeChange, Branch};
+use crate::core::object::hash_object;
+use crate::utils::ignore::GitIgnore;
+use crate::utils::worktree::nested_repo_root;
+use std::collections::{BTreeMap, BTreeSet};
+use similar::{ChangeTag, DiffTag, TextDiff};
+use colored::*;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::io::Write;
 
-pub async fn generate_diff(repo: &Repository, index: &Index, staged: bool) -> Result<String, Box<dyn std::error::Error>> {
+const BINARY_SNIFF_LEN: usize = 8000;
+const BASE85_ALPHABET: &[u8; 85] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz!#$%&()*+-;<=>?@^_`{|}~";
+const BINARY_PATCH_LINE_BYTES: usize = 52;
+/// Hash digits shown on `index` lines, as git does by default.
+const INDEX_ABBREV: usize = 7;
+
+/// With `binary`, binary files get a base85 `GIT binary patch` instead of a
+/// one-line "Binary files ... differ" note.
+pub async fn generate_diff(repo: &Repository, index: &Index, staged: bool, binary: bool) -> Result<String, Box<dyn std::error::Error>> {
+    generate_diff_for_paths(repo, index, staged, binary, &PathScope::default()).await
+}
+
+/// Like `generate_diff`, but only for files in `paths`; files outside
+/// them are never read or diffed.
+pub async fn generate_diff_for_paths(
+    repo: &Repository,
+    index: &Index,
+    staged: bool,
+    binary: bool,
+    paths: &PathScope
+) -> Result<String, Box<dyn std::error::Error>> {
     let diff_output = if staged {
-        generate_staged_diff(repo, index).await
+        generate_staged_diff(repo, index, binary, paths).await
     } else {
-        generate_working_diff(repo, index).await
+        generate_working_diff(repo, index, binary, paths).await
     };
     
     Ok(diff_output)
 }
 
 pub async fn get_staged_diff(repo: &Repository, index: &Index) -> String {
-    generate_staged_diff(repo, index).await
+    generate_staged_diff(repo, index, false, &PathScope::default()).await
 }
 
-async fn generate_staged_diff(repo: &Repository, index: &Index) -> String {
-    let mut diff_output = String::new();
+/// Content is treated as binary when it has a NUL byte near the start or is
+/// not valid UTF-8, since neither can go through the line-based text diff.
+pub fn is_binary(content: &[u8]) -> bool {
+    content.iter().take(BINARY_SNIFF_LEN).any(|&b| b == 0) || std::str::from_utf8(content).is_err()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum StagedStatus {
+    Added,
+    Modified,
+    Deleted,
+}
+
+/// Staged changes, HEAD against the index, grouped as additions,
+/// modifications, then deletions, each sorted by path, so the same staged
+/// state always yields the same diff. The working tree is never read, so
+/// edits made after staging only show up in the working diff.
+async fn generate_staged_diff(repo: &Repository, index: &Index, binary: bool, paths: &PathScope) -> String {
+    let head_entries = head_entries(repo);
+    let mut changes = Vec::new();
+    let mut nested_repos = BTreeSet::new();
+    let mut staged_paths = BTreeSet::new();
     
-    for (file_path, _hash) in &index.entries {
-        if let Ok(current_content) = std::fs::read_to_string(file_path) {
-            let old_content = get_file_from_last_commit(repo, file_path).unwrap_or_default();
-            
-            if old_content != current_content {
-                let old_lines: Vec<&str> = old_content.lines().collect();
-                let current_lines: Vec<&str> = current_content.lines().collect();
-                let diff = TextDiff::from_slices(&old_lines, &current_lines);
-                diff_output.push_str(&format_diff_header(file_path, "staged"));
-                diff_output.push_str(&format_diff_content(&diff));
-            }
+    for (file_path, staged_hash) in &index.entries {
+        if !paths.matches(file_path) {
+            continue;
+        }
+        if let Some(nested) = nested_repo_root(file_path) {
+            nested_repos.insert(nested);
+            continue;
+        }
+        let relative = file_path.as_str();
+        staged_paths.insert(relative);
+        let staged = DiffSide { hash: staged_hash, mode: index.metadata.get(file_path).map(|m| m.mode.as_str()) };
+        let change = match head_entries.get(relative) {
+            None => (StagedStatus::Added, relative, None, Some(staged)),
+            Some(head) if &head.hash != staged_hash => {
+                (StagedStatus::Modified, relative, Some(DiffSide::from_entry(head)), Some(staged))
+            },
+            _ => continue,
+        };
+        changes.push(change);
+    }
+    for (path, head) in &head_entries {
+        if !staged_paths.contains(path.as_str()) && nested_repo_root(path).is_none() && paths.matches(path) {
+            changes.push((StagedStatus::Deleted, path, Some(DiffSide::from_entry(head)), None));
         }
     }
+    changes.sort_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)));
+    
+    let mut diff_output = String::new();
+    for (_, file_path, old, new) in changes {
+        diff_output.push_str(&format_file_diff(repo, file_path, old, new, binary));
+    }
+    for nested in nested_repos {
+        diff_output.push_str(&format_nested_repo(&nested));
+    }
     
     diff_output
 }
 
-async fn generate_working_diff(repo: &Repository, index: &Index) -> String {
+/// Unstaged changes, the index against the working tree.
+async fn generate_working_diff(repo: &Repository, index: &Index, binary: bool, paths: &PathScope) -> String {
     let mut diff_output = String::new();
+    let mut entries: Vec<(&String, &String)> = index.entries.iter()
+        .filter(|(path, _)| paths.matches(path))
+        .collect();
+    entries.sort();
+    let mut nested_repos = BTreeSet::new();
     
-    for (file_path, staged_hash) in &index.entries {
-        if let Ok(current_content) = std::fs::read_to_string(file_path) {
-            let current_hash = crate::core::object::hash_content(current_content.as_bytes());
+    for (file_path, staged_hash) in entries {
+        if let Some(nested) = nested_repo_root(file_path) {
+            nested_repos.insert(nested);
+            continue;
+        }
+        if let Ok(current_content) = std::fs::read(file_path) {
+            let current_hash = hash_object(&ObjectType::Blob, &current_content);
             
             if &current_hash != staged_hash {
-                let staged_content = get_blob_content(repo, staged_hash).unwrap_or_default();
-                let staged_lines: Vec<&str> = staged_content.lines().collect();
-                let current_lines: Vec<&str> = current_content.lines().collect();
-                let diff = TextDiff::from_slices(&staged_lines, &current_lines);
-                
-                diff_output.push_str(&format_diff_header(file_path, "working"));
-                diff_output.push_str(&format_diff_content(&diff));
+                let mode = index.metadata.get(file_path).map(|m| m.mode.as_str());
+                let staged = DiffSide { hash: staged_hash, mode };
+                let staged_content = Object::read(repo, staged_hash).unwrap_or_default();
+                diff_output.push_str(&format_text_or_binary_diff(
+                    file_path, Some(staged), Some(DiffSide { hash: &current_hash, mode }),
+                    &staged_content, &current_content, binary));
             }
         }
     }
+    for nested in nested_repos {
+        diff_output.push_str(&format_nested_repo(&nested));
+    }
     
     diff_output
 }
 
-fn format_diff_header(file_path: &str, diff_type: &str) -> String {
-    format!("diff --aigit a/{} b/{} ({})\n--- a/{}\n+++ b/{}\n", 
-            file_path, file_path, diff_type, file_path, file_path)
+/// One side of a file diff: the blob and, when known, its mode. A missing
+/// side means the file does not exist there.
+#[derive(Clone, Copy)]
+pub struct DiffSide<'a> {
+    pub hash: &'a str,
+    pub mode: Option<&'a str>,
+}
+
+impl<'a> DiffSide<'a> {
+    fn from_entry(entry: &'a TreeEntry) -> Self {
+        DiffSide { hash: &entry.hash, mode: Some(&entry.mode) }
+    }
+}
+
+/// Index entries inside a nested repository are summarised as one entry
+/// rather than diffed file by file.
+fn format_nested_repo(repo_path: &str) -> String {
+    format!("diff --git a/{} b/{}\nnested repo: {}\n", repo_path, repo_path, repo_path)
+}
+
+fn format_file_diff(repo: &Repository, file_path: &str, old: Option<DiffSide>, new: Option<DiffSide>, binary: bool) -> String {
+    let read = |side: Option<DiffSide>| side.and_then(|side| Object::read(repo, side.hash).ok()).unwrap_or_default();
+    format_text_or_binary_diff(file_path, old, new, &read(old), &read(new), binary)
+}
+
+/// A git-style diff of one file, ready for `patch -p1` or `git apply`.
+/// Binary content gets a `Binary files ... differ` line, or with `binary`
+/// a `GIT binary patch`.
+fn format_text_or_binary_diff(
+    file_path: &str,
+    old: Option<DiffSide>,
+    new: Option<DiffSide>,
+    old_content: &[u8],
+    new_content: &[u8],
+    binary: bool
+) -> String {
+    let mut output = format_git_header(file_path, old, new);
+    let (old_name, new_name) = side_names(file_path, old.is_some(), new.is_some());
+    if is_binary(old_content) || is_binary(new_content) {
+        if binary {
+            output.push_str(&format_binary_patch(old_content, new_content));
+        } else {
+            output.push_str(&format!("Binary files {} and {} differ\n", old_name, new_name));
+        }
+        return output;
+    }
+
+    output.push_str(&format!("--- {}\n+++ {}\n", old_name, new_name));
+    output.push_str(&diff_text(&String::from_utf8_lossy(old_content), &String::from_utf8_lossy(new_content)));
+    output
+}
+
+/// The `diff --git` line plus the mode and `index` lines git writes before
+/// `---`/`+++`. Hashes are abbreviated; a missing side is all zeros.
+pub fn format_git_header(file_path: &str, old: Option<DiffSide>, new: Option<DiffSide>) -> String {
+    let path = file_path.strip_prefix("./").unwrap_or(file_path);
+    let mut header = format!("diff --git a/{} b/{}\n", path, path);
+    let short = |side: Option<DiffSide>| side
+        .map(|side| side.hash[..INDEX_ABBREV.min(side.hash.len())].to_string())
+        .unwrap_or_else(|| "0".repeat(INDEX_ABBREV));
+
+    let mut index_mode = None;
+    match (old.and_then(|o| o.mode), new.and_then(|n| n.mode)) {
+        (_, Some(mode)) if old.is_none() => header.push_str(&format!("new file mode {}\n", mode)),
+        (Some(mode), _) if new.is_none() => header.push_str(&format!("deleted file mode {}\n", mode)),
+        (Some(old_mode), Some(new_mode)) if old_mode != new_mode => {
+            header.push_str(&format!("old mode {}\nnew mode {}\n", old_mode, new_mode));
+        },
+        (Some(mode), Some(_)) => index_mode = Some(mode),
+        _ => {},
+    }
+    header.push_str(&format!("index {}..{}", short(old), short(new)));
+    if let Some(mode) = index_mode {
+        header.push_str(&format!(" {}", mode));
+    }
+    header.push('\n');
+    header
+}
+
+/// `a/<path>` and `b/<path>`, or `/dev/null` for a side that does not exist.
+fn side_names(path: &str, old_exists: bool, new_exists: bool) -> (String, String) {
+    let old = if old_exists { format!("a/{}", path) } else { "/dev/null".to_string() };
+    let new = if new_exists { format!("b/{}", path) } else { "/dev/null".to_string() };
+    (old, new)
+}
+
+/// A `GIT binary patch` body: the new content as a forward literal followed
+/// by the old content as the reverse literal, both zlib-compressed and
+/// base85-encoded the way `git apply` expects.
+pub fn format_binary_patch(old_content: &[u8], new_content: &[u8]) -> String {
+    let mut output = String::from("GIT binary patch\n");
+    output.push_str(&format_binary_literal(new_content));
+    output.push('\n');
+    output.push_str(&format_binary_literal(old_content));
+    output.push('\n');
+    output
+}
+
+fn format_binary_literal(content: &[u8]) -> String {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    // Writing into a Vec cannot fail.
+    encoder.write_all(content).expect("in-memory zlib write");
+    let compressed = encoder.finish().expect("in-memory zlib finish");
+
+    let mut output = format!("literal {}\n", content.len());
+    for chunk in compressed.chunks(BINARY_PATCH_LINE_BYTES) {
+        // Line length prefix: 'A'..='Z' for 1-26 bytes, 'a'..='z' for 27-52.
+        let len_char = if chunk.len() <= 26 {
+            (b'A' + chunk.len() as u8 - 1) as char
+        } else {
+            (b'a' + chunk.len() as u8 - 27) as char
+        };
+        output.push(len_char);
+        output.push_str(&encode_base85(chunk));
+        output.push('\n');
+    }
+    output
+}
+
+/// Git's base85 flavour: each 4-byte group (zero padded) becomes 5 characters.
+pub fn encode_base85(data: &[u8]) -> String {
+    let mut output = String::with_capacity(data.len().div_ceil(4) * 5);
+    for group in data.chunks(4) {
+        let mut word = [0u8; 4];
+        word[..group.len()].copy_from_slice(group);
+        let mut value = u32::from_be_bytes(word);
+
+        let mut encoded = [0u8; 5];
+        for slot in encoded.iter_mut().rev() {
+            *slot = BASE85_ALPHABET[(value % 85) as usize];
+            value /= 85;
+        }
+        output.extend(encoded.iter().map(|&b| b as char));
+    }
+    output
 }
 
 fn format_diff_content(diff: &TextDiff<str>) -> String {
@@ -90,17 +303,335 @@ fn format_diff_content(diff: &TextDiff<str>) -> String {
             }
         }
         
+        // An empty side names the line it follows, so a new file is "-0,0".
+        let old_start = if old_count == 0 { first_old_line - 1 } else { first_old_line };
+        let new_start = if new_count == 0 { first_new_line - 1 } else { first_new_line };
         output.push_str(&format!("@@ -{},{} +{},{} @@\n", 
-                                first_old_line, old_count, 
-                                first_new_line, new_count));
+                                old_start, old_count, 
+                                new_start, new_count));
         output.push_str(&group_output);
     }
     
     output
 }
 
-fn get_file_from_last_commit(_repo: &Repository, _file_path: &str) -> Option<String> {
-    None
+pub struct FileStat {
+    pub path: String,
+    pub additions: usize,
+    pub deletions: usize,
+}
+
+pub fn diff_text(old_content: &str, new_content: &str) -> String {
+    let old_lines: Vec<&str> = old_content.lines().collect();
+    let new_lines: Vec<&str> = new_content.lines().collect();
+    let diff = TextDiff::from_slices(&old_lines, &new_lines);
+    format_diff_content(&diff)
+}
+
+/// Per-file counts for a diff made of `diff --git` sections, in the order
+/// the files appear.
+pub async fn diff_file_stats(diff: &str) -> Vec<FileStat> {
+    let mut sections: Vec<(String, String)> = Vec::new();
+    for line in diff.lines() {
+        if let Some(header) = line.strip_prefix("diff --git a/") {
+            let path = header.split(" b/").next().unwrap_or(header);
+            sections.push((path.strip_prefix("./").unwrap_or(path).to_string(), String::new()));
+        } else if let Some((_, body)) = sections.last_mut() {
+            body.push_str(line);
+            body.push('\n');
+        }
+    }
+
+    let mut stats = Vec::new();
+    for (path, body) in sections {
+        let (additions, deletions, _) = calculate_diff_stats(&body).await;
+        stats.push(FileStat { path, additions, deletions });
+    }
+    stats
+}
+
+pub fn print_diff(diff: &str) {
+    for line in diff.lines() {
+        if line.starts_with("@@") {
+            println!("{}", line.cyan());
+        } else if line.starts_with('+') {
+            println!("{}", line.green());
+        } else if line.starts_with('-') {
+            println!("{}", line.red());
+        } else {
+            println!("{}", line);
+        }
+    }
+}
+
+pub async fn commit_file_stats(repo: &Repository, commit: &Commit) -> Result<Vec<FileStat>, Box<dyn std::error::Error>> {
+    let parent_tree = match commit.parents.first() {
+        Some(parent) if !parent.is_empty() => {
+            let parent_commit: Commit = serde_json::from_slice(&Object::read(repo, parent)?)?;
+            Some(parent_commit.tree)
+        },
+        _ => None,
+    };
+
+    let mut stats = Vec::new();
+    for change in Tree::diff(repo, parent_tree.as_deref(), Some(&commit.tree))? {
+        let old_content = change.old_hash.as_deref().and_then(|h| get_blob_content(repo, h)).unwrap_or_default();
+        let new_content = change.new_hash.as_deref().and_then(|h| get_blob_content(repo, h)).unwrap_or_default();
+        let (additions, deletions, _) = calculate_diff_stats(&diff_text(&old_content, &new_content)).await;
+
+        stats.push(FileStat {
+            path: change.path,
+            additions,
+            deletions,
+        });
+    }
+
+    Ok(stats)
+}
+
+/// The changes `commit` made to its first parent as a unified diff that
+/// `aigit apply` accepts: added and deleted files use `/dev/null` on the
+/// missing side.
+pub fn commit_patch(repo: &Repository, commit: &Commit) -> Result<String, Box<dyn std::error::Error>> {
+    commit_patch_for_paths(repo, commit, &PathScope::default())
+}
+
+/// Like `commit_patch`, but only for files at or under `paths` (every file
+/// when `paths` is empty).
+pub fn commit_patch_for_paths(repo: &Repository, commit: &Commit, paths: &PathScope) -> Result<String, Box<dyn std::error::Error>> {
+    format_changes_patch(repo, &commit_changes(repo, commit)?, paths)
+}
+
+/// A unified diff of `changes`, limited to `paths` (all when empty).
+pub fn format_changes_patch(repo: &Repository, changes: &[TreeChange], paths: &PathScope) -> Result<String, Box<dyn std::error::Error>> {
+    let mut output = String::new();
+    for change in changes {
+        let path = change.path.strip_prefix("./").unwrap_or(&change.path);
+        if !paths.matches(path) {
+            continue;
+        }
+        let old_content = match &change.old_hash {
+            Some(hash) => Object::read(repo, hash)?,
+            None => Vec::new(),
+        };
+        let new_content = match &change.new_hash {
+            Some(hash) => Object::read(repo, hash)?,
+            None => Vec::new(),
+        };
+
+        let old = change.old_hash.as_deref().map(|hash| DiffSide { hash, mode: None });
+        let new = change.new_hash.as_deref().map(|hash| DiffSide { hash, mode: None });
+        output.push_str(&format_text_or_binary_diff(path, old, new, &old_content, &new_content, false));
+    }
+
+    Ok(output)
+}
+
+/// The blobs that differ between the trees of commits `old` and `new`, as
+/// `(path, old_hash, new_hash)` changes sorted by path.
+pub fn commit_pair_changes(repo: &Repository, old: &str, new: &str) -> Result<Vec<TreeChange>, Box<dyn std::error::Error>> {
+    let tree_of = |hash: &str| -> Result<String, Box<dyn std::error::Error>> {
+        let commit: Commit = serde_json::from_slice(&Object::read(repo, hash)?)
+            .map_err(|_| format!("{} is not a commit", hash))?;
+        Ok(commit.tree)
+    };
+    let mut changes = Tree::diff(repo, Some(&tree_of(old)?), Some(&tree_of(new)?))?;
+    changes.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(changes)
+}
+
+/// The files `commit` changed relative to its first parent.
+pub fn commit_changes(repo: &Repository, commit: &Commit) -> Result<Vec<TreeChange>, Box<dyn std::error::Error>> {
+    let parent_tree = match commit.parents.first().or(commit.parent.as_ref()) {
+        Some(parent) if !parent.is_empty() => {
+            let parent_commit: Commit = serde_json::from_slice(&Object::read(repo, parent)?)?;
+            Some(parent_commit.tree)
+        },
+        _ => None,
+    };
+    Tree::diff(repo, parent_tree.as_deref(), Some(&commit.tree))
+}
+
+/// Paths given on the command line, compiled once: each is a file, a
+/// directory whose contents match, or a glob (`src/*.rs`, `**/tests/**`).
+/// A leading `**/` also matches at the top level. An empty scope matches
+/// everything.
+#[derive(Debug, Default)]
+pub struct PathScope {
+    specs: Vec<ScopeSpec>,
+}
+
+#[derive(Debug)]
+enum ScopeSpec {
+    Everything,
+    Path(String),
+    Glob(regex::Regex),
+}
+
+impl PathScope {
+    pub fn new(paths: &[String]) -> Result<Self, Box<dyn std::error::Error>> {
+        let specs = paths.iter().map(|spec| {
+            let spec = spec.strip_prefix("./").unwrap_or(spec).trim_end_matches('/');
+            if spec.is_empty() || spec == "." {
+                return Ok(ScopeSpec::Everything);
+            }
+            if !spec.contains(['*', '?', '[']) {
+                return Ok(ScopeSpec::Path(spec.to_string()));
+            }
+            let pattern = match spec.strip_prefix("**/") {
+                Some(rest) => format!("^(?:.*/)?{}", &GitIgnore::glob_to_regex(rest)[1..]),
+                None => GitIgnore::glob_to_regex(spec),
+            };
+            regex::Regex::new(&pattern)
+                .map(ScopeSpec::Glob)
+                .map_err(|_| format!("Invalid path pattern '{}'", spec).into())
+        }).collect::<Result<_, Box<dyn std::error::Error>>>()?;
+        Ok(PathScope { specs })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.specs.is_empty()
+    }
+
+    /// Whether `path` is one of the paths, lies under one of them, or
+    /// matches one used as a glob.
+    pub fn matches(&self, path: &str) -> bool {
+        let path = path.strip_prefix("./").unwrap_or(path);
+        self.specs.is_empty() || self.specs.iter().any(|spec| match spec {
+            ScopeSpec::Everything => true,
+            ScopeSpec::Path(spec) => path == spec
+                || path.strip_prefix(spec.as_str()).is_some_and(|rest| rest.starts_with('/')),
+            ScopeSpec::Glob(pattern) => pattern.is_match(path),
+        })
+    }
+}
+
+pub fn format_stat_block(stats: &[FileStat]) -> String {
+    let mut output = String::new();
+    let width = stats.iter().map(|s| s.path.len()).max().unwrap_or(0);
+    let max_changes = stats.iter().map(|s| s.additions + s.deletions).max().unwrap_or(0);
+    let scale = if max_changes > 50 { 50.0 / max_changes as f32 } else { 1.0 };
+
+    for stat in stats {
+        let changes = stat.additions + stat.deletions;
+        let plus = (stat.additions as f32 * scale).ceil() as usize;
+        let minus = (stat.deletions as f32 * scale).ceil() as usize;
+        output.push_str(&format!(" {:<width$} | {:>4} {}{}\n",
+                                 stat.path, changes, "+".repeat(plus), "-".repeat(minus),
+                                 width = width));
+    }
+
+    output.push_str(&format_short_stat(stats));
+    output.push('\n');
+    output
+}
+
+/// The summary line of a stat block: ` N files changed, N insertions(+), N deletions(-)`.
+pub fn format_short_stat(stats: &[FileStat]) -> String {
+    let additions: usize = stats.iter().map(|s| s.additions).sum();
+    let deletions: usize = stats.iter().map(|s| s.deletions).sum();
+    format!(" {} file{} changed, {} insertion{}(+), {} deletion{}(-)",
+            stats.len(), if stats.len() == 1 { "" } else { "s" },
+            additions, if additions == 1 { "" } else { "s" },
+            deletions, if deletions == 1 { "" } else { "s" })
+}
+
+pub fn list_hunks(old_content: &str, new_content: &str) -> Vec<String> {
+    let diff = TextDiff::from_lines(old_content, new_content);
+    let mut hunks = Vec::new();
+
+    for op in diff.ops().iter().filter(|op| op.tag() != DiffTag::Equal) {
+        let mut hunk = format!("@@ -{},{} +{},{} @@\n",
+                               op.old_range().start + 1, op.old_range().len(),
+                               op.new_range().start + 1, op.new_range().len());
+        for change in diff.iter_changes(op) {
+            let sign = match change.tag() {
+                ChangeTag::Delete => "-",
+                ChangeTag::Insert => "+",
+                ChangeTag::Equal => " ",
+            };
+            hunk.push_str(&format!("{}{}", sign, change));
+            if change.missing_newline() {
+                hunk.push('\n');
+            }
+        }
+        hunks.push(hunk);
+    }
+
+    hunks
+}
+
+// `selected` is indexed in the same order as `list_hunks`; unselected hunks keep the old lines.
+pub fn apply_selected_hunks(old_content: &str, new_content: &str, selected: &[bool]) -> String {
+    let diff = TextDiff::from_lines(old_content, new_content);
+    let old_lines: Vec<&str> = old_content.split_inclusive('\n').collect();
+    let new_lines: Vec<&str> = new_content.split_inclusive('\n').collect();
+    let mut output = String::new();
+    let mut hunk_index = 0;
+
+    for op in diff.ops() {
+        if op.tag() == DiffTag::Equal {
+            output.extend(old_lines[op.old_range()].iter().copied());
+            continue;
+        }
+
+        if selected.get(hunk_index).copied().unwrap_or(false) {
+            output.extend(new_lines[op.new_range()].iter().copied());
+        } else {
+            output.extend(old_lines[op.old_range()].iter().copied());
+        }
+        hunk_index += 1;
+    }
+
+    output
+}
+
+/// Every blob in HEAD's tree, keyed by path without a leading "./". Empty
+/// when HEAD is unborn.
+pub fn head_files(repo: &Repository) -> BTreeMap<String, String> {
+    head_entries(repo).into_iter()
+        .map(|(path, entry)| (path, entry.hash))
+        .collect()
+}
+
+/// Like `head_files`, with each blob's mode.
+fn head_entries(repo: &Repository) -> BTreeMap<String, TreeEntry> {
+    let files = Branch::get_current_commit(repo)
+        .and_then(|head| Object::read(repo, &head).ok())
+        .and_then(|content| serde_json::from_slice::<Commit>(&content).ok())
+        .and_then(|commit| Tree::from_hash(repo, &commit.tree).ok())
+        .and_then(|tree| tree.flatten_entries(repo, "").ok())
+        .unwrap_or_default();
+
+    files.into_iter()
+        .map(|(path, entry)| (path.strip_prefix("./").map(str::to_string).unwrap_or(path), entry))
+        .collect()
+}
+
+/// The hash of the blob `file_path` had in HEAD's tree, or `None` if HEAD is
+/// unborn or the path did not exist there.
+pub fn head_blob_hash(repo: &Repository, file_path: &str) -> Option<String> {
+    let head = Branch::get_current_commit(repo)?;
+    let commit: Commit = serde_json::from_slice(&Object::read(repo, &head).ok()?).ok()?;
+    let tree = Tree::from_hash(repo, &commit.tree).ok()?;
+
+    // Files added by directory are indexed (and committed) as "./x".
+    let relative = file_path.strip_prefix("./").unwrap_or(file_path);
+    let dotted = format!("./{}", relative);
+    [relative, dotted.as_str()].iter()
+        .find_map(|path| find_blob(repo, &tree, path))
+}
+
+/// Walks `path` down from `tree`. Subtrees may name entries by a multi-part
+/// path ("a/b.txt"), so the whole remainder is tried before descending.
+fn find_blob(repo: &Repository, tree: &Tree, path: &str) -> Option<String> {
+    if let Some(entry) = tree.get_entry(path) {
+        return (entry.entry_type == "blob").then(|| entry.hash.clone());
+    }
+
+    let (dir, rest) = path.split_once('/')?;
+    let entry = tree.get_entry(dir).filter(|entry| entry.entry_type == "tree")?;
+    find_blob(repo, &Tree::from_hash(repo, &entry.hash).ok()?, rest)
 }
 
 fn get_blob_content(repo: &Repository, hash: &str) -> Option<String> {
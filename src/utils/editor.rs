@@ -0,0 +1,54 @@
+use std::path::{Path, PathBuf};
+
+/// Editors tried, in order, when neither `core.editor` nor `$EDITOR` works.
+const FALLBACK_EDITORS: &[&str] = &["nano", "vi"];
+
+/// The program part of an editor command such as `code --wait`.
+pub fn editor_program(command: &str) -> &str {
+    command.split_whitespace().next().unwrap_or("")
+}
+
+/// Looks up the program of an editor command: paths are checked directly,
+/// bare names are searched for on `PATH`.
+pub fn find_program(command: &str) -> Option<PathBuf> {
+    let program = editor_program(command);
+    if program.is_empty() {
+        return None;
+    }
+    if program.contains('/') || program.contains(std::path::MAIN_SEPARATOR) {
+        return Some(PathBuf::from(program)).filter(|path| is_program(path));
+    }
+
+    let paths = std::env::var_os("PATH")?;
+    std::env::split_paths(&paths)
+        .map(|dir| dir.join(program))
+        .find(|path| is_program(path))
+}
+
+fn is_program(path: &Path) -> bool {
+    path.is_file() || (cfg!(windows) && path.with_extension("exe").is_file())
+}
+
+/// The editor command to run for `configured` (`core.editor`), plus a warning
+/// when it had to be replaced because its program does not exist. Without a
+/// configured editor `$EDITOR` (passed as `env_editor`) is used, then the
+/// first of nano or vi that is installed.
+pub fn resolve_editor(configured: Option<&str>, env_editor: Option<&str>) -> (String, Option<String>) {
+    let fallback = || env_editor
+        .filter(|editor| find_program(editor).is_some())
+        .map(str::to_string)
+        .or_else(|| FALLBACK_EDITORS.iter()
+            .find(|editor| find_program(editor).is_some())
+            .map(|editor| editor.to_string()))
+        .unwrap_or_else(|| FALLBACK_EDITORS[FALLBACK_EDITORS.len() - 1].to_string());
+
+    match configured.map(str::trim).filter(|editor| !editor.is_empty()) {
+        Some(editor) if find_program(editor).is_some() => (editor.to_string(), None),
+        Some(editor) => {
+            let fallback = fallback();
+            let warning = format!("configured editor '{}' not found, falling back to {}", editor, fallback);
+            (fallback, Some(warning))
+        },
+        None => (fallback(), None),
+    }
+}
@@ -0,0 +1,37 @@
+use crate::core::Repository;
+use std::path::PathBuf;
+use std::process::{Command, ExitStatus};
+
+pub fn hook_path(repo: &Repository, name: &str) -> PathBuf {
+    repo.git_dir.join("hooks").join(name)
+}
+
+/// Runs `.aigit/hooks/<name>` from the working tree root if it exists and is
+/// executable. Returns `None` when there is no hook to run.
+pub fn run_hook(repo: &Repository, name: &str, args: &[&str]) -> Result<Option<ExitStatus>, Box<dyn std::error::Error>> {
+    let path = hook_path(repo, name);
+    if !is_executable(&path) {
+        return Ok(None);
+    }
+
+    let status = Command::new(&path)
+        .args(args)
+        .current_dir(&repo.path)
+        .status()
+        .map_err(|e| format!("Failed to run {} hook: {}", name, e))?;
+
+    Ok(Some(status))
+}
+
+#[cfg(unix)]
+fn is_executable(path: &std::path::Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::metadata(path)
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &std::path::Path) -> bool {
+    path.is_file()
+}
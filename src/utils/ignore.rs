@@ -93,7 +93,7 @@ impl GitIgnore {
         })
     }
 
-    fn glob_to_regex(glob: &str) -> String {
+    pub fn glob_to_regex(glob: &str) -> String {
         let mut regex = String::new();
         regex.push_str("^");
         
@@ -144,8 +144,13 @@ impl GitIgnore {
     }
 
     pub fn is_ignored<P: AsRef<Path>>(&self, path: P) -> bool {
-        let path_str = path.as_ref().to_string_lossy();
-        let is_dir = path.as_ref().is_dir();
+        self.is_ignored_entry(path.as_ref(), path.as_ref().is_dir())
+    }
+
+    /// `is_ignored` for a path whose type is already known, such as a walk
+    /// entry relative to the repository root rather than the current directory.
+    pub fn is_ignored_entry(&self, path: &Path, is_dir: bool) -> bool {
+        let path_str = path.to_string_lossy();
         
         let mut ignored = false;
         
@@ -1,4 +1,12 @@
 pub mod diff;
 pub mod analyzer;
+pub mod complexity;
 pub mod compression;
 pub mod ignore;
+pub mod hooks;
+pub mod editor;
+pub mod trailers;
+pub mod worktree;
+pub mod conflicts;
+pub mod patch;
+pub mod progress;
@@ -0,0 +1,405 @@
+use crate::core::Commit;
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+
+const DEV_NULL: &str = "/dev/null";
+/// The fixed date git writes on the `From <hash>` line of each patch mail.
+const MBOX_FROM_DATE: &str = "Mon Sep 17 00:00:00 2001";
+const SIGNATURE_SEPARATOR: &str = "-- ";
+
+/// One `@@ -old_start,old_count +new_start,new_count @@` block. `lines` keep
+/// their leading ` `, `-` or `+`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hunk {
+    pub old_start: usize,
+    pub old_count: usize,
+    pub new_start: usize,
+    pub new_count: usize,
+    pub lines: Vec<String>,
+}
+
+/// The changes to one file. `None` on either side means the file does not
+/// exist there (`/dev/null`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilePatch {
+    pub old_path: Option<String>,
+    pub new_path: Option<String>,
+    pub hunks: Vec<Hunk>,
+    pub binary: bool,
+}
+
+impl FilePatch {
+    /// The path the patch is about, preferring the post-image name.
+    pub fn path(&self) -> &str {
+        self.new_path.as_deref()
+            .or(self.old_path.as_deref())
+            .unwrap_or("")
+    }
+
+    /// The patch that undoes this one.
+    pub fn reversed(&self) -> Self {
+        Self {
+            old_path: self.new_path.clone(),
+            new_path: self.old_path.clone(),
+            hunks: self.hunks.iter().map(|hunk| Hunk {
+                old_start: hunk.new_start,
+                old_count: hunk.new_count,
+                new_start: hunk.old_start,
+                new_count: hunk.old_count,
+                lines: hunk.lines.iter().map(|line| match line.split_at(1) {
+                    ("-", rest) => format!("+{}", rest),
+                    ("+", rest) => format!("-{}", rest),
+                    _ => line.clone(),
+                }).collect(),
+            }).collect(),
+            binary: self.binary,
+        }
+    }
+}
+
+/// Parses unified diffs as written by `aigit diff` or `git diff`. Anything
+/// outside a `---`/`+++` header and its hunks (commit messages, `diff` and
+/// `index` lines) is skipped.
+pub fn parse_patch(text: &str) -> Result<Vec<FilePatch>, Box<dyn std::error::Error>> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut patches: Vec<FilePatch> = Vec::new();
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i];
+
+        if let Some(names) = line.strip_prefix("Binary files ").and_then(|rest| rest.strip_suffix(" differ")) {
+            let (old, new) = names.split_once(" and ").unwrap_or((names, names));
+            patches.push(FilePatch {
+                old_path: header_path(old, "a/")?,
+                new_path: header_path(new, "b/")?,
+                hunks: Vec::new(),
+                binary: true,
+            });
+            i += 1;
+            continue;
+        }
+        if line == "GIT binary patch" {
+            if let Some(patch) = patches.last_mut() {
+                patch.binary = true;
+            }
+            i += 1;
+            continue;
+        }
+
+        if !(line.starts_with("--- ") && i + 1 < lines.len() && lines[i + 1].starts_with("+++ ")) {
+            i += 1;
+            continue;
+        }
+
+        let mut patch = FilePatch {
+            old_path: header_path(&line[4..], "a/")?,
+            new_path: header_path(&lines[i + 1][4..], "b/")?,
+            hunks: Vec::new(),
+            binary: false,
+        };
+        i += 2;
+
+        while i < lines.len() && lines[i].starts_with("@@") {
+            let (old_start, old_count, new_start, new_count) = parse_hunk_header(lines[i])
+                .ok_or_else(|| format!("Malformed hunk header: {}", lines[i]))?;
+            i += 1;
+
+            let mut hunk = Hunk { old_start, old_count, new_start, new_count, lines: Vec::new() };
+            let (mut old_seen, mut new_seen) = (0, 0);
+            while i < lines.len() && (old_seen < old_count || new_seen < new_count) {
+                let body = lines[i];
+                match body.chars().next() {
+                    Some(' ') => { old_seen += 1; new_seen += 1; },
+                    Some('-') => old_seen += 1,
+                    Some('+') => new_seen += 1,
+                    Some('\\') => { i += 1; continue; },
+                    // Some editors strip the space from blank context lines.
+                    None => { old_seen += 1; new_seen += 1; },
+                    _ => break,
+                }
+                hunk.lines.push(if body.is_empty() { " ".to_string() } else { body.to_string() });
+                i += 1;
+            }
+            if old_seen != old_count || new_seen != new_count {
+                return Err(format!("Truncated hunk in patch for {}", patch.path()).into());
+            }
+            // A trailing "\ No newline at end of file" belongs to the last hunk.
+            while i < lines.len() && lines[i].starts_with('\\') {
+                i += 1;
+            }
+            patch.hunks.push(hunk);
+        }
+
+        patches.push(patch);
+    }
+
+    if patches.is_empty() {
+        return Err("No file changes found in patch".into());
+    }
+    Ok(patches)
+}
+
+fn header_path(raw: &str, prefix: &str) -> Result<Option<String>, String> {
+    // Strip a trailing timestamp ("--- a/file\t2024-01-01 ...").
+    let raw = raw.split('\t').next().unwrap_or(raw).trim_end();
+    if raw == DEV_NULL {
+        return Ok(None);
+    }
+    let path = raw.strip_prefix(prefix).unwrap_or(raw);
+    check_patch_path(path)?;
+    Ok(Some(path.to_string()))
+}
+
+/// Where `path` from a patch lives under the work tree `root`, after the
+/// same check `parse_patch` makes on every header.
+pub fn patch_target(root: &std::path::Path, path: &str) -> Result<std::path::PathBuf, String> {
+    check_patch_path(path)?;
+    Ok(root.join(path))
+}
+
+/// Refuses a path named in a patch unless it stays inside the work tree:
+/// absolute paths, `..` components and anything under `.aigit` are rejected
+/// before a single file is read or written.
+pub fn check_patch_path(path: &str) -> Result<(), String> {
+    if path.is_empty() {
+        return Err("Patch names an empty path".to_string());
+    }
+    if path.starts_with(['/', '\\']) || std::path::Path::new(path).is_absolute() {
+        return Err(format!("Patch path '{}' is absolute", path));
+    }
+    let mut components = path.split(['/', '\\']).filter(|part| !part.is_empty() && *part != ".");
+    if components.clone().any(|part| part == "..") {
+        return Err(format!("Patch path '{}' leaves the working tree", path));
+    }
+    if components.next().is_some_and(|first| first.eq_ignore_ascii_case(".aigit")) {
+        return Err(format!("Patch path '{}' is inside .aigit", path));
+    }
+    Ok(())
+}
+
+fn parse_hunk_header(line: &str) -> Option<(usize, usize, usize, usize)> {
+    let ranges = line.strip_prefix("@@ ")?.split(" @@").next()?;
+    let (old, new) = ranges.split_once(' ')?;
+    let (old_start, old_count) = parse_range(old.strip_prefix('-')?)?;
+    let (new_start, new_count) = parse_range(new.strip_prefix('+')?)?;
+    Some((old_start, old_count, new_start, new_count))
+}
+
+fn parse_range(range: &str) -> Option<(usize, usize)> {
+    match range.split_once(',') {
+        Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+        None => Some((range.parse().ok()?, 1)),
+    }
+}
+
+/// Applies `patch` to `original` (`None` when the file does not exist) and
+/// returns the new content, or `None` when the patch deletes the file. Each
+/// hunk must match exactly; it is looked for at its recorded line first and
+/// then anywhere after the previous hunk.
+pub fn apply_file_patch(original: Option<&str>, patch: &FilePatch) -> Result<Option<String>, String> {
+    if patch.binary {
+        return Err(format!("{}: binary patches are not supported", patch.path()));
+    }
+    match (original, &patch.old_path) {
+        (Some(_), None) => return Err(format!("{}: already exists in working tree", patch.path())),
+        (None, Some(_)) => return Err(format!("{}: does not exist in working tree", patch.path())),
+        _ => {}
+    }
+
+    let content = original.unwrap_or("");
+    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+    let ends_with_newline = content.is_empty() || content.ends_with('\n');
+    let mut search_from = 0;
+    let mut offset: isize = 0;
+
+    for (n, hunk) in patch.hunks.iter().enumerate() {
+        let old: Vec<&str> = hunk.lines.iter()
+            .filter(|line| !line.starts_with('+'))
+            .map(|line| &line[1..])
+            .collect();
+        let new: Vec<String> = hunk.lines.iter()
+            .filter(|line| !line.starts_with('-'))
+            .map(|line| line[1..].to_string())
+            .collect();
+
+        // A zero-length old side names the line *after which* to insert.
+        let recorded = if hunk.old_count == 0 { hunk.old_start } else { hunk.old_start.saturating_sub(1) };
+        let expected = (recorded as isize + offset).max(0) as usize;
+        let matches_at = |start: usize| {
+            start >= search_from
+                && start + old.len() <= lines.len()
+                && lines[start..start + old.len()].iter().zip(&old).all(|(a, b)| a == b)
+        };
+
+        let start = if matches_at(expected) {
+            Some(expected)
+        } else {
+            (search_from..=lines.len().saturating_sub(old.len())).find(|&start| matches_at(start))
+        };
+        let start = start.ok_or_else(|| format!("{}: hunk #{} does not apply", patch.path(), n + 1))?;
+
+        lines.splice(start..start + old.len(), new.iter().cloned());
+        search_from = start + new.len();
+        offset += new.len() as isize - old.len() as isize;
+    }
+
+    if patch.new_path.is_none() {
+        if !lines.is_empty() {
+            return Err(format!("{}: file is not empty after removing its lines", patch.path()));
+        }
+        return Ok(None);
+    }
+
+    let mut result = lines.join("\n");
+    if ends_with_newline && !result.is_empty() {
+        result.push('\n');
+    }
+    Ok(Some(result))
+}
+
+/// Applies every file patch against `read`, which returns the current content
+/// of a path. Nothing is written: the result maps each path to its new content
+/// (`None` for deletions). All failures are collected before returning.
+pub fn apply_patches<F>(patches: &[FilePatch], read: F) -> Result<BTreeMap<String, Option<String>>, Vec<String>>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut results = BTreeMap::new();
+    let mut errors = Vec::new();
+
+    for patch in patches {
+        let original = match &patch.old_path {
+            Some(path) => results.get(path).cloned().unwrap_or_else(|| read(path)),
+            None => patch.new_path.as_deref().and_then(&read),
+        };
+        match apply_file_patch(original.as_deref(), patch) {
+            Ok(content) => {
+                if let (Some(old), Some(new)) = (&patch.old_path, &patch.new_path) {
+                    if old != new {
+                        results.insert(old.clone(), None);
+                    }
+                }
+                results.insert(patch.path().to_string(), content);
+            },
+            Err(e) => errors.push(e),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(results)
+    } else {
+        Err(errors)
+    }
+}
+
+/// A commit as exchanged by `format-patch` and `am`: who wrote it, when, its
+/// message and the diff to apply.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MailPatch {
+    pub author_name: String,
+    pub author_email: String,
+    pub date: DateTime<Utc>,
+    pub subject: String,
+    pub body: String,
+    pub diff: String,
+}
+
+impl MailPatch {
+    /// The commit message: the subject, then the body after a blank line.
+    pub fn message(&self) -> String {
+        if self.body.is_empty() {
+            self.subject.clone()
+        } else {
+            format!("{}\n\n{}", self.subject, self.body)
+        }
+    }
+}
+
+/// Formats `commit` as patch `number` of `total` in mbox form.
+pub fn format_mail(hash: &str, commit: &Commit, number: usize, total: usize, diff: &str) -> String {
+    let subject = commit.short_message();
+    let body = commit.message.lines().skip(1).collect::<Vec<_>>().join("\n");
+    let prefix = if total > 1 { format!("[PATCH {}/{}]", number, total) } else { "[PATCH]".to_string() };
+
+    let mut mail = format!("From {} {}\nFrom: {} <{}>\nDate: {}\nSubject: {} {}\n\n",
+                           hash, MBOX_FROM_DATE,
+                           commit.author.name, commit.author.email,
+                           commit.author.timestamp.to_rfc2822(),
+                           prefix, subject);
+    let body = body.trim();
+    if !body.is_empty() {
+        mail.push_str(body);
+        mail.push('\n');
+    }
+    mail.push_str("---\n");
+    mail.push_str(diff);
+    mail.push_str(&format!("{}\naigit {}\n", SIGNATURE_SEPARATOR, env!("CARGO_PKG_VERSION")));
+    mail
+}
+
+/// Parses a patch mail written by `format-patch` (or `git format-patch`).
+pub fn parse_mail(text: &str) -> Result<MailPatch, Box<dyn std::error::Error>> {
+    let mut lines = text.lines().peekable();
+    if lines.peek().is_some_and(|line| line.starts_with("From ") && !line.starts_with("From: ")) {
+        lines.next();
+    }
+
+    let mut headers: Vec<(String, String)> = Vec::new();
+    for line in lines.by_ref() {
+        if line.is_empty() {
+            break;
+        }
+        match (line.starts_with([' ', '\t']), headers.last_mut()) {
+            (true, Some((_, value))) => {
+                value.push(' ');
+                value.push_str(line.trim());
+            },
+            _ => if let Some((name, value)) = line.split_once(':') {
+                headers.push((name.trim().to_lowercase(), value.trim().to_string()));
+            },
+        }
+    }
+    let header = |name: &str| headers.iter().find(|(n, _)| n == name).map(|(_, v)| v.clone());
+
+    let from = header("from").ok_or("Patch has no From: header")?;
+    let (author_name, author_email) = match from.rsplit_once('<') {
+        Some((name, email)) => (name.trim().trim_matches('"').to_string(), email.trim_end_matches('>').trim().to_string()),
+        None => (from.clone(), String::new()),
+    };
+    let date = header("date")
+        .and_then(|date| DateTime::parse_from_rfc2822(&date).ok())
+        .map(|date| date.with_timezone(&Utc))
+        .ok_or("Patch has no valid Date: header")?;
+    let subject = header("subject").ok_or("Patch has no Subject: header")?;
+    let subject = match subject.strip_prefix('[').and_then(|rest| rest.split_once(']')) {
+        Some((tag, rest)) if tag.contains("PATCH") => rest.trim().to_string(),
+        _ => subject,
+    };
+
+    let mut body = Vec::new();
+    for line in lines.by_ref() {
+        if line == "---" {
+            break;
+        }
+        body.push(line);
+    }
+
+    let mut diff = String::new();
+    for line in lines {
+        if line == SIGNATURE_SEPARATOR {
+            break;
+        }
+        diff.push_str(line);
+        diff.push('\n');
+    }
+
+    Ok(MailPatch {
+        author_name,
+        author_email,
+        date,
+        subject,
+        body: body.join("\n").trim().to_string(),
+        diff,
+    })
+}
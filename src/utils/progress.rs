@@ -0,0 +1,20 @@
+use indicatif::{ProgressBar, ProgressStyle};
+use std::io::IsTerminal;
+
+/// A determinate bar for a long pass over the object store, counted in
+/// objects. Callers set the length per phase. Hidden when stderr is not a
+/// terminal, so scripts and logs only get the final report.
+pub fn object_progress(message: &str) -> ProgressBar {
+    if !std::io::stderr().is_terminal() {
+        return ProgressBar::hidden();
+    }
+
+    let pb = ProgressBar::new(0);
+    pb.set_style(ProgressStyle::default_bar()
+        .template("{spinner} {msg} [{bar:30}] {pos}/{len}")
+        .unwrap()
+        .progress_chars("=> "));
+    pb.set_message(message.to_string());
+    pb.enable_steady_tick(std::time::Duration::from_millis(100));
+    pb
+}
@@ -0,0 +1,52 @@
+use regex::Regex;
+
+/// Parses a `Key: Value` trailer, rejecting keys with spaces or empty values.
+pub fn parse_trailer(trailer: &str) -> Result<(String, String), Box<dyn std::error::Error>> {
+    let (key, value) = trailer.split_once(':')
+        .ok_or_else(|| format!("Invalid trailer '{}': expected 'Key: Value'", trailer))?;
+    let key = key.trim();
+    let value = value.trim();
+
+    let key_pattern = Regex::new(r"^[A-Za-z0-9][A-Za-z0-9-]*$")?;
+    if !key_pattern.is_match(key) || value.is_empty() {
+        return Err(format!("Invalid trailer '{}': expected 'Key: Value'", trailer).into());
+    }
+
+    Ok((key.to_string(), value.to_string()))
+}
+
+fn is_trailer_line(line: &str) -> bool {
+    parse_trailer(line).is_ok()
+}
+
+/// Appends trailers to the message's trailer block, starting a new block when
+/// the last paragraph isn't one. Trailers already present are not repeated.
+pub fn append_trailers(message: &str, trailers: &[String]) -> Result<String, Box<dyn std::error::Error>> {
+    let mut lines: Vec<String> = Vec::new();
+    for trailer in trailers {
+        let (key, value) = parse_trailer(trailer)?;
+        lines.push(format!("{}: {}", key, value));
+    }
+
+    let existing: Vec<&str> = message.lines().collect();
+    let mut new_lines: Vec<String> = Vec::new();
+    for line in lines {
+        if !existing.contains(&line.as_str()) && !new_lines.contains(&line) {
+            new_lines.push(line);
+        }
+    }
+
+    // Nothing to add: leave the message exactly as the cleanup mode produced it.
+    if new_lines.is_empty() {
+        return Ok(message.to_string());
+    }
+    let message = message.trim_end();
+
+    let last_paragraph: Vec<&str> = message.rsplit("\n\n").next().unwrap_or("").lines().collect();
+    let has_trailer_block = message.contains("\n\n")
+        && !last_paragraph.is_empty()
+        && last_paragraph.iter().all(|line| is_trailer_line(line));
+
+    let separator = if has_trailer_block { "\n" } else { "\n\n" };
+    Ok(format!("{}{}{}", message, separator, new_lines.join("\n")))
+}
@@ -0,0 +1,206 @@
+use std::path::{Component, Path, PathBuf};
+use walkdir::WalkDir;
+
+pub const SYMLINK_MODE: &str = "120000";
+
+/// Directory names that hold a repository's internals.
+pub const REPO_DIR_NAMES: [&str; 2] = [".aigit", ".git"];
+
+/// What a walk of the working tree found: the files and symlinks to consider,
+/// and the nested repositories it did not descend into.
+#[derive(Default)]
+pub struct WorktreeWalk {
+    pub files: Vec<PathBuf>,
+    /// Relative paths of nested repositories, each ending in `/`.
+    pub nested_repos: Vec<String>,
+}
+
+/// A path argument given in the subdirectory `prefix` of the working tree,
+/// rewritten relative to the root: `a.txt` in `src` becomes `src/a.txt` and
+/// `.` becomes `src`. At the root (empty `prefix`), and for absolute paths
+/// and `-` (standard input), the argument is returned unchanged.
+pub fn root_relative(prefix: &Path, arg: &str) -> String {
+    if prefix.as_os_str().is_empty() || arg == "-" || Path::new(arg).is_absolute() {
+        return arg.to_string();
+    }
+
+    let mut parts: Vec<String> = Vec::new();
+    for component in prefix.join(arg).components() {
+        match component {
+            Component::CurDir => {},
+            Component::ParentDir if parts.last().is_some_and(|part| part != "..") => {
+                parts.pop();
+            },
+            other => parts.push(other.as_os_str().to_string_lossy().to_string()),
+        }
+    }
+
+    if parts.is_empty() {
+        ".".to_string()
+    } else {
+        parts.join("/")
+    }
+}
+
+/// Whether `dir` is the root of its own repository, i.e. it contains a
+/// `.aigit` or `.git` directory.
+pub fn is_nested_repo<P: AsRef<Path>>(dir: P) -> bool {
+    let dir = dir.as_ref();
+    REPO_DIR_NAMES.iter().any(|name| dir.join(name).is_dir())
+}
+
+/// Walks the working tree under `root`. Repository directories are skipped,
+/// and nested repositories are treated as boundaries: their contents are not
+/// listed, only their path.
+pub fn walk_worktree<P: AsRef<Path>>(root: P) -> WorktreeWalk {
+    let mut walk = WorktreeWalk::default();
+    let mut entries = WalkDir::new(root).into_iter();
+
+    while let Some(entry) = entries.next() {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(_) => continue,
+        };
+        let file_type = entry.file_type();
+
+        if file_type.is_dir() {
+            let is_repo_dir = entry.file_name().to_str().is_some_and(|name| REPO_DIR_NAMES.contains(&name));
+            if is_repo_dir {
+                entries.skip_current_dir();
+            } else if entry.depth() > 0 && is_nested_repo(entry.path()) {
+                walk.nested_repos.push(format!("{}/", relative_path(entry.path())));
+                entries.skip_current_dir();
+            }
+        } else if file_type.is_file() || file_type.is_symlink() {
+            walk.files.push(entry.into_path());
+        }
+    }
+
+    walk.nested_repos.sort();
+    walk
+}
+
+/// The nested repository containing the relative path `path`, if any, as
+/// `dir/`. The repository at the working tree root does not count.
+pub fn nested_repo_root(path: &str) -> Option<String> {
+    let path = Path::new(path.strip_prefix("./").unwrap_or(path));
+    let mut ancestors: Vec<&Path> = path.ancestors()
+        .skip(1)
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .collect();
+    ancestors.reverse();
+
+    ancestors.into_iter()
+        .find(|dir| is_nested_repo(dir))
+        .map(|dir| format!("{}/", dir.to_string_lossy()))
+}
+
+/// Removes a file or symlink, then any parent directories the removal left
+/// empty, up to the working tree root. A path that is already gone is fine.
+pub fn remove_file_and_empty_parents<P: AsRef<Path>>(path: P) -> std::io::Result<()> {
+    let path = path.as_ref();
+    if std::fs::symlink_metadata(path).is_err() {
+        return Ok(());
+    }
+    std::fs::remove_file(path)?;
+
+    let mut dir = path.parent();
+    while let Some(parent) = dir {
+        if parent.as_os_str().is_empty() || parent == Path::new(".") || std::fs::remove_dir(parent).is_err() {
+            break;
+        }
+        dir = parent.parent();
+    }
+    Ok(())
+}
+
+fn relative_path(path: &Path) -> String {
+    let path = path.strip_prefix(".").unwrap_or(path);
+    path.to_string_lossy().replace('\\', "/")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EntryKind {
+    File,
+    Symlink,
+    Directory,
+}
+
+impl EntryKind {
+    pub fn from_mode(mode: &str) -> Self {
+        if mode == SYMLINK_MODE {
+            EntryKind::Symlink
+        } else {
+            EntryKind::File
+        }
+    }
+}
+
+/// Returns what currently occupies `path` in the working tree, without
+/// following symlinks.
+pub fn kind_of<P: AsRef<Path>>(path: P) -> Option<EntryKind> {
+    let file_type = std::fs::symlink_metadata(path).ok()?.file_type();
+    Some(if file_type.is_symlink() {
+        EntryKind::Symlink
+    } else if file_type.is_dir() {
+        EntryKind::Directory
+    } else {
+        EntryKind::File
+    })
+}
+
+/// Reads the content that gets stored as a blob: the link target for
+/// symlinks, the file bytes otherwise.
+pub fn read_entry_content<P: AsRef<Path>>(path: P) -> std::io::Result<Vec<u8>> {
+    let path = path.as_ref();
+    if kind_of(path) == Some(EntryKind::Symlink) {
+        return Ok(link_target_bytes(&std::fs::read_link(path)?));
+    }
+    std::fs::read(path)
+}
+
+#[cfg(unix)]
+fn link_target_bytes(target: &Path) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    target.as_os_str().as_bytes().to_vec()
+}
+
+#[cfg(not(unix))]
+fn link_target_bytes(target: &Path) -> Vec<u8> {
+    target.to_string_lossy().replace('\\', "/").into_bytes()
+}
+
+/// Writes a blob back into the working tree. Symlink blobs recreate the link
+/// on Unix; elsewhere the target path is written as a plain file.
+pub fn write_entry<P: AsRef<Path>>(path: P, content: &[u8], mode: &str) -> std::io::Result<()> {
+    let path = path.as_ref();
+    if let Some(parent) = path.parent() {
+        if !parent.as_os_str().is_empty() {
+            std::fs::create_dir_all(parent)?;
+        }
+    }
+
+    // Never write through an existing link; replace the entry itself.
+    if kind_of(path) == Some(EntryKind::Symlink) {
+        std::fs::remove_file(path)?;
+    }
+
+    if EntryKind::from_mode(mode) == EntryKind::Symlink {
+        return create_symlink(content, path);
+    }
+    std::fs::write(path, content)
+}
+
+#[cfg(unix)]
+fn create_symlink(target: &[u8], path: &Path) -> std::io::Result<()> {
+    use std::os::unix::ffi::OsStrExt;
+    if path.exists() {
+        std::fs::remove_file(path)?;
+    }
+    std::os::unix::fs::symlink(std::ffi::OsStr::from_bytes(target), path)
+}
+
+#[cfg(not(unix))]
+fn create_symlink(target: &[u8], path: &Path) -> std::io::Result<()> {
+    std::fs::write(path, target)
+}